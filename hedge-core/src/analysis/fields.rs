@@ -0,0 +1,158 @@
+use super::{FaceVectors, VertexScalars};
+use crate::math;
+use crate::*;
+
+/// Stores `f` evaluated at every vertex of the mesh.
+pub fn vertex_scalars<S, F>(mesh: &Mesh<S>, f: F) -> VertexScalars
+where
+    S: Scalar,
+    F: Fn(VertexFn<'_, S>) -> f32,
+{
+    let mut field = VertexScalars::new();
+    for vertex in mesh.vertices() {
+        field.set(vertex.index, f(vertex));
+    }
+    field
+}
+
+/// Computes the gradient of a piecewise linear field over each face.
+///
+/// Polygons are fan triangulated and the gradients of their triangles are
+/// averaged by area. Faces with a vertex missing from the field, or without
+/// any area, are left out.
+pub fn face_gradients<S: Scalar>(mesh: &Mesh<S>, field: &VertexScalars) -> FaceVectors {
+    let mut gradients = FaceVectors::new();
+    for face in mesh.faces() {
+        let corners: Option<Vec<(math::Vec3, f64)>> = face
+            .vertices()
+            .map(|v| {
+                field
+                    .get(v.index)
+                    .map(|f| (mesh.vertex_position(v.index), *f as f64))
+            })
+            .collect();
+        let Some(corners) = corners else {
+            continue;
+        };
+
+        let mut sum = [0.0; 3];
+        let mut total_area = 0.0;
+        for i in 1..corners.len().saturating_sub(1) {
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            let area_vector = math::cross(
+                math::sub(triangle[1].0, triangle[0].0),
+                math::sub(triangle[2].0, triangle[0].0),
+            );
+            let double_area = math::length(area_vector);
+            if double_area <= 0.0 {
+                continue;
+            }
+            let normal = math::scale(area_vector, 1.0 / double_area);
+            // The gradient of a linear triangle is the sum of N x e over the
+            // edges opposite each corner, weighted by the value at that corner.
+            let gradient = (0..3).fold([0.0; 3], |g, k| {
+                let opposite = math::sub(triangle[(k + 2) % 3].0, triangle[(k + 1) % 3].0);
+                math::add(g, math::scale(math::cross(normal, opposite), triangle[k].1))
+            });
+            sum = math::add(sum, math::scale(gradient, 0.5));
+            total_area += 0.5 * double_area;
+        }
+        if total_area > 0.0 {
+            let gradient = math::scale(sum, 1.0 / total_area);
+            gradients.set(face.index, gradient.map(|c| c as f32));
+        }
+    }
+    gradients
+}
+
+/// Smooths a field by repeatedly moving each value towards the average of its
+/// neighbors by `strength`, without touching the geometry.
+///
+/// Vertices without a value are skipped and do not contribute to their
+/// neighbors.
+pub fn smooth_scalars<S: Scalar>(
+    mesh: &Mesh<S>,
+    field: &VertexScalars,
+    iterations: usize,
+    strength: f32,
+) -> VertexScalars {
+    let mut current = field.clone();
+    for _ in 0..iterations {
+        let mut next = current.clone();
+        for vertex in mesh.vertices() {
+            let Some(value) = current.get(vertex.index).copied() else {
+                continue;
+            };
+            let neighbors: Vec<f32> = vertex
+                .edges()
+                .filter_map(|edge| current.get(edge.twin().vertex().index).copied())
+                .collect();
+            if neighbors.is_empty() {
+                continue;
+            }
+            let average = neighbors.iter().sum::<f32>() / neighbors.len() as f32;
+            next.set(vertex.index, value + strength * (average - value));
+        }
+        current = next;
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_fields_have_constant_gradients() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(3, 3);
+        let field = vertex_scalars(&mesh, |v| {
+            let p = v.position().unwrap();
+            2.0 * p[0] - p[1]
+        });
+        let gradients = face_gradients(&mesh, &field);
+        for face in mesh.faces() {
+            let g = gradients.get(face.index).unwrap();
+            assert!((g[0] - 2.0).abs() < 1.0e-5);
+            assert!((g[1] + 1.0).abs() < 1.0e-5);
+            assert!(g[2].abs() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn gradients_lie_in_the_surface() {
+        let mesh: Mesh = builder::cube();
+        let field = vertex_scalars(&mesh, |v| {
+            let p = v.position().unwrap();
+            p[0] + p[1] + p[2]
+        });
+        let gradients = face_gradients(&mesh, &field);
+        for face in mesh.faces() {
+            let g = gradients.get(face.index).unwrap().map(|c| c as f64);
+            assert!(math::dot(g, mesh.face_normal(face.index)).abs() < 1.0e-5);
+            assert!((math::length(g) - 2.0f64.sqrt()).abs() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn smoothing_flattens_a_spike() {
+        let mesh: Mesh = builder::grid(4, 4);
+        let field = vertex_scalars(&mesh, |v| {
+            let p = v.position().unwrap();
+            if p == [2.0, 2.0, 0.0] {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        let smoothed = smooth_scalars(&mesh, &field, 10, 0.5);
+        let values: Vec<f32> = mesh
+            .vertices()
+            .map(|v| *smoothed.get(v.index).unwrap())
+            .collect();
+        let max = values.iter().cloned().fold(f32::MIN, f32::max);
+        assert!(max < 0.25);
+        assert!(values.iter().all(|v| *v >= 0.0));
+        assert_eq!(smooth_scalars(&mesh, &field, 0, 0.5).len(), field.len());
+    }
+}
@@ -0,0 +1,172 @@
+use super::VertexScalars;
+use crate::math;
+use crate::*;
+use std::collections::HashMap;
+
+/// A polyline along which a vertex field equals `value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isoline<S: Scalar = f32> {
+    pub value: f32,
+    pub points: Vec<Position<S>>,
+    /// The edge each point lies on.
+    pub edges: Vec<EdgeIndex>,
+    /// True when the last point connects back to the first.
+    pub closed: bool,
+}
+
+/// Extracts the polylines where `field` crosses each of the `values`.
+///
+/// The field is interpolated linearly along edges and every face contributes
+/// straight segments between its crossings. Lines are oriented so that larger
+/// values lie to the left when looking down on the front of the faces. Faces
+/// with a vertex missing from the field are skipped.
+pub fn extract_isolines<S: Scalar>(
+    mesh: &Mesh<S>,
+    field: &VertexScalars,
+    values: &[f32],
+) -> Vec<Isoline<S>> {
+    values
+        .iter()
+        .flat_map(|value| extract(mesh, field, *value))
+        .collect()
+}
+
+/// The half-edge with the lower offset represents both sides of an edge.
+fn canonical<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex) -> EdgeIndex {
+    let twin = mesh.edge(edge).twin().index;
+    if twin.is_valid() && twin.offset < edge.offset {
+        twin
+    } else {
+        edge
+    }
+}
+
+fn extract<S: Scalar>(mesh: &Mesh<S>, field: &VertexScalars, value: f32) -> Vec<Isoline<S>> {
+    let above = |v: VertexIndex| field.get(v).map(|f| *f >= value);
+
+    // Segments run from the edge where a face loop leaves the region above
+    // `value` back to the edge where it entered, keeping that region on the left.
+    let mut segments: HashMap<EdgeIndex, EdgeIndex> = HashMap::new();
+    let mut has_predecessor: HashMap<EdgeIndex, bool> = HashMap::new();
+    for face in mesh.faces() {
+        let edges: Vec<EdgeFn<'_, S>> = face.edges().collect();
+        let sides: Option<Vec<bool>> = edges.iter().map(|e| above(e.vertex().index)).collect();
+        let Some(sides) = sides else {
+            continue;
+        };
+        let crossings: Vec<(usize, bool)> = (0..edges.len())
+            .filter(|i| sides[*i] != sides[(i + 1) % edges.len()])
+            .map(|i| (i, sides[(i + 1) % edges.len()]))
+            .collect();
+        let Some(first_entry) = crossings.iter().position(|(_, entering)| *entering) else {
+            continue;
+        };
+        for n in (0..crossings.len()).step_by(2) {
+            let (entry, _) = crossings[(first_entry + n) % crossings.len()];
+            let (exit, _) = crossings[(first_entry + n + 1) % crossings.len()];
+            let start = canonical(mesh, edges[exit].index);
+            let end = canonical(mesh, edges[entry].index);
+            segments.insert(start, end);
+            has_predecessor.insert(end, true);
+            has_predecessor.entry(start).or_insert(false);
+        }
+    }
+
+    let point = |edge: EdgeIndex| -> Position<S> {
+        let edge = mesh.edge(edge);
+        let (a, b) = (edge.vertex().index, edge.next().vertex().index);
+        let (fa, fb) = (field.get(a).copied(), field.get(b).copied());
+        let t = match (fa, fb) {
+            (Some(fa), Some(fb)) if fa != fb => ((value - fa) / (fb - fa)) as f64,
+            _ => 0.5,
+        };
+        scalar::from_f64(math::lerp(
+            mesh.vertex_position(a),
+            mesh.vertex_position(b),
+            t,
+        ))
+    };
+
+    // Open lines start where no segment leads in, what remains are loops.
+    let mut starts: Vec<EdgeIndex> = has_predecessor
+        .iter()
+        .filter(|(_, has)| !**has)
+        .map(|(edge, _)| *edge)
+        .collect();
+    starts.sort_by_key(|e| e.offset);
+    let mut rest: Vec<EdgeIndex> = segments.keys().copied().collect();
+    rest.sort_by_key(|e| e.offset);
+
+    let mut lines = Vec::new();
+    for start in starts.into_iter().chain(rest) {
+        if !segments.contains_key(&start) {
+            continue;
+        }
+        let mut edges = vec![start];
+        let mut current = start;
+        let mut closed = false;
+        while let Some(next) = segments.remove(&current) {
+            if next == start {
+                closed = true;
+                break;
+            }
+            edges.push(next);
+            current = next;
+        }
+        lines.push(Isoline {
+            value,
+            points: edges.iter().map(|e| point(*e)).collect(),
+            edges,
+            closed,
+        });
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_from<S: Scalar, F: Fn(Vec<f64>) -> f32>(mesh: &Mesh<S>, f: F) -> VertexScalars {
+        let mut field = VertexScalars::new();
+        for vertex in mesh.vertices() {
+            field.set(vertex.index, f(mesh.vertex_position(vertex.index).to_vec()));
+        }
+        field
+    }
+
+    #[test]
+    fn straight_lines_cross_a_grid() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(4, 2);
+        let field = field_from(&mesh, |p| p[0] as f32);
+        let lines = extract_isolines(&mesh, &field, &[1.5, 2.5]);
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(!line.closed);
+            assert_eq!(line.points.len(), 3);
+            assert!(line.points.iter().all(|p| p[0] == line.value));
+            // Larger values are to the left, so the line runs towards -y.
+            assert!(line.points[0][1] > line.points[2][1]);
+        }
+    }
+
+    #[test]
+    fn rings_close_around_a_peak() {
+        let mesh: Mesh = builder::grid(4, 4);
+        let field = field_from(&mesh, |p| {
+            4.0 - ((p[0] - 2.0).abs() + (p[1] - 2.0).abs()) as f32
+        });
+        let lines = extract_isolines(&mesh, &field, &[3.5]);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].closed);
+        assert_eq!(lines[0].points.len(), 4);
+    }
+
+    #[test]
+    fn values_outside_the_range_produce_nothing() {
+        let mesh: Mesh = builder::grid(2, 2);
+        let field = field_from(&mesh, |p| p[1] as f32);
+        assert!(extract_isolines(&mesh, &field, &[-1.0, 10.0]).is_empty());
+    }
+}
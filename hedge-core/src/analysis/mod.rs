@@ -0,0 +1,23 @@
+//! Analysis of meshes and of fields defined over their surface.
+
+use crate::attributes::Channel;
+use crate::{Face, Vertex};
+
+mod fields;
+mod isolines;
+mod streamlines;
+mod watertight;
+
+pub use self::fields::{face_gradients, smooth_scalars, vertex_scalars};
+pub use self::isolines::{extract_isolines, Isoline};
+pub use self::streamlines::{
+    face_tangents, trace_streamline, trace_streamline_with, Streamline, StreamlineOptions,
+};
+pub use self::watertight::{watertight_report, WatertightReport};
+
+/// A scalar value per vertex.
+pub type VertexScalars = Channel<Vertex, f32>;
+
+/// A vector per face, such as the gradient of a scalar field or a tangent
+/// field to trace streamlines along.
+pub type FaceVectors = Channel<Face, [f32; 3]>;
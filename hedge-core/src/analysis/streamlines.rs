@@ -0,0 +1,232 @@
+use super::FaceVectors;
+use crate::math::{self, Vec3};
+use crate::sample::SurfacePoint;
+use crate::*;
+
+/// Limits for `trace_streamline_with`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamlineOptions {
+    /// Maximum number of faces the line may cross.
+    pub max_segments: usize,
+}
+
+impl Default for StreamlineOptions {
+    fn default() -> Self {
+        StreamlineOptions { max_segments: 1024 }
+    }
+}
+
+/// A polyline across the surface which follows a tangent field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Streamline<S: Scalar = f32> {
+    pub points: Vec<Position<S>>,
+    /// The face crossed between each point and the next one.
+    pub faces: Vec<FaceIndex>,
+}
+
+/// Stores `f` evaluated for every face, projected into the plane of the face.
+pub fn face_tangents<S, F>(mesh: &Mesh<S>, f: F) -> FaceVectors
+where
+    S: Scalar,
+    F: Fn(FaceFn<'_, S>) -> [f32; 3],
+{
+    let mut field = FaceVectors::new();
+    for face in mesh.faces() {
+        let vector = f(face).map(|c| c as f64);
+        let tangent = tangential(vector, mesh.face_normal(face.index));
+        field.set(face.index, tangent.map(|c| c as f32));
+    }
+    field
+}
+
+fn tangential(vector: Vec3, normal: Vec3) -> Vec3 {
+    math::sub(vector, math::scale(normal, math::dot(vector, normal)))
+}
+
+/// Traces a line from `start` along `field` with the default limits.
+pub fn trace_streamline<S: Scalar>(
+    mesh: &Mesh<S>,
+    start: &SurfacePoint<S>,
+    field: &FaceVectors,
+) -> Streamline<S> {
+    trace_streamline_with(mesh, start, field, &StreamlineOptions::default())
+}
+
+/// Traces a line from `start` along `field`, walking straight across each
+/// face in the direction of its vector until an edge is reached.
+///
+/// Only the face and position of `start` are used. The line ends on the
+/// boundary, on faces without a vector, where the field turns back towards
+/// the face it came from or after `options.max_segments` faces.
+pub fn trace_streamline_with<S: Scalar>(
+    mesh: &Mesh<S>,
+    start: &SurfacePoint<S>,
+    field: &FaceVectors,
+    options: &StreamlineOptions,
+) -> Streamline<S> {
+    let mut line = Streamline {
+        points: vec![start.position],
+        faces: Vec::new(),
+    };
+    let mut face = start.face;
+    let mut position = scalar::to_f64(start.position);
+    let mut entry: Option<EdgeIndex> = None;
+
+    while line.faces.len() < options.max_segments && mesh.face(face).is_valid() {
+        let normal = mesh.face_normal(face);
+        let Some(vector) = field.get(face) else {
+            break;
+        };
+        let direction = math::normalize(tangential(vector.map(|c| c as f64), normal));
+        if math::length(direction) == 0.0 {
+            break;
+        }
+        if let Some(entry) = entry {
+            let along = math::sub(
+                mesh.vertex_position(mesh.edge(entry).next().vertex().index),
+                mesh.vertex_position(mesh.edge(entry).vertex().index),
+            );
+            if math::dot(direction, math::cross(normal, along)) <= 0.0 {
+                break;
+            }
+        }
+
+        let Some((exit, point)) = exit_edge(mesh, face, position, direction, normal, entry) else {
+            break;
+        };
+        line.points.push(scalar::from_f64(point));
+        line.faces.push(face);
+
+        let twin = mesh.edge(exit).twin();
+        if !twin.face().is_valid() {
+            break;
+        }
+        face = twin.face().index;
+        entry = Some(twin.index);
+        position = point;
+    }
+    line
+}
+
+/// The first edge hit by a ray inside the plane of a face.
+fn exit_edge<S: Scalar>(
+    mesh: &Mesh<S>,
+    face: FaceIndex,
+    origin: Vec3,
+    direction: Vec3,
+    normal: Vec3,
+    entry: Option<EdgeIndex>,
+) -> Option<(EdgeIndex, Vec3)> {
+    let mut best: Option<(f64, EdgeIndex)> = None;
+    for edge in mesh.face(face).edges() {
+        if Some(edge.index) == entry {
+            continue;
+        }
+        let a = mesh.vertex_position(edge.vertex().index);
+        let b = mesh.vertex_position(edge.next().vertex().index);
+        let along = math::sub(b, a);
+        let offset = math::sub(a, origin);
+        let denominator = math::dot(math::cross(direction, along), normal);
+        if denominator.abs() < f64::EPSILON {
+            continue;
+        }
+        let t = math::dot(math::cross(offset, along), normal) / denominator;
+        let s = math::dot(math::cross(offset, direction), normal) / denominator;
+        if t > 0.0 && (0.0..=1.0).contains(&s) && best.map(|(bt, _)| t < bt).unwrap_or(true) {
+            best = Some((t, edge.index));
+        }
+    }
+    best.map(|(t, edge)| (edge, math::add(origin, math::scale(direction, t))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_at(mesh: &Mesh, position: Position) -> SurfacePoint {
+        let face = mesh
+            .faces()
+            .find(|face| {
+                let corners: Vec<Position> = face.vertices().filter_map(|v| v.position()).collect();
+                (0..2).all(|axis| {
+                    let min = corners.iter().map(|p| p[axis]).fold(f32::MAX, f32::min);
+                    let max = corners.iter().map(|p| p[axis]).fold(f32::MIN, f32::max);
+                    min < position[axis] && position[axis] < max
+                })
+            })
+            .unwrap();
+        let vertex = face.vertices().next().unwrap().index;
+        SurfacePoint {
+            face: face.index,
+            vertices: [vertex; 3],
+            barycentric: [1.0, 0.0, 0.0],
+            position,
+            normal: [0.0, 0.0, 1.0],
+        }
+    }
+
+    #[test]
+    fn constant_fields_trace_straight_lines() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(4, 2);
+        let field = face_tangents(&mesh, |_| [1.0, 0.0, 0.5]);
+        let line = trace_streamline(&mesh, &start_at(&mesh, [0.5, 0.5, 0.0]), &field);
+        assert_eq!(line.faces.len(), 4);
+        assert_eq!(line.points.len(), 5);
+        assert!(line.points.iter().all(|p| (p[1] - 0.5).abs() < 1.0e-6));
+        assert!((line.points[4][0] - 4.0).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn lines_stop_where_the_field_turns_back() {
+        let mesh: Mesh = builder::grid(4, 1);
+        // Every face points towards the middle of the strip.
+        let field = face_tangents(&mesh, |face| {
+            let x = face
+                .vertices()
+                .filter_map(|v| v.position())
+                .map(|p| p[0])
+                .sum::<f32>()
+                / 4.0;
+            [2.0 - x, 0.0, 0.0]
+        });
+        let line = trace_streamline(&mesh, &start_at(&mesh, [0.5, 0.5, 0.0]), &field);
+        assert_eq!(line.faces.len(), 2);
+        assert!((line.points.last().unwrap()[0] - 2.0).abs() < 1.0e-6);
+
+        let options = StreamlineOptions { max_segments: 1 };
+        let start = start_at(&mesh, [0.5, 0.5, 0.0]);
+        assert_eq!(
+            trace_streamline_with(&mesh, &start, &field, &options)
+                .faces
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn lines_wrap_around_closed_surfaces() {
+        let mesh: Mesh = builder::cube();
+        let field = face_tangents(&mesh, |face| {
+            // Circulate around the z axis on the sides of the cube.
+            let n = mesh.face_normal(face.index);
+            [-n[1] as f32, n[0] as f32, 0.0]
+        });
+        let side = mesh
+            .faces()
+            .find(|face| mesh.face_normal(face.index)[1] < -0.99)
+            .unwrap();
+        let start = SurfacePoint {
+            face: side.index,
+            vertices: [side.vertices().next().unwrap().index; 3],
+            barycentric: [1.0, 0.0, 0.0],
+            position: [0.5, 0.0, 0.5],
+            normal: [0.0, -1.0, 0.0],
+        };
+        let options = StreamlineOptions { max_segments: 8 };
+        let line = trace_streamline_with(&mesh, &start, &field, &options);
+        assert_eq!(line.faces.len(), 8);
+        assert_eq!(line.faces[0], line.faces[4]);
+        assert!(line.points.iter().all(|p| (p[2] - 0.5).abs() < 1.0e-6));
+    }
+}
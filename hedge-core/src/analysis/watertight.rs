@@ -0,0 +1,207 @@
+use crate::bvh::{Aabb, Bvh};
+use crate::math::{self, Vec3};
+use crate::predicates::orient3d;
+use crate::*;
+use std::collections::HashSet;
+
+/// The outcome of `watertight_report`, listing everything that keeps a mesh
+/// from bounding a solid.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatertightReport {
+    /// Half-edges without a face.
+    pub boundary_edges: Vec<EdgeIndex>,
+    /// Half-edges which run in the same direction as their twin.
+    pub inconsistent_edges: Vec<EdgeIndex>,
+    /// Pairs of faces which pass through each other.
+    pub intersecting_faces: Vec<(FaceIndex, FaceIndex)>,
+    /// Signed volume enclosed by the faces, positive when they face outwards.
+    pub volume: f64,
+    /// The tolerance the report was created with.
+    pub eps: f64,
+}
+
+impl WatertightReport {
+    pub fn is_closed(&self) -> bool {
+        self.boundary_edges.is_empty()
+    }
+
+    pub fn is_consistently_oriented(&self) -> bool {
+        self.inconsistent_edges.is_empty()
+    }
+
+    pub fn is_self_intersecting(&self) -> bool {
+        !self.intersecting_faces.is_empty()
+    }
+
+    pub fn has_positive_volume(&self) -> bool {
+        self.volume > self.eps
+    }
+
+    /// True when the mesh passes every check.
+    pub fn passed(&self) -> bool {
+        self.is_closed()
+            && self.is_consistently_oriented()
+            && !self.is_self_intersecting()
+            && self.has_positive_volume()
+    }
+}
+
+/// Checks that a mesh is closed, consistently oriented, free of self
+/// intersections and encloses a positive volume.
+///
+/// Faces are split into fans of triangles. Two faces only count as
+/// intersecting when an edge of one crosses the other further than `eps`
+/// from its plane, so neighbors touching along shared edges and corners are
+/// fine.
+pub fn watertight_report<S: Scalar>(mesh: &Mesh<S>, eps: f64) -> WatertightReport {
+    let mut report = WatertightReport {
+        eps,
+        ..WatertightReport::default()
+    };
+    for edge in mesh.edges() {
+        if !edge.face().is_valid() {
+            report.boundary_edges.push(edge.index);
+        }
+        let twin = edge.twin();
+        let reversed = twin.is_valid()
+            && edge.vertex().point().is_some()
+            && edge.vertex().element().map(|v| v.point_index)
+                == twin.next().vertex().element().map(|v| v.point_index);
+        if !reversed {
+            report.inconsistent_edges.push(edge.index);
+        }
+    }
+
+    let triangles = triangles(mesh);
+    let bounds = Aabb::from_points(triangles.iter().flat_map(|(_, t)| t.iter()));
+    let center = bounds.center();
+    report.volume = triangles
+        .iter()
+        .map(|(_, t)| {
+            let [a, b, c] = t.map(|p| math::sub(p, center));
+            math::dot(a, math::cross(b, c)) / 6.0
+        })
+        .sum();
+
+    let bvh = Bvh::new(mesh);
+    let mut intersecting = HashSet::new();
+    for (face, triangle) in &triangles {
+        let mut aabb = Aabb::from_points(triangle.iter());
+        aabb.min = aabb.min.map(|m| m - eps);
+        aabb.max = aabb.max.map(|m| m + eps);
+        for (other, candidate) in bvh.query_triangles(&aabb) {
+            if other == *face || intersecting.contains(&(other, *face)) {
+                continue;
+            }
+            let pair = (*face, other);
+            if !intersecting.contains(&pair)
+                && (crosses(triangle, &candidate, eps) || crosses(&candidate, triangle, eps))
+            {
+                intersecting.insert(pair);
+                report.intersecting_faces.push(pair);
+            }
+        }
+    }
+    report
+}
+
+fn triangles<S: Scalar>(mesh: &Mesh<S>) -> Vec<(FaceIndex, [Vec3; 3])> {
+    let mut triangles = Vec::new();
+    for face in mesh.faces() {
+        let corners: Vec<Vec3> = face
+            .vertices()
+            .map(|v| mesh.vertex_position(v.index))
+            .collect();
+        for i in 1..corners.len().saturating_sub(1) {
+            triangles.push((face.index, [corners[0], corners[i], corners[i + 1]]));
+        }
+    }
+    triangles
+}
+
+/// Whether an edge of `a` passes through the inside of `b`.
+fn crosses(a: &[Vec3; 3], b: &[Vec3; 3], eps: f64) -> bool {
+    let double_area = math::length(math::cross(math::sub(b[1], b[0]), math::sub(b[2], b[0])));
+    if double_area <= 0.0 {
+        return false;
+    }
+    // Points within `eps` of the plane of `b` count as lying on it.
+    let side = |p: Vec3| {
+        let det = orient3d(b[0], b[1], b[2], p);
+        if det.abs() / double_area <= eps {
+            0.0
+        } else {
+            det.signum()
+        }
+    };
+    let sides = a.map(side);
+    (0..3).any(|i| {
+        let j = (i + 1) % 3;
+        if sides[i] * sides[j] >= 0.0 {
+            return false;
+        }
+        let (p, q) = (a[i], a[j]);
+        let around = [
+            orient3d(p, q, b[0], b[1]),
+            orient3d(p, q, b[1], b[2]),
+            orient3d(p, q, b[2], b[0]),
+        ];
+        around.iter().all(|s| *s > 0.0) || around.iter().all(|s| *s < 0.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cubes_are_watertight() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::cube();
+        let report = watertight_report(&mesh, 1.0e-9);
+        assert!(report.passed(), "{:?}", report);
+        assert!((report.volume - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn open_and_inverted_meshes_fail() {
+        let grid: Mesh = builder::grid(2, 2);
+        let report = watertight_report(&grid, 1.0e-9);
+        assert!(!report.is_closed());
+        assert_eq!(report.boundary_edges.len(), 8);
+        assert!(report.is_consistently_oriented());
+        assert!(!report.passed());
+
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let inward = [vec![0, 1, 2], vec![0, 3, 1], vec![1, 3, 2], vec![0, 2, 3]];
+        let mesh: Mesh = builder::from_polygons(&positions, &inward);
+        let report = watertight_report(&mesh, 1.0e-9);
+        assert!(report.is_closed() && report.is_consistently_oriented());
+        assert!(report.volume < 0.0);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn crossing_faces_are_reported() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0],
+            [0.5, 0.5, -1.0],
+            [0.5, 0.5, 1.0],
+            [3.0, 3.0, 0.0],
+        ];
+        let mesh: Mesh = builder::from_polygons(&positions, &[vec![0, 1, 2], vec![3, 5, 4]]);
+        let report = watertight_report(&mesh, 1.0e-9);
+        assert_eq!(report.intersecting_faces.len(), 1);
+
+        // Neighbors sharing an edge only touch.
+        let mesh: Mesh = builder::triangle_grid(2, 2);
+        assert!(!watertight_report(&mesh, 1.0e-9).is_self_intersecting());
+    }
+}
@@ -0,0 +1,329 @@
+//! Per-element attribute storage, dense and sparse.
+
+use crate::{hbuf, Generation, Handle, Offset};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Neg;
+
+/// Values stored densely by element offset.
+///
+/// The generation of the handle used to write a value is kept alongside it so
+/// stale handles, or cells that were reused by the mesh, read back as `None`.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Channel<K, T> {
+    values: Vec<Option<(Generation, T)>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _kind: PhantomData<K>,
+}
+
+impl<K, T> Default for Channel<K, T> {
+    fn default() -> Self {
+        Channel {
+            values: Vec::new(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<K, T: Clone> Clone for Channel<K, T> {
+    fn clone(&self) -> Self {
+        Channel {
+            values: self.values.clone(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<K, T> fmt::Debug for Channel<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Channel<> {{ {} values }}", self.len())
+    }
+}
+
+impl<K, T> Channel<K, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, index: Handle<K>) -> Option<&T> {
+        match self.values.get(index.offset as usize) {
+            Some(Some((generation, value))) if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, index: Handle<K>) -> Option<&mut T> {
+        match self.values.get_mut(index.offset as usize) {
+            Some(Some((generation, value))) if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Stores a value, returning whatever was previously set for the same handle.
+    pub fn set(&mut self, index: Handle<K>, value: T) -> Option<T> {
+        let offset = index.offset as usize;
+        if self.values.len() <= offset {
+            self.values.resize_with(offset + 1, || None);
+        }
+        self.values[offset]
+            .replace((index.generation, value))
+            .and_then(|(generation, value)| (generation == index.generation).then_some(value))
+    }
+
+    pub fn remove(&mut self, index: Handle<K>) -> Option<T> {
+        self.get(index)?;
+        self.values[index.offset as usize]
+            .take()
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains(&self, index: Handle<K>) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Returns the number of stored values.
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.iter().all(|v| v.is_none())
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<K>, &T)> {
+        self.values.iter().enumerate().filter_map(|(offset, slot)| {
+            slot.as_ref()
+                .map(|(generation, value)| (Handle::new(offset as u32, *generation), value))
+        })
+    }
+
+    /// Moves every value to the handle its element was renumbered to,
+    /// dropping values of elements missing from the map.
+    pub fn remapped(&self, map: &HashMap<Handle<K>, Handle<K>>) -> Channel<K, T>
+    where
+        T: Clone,
+    {
+        let mut moved = Channel::new();
+        for (index, value) in self.iter() {
+            if let Some(target) = map.get(&index) {
+                moved.set(*target, value.clone());
+            }
+        }
+        moved
+    }
+}
+
+impl<K, T: Copy + Neg<Output = T>> Channel<K, [T; 3]> {
+    /// Negates every stored vector, for normals of faces that were flipped.
+    pub fn flip_normals(&mut self) {
+        for (_, value) in self.values.iter_mut().flatten() {
+            *value = value.map(|c| -c);
+        }
+    }
+}
+
+/// Values attached to a few elements, stored in a hash map.
+///
+/// Fits side data on a handful of faces or edges where a dense `Channel`
+/// would mostly hold empty slots. Like a channel it keeps the generation of
+/// the handle a value was inserted with and ignores stale handles.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "V: serde::Serialize",
+        deserialize = "V: serde::Deserialize<'de>"
+    ))
+)]
+pub struct SparseElementMap<K, V> {
+    values: HashMap<Offset, (Generation, V)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _kind: PhantomData<K>,
+}
+
+impl<K, V> Default for SparseElementMap<K, V> {
+    fn default() -> Self {
+        SparseElementMap {
+            values: HashMap::new(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<K, V: Clone> Clone for SparseElementMap<K, V> {
+    fn clone(&self) -> Self {
+        SparseElementMap {
+            values: self.values.clone(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for SparseElementMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SparseElementMap<> {{ {} values }}", self.len())
+    }
+}
+
+impl<K, V> SparseElementMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, index: Handle<K>) -> Option<&V> {
+        match self.values.get(&index.offset) {
+            Some((generation, value)) if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, index: Handle<K>) -> Option<&mut V> {
+        match self.values.get_mut(&index.offset) {
+            Some((generation, value)) if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Stores a value, returning whatever was previously set for the same
+    /// handle. A value left behind by an older generation is dropped.
+    pub fn insert(&mut self, index: Handle<K>, value: V) -> Option<V> {
+        self.values
+            .insert(index.offset, (index.generation, value))
+            .and_then(|(generation, value)| (generation == index.generation).then_some(value))
+    }
+
+    pub fn remove(&mut self, index: Handle<K>) -> Option<V> {
+        self.get(index)?;
+        self.values.remove(&index.offset).map(|(_, value)| value)
+    }
+
+    pub fn contains(&self, index: Handle<K>) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Returns the number of stored values, including any whose element
+    /// has since been removed.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Stored values in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<K>, &V)> {
+        self.values
+            .iter()
+            .map(|(offset, (generation, value))| (Handle::new(*offset, *generation), value))
+    }
+
+    /// Drops the values of elements which are no longer live in `buffer`.
+    pub fn retain_live<D: Default>(&mut self, buffer: &hbuf::ElementBuffer<D, K>) {
+        self.values.retain(|offset, (generation, _)| {
+            buffer.get(Handle::new(*offset, *generation)).is_some()
+        });
+    }
+
+    /// Follows a `defragment` or `compact` of the element buffer, given the
+    /// plan it returned. Each element moves from the second offset of a
+    /// pair to the first and keeps its generation, values of the cells that
+    /// were free are dropped.
+    pub fn apply_defragment(&mut self, plan: &[(Offset, Offset)]) {
+        let mut moved = Vec::with_capacity(plan.len());
+        for (free, active) in plan {
+            self.values.remove(free);
+            if let Some(entry) = self.values.remove(active) {
+                moved.push((*free, entry));
+            }
+        }
+        self.values.extend(moved);
+    }
+
+    /// Moves every value to the handle its element was renumbered to,
+    /// dropping values of elements missing from the map.
+    pub fn remap(&mut self, map: &HashMap<Handle<K>, Handle<K>>) {
+        let values = std::mem::take(&mut self.values);
+        for (offset, (generation, value)) in values {
+            if let Some(target) = map.get(&Handle::new(offset, generation)) {
+                self.values
+                    .insert(target.offset, (target.generation, value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Element;
+
+    #[test]
+    fn stale_handles_are_rejected() {
+        let mut channel: Channel<Element, f32> = Channel::new();
+        let h1 = Handle::new(3, 1);
+        let h2 = Handle::new(3, 2);
+
+        assert!(channel.set(h1, 1.0).is_none());
+        assert_eq!(channel.get(h1), Some(&1.0));
+        assert!(channel.get(h2).is_none());
+
+        assert!(channel.set(h2, 2.0).is_none());
+        assert!(channel.get(h1).is_none());
+        assert_eq!(channel.len(), 1);
+        assert_eq!(channel.remove(h2), Some(2.0));
+        assert!(channel.is_empty());
+    }
+
+    #[test]
+    fn iterates_stored_values() {
+        let mut channel: Channel<Element, u32> = Channel::new();
+        channel.set(Handle::new(1, 1), 10);
+        channel.set(Handle::new(5, 1), 50);
+        let values: Vec<u32> = channel.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![10, 50]);
+    }
+
+    #[test]
+    fn sparse_values_follow_defragment() {
+        let mut buffer: hbuf::ElementBuffer<u32> = hbuf::ElementBuffer::default();
+        let handles: Vec<Handle<u32>> = (0..6).map(|i| buffer.push(i)).collect();
+        let mut map: SparseElementMap<u32, &str> = SparseElementMap::new();
+        map.insert(handles[1], "removed");
+        map.insert(handles[5], "last");
+        assert!(map.get(Handle::new(handles[5].offset, 2)).is_none());
+
+        buffer.remove(handles[1]);
+        map.retain_live(&buffer);
+        assert_eq!(map.len(), 1);
+
+        let plan = buffer.defragment();
+        map.apply_defragment(&plan);
+        let (moved, value) = map.iter().next().unwrap();
+        assert_eq!(*value, "last");
+        assert_eq!(buffer.get(moved), Some(&5));
+
+        let target = Handle::new(1, 1);
+        map.remap(&HashMap::from([(moved, target)]));
+        assert_eq!(map.get(target), Some(&"last"));
+        assert_eq!(map.remove(target), Some("last"));
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,401 @@
+//! Union, intersection and difference of closed meshes.
+//!
+//! Every face is fan triangulated and each triangle is cut by the planes of
+//! the triangles of the other mesh passing through it, so no piece crosses
+//! the other surface. Which side of a plane a corner lies on is decided
+//! exactly with `predicates::orient3d`. Pieces are then kept or dropped depending on whether
+//! their centroid lies inside the other mesh, found by casting a ray.
+//! Finally corners landing on the sides of neighboring pieces are inserted
+//! into them and coincident corners are welded, which stitches the pieces
+//! into a closed mesh again.
+//!
+//! Inputs are expected to be closed, consistently oriented and in general
+//! position with respect to each other. Faces of the two meshes lying in a
+//! common plane are not handled and give unpredictable results.
+
+use crate::bvh::{Aabb, Bvh};
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::HashMap;
+
+/// Number of faces cut between progress reports.
+const CHECKPOINT_INTERVAL: usize = 64;
+
+/// Which parts of two solids a boolean operation keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    /// Everything inside either solid.
+    Union,
+    /// Everything inside both solids.
+    Intersection,
+    /// Everything inside the first solid but not inside the second.
+    Difference,
+}
+
+/// Combines two closed meshes into a new one, see the module docs.
+pub fn boolean<S: Scalar>(a: &Mesh<S>, b: &Mesh<S>, op: BooleanOp) -> Result<Mesh<S>> {
+    boolean_with(a, b, op, &mut progress::Silent)
+}
+
+/// `boolean` reporting the share of faces of both meshes cut so far.
+pub fn boolean_with<S: Scalar>(
+    a: &Mesh<S>,
+    b: &Mesh<S>,
+    op: BooleanOp,
+    progress: &mut dyn Progress,
+) -> Result<Mesh<S>> {
+    for mesh in [a, b] {
+        if let Some(edge) = mesh.edges().find(|e| !e.face().is_valid()) {
+            return Err(Error::InvalidArgument(format!(
+                "booleans need closed meshes, {:?} lies on a boundary",
+                edge.index
+            )));
+        }
+    }
+    let (bvh_a, bvh_b) = (Bvh::new(a), Bvh::new(b));
+    let bounds = bvh_a.bounds().union(&bvh_b.bounds());
+    if bounds.is_empty() {
+        return Ok(Mesh::new());
+    }
+    let diagonal = math::length(bounds.extent()).max(f64::MIN_POSITIVE);
+    let eps = 1.0e-10 * diagonal;
+    let weld = 1.0e-7 * diagonal;
+
+    // Which pieces of each mesh survive and whether they need flipping.
+    let (keep_a, keep_b, flip_b) = match op {
+        BooleanOp::Union => (false, false, false),
+        BooleanOp::Intersection => (true, true, false),
+        BooleanOp::Difference => (false, true, true),
+    };
+    let total = a.face_count() + b.face_count();
+    let mut polygons = pieces(a, &bvh_b, progress, 0, total)?
+        .into_iter()
+        .filter(|p| is_inside(&bvh_b, centroid(p), eps) == keep_a)
+        .collect::<Vec<_>>();
+    for mut piece in pieces(b, &bvh_a, progress, a.face_count(), total)? {
+        if is_inside(&bvh_a, centroid(&piece), eps) == keep_b {
+            if flip_b {
+                piece.reverse();
+            }
+            polygons.push(piece);
+        }
+    }
+    let mesh = stitch(&polygons, weld);
+    progress::checkpoint(progress, total, total)?;
+    Ok(mesh)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Everything inside this mesh or `other`, see `boolean::boolean`.
+    pub fn union(&self, other: &Mesh<S>) -> Result<Mesh<S>> {
+        boolean(self, other, BooleanOp::Union)
+    }
+
+    /// Everything inside both this mesh and `other`.
+    pub fn intersection(&self, other: &Mesh<S>) -> Result<Mesh<S>> {
+        boolean(self, other, BooleanOp::Intersection)
+    }
+
+    /// Everything inside this mesh but outside of `other`.
+    pub fn difference(&self, other: &Mesh<S>) -> Result<Mesh<S>> {
+        boolean(self, other, BooleanOp::Difference)
+    }
+}
+
+/// The triangles of `mesh` cut by the planes of the triangles of `other`
+/// passing through them, as convex polygons. Faces are counted towards
+/// `total` starting at `done`.
+fn pieces<S: Scalar>(
+    mesh: &Mesh<S>,
+    other: &Bvh,
+    progress: &mut dyn Progress,
+    done: usize,
+    total: usize,
+) -> Result<Vec<Vec<Vec3>>> {
+    let mut pieces = Vec::new();
+    for (k, face) in mesh.faces().enumerate() {
+        if k % CHECKPOINT_INTERVAL == 0 {
+            progress::checkpoint(progress, done + k, total)?;
+        }
+        let corners: Vec<Vec3> = face
+            .vertices()
+            .map(|v| mesh.vertex_position(v.index))
+            .collect();
+        for i in 1..corners.len().saturating_sub(1) {
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            let mut parts = vec![triangle.to_vec()];
+            let bounds = Aabb::from_points(triangle.iter());
+            for (_, candidate) in other.query_triangles(&bounds) {
+                if !straddles(&candidate, &triangle) || !straddles(&triangle, &candidate) {
+                    continue;
+                }
+                parts = parts
+                    .into_iter()
+                    .flat_map(|part| split(part, &candidate))
+                    .collect();
+            }
+            pieces.extend(parts);
+        }
+    }
+    Ok(pieces)
+}
+
+/// Positive on the side of the plane of `triangle` its normal points to,
+/// negative on the other and exactly zero in the plane. The magnitude is
+/// proportional to the distance from the plane.
+fn side(triangle: &[Vec3; 3], p: Vec3) -> f64 {
+    -predicates::orient3d(triangle[0], triangle[1], triangle[2], p)
+}
+
+/// True when corners lie on both sides of the plane of `triangle`, which a
+/// degenerate triangle never has.
+fn straddles(triangle: &[Vec3; 3], corners: &[Vec3]) -> bool {
+    let sides = corners.iter().map(|p| side(triangle, *p));
+    let (below, above) = sides.fold((false, false), |(below, above), d| {
+        (below || d < 0.0, above || d > 0.0)
+    });
+    below && above
+}
+
+/// Splits a convex polygon by the plane of `triangle`, giving it back whole
+/// unless corners lie on both sides.
+fn split(polygon: Vec<Vec3>, triangle: &[Vec3; 3]) -> Vec<Vec<Vec3>> {
+    if !straddles(triangle, &polygon) {
+        return vec![polygon];
+    }
+    let (mut above, mut below) = (Vec::new(), Vec::new());
+    for (i, p) in polygon.iter().enumerate() {
+        let q = polygon[(i + 1) % polygon.len()];
+        let (dp, dq) = (side(triangle, *p), side(triangle, q));
+        if dp >= 0.0 {
+            above.push(*p);
+        }
+        if dp <= 0.0 {
+            below.push(*p);
+        }
+        if (dp > 0.0 && dq < 0.0) || (dp < 0.0 && dq > 0.0) {
+            let crossing = math::lerp(*p, q, dp / (dp - dq));
+            above.push(crossing);
+            below.push(crossing);
+        }
+    }
+    [above, below]
+        .into_iter()
+        .filter(|part| part.len() >= 3)
+        .collect()
+}
+
+fn centroid(polygon: &[Vec3]) -> Vec3 {
+    let sum = polygon.iter().fold([0.0; 3], |sum, p| math::add(sum, *p));
+    math::scale(sum, 1.0 / polygon.len() as f64)
+}
+
+/// Winding number test along a ray in an arbitrary direction, which makes
+/// passing exactly through an edge of the mesh unlikely.
+fn is_inside(bvh: &Bvh, point: Vec3, eps: f64) -> bool {
+    let direction = math::normalize([0.8631, 0.3951, 0.3146]);
+    let mut hits = bvh.intersect_ray_all(point, direction);
+    hits.retain(|hit| hit.distance > eps);
+    // A ray through a shared edge hits both triangles and must only count once.
+    hits.dedup_by(|a, b| a.front_facing == b.front_facing && (a.distance - b.distance).abs() < eps);
+    let winding: i32 = hits
+        .iter()
+        .map(|hit| if hit.front_facing { -1 } else { 1 })
+        .sum();
+    winding > 0
+}
+
+/// Builds a mesh from polygons whose corners may sit on the sides of their
+/// neighbors, welding corners closer than `weld`.
+fn stitch<S: Scalar>(polygons: &[Vec<Vec3>], weld: f64) -> Mesh<S> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut welded: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let cell = |p: Vec3| p.map(|v| (v / weld).floor() as i64);
+    let mut corner_index = |p: Vec3, positions: &mut Vec<Vec3>| {
+        let key = cell(p);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let nearby = welded.get(&[key[0] + x, key[1] + y, key[2] + z]);
+                    let found = nearby
+                        .into_iter()
+                        .flatten()
+                        .find(|i| math::distance(positions[**i], p) <= weld);
+                    if let Some(index) = found {
+                        return *index;
+                    }
+                }
+            }
+        }
+        positions.push(p);
+        welded.entry(key).or_default().push(positions.len() - 1);
+        positions.len() - 1
+    };
+    let indexed: Vec<Vec<usize>> = polygons
+        .iter()
+        .map(|polygon| {
+            polygon
+                .iter()
+                .map(|p| corner_index(*p, &mut positions))
+                .collect()
+        })
+        .collect();
+
+    // Corners are bucketed by a coarser grid sized to the pieces so that
+    // each side only looks at the corners around it.
+    let sides: Vec<f64> = indexed
+        .iter()
+        .flat_map(|polygon| {
+            (0..polygon.len()).map(|i| {
+                let j = (i + 1) % polygon.len();
+                math::distance(positions[polygon[i]], positions[polygon[j]])
+            })
+        })
+        .collect();
+    let bucket = (sides.iter().sum::<f64>() / sides.len().max(1) as f64).max(weld);
+    let bucket_of = |p: Vec3| p.map(|v| (v / bucket).floor() as i64);
+    let mut buckets: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (index, p) in positions.iter().enumerate() {
+        buckets.entry(bucket_of(*p)).or_default().push(index);
+    }
+
+    let mut stitched = Vec::with_capacity(indexed.len());
+    for polygon in indexed {
+        let mut corners = Vec::with_capacity(polygon.len());
+        for (i, start) in polygon.iter().enumerate() {
+            let end = polygon[(i + 1) % polygon.len()];
+            corners.push(*start);
+            if *start == end {
+                continue;
+            }
+            let (p, q) = (positions[*start], positions[end]);
+            let along = math::sub(q, p);
+            let length = math::dot(along, along);
+            let (low, high) = (bucket_of(p), bucket_of(q));
+            let mut between = Vec::new();
+            for x in low[0].min(high[0])..=low[0].max(high[0]) {
+                for y in low[1].min(high[1])..=low[1].max(high[1]) {
+                    for z in low[2].min(high[2])..=low[2].max(high[2]) {
+                        for index in buckets.get(&[x, y, z]).into_iter().flatten() {
+                            let offset = math::sub(positions[*index], p);
+                            let t = math::dot(offset, along) / length;
+                            if *index == *start || *index == end || t <= 0.0 || t >= 1.0 {
+                                continue;
+                            }
+                            let off_line = math::sub(offset, math::scale(along, t));
+                            if math::length(off_line) <= weld {
+                                between.push((t, *index));
+                            }
+                        }
+                    }
+                }
+            }
+            between.sort_by(|a, b| a.0.total_cmp(&b.0));
+            corners.extend(between.into_iter().map(|(_, index)| index));
+        }
+        corners.dedup();
+        while corners.len() > 1 && corners.first() == corners.last() {
+            corners.pop();
+        }
+        if corners.len() >= 3 {
+            stitched.push(corners);
+        }
+    }
+    let positions: Vec<Position<S>> = positions.into_iter().map(scalar::from_f64).collect();
+    Mesh::from_polygons(&positions, &stitched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+
+    fn shifted_cube(offset: Vec3) -> Mesh<f64> {
+        let mut mesh: Mesh<f64> = builder::cube();
+        for (_, point) in mesh.points.iter_mut() {
+            point.position = math::add(point.position, offset);
+        }
+        mesh
+    }
+
+    fn assert_solid(mesh: &Mesh<f64>, volume: f64) {
+        builder::assert_connectivity(mesh);
+        let report = analysis::watertight_report(mesh, 1.0e-9);
+        assert!(report.is_closed(), "{:?}", report);
+        assert!(report.is_consistently_oriented());
+        assert!((report.volume - volume).abs() < 1.0e-9, "{}", report.volume);
+    }
+
+    #[test]
+    fn overlapping_cubes_combine_into_solids() {
+        let _ = env_logger::try_init();
+        let a = shifted_cube([0.0; 3]);
+        let b = shifted_cube([0.5, 0.3, 0.2]);
+        let overlap = 0.5 * 0.7 * 0.8;
+        assert_solid(&a.union(&b).unwrap(), 2.0 - overlap);
+        assert_solid(&a.intersection(&b).unwrap(), overlap);
+        assert_solid(&a.difference(&b).unwrap(), 1.0 - overlap);
+        assert_solid(&b.difference(&a).unwrap(), 1.0 - overlap);
+    }
+
+    #[test]
+    fn disjoint_and_open_inputs() {
+        let a = shifted_cube([0.0; 3]);
+        let b = shifted_cube([3.0, 0.0, 0.0]);
+        assert_solid(&a.union(&b).unwrap(), 2.0);
+        assert_eq!(a.intersection(&b).unwrap().face_count(), 0);
+        assert_solid(&a.difference(&b).unwrap(), 1.0);
+
+        let open: Mesh<f64> = builder::grid(1, 1);
+        assert!(matches!(a.union(&open), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn reports_progress_until_cancelled() {
+        let a: Mesh<f64> = builder::octahedron();
+        let b = shifted_cube([0.2, 0.1, 0.3]);
+        let mut fractions = Vec::new();
+        let mut record = |fraction| fractions.push(fraction);
+        boolean_with(&a, &b, BooleanOp::Union, &mut record).unwrap();
+        assert_eq!(fractions.len(), 3);
+        assert!(fractions.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+
+        let cancellation = progress::Cancellation::new();
+        cancellation.cancel();
+        let result = boolean_with(&a, &b, BooleanOp::Union, &mut cancellation.clone());
+        assert_eq!(result.unwrap_err(), Error::Cancelled);
+    }
+
+    #[test]
+    fn sides_of_planes_are_exact() {
+        let triangle = [[0.1, 0.0, 1.0], [1.0, 0.1, 1.0], [0.0, 1.0, 1.0]];
+        let above = [0.3, 0.3, 1.0 + f64::EPSILON];
+        let below = [0.3, 0.3, 1.0 - f64::EPSILON];
+        assert!(side(&triangle, above) > 0.0 && side(&triangle, below) < 0.0);
+        assert_eq!(side(&triangle, [0.5, 0.5, 1.0]), 0.0);
+        // Corners in the plane don't count as being on either side.
+        let touching = [[0.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.5, 0.0, 2.0]];
+        assert!(!straddles(&triangle, &touching));
+        assert_eq!(split(touching.to_vec(), &triangle).len(), 1);
+        assert!(straddles(&triangle, &[above, below, [0.0; 3]]));
+    }
+
+    #[test]
+    fn slanted_faces_keep_volumes_consistent() {
+        let octahedron: Mesh<f64> = builder::octahedron();
+        let cube = shifted_cube([0.1, 0.2, -0.3]);
+        let volume = |mesh: &Mesh<f64>| {
+            let report = analysis::watertight_report(mesh, 1.0e-9);
+            assert!(report.is_closed() && report.is_consistently_oriented());
+            report.volume
+        };
+        let both = volume(&octahedron.intersection(&cube).unwrap());
+        let either = volume(&octahedron.union(&cube).unwrap());
+        let outside = volume(&octahedron.difference(&cube).unwrap());
+        let whole = volume(&octahedron);
+        assert!(both > 0.0 && both < 1.0);
+        assert!((either - (whole + 1.0 - both)).abs() < 1.0e-9);
+        assert!((outside - (whole - both)).abs() < 1.0e-9);
+    }
+}
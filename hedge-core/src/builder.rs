@@ -0,0 +1,1232 @@
+//! Bulk construction of connectivity from indexed polygons.
+
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+/// The vertices of each point, kept by the mesh for `Mesh::add_face`
+/// along with the version of the vertex buffer they were taken from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PointVertices {
+    version: Option<hbuf::Version>,
+    vertices: HashMap<PointIndex, Vec<VertexIndex>>,
+}
+
+impl PointVertices {
+    fn at(&self, point: PointIndex) -> impl Iterator<Item = VertexIndex> + '_ {
+        self.vertices.get(&point).into_iter().flatten().copied()
+    }
+
+    fn insert(&mut self, point: PointIndex, vertex: VertexIndex) {
+        self.vertices.entry(point).or_default().push(vertex);
+    }
+}
+
+/// Builds faces for every polygon, pairing twins through a directed edge map.
+///
+/// Each point gets a single vertex and boundary half-edges are linked into
+/// loops once every polygon has been added. Polygons which would reuse an
+/// already claimed half-edge (non-manifold or inconsistently wound input)
+/// are skipped and an invalid index is returned in their slot.
+pub(crate) fn add_polygons<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    polygons: &[Vec<PointIndex>],
+) -> Vec<FaceIndex> {
+    let mut vertices: HashMap<PointIndex, VertexIndex> = HashMap::new();
+    let mut half_edges: HashMap<(PointIndex, PointIndex), EdgeIndex> = HashMap::new();
+    let mut faces = Vec::with_capacity(polygons.len());
+
+    for (polygon_index, polygon) in polygons.iter().enumerate() {
+        if !is_buildable(mesh, polygon, &half_edges) {
+            log::warn!("Skipping polygon {} which can't be built.", polygon_index);
+            faces.push(FaceIndex::default());
+            continue;
+        }
+
+        let loop_edges: Vec<EdgeIndex> = (0..polygon.len())
+            .map(|i| {
+                let a = polygon[i];
+                let b = polygon[(i + 1) % polygon.len()];
+                if let Some(edge) = half_edges.get(&(a, b)) {
+                    return *edge;
+                }
+                let va = vertex_for(mesh, &mut vertices, a);
+                let vb = vertex_for(mesh, &mut vertices, b);
+                let edge = utils::full_edge_unchecked(mesh, va, vb);
+                let twin = mesh.edge(edge).twin().index;
+                half_edges.insert((a, b), edge);
+                half_edges.insert((b, a), twin);
+                edge
+            })
+            .collect();
+
+        let face = mesh.add_element(Face::new(loop_edges[0]));
+        for i in 0..loop_edges.len() {
+            let next = loop_edges[(i + 1) % loop_edges.len()];
+            utils::link_unchecked(mesh, loop_edges[i], next);
+            if let Some(edge) = mesh.get_element_mut(loop_edges[i]) {
+                edge.face_index = face;
+            }
+        }
+        faces.push(face);
+    }
+
+    let boundary: Vec<EdgeIndex> = half_edges
+        .values()
+        .copied()
+        .filter(|edge| !mesh.edge(*edge).face().is_valid())
+        .collect();
+    link_boundary_loops(mesh, &boundary);
+
+    for edge_index in half_edges.values() {
+        let edge = mesh.edge(*edge_index);
+        let vertex_index = edge.vertex().index;
+        let is_boundary = !edge.face().is_valid();
+        if let Some(vertex) = mesh.get_element_mut(vertex_index) {
+            // Boundary vertices prefer an outgoing boundary edge so that
+            // circulation starts and ends at the open side of the fan.
+            if is_boundary || !vertex.edge_index.is_valid() {
+                vertex.edge_index = *edge_index;
+            }
+        }
+    }
+
+    faces
+}
+
+fn is_buildable<S: Scalar>(
+    mesh: &Mesh<S>,
+    polygon: &[PointIndex],
+    half_edges: &HashMap<(PointIndex, PointIndex), EdgeIndex>,
+) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+    (0..polygon.len()).all(|i| {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        let unique = !polygon[i + 1..].contains(&a);
+        let unclaimed = half_edges
+            .get(&(a, b))
+            .map(|edge| !mesh.edge(*edge).face().is_valid())
+            .unwrap_or(true);
+        unique && unclaimed && mesh.points.get(a).is_some()
+    })
+}
+
+fn vertex_for<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vertices: &mut HashMap<PointIndex, VertexIndex>,
+    point: PointIndex,
+) -> VertexIndex {
+    *vertices
+        .entry(point)
+        .or_insert_with(|| mesh.add_element(Vertex::at_point(point)))
+}
+
+/// Adds points for the positions and builds the polygons over them.
+pub(crate) fn from_polygons<S: Scalar>(positions: &[[f64; 3]], polygons: &[Vec<usize>]) -> Mesh<S> {
+    let mut mesh = Mesh::new();
+    let points: Vec<PointIndex> = positions
+        .iter()
+        .map(|p| mesh.add_element(Point::from_position(scalar::from_f64(*p))))
+        .collect();
+    let polygons: Vec<Vec<PointIndex>> = polygons
+        .iter()
+        .map(|polygon| polygon.iter().map(|i| points[*i]).collect())
+        .collect();
+    add_polygons(&mut mesh, &polygons);
+    mesh
+}
+
+/// A quad grid in the XY plane with `cols * rows` unit cells, used by tests.
+#[cfg(test)]
+pub(crate) fn grid<S: Scalar>(cols: usize, rows: usize) -> Mesh<S> {
+    let positions: Vec<[f64; 3]> = (0..=rows)
+        .flat_map(|y| (0..=cols).map(move |x| [x as f64, y as f64, 0.0]))
+        .collect();
+    let polygons: Vec<Vec<usize>> = (0..rows)
+        .flat_map(|y| {
+            (0..cols).map(move |x| {
+                let i = y * (cols + 1) + x;
+                vec![i, i + 1, i + cols + 2, i + cols + 1]
+            })
+        })
+        .collect();
+    from_polygons(&positions, &polygons)
+}
+
+/// An axis aligned unit cube with outward facing quads, used by tests.
+#[cfg(test)]
+pub(crate) fn cube<S: Scalar>() -> Mesh<S> {
+    let positions: Vec<[f64; 3]> = (0..8)
+        .map(|i| [(i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64])
+        .collect();
+    let polygons = [
+        vec![0, 2, 3, 1],
+        vec![4, 5, 7, 6],
+        vec![0, 1, 5, 4],
+        vec![2, 6, 7, 3],
+        vec![0, 4, 6, 2],
+        vec![1, 3, 7, 5],
+    ];
+    from_polygons(&positions, &polygons)
+}
+
+/// A grid like `grid` with every cell split into two triangles, used by tests.
+#[cfg(test)]
+pub(crate) fn triangle_grid<S: Scalar>(cols: usize, rows: usize) -> Mesh<S> {
+    let positions: Vec<[f64; 3]> = (0..=rows)
+        .flat_map(|y| (0..=cols).map(move |x| [x as f64, y as f64, 0.0]))
+        .collect();
+    let polygons: Vec<Vec<usize>> = (0..rows)
+        .flat_map(|y| {
+            (0..cols).flat_map(move |x| {
+                let i = y * (cols + 1) + x;
+                [
+                    vec![i, i + 1, i + cols + 2],
+                    vec![i, i + cols + 2, i + cols + 1],
+                ]
+            })
+        })
+        .collect();
+    from_polygons(&positions, &polygons)
+}
+
+/// A closed octahedron with unit radius and outward facing triangles, used by tests.
+#[cfg(test)]
+pub(crate) fn octahedron<S: Scalar>() -> Mesh<S> {
+    let positions = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0],
+    ];
+    let polygons = [
+        vec![0, 2, 4],
+        vec![2, 1, 4],
+        vec![1, 3, 4],
+        vec![3, 0, 4],
+        vec![2, 0, 5],
+        vec![1, 2, 5],
+        vec![3, 1, 5],
+        vec![0, 3, 5],
+    ];
+    from_polygons(&positions, &polygons)
+}
+
+/// Panics unless every live element is wired up consistently, used by tests.
+#[cfg(test)]
+pub(crate) fn assert_connectivity<S: Scalar>(mesh: &Mesh<S>) {
+    if let Err(error) = mesh.validate() {
+        panic!("{:?}: {}", mesh, error);
+    }
+}
+
+/// The half-edges leaving each vertex inside a face.
+fn corners_by_vertex<S: Scalar>(mesh: &Mesh<S>) -> HashMap<VertexIndex, Vec<EdgeIndex>> {
+    let mut corners: HashMap<VertexIndex, Vec<EdgeIndex>> = HashMap::new();
+    for (index, edge) in mesh.edges.iter() {
+        if edge.face_index.is_valid() {
+            corners.entry(edge.vertex_index).or_default().push(index);
+        }
+    }
+    corners
+}
+
+/// The number of faces reached by turning around the origin of `corner`
+/// both ways across edges with a face on each side.
+fn fan_size<S: Scalar>(mesh: &Mesh<S>, corner: EdgeIndex) -> usize {
+    let mut count = 1;
+    let mut current = mesh.edge(corner);
+    loop {
+        let next = current.prev().twin();
+        if next.index == corner {
+            return count;
+        }
+        if !next.face().is_valid() {
+            break;
+        }
+        count += 1;
+        current = next;
+    }
+    let mut current = mesh.edge(corner);
+    while current.twin().face().is_valid() {
+        current = current.twin().next();
+        count += 1;
+    }
+    count
+}
+
+/// Connects face-less half-edges into loops running along the open borders.
+pub(crate) fn link_boundary_loops<S: Scalar>(mesh: &mut Mesh<S>, boundary: &[EdgeIndex]) {
+    let mut outgoing: HashMap<VertexIndex, Vec<EdgeIndex>> = HashMap::new();
+    for edge in boundary {
+        outgoing
+            .entry(mesh.edge(*edge).vertex().index)
+            .or_default()
+            .push(*edge);
+    }
+    for edge in boundary {
+        let dest = mesh.edge(*edge).twin().vertex().index;
+        if let Some(next) = outgoing.get_mut(&dest).and_then(|edges| edges.pop()) {
+            utils::link_unchecked(mesh, *edge, next);
+        } else {
+            log::warn!("Boundary edge {:?} has no continuation.", edge);
+        }
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Builds a mesh from positions and polygons listing indices into them.
+    ///
+    /// Polygons which can't be built, because they repeat a corner, refer to
+    /// a missing position or would reuse a half-edge claimed by an earlier
+    /// polygon, are skipped.
+    pub fn from_polygons(positions: &[Position<S>], polygons: &[Vec<usize>]) -> Self {
+        let mut mesh = Mesh::new();
+        let points = add_points(&mut mesh, positions);
+        let polygons: Vec<Vec<PointIndex>> = polygons
+            .iter()
+            .map(|polygon| {
+                polygon
+                    .iter()
+                    .map(|i| points.get(*i).copied().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        add_polygons(&mut mesh, &polygons);
+        mesh
+    }
+
+    /// Builds a mesh from a triangle list, three indices into `positions`
+    /// per triangle.
+    ///
+    /// Unlike `from_polygons` nothing is skipped. Fails on a partial
+    /// triangle, an index past the positions or a triangle repeating a
+    /// corner, and with `Error::NonManifold` when an edge is shared by more
+    /// than two triangles, when neighbors wind opposite ways or when the
+    /// triangles around a position don't form a single fan.
+    pub fn from_indexed_triangles(positions: &[Position<S>], indices: &[u32]) -> Result<Self> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(Error::InvalidArgument(format!(
+                "{} indices don't make whole triangles",
+                indices.len()
+            )));
+        }
+        let triangles: Vec<Vec<usize>> = indices
+            .chunks(3)
+            .map(|t| t.iter().map(|i| *i as usize).collect())
+            .collect();
+        for (i, triangle) in triangles.iter().enumerate() {
+            if let Some(index) = triangle.iter().find(|index| **index >= positions.len()) {
+                return Err(Error::InvalidArgument(format!(
+                    "triangle {} refers to position {} of {}",
+                    i,
+                    index,
+                    positions.len()
+                )));
+            }
+            if triangle[0] == triangle[1]
+                || triangle[1] == triangle[2]
+                || triangle[0] == triangle[2]
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "triangle {} repeats a corner, {:?}",
+                    i, triangle
+                )));
+            }
+        }
+
+        let mut mesh = Mesh::new();
+        let points = add_points(&mut mesh, positions);
+        let polygons: Vec<Vec<PointIndex>> = triangles
+            .iter()
+            .map(|t| t.iter().map(|i| points[*i]).collect())
+            .collect();
+        let faces = add_polygons(&mut mesh, &polygons);
+        if let Some(i) = faces.iter().position(|face| !face.is_valid()) {
+            return Err(Error::NonManifold(format!(
+                "triangle {} shares an edge with more than one triangle or winds against its neighbor",
+                i
+            )));
+        }
+        for (vertex, corners) in corners_by_vertex(&mesh) {
+            if fan_size(&mesh, corners[0]) != corners.len() {
+                return Err(Error::NonManifold(format!(
+                    "the triangles around {:?} form more than one fan",
+                    vertex
+                )));
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Adds a face with corners at `points`, in order.
+    ///
+    /// Sides running along the open side of existing faces reuse their
+    /// boundary half-edges, corners reuse a vertex of their point on the
+    /// boundary when there is one and get a new vertex otherwise. Boundary
+    /// loops around the face are linked up again. The face can't be added
+    /// when a corner repeats, a point is missing or a side would reuse a
+    /// half-edge which already has a face. Every check runs before the mesh
+    /// is changed, a failed call leaves it as it was.
+    ///
+    /// Existing half-edges are found around the vertices of `points`, which
+    /// the mesh looks up in a map from points to vertices. The map is built
+    /// on first use and again whenever the vertices were changed by
+    /// something other than `add_face`.
+    pub fn add_face(&mut self, points: &[PointIndex]) -> Result<FaceIndex> {
+        let n = points.len();
+        if n < 3 {
+            return Err(Error::InvalidArgument(format!(
+                "a face needs at least 3 corners, got {}",
+                n
+            )));
+        }
+        if let Some(point) = points.iter().find(|p| self.points.get(**p).is_none()) {
+            return Err(Error::InvalidPoint(*point));
+        }
+        if (0..n).any(|i| points[i + 1..].contains(&points[i])) {
+            return Err(Error::InvalidArgument(format!(
+                "corners of {:?} repeat",
+                points
+            )));
+        }
+        let point_of =
+            |mesh: &Mesh<S>, vertex: VertexIndex| mesh.vertices.get(vertex).map(|v| v.point_index);
+        let side_of = |from: Option<PointIndex>, to: Option<PointIndex>| {
+            (0..n).find(|i| from == Some(points[*i]) && to == Some(points[(i + 1) % n]))
+        };
+
+        // Half-edges already running along each side.
+        self.sync_point_vertices();
+        let mut open = vec![None; n];
+        let mut claimed: Vec<(usize, VertexIndex, VertexIndex)> = Vec::new();
+        let mut around = Vec::new();
+        for point in points {
+            for vertex in self.point_vertices.at(*point) {
+                for edge in self.vertex(vertex).edges() {
+                    let near = [edge, edge.prev(), edge.twin(), edge.twin().prev()];
+                    around.extend(near.iter().filter(|e| e.is_valid()).map(|e| e.index));
+                }
+            }
+        }
+        around.sort_by_key(|e| e.offset);
+        around.dedup();
+        for edge in around.iter().map(|e| self.edge(*e)) {
+            let (from, to) = (edge.vertex().index, edge.twin().vertex().index);
+            if let Some(side) = side_of(point_of(self, from), point_of(self, to)) {
+                if edge.face().is_valid() {
+                    claimed.push((side, from, to));
+                } else if open[side].is_none() {
+                    open[side] = Some((edge.index, from, to));
+                }
+            }
+        }
+
+        let mut corners: Vec<Option<VertexIndex>> = vec![None; n];
+        for (side, found) in open.iter().enumerate() {
+            if let Some((_, from, to)) = *found {
+                for (corner, vertex) in [(side, from), ((side + 1) % n, to)] {
+                    if corners[corner].is_some_and(|v| v != vertex) {
+                        return Err(Error::NonManifold(format!(
+                            "open sides around {:?} meet at different vertices",
+                            points
+                        )));
+                    }
+                    corners[corner] = Some(vertex);
+                }
+            }
+        }
+        // Corners without a vertex yet stay `None` until every check passed.
+        let corners: Vec<Option<VertexIndex>> = corners
+            .into_iter()
+            .zip(points)
+            .map(|(corner, point)| corner.or_else(|| self.corner_vertex(*point)))
+            .collect();
+
+        let taken = claimed.iter().any(|(side, from, to)| {
+            open[*side].is_none()
+                && corners[*side] == Some(*from)
+                && corners[(*side + 1) % n] == Some(*to)
+        });
+        if taken {
+            return Err(Error::NonManifold(format!(
+                "a side of {:?} would reuse a half-edge which has a face",
+                points
+            )));
+        }
+        let corners: Vec<VertexIndex> = corners
+            .into_iter()
+            .zip(points)
+            .map(|(corner, point)| corner.unwrap_or_else(|| self.add_corner_vertex(*point)))
+            .collect();
+
+        let sides: Vec<EdgeIndex> = (0..n)
+            .map(|side| match open[side] {
+                Some((edge, _, _)) => edge,
+                None => {
+                    let edge =
+                        utils::full_edge_unchecked(self, corners[side], corners[(side + 1) % n]);
+                    around.push(self.edge(edge).twin().index);
+                    edge
+                }
+            })
+            .collect();
+        let face = self.add_element(Face::new(sides[0]));
+        for (side, edge) in sides.iter().enumerate() {
+            utils::link_unchecked(self, *edge, sides[(side + 1) % n]);
+            if let Some(e) = self.get_element_mut(*edge) {
+                e.face_index = face;
+            }
+        }
+
+        // Each boundary half-edge coming into a corner continues with the
+        // next boundary half-edge around the corner.
+        let mut incoming: Vec<EdgeIndex> = around
+            .into_iter()
+            .filter(|e| {
+                let to = self.edge(*e).twin().vertex().index;
+                !self.edge(*e).face().is_valid() && corners.contains(&to)
+            })
+            .collect();
+        incoming.sort_by_key(|e| e.offset);
+        incoming.dedup();
+        for edge in incoming {
+            let mut outgoing = self.edge(edge).twin();
+            for _ in 0..self.edge_count() {
+                if !outgoing.face().is_valid() {
+                    break;
+                }
+                outgoing = outgoing.prev().twin();
+            }
+            let (vertex, outgoing) = (outgoing.vertex().index, outgoing.index);
+            utils::link_unchecked(self, edge, outgoing);
+            if let Some(v) = self.get_element_mut(vertex) {
+                v.edge_index = outgoing;
+            }
+        }
+        for (corner, edge) in corners.iter().zip(&sides) {
+            let current = self.vertex(*corner).edge();
+            if !current.is_valid() || current.vertex().index != *corner {
+                if let Some(v) = self.get_element_mut(*corner) {
+                    v.edge_index = *edge;
+                }
+            }
+        }
+        self.point_vertices.version = Some(self.vertices.version());
+        Ok(face)
+    }
+
+    /// Copies faces with new points placed by `transform`, returning the
+    /// copies in the order of `faces`.
+    ///
+    /// `transform` is row major like `scene::Transform::matrix`, with the
+    /// last row `[0, 0, 0, 1]`. The copies are connected to each other like
+    /// the originals but share nothing with the rest of the mesh. A
+    /// transform which mirrors, with a negative determinant, reverses the
+    /// winding of the copies so their normals still point outwards.
+    ///
+    /// Fails with `Error::InvalidFace` for a face which isn't live and with
+    /// `Error::InvalidArgument` for one listed twice, before anything is
+    /// copied.
+    pub fn duplicate_faces(
+        &mut self,
+        faces: &[FaceIndex],
+        transform: &[[f32; 4]; 4],
+    ) -> Result<Vec<FaceIndex>> {
+        let transform = scene::Transform {
+            matrix: transform.map(|row| row.map(f64::from)),
+        };
+        self.duplicate_transformed(faces, &transform)
+    }
+
+    /// `duplicate_faces` without rounding the transform to `f32`.
+    pub(crate) fn duplicate_transformed(
+        &mut self,
+        faces: &[FaceIndex],
+        transform: &scene::Transform,
+    ) -> Result<Vec<FaceIndex>> {
+        let mut listed = HashSet::with_capacity(faces.len());
+        for face in faces {
+            if !self.face(*face).is_valid() {
+                return Err(Error::InvalidFace(*face));
+            }
+            if !listed.insert(*face) {
+                return Err(Error::InvalidArgument(format!(
+                    "{:?} is listed more than once",
+                    face
+                )));
+            }
+        }
+        let mirrored = transform.determinant() < 0.0;
+        let mut copies: HashMap<VertexIndex, PointIndex> = HashMap::new();
+        let mut polygons = Vec::with_capacity(faces.len());
+        for face in faces {
+            let mut corners: Vec<VertexIndex> =
+                self.face(*face).vertices().map(|v| v.index).collect();
+            if mirrored {
+                corners.reverse();
+            }
+            let polygon = corners
+                .into_iter()
+                .map(|vertex| {
+                    let position = transform.apply(self.vertex_position(vertex));
+                    *copies.entry(vertex).or_insert_with(|| {
+                        self.add_element(Point::from_position(scalar::from_f64(position)))
+                    })
+                })
+                .collect();
+            polygons.push(polygon);
+        }
+        Ok(add_polygons(self, &polygons))
+    }
+
+    /// Copies every element of `other` into this mesh, returning where each
+    /// of them ended up.
+    ///
+    /// The copy keeps the connectivity of `other` exactly and stays
+    /// disconnected from what was already here. Morph targets are merged by
+    /// name, and vertex constraints and normals come along with their
+    /// vertices.
+    pub fn append(&mut self, other: &Mesh<S>) -> canonical::Renumbering {
+        let mut maps = canonical::Renumbering::default();
+        for (index, point) in other.points.iter() {
+            maps.points.insert(index, self.add_element(point.clone()));
+        }
+        for (index, _) in other.vertices.iter() {
+            maps.vertices
+                .insert(index, self.add_element(Vertex::default()));
+        }
+        for (index, _) in other.faces.iter() {
+            maps.faces.insert(index, self.add_element(Face::default()));
+        }
+        for (index, _) in other.edges.iter() {
+            maps.edges.insert(index, self.add_element(Edge::default()));
+        }
+
+        for (index, vertex) in other.vertices.iter() {
+            if let Some(copy) = self.get_element_mut(maps.vertices[&index]) {
+                copy.edge_index = moved(&maps.edges, vertex.edge_index);
+                copy.point_index = moved(&maps.points, vertex.point_index);
+            }
+        }
+        for (index, face) in other.faces.iter() {
+            if let Some(copy) = self.get_element_mut(maps.faces[&index]) {
+                copy.edge_index = moved(&maps.edges, face.edge_index);
+            }
+        }
+        for (index, edge) in other.edges.iter() {
+            if let Some(copy) = self.get_element_mut(maps.edges[&index]) {
+                *copy = Edge {
+                    twin_index: moved(&maps.edges, edge.twin_index),
+                    next_index: moved(&maps.edges, edge.next_index),
+                    prev_index: moved(&maps.edges, edge.prev_index),
+                    face_index: moved(&maps.faces, edge.face_index),
+                    vertex_index: moved(&maps.vertices, edge.vertex_index),
+                };
+            }
+        }
+
+        for target in &other.morph_targets {
+            let deltas = target.deltas.remapped(&maps.points);
+            let merged = self.add_morph_target(&target.name);
+            for (point, delta) in deltas.iter() {
+                merged.deltas.set(point, *delta);
+            }
+        }
+        for (vertex, constraint) in other.constraints.iter() {
+            self.constraints.set(maps.vertices[&vertex], *constraint);
+        }
+        for (vertex, normal) in other.vertex_normals.iter() {
+            self.vertex_normals.set(maps.vertices[&vertex], *normal);
+        }
+        maps
+    }
+
+    /// A vertex of `point` on the boundary or without edges, if any.
+    fn corner_vertex(&self, point: PointIndex) -> Option<VertexIndex> {
+        self.point_vertices.at(point).find(|index| {
+            let edge = self.vertex(*index).edge();
+            !edge.is_valid() || self.vertex(*index).edges().any(|e| !e.face().is_valid())
+        })
+    }
+
+    /// Adds a vertex at `point` and keeps the point to vertex map current.
+    fn add_corner_vertex(&mut self, point: PointIndex) -> VertexIndex {
+        let vertex = self.add_element(Vertex::at_point(point));
+        self.point_vertices.insert(point, vertex);
+        self.point_vertices.version = Some(self.vertices.version());
+        vertex
+    }
+
+    /// Rebuilds the point to vertex map unless the vertices are unchanged
+    /// since `add_face` last brought it up to date.
+    fn sync_point_vertices(&mut self) {
+        let version = self.vertices.version();
+        if self.point_vertices.version == Some(version) {
+            return;
+        }
+        let mut lookup = PointVertices {
+            version: Some(version),
+            ..Default::default()
+        };
+        for (index, vertex) in self.vertices.iter() {
+            lookup.insert(vertex.point_index, index);
+        }
+        self.point_vertices = lookup;
+    }
+
+    /// Like `from_polygons`, with the work spread over `threads` threads.
+    ///
+    /// Polygons are partitioned between the threads, which sort their
+    /// half-edges into shards by undirected edge so that twins can be paired
+    /// per shard without any shared state. Faces come out in the same order
+    /// as with `from_polygons`. The one difference is in how conflicts are
+    /// resolved: every polygon reusing a half-edge of an earlier polygon is
+    /// skipped, even when the earlier polygon was skipped itself.
+    pub fn from_polygons_parallel(
+        positions: &[Position<S>],
+        polygons: &[Vec<usize>],
+        threads: usize,
+    ) -> Self {
+        let threads = threads.max(1);
+        let shards = threads;
+        let mut mesh = Mesh::new();
+        let points = add_points(&mut mesh, positions);
+
+        let mut accepted: Vec<bool> = parallel::in_chunks(polygons, threads, |_, chunk| {
+            chunk
+                .iter()
+                .map(|polygon| {
+                    polygon.len() >= 3
+                        && polygon
+                            .iter()
+                            .enumerate()
+                            .all(|(k, i)| *i < positions.len() && !polygon[k + 1..].contains(i))
+                })
+                .collect::<Vec<_>>()
+        })
+        .concat();
+
+        // Every half-edge goes into the shard of its undirected edge.
+        let buckets: Vec<Vec<Vec<Side>>> =
+            parallel::in_chunks(polygons, threads, |start, chunk| {
+                let mut buckets = vec![Vec::new(); shards];
+                for (offset, polygon) in chunk.iter().enumerate() {
+                    let index = start + offset;
+                    if !accepted[index] {
+                        continue;
+                    }
+                    for side in 0..polygon.len() {
+                        let (a, b) = (polygon[side], polygon[(side + 1) % polygon.len()]);
+                        let key = (a.min(b), a.max(b));
+                        let hash =
+                            (key.0 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ key.1 as u64;
+                        buckets[(hash % shards as u64) as usize].push(Side {
+                            key,
+                            forward: a < b,
+                            polygon: index,
+                            side,
+                        });
+                    }
+                }
+                buckets
+            });
+        let shard_indices: Vec<usize> = (0..shards).collect();
+        let sorted: Vec<(Vec<Side>, Vec<usize>)> =
+            parallel::in_chunks(&shard_indices, threads, |_, chunk| {
+                chunk
+                    .iter()
+                    .map(|shard| {
+                        let mut sides: Vec<Side> =
+                            buckets.iter().flat_map(|b| &b[*shard]).copied().collect();
+                        sides.sort_by_key(|s| (s.key, s.forward, s.polygon));
+                        // Only the first polygon may claim each half-edge.
+                        let rejected = sides
+                            .windows(2)
+                            .filter(|w| (w[0].key, w[0].forward) == (w[1].key, w[1].forward))
+                            .map(|w| w[1].polygon)
+                            .collect();
+                        (sides, rejected)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .concat();
+        for polygon in sorted.iter().flat_map(|(_, rejected)| rejected) {
+            accepted[*polygon] = false;
+        }
+        for (index, _) in accepted.iter().enumerate().filter(|(_, a)| !**a) {
+            log::warn!("Skipping polygon {} which can't be built.", index);
+        }
+
+        let mut first_edge = vec![0; polygons.len()];
+        let mut interior = 0;
+        for (index, polygon) in polygons.iter().enumerate() {
+            first_edge[index] = interior;
+            if accepted[index] {
+                interior += polygon.len();
+            }
+        }
+        let id = |side: &Side| first_edge[side.polygon] + side.side;
+
+        // Pair twins per shard, half-edges without a partner get one on the boundary.
+        let paired = parallel::in_chunks(&sorted, threads, |_, chunk| {
+            chunk
+                .iter()
+                .map(|(sides, _)| {
+                    let mut twins = Vec::new();
+                    let mut open = Vec::new();
+                    let live: Vec<&Side> = sides.iter().filter(|s| accepted[s.polygon]).collect();
+                    let mut i = 0;
+                    while i < live.len() {
+                        if i + 1 < live.len() && live[i + 1].key == live[i].key {
+                            twins.push((id(live[i]), id(live[i + 1])));
+                            i += 2;
+                        } else {
+                            open.push(*live[i]);
+                            i += 1;
+                        }
+                    }
+                    (twins, open)
+                })
+                .collect::<Vec<_>>()
+        })
+        .concat();
+
+        let boundary_count: usize = paired.iter().map(|(_, open)| open.len()).sum();
+        let mut twin = vec![0; interior + boundary_count];
+        let mut boundary_origin = Vec::with_capacity(boundary_count);
+        for (twins, open) in &paired {
+            for (a, b) in twins {
+                twin[*a] = *b;
+                twin[*b] = *a;
+            }
+            for side in open {
+                let (a, b) = (id(side), interior + boundary_origin.len());
+                twin[a] = b;
+                twin[b] = a;
+                let polygon = &polygons[side.polygon];
+                boundary_origin.push(polygon[(side.side + 1) % polygon.len()]);
+            }
+        }
+
+        let mut vertex_of = vec![VertexIndex::default(); positions.len()];
+        for (index, polygon) in polygons.iter().enumerate() {
+            if !accepted[index] {
+                continue;
+            }
+            for point in polygon {
+                if !vertex_of[*point].is_valid() {
+                    vertex_of[*point] = mesh.add_element(Vertex::at_point(points[*point]));
+                }
+            }
+        }
+        let handles: Vec<EdgeIndex> = (0..twin.len())
+            .map(|_| mesh.add_element(Edge::default()))
+            .collect();
+        let mut face_of = vec![FaceIndex::default(); polygons.len()];
+        for index in 0..polygons.len() {
+            if accepted[index] {
+                face_of[index] = mesh.add_element(Face::new(handles[first_edge[index]]));
+            }
+        }
+
+        let edges: Vec<Edge> = parallel::in_chunks(polygons, threads, |start, chunk| {
+            let mut edges = Vec::new();
+            for (offset, polygon) in chunk.iter().enumerate() {
+                let index = start + offset;
+                if !accepted[index] {
+                    continue;
+                }
+                let (first, n) = (first_edge[index], polygon.len());
+                edges.extend((0..n).map(|side| Edge {
+                    twin_index: handles[twin[first + side]],
+                    next_index: handles[first + (side + 1) % n],
+                    prev_index: handles[first + (side + n - 1) % n],
+                    face_index: face_of[index],
+                    vertex_index: vertex_of[polygon[side]],
+                }));
+            }
+            edges
+        })
+        .concat();
+        for (handle, edge) in handles.iter().zip(edges) {
+            mesh.edges[*handle] = edge;
+        }
+        for (offset, origin) in boundary_origin.iter().enumerate() {
+            let index = interior + offset;
+            mesh.edges[handles[index]] = Edge {
+                twin_index: handles[twin[index]],
+                vertex_index: vertex_of[*origin],
+                ..Edge::default()
+            };
+        }
+        link_boundary_loops(&mut mesh, &handles[interior..]);
+
+        for (index, handle) in handles.iter().enumerate() {
+            let vertex = mesh.edges[*handle].vertex_index;
+            if let Some(vertex) = mesh.vertices.get_mut(vertex) {
+                if index >= interior || !vertex.edge_index.is_valid() {
+                    vertex.edge_index = *handle;
+                }
+            }
+        }
+        mesh
+    }
+}
+
+fn moved<K: Default>(map: &HashMap<Handle<K>, Handle<K>>, index: Handle<K>) -> Handle<K> {
+    map.get(&index).copied().unwrap_or_default()
+}
+
+fn add_points<S: Scalar>(mesh: &mut Mesh<S>, positions: &[Position<S>]) -> Vec<PointIndex> {
+    positions
+        .iter()
+        .map(|p| mesh.add_element(Point::from_position(*p)))
+        .collect()
+}
+
+/// A half-edge of a polygon, keyed by the undirected edge it lies on.
+#[derive(Debug, Clone, Copy)]
+struct Side {
+    key: (usize, usize),
+    forward: bool,
+    polygon: usize,
+    side: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad_points(mesh: &mut Mesh) -> Vec<PointIndex> {
+        [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ]
+        .iter()
+        .map(|p| mesh.add_element(Point::from_position(*p)))
+        .collect()
+    }
+
+    #[test]
+    fn pairs_twins_between_faces() {
+        let mut mesh = Mesh::default();
+        let p = quad_points(&mut mesh);
+        let faces = add_polygons(&mut mesh, &[vec![p[0], p[1], p[2]], vec![p[0], p[2], p[3]]]);
+
+        assert!(faces.iter().all(|f| f.is_valid()));
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.edge_count(), 10);
+
+        let interior = mesh.edges().filter(|e| !e.is_boundary()).count();
+        assert_eq!(interior, 2);
+        for edge in mesh.edges() {
+            assert!(edge.next().is_valid() && edge.prev().is_valid());
+            assert_eq!(edge.next().prev().index, edge.index);
+            assert_eq!(edge.twin().twin().index, edge.index);
+            assert_eq!(edge.next().vertex().index, edge.twin().vertex().index);
+        }
+    }
+
+    #[test]
+    fn boundary_loop_is_closed() {
+        let mut mesh = Mesh::default();
+        let p = quad_points(&mut mesh);
+        add_polygons(&mut mesh, &[vec![p[0], p[1], p[2]], vec![p[0], p[2], p[3]]]);
+
+        let root = mesh
+            .edges()
+            .find(|e| !e.face().is_valid())
+            .expect("Expected a boundary edge.");
+        assert_eq!(FaceEdges::from_edge(root).count(), 4);
+    }
+
+    #[test]
+    fn cube_is_closed() {
+        let mesh: Mesh = cube();
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(mesh.edge_count(), 24);
+        assert!(mesh.edges().all(|e| !e.is_boundary()));
+        for face in mesh.faces() {
+            let n = mesh.face_normal(face.index);
+            let c = mesh.vertex_position(face.vertices().next().unwrap().index);
+            // Every face points away from the cube center.
+            assert!(math::dot(n, math::sub(c, [0.5; 3])) > 0.0);
+        }
+    }
+
+    fn jittered_triangles(size: usize) -> (Vec<[f64; 3]>, Vec<Vec<usize>>) {
+        let mut rng = crate::random::Rng::new(5);
+        let positions = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| [x as f64, y as f64, 0.0]))
+            .map(|p| {
+                [
+                    p[0] + 0.3 * rng.next_f64(),
+                    p[1] + 0.3 * rng.next_f64(),
+                    p[2],
+                ]
+            })
+            .collect();
+        let mut polygons = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let a = y * (size + 1) + x;
+                let [b, c, d] = [a + 1, a + size + 2, a + size + 1];
+                if (x + y) % 2 == 0 {
+                    polygons.extend([vec![a, b, c], vec![a, c, d]]);
+                } else {
+                    polygons.extend([vec![a, b, d], vec![b, c, d]]);
+                }
+            }
+        }
+        (positions, polygons)
+    }
+
+    #[test]
+    fn parallel_construction_matches_sequential() {
+        let (positions, mut polygons) = jittered_triangles(24);
+        // A hole and a conflicting polygon which has to be skipped.
+        polygons.remove(300);
+        polygons.push(vec![0, 1, 26]);
+        let serial: Mesh<f64> = Mesh::from_polygons(&positions, &polygons);
+        for threads in [1, 3, 8] {
+            let parallel: Mesh<f64> = Mesh::from_polygons_parallel(&positions, &polygons, threads);
+            assert_connectivity(&parallel);
+            assert_eq!(parallel.face_count(), serial.face_count());
+            assert_eq!(parallel.edge_count(), serial.edge_count());
+            assert_eq!(parallel.vertex_count(), serial.vertex_count());
+            for (a, b) in serial.faces().zip(parallel.faces()) {
+                let corners =
+                    |f: FaceFn<f64>| f.vertices().map(|v| v.position()).collect::<Vec<_>>();
+                assert_eq!(corners(a), corners(b));
+            }
+            let boundary = |m: &Mesh<f64>| m.edges().filter(|e| !e.face().is_valid()).count();
+            assert_eq!(boundary(&parallel), boundary(&serial));
+            assert!(parallel
+                .vertices()
+                .filter(|v| v.edges().any(|e| !e.face().is_valid()))
+                .all(|v| !v.edge().face().is_valid()));
+        }
+    }
+
+    #[test]
+    fn faces_are_added_one_at_a_time() {
+        let mut mesh: Mesh = Mesh::new();
+        let p: Vec<PointIndex> = (0..9)
+            .map(|i| {
+                let position = [(i % 3) as f32, (i / 3) as f32, 0.0];
+                mesh.add_element(Point::from_position(position))
+            })
+            .collect();
+        let cell = |x: usize, y: usize| {
+            let a = y * 3 + x;
+            vec![p[a], p[a + 1], p[a + 4], p[a + 3]]
+        };
+        // Opposite corners first, so the middle vertex joins two fans before
+        // the last cells close the gaps between them.
+        for (x, y) in [(0, 0), (1, 1), (1, 0), (0, 1)] {
+            assert!(mesh.add_face(&cell(x, y)).unwrap().is_valid());
+            assert_connectivity(&mesh);
+        }
+        let grid: Mesh = grid(2, 2);
+        assert_eq!(mesh.face_count(), grid.face_count());
+        assert_eq!(mesh.vertex_count(), grid.vertex_count());
+        assert_eq!(mesh.edge_count(), grid.edge_count());
+        let root = mesh.edges().find(|e| !e.face().is_valid()).unwrap();
+        assert_eq!(FaceEdges::from_edge(root).count(), 8);
+
+        assert!(matches!(
+            mesh.add_face(&cell(0, 0)),
+            Err(Error::NonManifold(_))
+        ));
+        assert!(matches!(
+            mesh.add_face(&[p[0], p[1], p[0]]),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(mesh.face_count(), 4);
+    }
+
+    #[test]
+    fn faces_are_added_to_meshes_built_elsewhere() {
+        let mut mesh: Mesh = grid(1, 1);
+        let mut p: Vec<PointIndex> = mesh.points.iter().map(|(index, _)| index).collect();
+        for position in [[2.0, 0.0, 0.0], [2.0, 1.0, 0.0], [1.0, 2.0, 0.0]] {
+            p.push(mesh.add_element(Point::from_position(position)));
+        }
+        assert!(mesh.add_face(&[p[1], p[4], p[5], p[3]]).is_ok());
+        assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.edge_count(), 14);
+
+        // A clone has vertices of its own, its lookup is built anew.
+        let mut copy = mesh.clone();
+        assert!(copy.add_face(&[p[3], p[5], p[6]]).is_ok());
+        assert_connectivity(&copy);
+        assert_eq!(copy.vertex_count(), 7);
+        assert_eq!(copy.edge_count(), 18);
+        assert!(copy.add_face(&[p[1], p[4], p[5], p[3]]).is_err());
+    }
+
+    #[test]
+    fn failed_faces_leave_the_mesh_unchanged() {
+        let mut mesh: Mesh = Mesh::new();
+        let p: Vec<PointIndex> = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ]
+        .into_iter()
+        .map(|position| mesh.add_element(Point::from_position(position)))
+        .collect();
+        mesh.add_face(&p[..3]).unwrap();
+        let (vertices, edges) = (mesh.vertex_count(), mesh.edge_count());
+        assert!(mesh.add_face(&[p[0], p[1], p[3]]).is_err());
+        assert_eq!(mesh.vertex_count(), vertices);
+        assert_eq!(mesh.edge_count(), edges);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.validate(), Ok(()));
+        assert_eq!(
+            mesh.add_face(&[p[0], PointIndex::default(), p[3]]),
+            Err(Error::InvalidPoint(PointIndex::default()))
+        );
+    }
+
+    #[test]
+    fn skips_conflicting_polygons() {
+        let mut mesh = Mesh::default();
+        let p = quad_points(&mut mesh);
+        let faces = add_polygons(&mut mesh, &[vec![p[0], p[1], p[2]], vec![p[0], p[1], p[3]]]);
+        assert!(faces[0].is_valid());
+        assert!(!faces[1].is_valid());
+        assert_eq!(mesh.face_count(), 1);
+    }
+
+    #[test]
+    fn duplicates_are_placed_and_mirrored() {
+        let mut mesh: Mesh = grid(2, 1);
+        let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let moved = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let copies = mesh.duplicate_faces(&faces, &moved).unwrap();
+        assert_connectivity(&mesh);
+        assert_eq!(copies.len(), 2);
+        assert_eq!(mesh.face_count(), 4);
+        assert_eq!(mesh.point_count(), 12);
+        for copy in &copies {
+            assert!(mesh
+                .face(*copy)
+                .vertices()
+                .all(|v| v.position().unwrap()[2] == 1.0));
+            assert!(mesh.face_normal(*copy)[2] > 0.99);
+        }
+        assert!(mesh
+            .face(copies[0])
+            .edges()
+            .any(|e| e.twin().face().index == copies[1]));
+
+        let mut mirror = moved;
+        mirror[2] = [0.0, 0.0, -1.0, 0.0];
+        let mirrored = mesh.duplicate_faces(&faces[..1], &mirror).unwrap();
+        assert_connectivity(&mesh);
+        assert!(mesh.face_normal(mirrored[0])[2] < -0.99);
+
+        let faces = mesh.face_count();
+        let points = mesh.point_count();
+        let dead = FaceIndex::default();
+        assert_eq!(
+            mesh.duplicate_faces(&[copies[0], dead], &mirror),
+            Err(Error::InvalidFace(dead))
+        );
+        assert!(matches!(
+            mesh.duplicate_faces(&[copies[0], copies[0]], &mirror),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!((mesh.face_count(), mesh.point_count()), (faces, points));
+    }
+
+    #[test]
+    fn appended_meshes_keep_their_connectivity() {
+        let mut mesh: Mesh = grid(2, 1);
+        let mut cube: Mesh = cube();
+        let corner = cube.vertices().next().unwrap().index;
+        cube.constraints.pin(corner);
+        let point = cube.points.iter().next().unwrap().0;
+        let base = cube.clone();
+        cube.add_morph_target("lift")
+            .set_position(&base, point, [0.0, 0.0, 5.0]);
+        let maps = mesh.append(&cube);
+        assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 2 + 6);
+        assert_eq!(mesh.vertex_count(), 6 + 8);
+        assert_eq!(mesh.edge_count(), 14 + 24);
+        assert_eq!(maps.faces.len(), 6);
+        for (old, new) in &maps.faces {
+            let count = |m: &Mesh, f: FaceIndex| m.face(f).edges().count();
+            assert_eq!(count(&cube, *old), count(&mesh, *new));
+            assert!(mesh
+                .face(*new)
+                .edges()
+                .all(|e| maps.edges.values().any(|copy| *copy == e.twin().index)));
+        }
+        assert_eq!(
+            mesh.vertex(maps.vertices[&corner]).position(),
+            cube.vertex(corner).position()
+        );
+        assert!(mesh.constraints.is_fixed(&mesh, maps.vertices[&corner]));
+        let lift = mesh.morph_target("lift").unwrap();
+        assert!(lift.deltas.contains(maps.points[&point]));
+    }
+
+    #[test]
+    fn indexed_triangles_reject_non_manifold_input() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+        ];
+        let mesh: Mesh = Mesh::from_indexed_triangles(&positions, &[0, 1, 2, 0, 2, 3]).unwrap();
+        assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.edges().filter(|e| !e.is_boundary()).count(), 2);
+        // A closed tetrahedron over the first, second, fourth and fifth positions.
+        let closed = [0, 3, 1, 0, 1, 4, 1, 3, 4, 0, 4, 3];
+        let tetrahedron: Mesh = Mesh::from_indexed_triangles(&positions, &closed).unwrap();
+        assert!(tetrahedron.edges().all(|e| !e.is_boundary()));
+
+        let build = |indices: &[u32]| Mesh::<f32>::from_indexed_triangles(&positions, indices);
+        assert!(matches!(build(&[0, 1]), Err(Error::InvalidArgument(_))));
+        assert!(matches!(build(&[0, 1, 9]), Err(Error::InvalidArgument(_))));
+        assert!(matches!(build(&[0, 1, 1]), Err(Error::InvalidArgument(_))));
+        // A fin on the shared edge, and a neighbor wound the other way.
+        assert!(matches!(
+            build(&[0, 1, 2, 0, 2, 3, 2, 0, 4]),
+            Err(Error::NonManifold(_))
+        ));
+        assert!(matches!(
+            build(&[0, 1, 2, 0, 3, 2]),
+            Err(Error::NonManifold(_))
+        ));
+        // Two triangles touching only at a corner.
+        assert!(matches!(
+            build(&[0, 1, 3, 1, 5, 6]),
+            Err(Error::NonManifold(_))
+        ));
+    }
+}
@@ -0,0 +1,373 @@
+//! Bounding volume hierarchy over the faces of a mesh.
+//!
+//! Faces are fan triangulated when the hierarchy is built, so queries report
+//! the face a triangle came from. The hierarchy does not track later changes
+//! to the mesh and needs to be rebuilt after editing.
+
+use crate::math::{self, Vec3};
+use crate::*;
+use std::cell::Cell;
+
+const LEAF_SIZE: usize = 4;
+
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Default for Aabb {
+    /// An empty box which grows to fit whatever is added to it.
+    fn default() -> Self {
+        Aabb {
+            min: [f64::INFINITY; 3],
+            max: [f64::NEG_INFINITY; 3],
+        }
+    }
+}
+
+impl Aabb {
+    pub fn from_points<'a, I: IntoIterator<Item = &'a [f64; 3]>>(points: I) -> Self {
+        let mut aabb = Aabb::default();
+        for p in points {
+            aabb.grow(*p);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn grow(&mut self, p: [f64; 3]) {
+        self.min = [0, 1, 2].map(|axis| self.min[axis].min(p[axis]));
+        self.max = [0, 1, 2].map(|axis| self.max[axis].max(p[axis]));
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut out = *self;
+        out.grow(other.min);
+        out.grow(other.max);
+        out
+    }
+
+    pub fn extent(&self) -> [f64; 3] {
+        math::sub(self.max, self.min)
+    }
+
+    pub fn center(&self) -> [f64; 3] {
+        math::scale(math::add(self.min, self.max), 0.5)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    /// Entry and exit distances of a ray given the reciprocal of its direction.
+    fn ray_interval(&self, origin: Vec3, inverse: Vec3) -> Option<(f64, f64)> {
+        let mut near = 0.0f64;
+        let mut far = f64::INFINITY;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inverse[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inverse[axis];
+            let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            // NaN appears for rays parallel to a slab starting on its plane.
+            near = if t0.is_nan() { near } else { near.max(t0) };
+            far = if t1.is_nan() { far } else { far.min(t1) };
+        }
+        (near <= far).then_some((near, far))
+    }
+}
+
+/// A ray intersection with one of the triangles of a face.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub face: FaceIndex,
+    pub distance: f64,
+    /// Weights of the triangle corners `vertices` at the hit point.
+    pub barycentric: [f64; 3],
+    pub vertices: [VertexIndex; 3],
+    /// True when the ray hits the side the face normal points towards.
+    pub front_facing: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Triangle {
+    face: FaceIndex,
+    vertices: [VertexIndex; 3],
+    positions: [Vec3; 3],
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Aabb,
+    /// Children for inner nodes, a range of `triangles` for leaves.
+    start: usize,
+    count: usize,
+    is_leaf: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    pub fn new<S: Scalar>(mesh: &Mesh<S>) -> Self {
+        let mut triangles = Vec::new();
+        for face in mesh.faces() {
+            let vertices: Vec<VertexIndex> = face.vertices().map(|v| v.index).collect();
+            for i in 1..vertices.len().saturating_sub(1) {
+                let corners = [vertices[0], vertices[i], vertices[i + 1]];
+                triangles.push(Triangle {
+                    face: face.index,
+                    vertices: corners,
+                    positions: corners.map(|v| mesh.vertex_position(v)),
+                });
+            }
+        }
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            triangles,
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.nodes.push(Node {
+                bounds: Aabb::default(),
+                start: 0,
+                count: bvh.triangles.len(),
+                is_leaf: true,
+            });
+            bvh.split(0);
+        }
+        bvh
+    }
+
+    fn triangle_bounds(&self, range: std::ops::Range<usize>) -> Aabb {
+        Aabb::from_points(
+            self.triangles[range]
+                .iter()
+                .flat_map(|t| t.positions.iter()),
+        )
+    }
+
+    fn split(&mut self, node: usize) {
+        let (start, count) = (self.nodes[node].start, self.nodes[node].count);
+        self.nodes[node].bounds = self.triangle_bounds(start..start + count);
+        if count <= LEAF_SIZE {
+            return;
+        }
+
+        let centroid = |t: &Triangle| {
+            math::scale(
+                math::add(math::add(t.positions[0], t.positions[1]), t.positions[2]),
+                1.0 / 3.0,
+            )
+        };
+        let centroids = Aabb::from_points(
+            self.triangles[start..start + count]
+                .iter()
+                .map(centroid)
+                .collect::<Vec<_>>()
+                .iter(),
+        );
+        let extent = centroids.extent();
+        let axis = (0..3)
+            .max_by(|a, b| extent[*a].total_cmp(&extent[*b]))
+            .unwrap_or(0);
+        if extent[axis] <= 0.0 {
+            return;
+        }
+        self.triangles[start..start + count]
+            .sort_by(|a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+
+        let half = count / 2;
+        let left = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: Aabb::default(),
+            start,
+            count: half,
+            is_leaf: true,
+        });
+        self.nodes.push(Node {
+            bounds: Aabb::default(),
+            start: start + half,
+            count: count - half,
+            is_leaf: true,
+        });
+        self.nodes[node].start = left;
+        self.nodes[node].is_leaf = false;
+        self.split(left);
+        self.split(left + 1);
+    }
+
+    /// Bounds of every face, empty when the mesh had no faces.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map(|n| n.bounds).unwrap_or_default()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn visit<F, G>(&self, mut enter: F, mut leaf: G)
+    where
+        F: FnMut(&Aabb) -> bool,
+        G: FnMut(&Triangle),
+    {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !enter(&node.bounds) {
+                continue;
+            }
+            if node.is_leaf {
+                self.triangles[node.start..node.start + node.count]
+                    .iter()
+                    .for_each(&mut leaf);
+            } else {
+                stack.push(node.start);
+                stack.push(node.start + 1);
+            }
+        }
+    }
+
+    /// Every intersection along the ray, sorted by distance.
+    pub fn intersect_ray_all(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<RayHit> {
+        let inverse = direction.map(|d| 1.0 / d);
+        let mut hits = Vec::new();
+        self.visit(
+            |bounds| bounds.ray_interval(origin, inverse).is_some(),
+            |triangle| hits.extend(intersect_triangle(triangle, origin, direction)),
+        );
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// The closest intersection along the ray.
+    pub fn intersect_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Option<RayHit> {
+        let inverse = direction.map(|d| 1.0 / d);
+        let closest: Cell<Option<RayHit>> = Cell::new(None);
+        self.visit(
+            |bounds| match (bounds.ray_interval(origin, inverse), closest.get()) {
+                (Some((near, _)), Some(hit)) => near <= hit.distance,
+                (interval, _) => interval.is_some(),
+            },
+            |triangle| {
+                if let Some(hit) = intersect_triangle(triangle, origin, direction) {
+                    if closest
+                        .get()
+                        .map(|c| hit.distance < c.distance)
+                        .unwrap_or(true)
+                    {
+                        closest.set(Some(hit));
+                    }
+                }
+            },
+        );
+        closest.get()
+    }
+
+    /// Faces with a triangle whose bounds overlap the box, may contain duplicates.
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<FaceIndex> {
+        self.query_triangles(aabb)
+            .into_iter()
+            .map(|(face, _)| face)
+            .collect()
+    }
+
+    /// Triangles whose bounds overlap the box, along with their face.
+    pub(crate) fn query_triangles(&self, aabb: &Aabb) -> Vec<(FaceIndex, [Vec3; 3])> {
+        let mut out = Vec::new();
+        self.visit(
+            |bounds| bounds.overlaps(aabb),
+            |triangle| {
+                if Aabb::from_points(triangle.positions.iter()).overlaps(aabb) {
+                    out.push((triangle.face, triangle.positions));
+                }
+            },
+        );
+        out
+    }
+}
+
+/// Möller-Trumbore ray triangle intersection.
+fn intersect_triangle(triangle: &Triangle, origin: Vec3, direction: Vec3) -> Option<RayHit> {
+    let [a, b, c] = triangle.positions;
+    let e1 = math::sub(b, a);
+    let e2 = math::sub(c, a);
+    let p = math::cross(direction, e2);
+    let det = math::dot(e1, p);
+    if det.abs() < f64::EPSILON * math::length(e1) * math::length(e2) {
+        return None;
+    }
+    let inverse = 1.0 / det;
+    let s = math::sub(origin, a);
+    let u = math::dot(s, p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = math::cross(s, e1);
+    let v = math::dot(direction, q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = math::dot(e2, q) * inverse;
+    if distance < 0.0 {
+        return None;
+    }
+    Some(RayHit {
+        face: triangle.face,
+        distance,
+        barycentric: [1.0 - u - v, u, v],
+        vertices: triangle.vertices,
+        front_facing: det > 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rays_hit_the_closest_face() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::cube();
+        let bvh = Bvh::new(&mesh);
+        assert_eq!(bvh.triangle_count(), 12);
+        assert_eq!(bvh.bounds().min, [0.0; 3]);
+        assert_eq!(bvh.bounds().max, [1.0; 3]);
+
+        let hit = bvh
+            .intersect_ray([0.25, 0.5, -1.0], [0.0, 0.0, 1.0])
+            .expect("Expected to hit the cube.");
+        assert!((hit.distance - 1.0).abs() < 1.0e-12);
+        assert!(hit.front_facing);
+        assert!(mesh.face_normal(hit.face)[2] < -0.99);
+
+        let hits = bvh.intersect_ray_all([0.25, 0.5, -1.0], [0.0, 0.0, 1.0]);
+        assert_eq!(hits.len(), 2);
+        assert!(!hits[1].front_facing);
+        assert!(bvh
+            .intersect_ray([2.0, 0.5, -1.0], [0.0, 0.0, 1.0])
+            .is_none());
+    }
+
+    #[test]
+    fn box_queries_find_overlapping_faces() {
+        let mesh: Mesh = builder::grid(8, 8);
+        let bvh = Bvh::new(&mesh);
+        let query = Aabb {
+            min: [0.5, 0.5, -1.0],
+            max: [1.5, 1.5, 1.0],
+        };
+        let mut faces = bvh.query_aabb(&query);
+        faces.sort_by_key(|f| f.offset);
+        faces.dedup();
+        assert_eq!(faces.len(), 4);
+    }
+}
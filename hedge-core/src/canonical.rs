@@ -0,0 +1,525 @@
+//! Element orders which only depend on a mesh's content.
+//!
+//! Each connected piece is walked breadth first from a half-edge leaving its
+//! smallest point, comparing positions after snapping them to a grid. When
+//! several half-edges leave that point the walk producing the smallest code
+//! wins, so the order survives any renumbering of the elements.
+
+use crate::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Where a walk could not go, like the face of a boundary edge.
+const NONE: i64 = -1;
+
+impl<S: Scalar> Mesh<S> {
+    /// A digest of connectivity and positions snapped to the default weld
+    /// distance, equal for meshes which only differ in their numbering.
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash_with(&Tolerances::default())
+    }
+
+    /// Like `content_hash`, snapping positions to a grid of `weld` sized
+    /// cells. Positions on either side of a cell border hash differently.
+    pub fn content_hash_with(&self, tolerances: &Tolerances) -> u64 {
+        let order = Order::new(self, tolerances.weld);
+        let mut hash = Fnv::default();
+        for count in [
+            order.edges.len(),
+            order.vertices.len(),
+            order.faces.len(),
+            order.points.len(),
+        ] {
+            hash.write(count as i64);
+        }
+        let edges = labels(&order.edges);
+        let vertices = labels(&order.vertices);
+        let faces = labels(&order.faces);
+        let points = labels(&order.points);
+        for index in &order.edges {
+            let Some(edge) = self.edges.get(*index) else {
+                continue;
+            };
+            hash.write(label(&edges, edge.next_index));
+            hash.write(label(&edges, edge.twin_index));
+            hash.write(label(&faces, edge.face_index));
+            hash.write(label(&vertices, edge.vertex_index));
+        }
+        for index in &order.vertices {
+            let point = self.vertices.get(*index).map(|v| v.point_index);
+            hash.write(point.map_or(NONE, |p| label(&points, p)));
+        }
+        for index in &order.points {
+            for value in order.snapped(self, *index) {
+                hash.write(value);
+            }
+        }
+        hash.finish()
+    }
+
+    /// Renumbers every element in canonical order, so meshes with the same
+    /// content end up with the same handles and serialize identically.
+    ///
+    /// Faces and vertices also get their first edge chosen canonically. All
+    /// handles into the mesh change. Morph targets, vertex normals and
+    /// property maps are carried along, other channels can be carried over with the returned
+    /// maps. The points are copied, leaving any shared pool.
+    pub fn canonicalize(&mut self) -> Renumbering {
+        self.canonicalize_with(&Tolerances::default())
+    }
+
+    /// Like `canonicalize`, comparing positions on a grid of `weld` cells.
+    pub fn canonicalize_with(&mut self, tolerances: &Tolerances) -> Renumbering {
+        let order = Order::new(self, tolerances.weld);
+        let renumbering = Renumbering {
+            edges: fresh(&order.edges),
+            vertices: fresh(&order.vertices),
+            faces: fresh(&order.faces),
+            points: fresh(&order.points),
+        };
+
+        let mut points = pool::PointBuffer::with_capacity(order.points.len());
+        for index in &order.points {
+            points.push(self.points.get(*index).cloned().unwrap_or_default());
+        }
+        let mut vertices = hbuf::ElementBuffer::with_capacity(order.vertices.len());
+        for index in &order.vertices {
+            let point = self.vertices.get(*index).map(|v| v.point_index);
+            vertices.push(Vertex::at_point(
+                point.map_or_else(Default::default, |p| moved(&renumbering.points, p)),
+            ));
+        }
+        let mut faces = hbuf::ElementBuffer::with_capacity(order.faces.len());
+        for _ in &order.faces {
+            faces.push(Face::default());
+        }
+        let mut edges = hbuf::ElementBuffer::with_capacity(order.edges.len());
+        for index in &order.edges {
+            let old = self.edges.get(*index).cloned().unwrap_or_default();
+            let edge = Edge {
+                twin_index: moved(&renumbering.edges, old.twin_index),
+                next_index: moved(&renumbering.edges, old.next_index),
+                prev_index: moved(&renumbering.edges, old.prev_index),
+                face_index: moved(&renumbering.faces, old.face_index),
+                vertex_index: moved(&renumbering.vertices, old.vertex_index),
+            };
+            let new = edges.push(edge.clone());
+            // The first edge of each face and the first outgoing edge of each
+            // vertex, preferring the boundary, become their roots.
+            if let Some(face) = faces.get_mut(edge.face_index) {
+                if !face.edge_index.is_valid() {
+                    face.edge_index = new;
+                }
+            }
+            if let Some(vertex) = vertices.get_mut(edge.vertex_index) {
+                let current = edges.get(vertex.edge_index);
+                let interior = current.is_some_and(|e| e.face_index.is_valid());
+                if current.is_none() || (interior && !edge.face_index.is_valid()) {
+                    vertex.edge_index = new;
+                }
+            }
+        }
+        self.edges = edges;
+        self.vertices = vertices;
+        self.faces = faces;
+        self.points = pool::PointPool::from(points);
+        for target in &mut self.morph_targets {
+            target.deltas = target.deltas.remapped(&renumbering.points);
+        }
+        self.constraints.remap(&renumbering.vertices);
+        self.vertex_normals = self.vertex_normals.remapped(&renumbering.vertices);
+        self.properties.renumber(&renumbering);
+        renumbering
+    }
+
+    /// Moves elements into the cells left free by removals, so each buffer
+    /// is packed from the front.
+    ///
+    /// Moved elements keep their generation but not their offset, so their
+    /// handles change. Morph targets, constraints, vertex normals and
+    /// property maps follow along, other channels can with the returned
+    /// maps. Points shared with another mesh stay where they are.
+    pub fn defrag(&mut self) -> Renumbering {
+        let renumbering = Renumbering {
+            edges: defragmented(&mut self.edges),
+            vertices: defragmented(&mut self.vertices),
+            faces: defragmented(&mut self.faces),
+            points: if self.points.is_shared() {
+                self.points
+                    .iter()
+                    .map(|(index, _)| (index, index))
+                    .collect()
+            } else {
+                defragmented(&mut self.points)
+            },
+        };
+        for (_, edge) in self.edges.iter_mut() {
+            edge.twin_index = moved(&renumbering.edges, edge.twin_index);
+            edge.next_index = moved(&renumbering.edges, edge.next_index);
+            edge.prev_index = moved(&renumbering.edges, edge.prev_index);
+            edge.face_index = moved(&renumbering.faces, edge.face_index);
+            edge.vertex_index = moved(&renumbering.vertices, edge.vertex_index);
+        }
+        for (_, vertex) in self.vertices.iter_mut() {
+            vertex.edge_index = moved(&renumbering.edges, vertex.edge_index);
+            vertex.point_index = moved(&renumbering.points, vertex.point_index);
+        }
+        for (_, face) in self.faces.iter_mut() {
+            face.edge_index = moved(&renumbering.edges, face.edge_index);
+        }
+        for target in &mut self.morph_targets {
+            target.deltas = target.deltas.remapped(&renumbering.points);
+        }
+        self.constraints.remap(&renumbering.vertices);
+        self.vertex_normals = self.vertex_normals.remapped(&renumbering.vertices);
+        self.properties.renumber(&renumbering);
+        renumbering
+    }
+}
+
+/// Where each element was moved, by `Mesh::canonicalize`, or copied to, by
+/// `Mesh::append`.
+#[derive(Debug, Clone, Default)]
+pub struct Renumbering {
+    pub edges: HashMap<EdgeIndex, EdgeIndex>,
+    pub vertices: HashMap<VertexIndex, VertexIndex>,
+    pub faces: HashMap<FaceIndex, FaceIndex>,
+    pub points: HashMap<PointIndex, PointIndex>,
+}
+
+/// Every live element of a mesh in canonical order.
+pub(crate) struct Order {
+    pub(crate) edges: Vec<EdgeIndex>,
+    pub(crate) vertices: Vec<VertexIndex>,
+    pub(crate) faces: Vec<FaceIndex>,
+    pub(crate) points: Vec<PointIndex>,
+    quantum: f64,
+}
+
+impl Order {
+    pub(crate) fn new<S: Scalar>(mesh: &Mesh<S>, quantum: f64) -> Self {
+        let mut order = Order {
+            edges: Vec::new(),
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            points: Vec::new(),
+            quantum,
+        };
+        let mut walks = Vec::new();
+        let mut reached = HashSet::new();
+        for (start, _) in mesh.edges.iter() {
+            if reached.contains(&start) {
+                continue;
+            }
+            let piece = order.walk(mesh, start);
+            reached.extend(piece.edges.iter().copied());
+            let smallest = piece
+                .edges
+                .iter()
+                .map(|e| order.origin(mesh, *e))
+                .min()
+                .unwrap_or_default();
+            let best = piece
+                .edges
+                .iter()
+                .filter(|e| order.origin(mesh, **e) == smallest)
+                .map(|e| order.walk(mesh, *e))
+                .min_by(|a, b| a.code.cmp(&b.code));
+            walks.extend(best);
+        }
+        walks.sort_by(|a, b| a.code.cmp(&b.code));
+
+        let mut seen_vertices = HashSet::new();
+        let mut seen_points = HashSet::new();
+        for walk in walks {
+            order.edges.extend(walk.edges);
+            order.faces.extend(walk.faces);
+            for vertex in walk.vertices {
+                if seen_vertices.insert(vertex) {
+                    order.vertices.push(vertex);
+                }
+            }
+            for point in walk.points {
+                if seen_points.insert(point) {
+                    order.points.push(point);
+                }
+            }
+        }
+
+        // Vertices without edges and unused points go last, by position.
+        let mut lone: Vec<VertexIndex> = mesh
+            .vertices
+            .iter()
+            .map(|(index, _)| index)
+            .filter(|index| !seen_vertices.contains(index))
+            .collect();
+        lone.sort_by_key(|v| {
+            let point = mesh.vertices.get(*v).map(|v| v.point_index);
+            point.map(|p| order.snapped(mesh, p))
+        });
+        for vertex in lone {
+            let point = mesh.vertices.get(vertex).map(|v| v.point_index);
+            if let Some(point) = point.filter(|p| mesh.points.get(*p).is_some()) {
+                if seen_points.insert(point) {
+                    order.points.push(point);
+                }
+            }
+            order.vertices.push(vertex);
+        }
+        let mut unused: Vec<PointIndex> = mesh
+            .points
+            .iter()
+            .map(|(index, _)| index)
+            .filter(|index| !seen_points.contains(index))
+            .collect();
+        unused.sort_by_key(|p| order.snapped(mesh, *p));
+        order.points.extend(unused);
+        order
+    }
+
+    /// A position snapped to the grid, exact bits without a grid.
+    fn snapped<S: Scalar>(&self, mesh: &Mesh<S>, point: PointIndex) -> [i64; 3] {
+        let position = mesh.position(point).map(scalar::to_f64).unwrap_or_default();
+        position.map(|x| {
+            if self.quantum > 0.0 {
+                (x / self.quantum).round() as i64
+            } else {
+                (x + 0.0).to_bits() as i64
+            }
+        })
+    }
+
+    fn origin<S: Scalar>(&self, mesh: &Mesh<S>, edge: EdgeIndex) -> [i64; 3] {
+        let point = mesh.vertex(mesh.edge(edge).vertex().index).element();
+        point.map_or([i64::MAX; 3], |v| self.snapped(mesh, v.point_index))
+    }
+
+    /// Labels the piece around `root` in the order a breadth first walk
+    /// reaches its elements.
+    fn walk<S: Scalar>(&self, mesh: &Mesh<S>, root: EdgeIndex) -> Walk {
+        let mut walk = Walk::default();
+        let mut edges = HashMap::new();
+        let mut vertices = HashMap::new();
+        let mut faces = HashMap::new();
+        let mut points = HashMap::new();
+        let mut queue = VecDeque::from([root]);
+        visit(&mut edges, &mut walk.edges, root);
+        while let Some(index) = queue.pop_front() {
+            let Some(edge) = mesh.edges.get(index) else {
+                walk.code.push([NONE; 8]);
+                continue;
+            };
+            let mut entry = [NONE; 8];
+            for (slot, next) in [edge.next_index, edge.twin_index].into_iter().enumerate() {
+                if mesh.edges.get(next).is_none() {
+                    continue;
+                }
+                let (label, new) = visit(&mut edges, &mut walk.edges, next);
+                if new {
+                    queue.push_back(next);
+                }
+                entry[slot] = label;
+            }
+            if mesh.faces.get(edge.face_index).is_some() {
+                entry[2] = visit(&mut faces, &mut walk.faces, edge.face_index).0;
+            }
+            if let Some(vertex) = mesh.vertices.get(edge.vertex_index) {
+                entry[3] = visit(&mut vertices, &mut walk.vertices, edge.vertex_index).0;
+                if mesh.points.get(vertex.point_index).is_some() {
+                    entry[4] = visit(&mut points, &mut walk.points, vertex.point_index).0;
+                    let position = self.snapped(mesh, vertex.point_index);
+                    entry[5..].copy_from_slice(&position);
+                }
+            }
+            walk.code.push(entry);
+        }
+        walk
+    }
+}
+
+#[derive(Default)]
+struct Walk {
+    code: Vec<[i64; 8]>,
+    edges: Vec<EdgeIndex>,
+    vertices: Vec<VertexIndex>,
+    faces: Vec<FaceIndex>,
+    points: Vec<PointIndex>,
+}
+
+/// Labels an element the first time it is reached, returning its label and
+/// whether it is new.
+fn visit<K>(
+    labels: &mut HashMap<Handle<K>, i64>,
+    order: &mut Vec<Handle<K>>,
+    index: Handle<K>,
+) -> (i64, bool) {
+    if let Some(label) = labels.get(&index) {
+        return (*label, false);
+    }
+    let label = order.len() as i64;
+    labels.insert(index, label);
+    order.push(index);
+    (label, true)
+}
+
+/// Handles in fresh buffers count up from the first cell.
+fn fresh<K>(order: &[Handle<K>]) -> HashMap<Handle<K>, Handle<K>> {
+    let mut buffer: hbuf::ElementBuffer<(), K> = hbuf::ElementBuffer::new();
+    order.iter().map(|old| (*old, buffer.push(()))).collect()
+}
+
+/// Defragments `buffer`, mapping every live element to where it ended up.
+fn defragmented<D: Default, K>(
+    buffer: &mut hbuf::ElementBuffer<D, K>,
+) -> HashMap<Handle<K>, Handle<K>> {
+    let mut map: HashMap<Handle<K>, Handle<K>> =
+        buffer.iter().map(|(index, _)| (index, index)).collect();
+    let generations: HashMap<Offset, Generation> = map
+        .keys()
+        .map(|index| (index.offset, index.generation))
+        .collect();
+    for (free, active) in buffer.defragment() {
+        let generation = generations[&active];
+        map.insert(
+            Handle::new(active, generation),
+            Handle::new(free, generation),
+        );
+    }
+    map
+}
+
+fn moved<K: Default>(map: &HashMap<Handle<K>, Handle<K>>, index: Handle<K>) -> Handle<K> {
+    map.get(&index).copied().unwrap_or_default()
+}
+
+fn labels<K>(order: &[Handle<K>]) -> HashMap<Handle<K>, i64> {
+    order
+        .iter()
+        .enumerate()
+        .map(|(label, index)| (*index, label as i64))
+        .collect()
+}
+
+fn label<K>(labels: &HashMap<Handle<K>, i64>, index: Handle<K>) -> i64 {
+    labels.get(&index).copied().unwrap_or(NONE)
+}
+
+/// 64 bit FNV-1a, which unlike the std hashers is fixed across releases.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, value: i64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::Channel;
+
+    /// The unit cube built from reversed points and faces.
+    fn renumbered(cube: &Mesh<f64>) -> Mesh<f64> {
+        let mut points: Vec<(PointIndex, [f64; 3])> =
+            cube.points.iter().map(|(i, p)| (i, p.position)).collect();
+        points.reverse();
+        let positions: Vec<[f64; 3]> = points.iter().map(|(_, p)| *p).collect();
+        let slot = |point: PointIndex| points.iter().position(|(i, _)| *i == point).unwrap();
+        let mut polygons: Vec<Vec<usize>> = cube
+            .faces()
+            .map(|f| {
+                let corners: Vec<usize> = f
+                    .vertices()
+                    .map(|v| slot(v.element().unwrap().point_index))
+                    .collect();
+                // Start each face at a different corner as well.
+                [&corners[1..], &corners[..1]].concat()
+            })
+            .collect();
+        polygons.reverse();
+        Mesh::from_polygons(&positions, &polygons)
+    }
+
+    #[test]
+    fn hashes_ignore_numbering() {
+        let _ = env_logger::try_init();
+        let cube: Mesh<f64> = builder::cube();
+        let renumbered = renumbered(&cube);
+        assert_eq!(cube.content_hash(), renumbered.content_hash());
+
+        // Moving a point, rewinding faces or dropping a face all show up.
+        let mut moved = renumbered.clone();
+        if let Some((_, point)) = moved.points.iter_mut().next() {
+            point.position[0] += 0.5;
+        }
+        assert_ne!(cube.content_hash(), moved.content_hash());
+        let mut flipped = cube.clone();
+        flipped.flip_all_faces();
+        assert_ne!(cube.content_hash(), flipped.content_hash());
+        let mut opened = cube.clone();
+        let face = opened.faces().next().unwrap().index;
+        ops::remove_face(&mut opened, face).unwrap();
+        assert_ne!(cube.content_hash(), opened.content_hash());
+
+        // Noise below the weld distance is snapped away.
+        let mut jittered = cube.clone();
+        for (_, point) in jittered.points.iter_mut() {
+            point.position[1] += 1.0e-9;
+        }
+        assert_eq!(cube.content_hash(), jittered.content_hash());
+    }
+
+    #[test]
+    fn canonical_meshes_are_laid_out_identically() {
+        let mut cube: Mesh<f64> = builder::cube();
+        let mut other = renumbered(&cube);
+        let mut normals: Channel<Face, [f64; 3]> = Channel::new();
+        for face in other.faces() {
+            normals.set(face.index, other.face_normal(face.index));
+        }
+        let hash = cube.content_hash();
+        cube.canonicalize();
+        let renumbering = other.canonicalize();
+        builder::assert_connectivity(&other);
+        assert_eq!(other.content_hash(), hash);
+
+        let layout = |mesh: &Mesh<f64>| {
+            format!(
+                "{:?} {:?} {:?} {:?}",
+                mesh.edges.iter().collect::<Vec<_>>(),
+                mesh.vertices.iter().collect::<Vec<_>>(),
+                mesh.faces.iter().collect::<Vec<_>>(),
+                mesh.points.iter().collect::<Vec<_>>()
+            )
+        };
+        assert_eq!(layout(&cube), layout(&other));
+        let normals = normals.remapped(&renumbering.faces);
+        for face in other.faces() {
+            assert_eq!(
+                normals.get(face.index),
+                Some(&other.face_normal(face.index))
+            );
+        }
+
+        // Cells freed by earlier edits are gone afterwards.
+        let mut opened: Mesh = builder::grid(2, 2);
+        let face = opened.faces().next().unwrap().index;
+        ops::remove_face(&mut opened, face).unwrap();
+        opened.canonicalize();
+        builder::assert_connectivity(&opened);
+        assert!(!opened.edges.has_inactive_cells());
+        assert!(!opened.vertices.has_inactive_cells());
+    }
+}
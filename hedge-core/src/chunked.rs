@@ -0,0 +1,337 @@
+//! Processing of meshes too large to keep in memory at once.
+//!
+//! A mesh is cut into chunks on a regular grid, each face going to the tile
+//! holding its centroid. Every chunk is a mesh of its own which records a
+//! global id per point. Points used by several chunks are shared, they stay
+//! locked while chunks are processed so that the pieces can be stitched
+//! back together afterwards.
+//!
+//! Chunks are kept in a `ChunkStore` which only has to hand out one chunk at
+//! a time, so it is free to write unloaded chunks to disk.
+
+use crate::attributes::Channel;
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Identifies a point across all chunks of a mesh.
+pub type GlobalId = u64;
+
+/// A tile of a larger mesh.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk<S: Scalar = f32> {
+    pub mesh: Mesh<S>,
+    pub ids: Channel<Point, GlobalId>,
+    /// Points which also belong to other chunks.
+    pub shared: HashSet<GlobalId>,
+}
+
+impl<S: Scalar> Chunk<S> {
+    /// True when the vertex sits on a point shared with another chunk and
+    /// must not be moved or removed.
+    pub fn is_locked(&self, vertex: VertexIndex) -> bool {
+        self.mesh
+            .vertex(vertex)
+            .element()
+            .and_then(|v| self.ids.get(v.point_index))
+            .map(|id| self.shared.contains(id))
+            .unwrap_or(false)
+    }
+}
+
+/// Storage for the chunks of a mesh which aren't being processed.
+pub trait ChunkStore<S: Scalar> {
+    fn chunk_count(&self) -> usize;
+    /// Hands out a chunk, which is given back through `unload` once done.
+    fn load(&mut self, chunk: usize) -> Result<Chunk<S>>;
+    fn unload(&mut self, chunk: usize, data: Chunk<S>) -> Result<()>;
+}
+
+/// Keeps every chunk in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore<S: Scalar = f32> {
+    chunks: Vec<Option<Chunk<S>>>,
+}
+
+impl<S: Scalar> MemoryStore<S> {
+    pub fn new(chunks: Vec<Chunk<S>>) -> Self {
+        MemoryStore {
+            chunks: chunks.into_iter().map(Some).collect(),
+        }
+    }
+}
+
+impl<S: Scalar> ChunkStore<S> for MemoryStore<S> {
+    fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn load(&mut self, chunk: usize) -> Result<Chunk<S>> {
+        self.chunks
+            .get_mut(chunk)
+            .and_then(Option::take)
+            .ok_or_else(|| Error::InvalidArgument(format!("chunk {} is not available", chunk)))
+    }
+
+    fn unload(&mut self, chunk: usize, data: Chunk<S>) -> Result<()> {
+        match self.chunks.get_mut(chunk) {
+            Some(slot) => {
+                *slot = Some(data);
+                Ok(())
+            }
+            None => Err(Error::InvalidArgument(format!(
+                "chunk {} is out of range",
+                chunk
+            ))),
+        }
+    }
+}
+
+/// Cuts a mesh into chunks on a grid of cubes with sides of `tile_size`.
+///
+/// Each chunk gets one vertex per point, so seams are welded.
+pub fn partition<S: Scalar>(mesh: &Mesh<S>, tile_size: f64) -> Result<Vec<Chunk<S>>> {
+    if tile_size <= 0.0 || !tile_size.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "chunks need a positive tile size, got {}",
+            tile_size
+        )));
+    }
+    let mut tiles: BTreeMap<[i64; 3], Vec<FaceIndex>> = BTreeMap::new();
+    for face in mesh.faces() {
+        let corners: Vec<Vec3> = face
+            .vertices()
+            .map(|v| mesh.vertex_position(v.index))
+            .collect();
+        let centroid = math::scale(
+            corners.iter().fold([0.0; 3], |sum, c| math::add(sum, *c)),
+            1.0 / corners.len().max(1) as f64,
+        );
+        let key = centroid.map(|c| (c / tile_size).floor() as i64);
+        tiles.entry(key).or_default().push(face.index);
+    }
+
+    let mut users: HashMap<GlobalId, usize> = HashMap::new();
+    let mut chunks = Vec::with_capacity(tiles.len());
+    for faces in tiles.values() {
+        let mut local: HashMap<GlobalId, usize> = HashMap::new();
+        let mut ids = Vec::new();
+        let mut positions = Vec::new();
+        let mut polygons = Vec::with_capacity(faces.len());
+        for face in faces {
+            let polygon = mesh
+                .face(*face)
+                .vertices()
+                .filter_map(|v| v.element().map(|v| v.point_index))
+                .map(|point| {
+                    let id = point.offset as GlobalId;
+                    *local.entry(id).or_insert_with(|| {
+                        ids.push(id);
+                        positions.push(mesh.position(point).unwrap_or_default());
+                        ids.len() - 1
+                    })
+                })
+                .collect();
+            polygons.push(polygon);
+        }
+        for id in &ids {
+            *users.entry(*id).or_default() += 1;
+        }
+        let chunk_mesh = Mesh::from_polygons(&positions, &polygons);
+        let mut point_ids = Channel::new();
+        for ((point, _), id) in chunk_mesh.points.iter().zip(&ids) {
+            point_ids.set(point, *id);
+        }
+        chunks.push(Chunk {
+            mesh: chunk_mesh,
+            ids: point_ids,
+            shared: HashSet::new(),
+        });
+    }
+    for chunk in &mut chunks {
+        chunk.shared = chunk
+            .ids
+            .iter()
+            .map(|(_, id)| *id)
+            .filter(|id| users.get(id).copied().unwrap_or(0) > 1)
+            .collect();
+    }
+    Ok(chunks)
+}
+
+/// Streams every chunk through `f`, loading one chunk at a time.
+pub fn for_each_chunk<S, C, F>(store: &mut C, mut f: F) -> Result<()>
+where
+    S: Scalar,
+    C: ChunkStore<S>,
+    F: FnMut(usize, &mut Chunk<S>) -> Result<()>,
+{
+    for index in 0..store.chunk_count() {
+        let mut chunk = store.load(index)?;
+        let result = f(index, &mut chunk);
+        store.unload(index, chunk)?;
+        result?;
+    }
+    Ok(())
+}
+
+/// Joins all chunks of a store into a single mesh, merging shared points.
+pub fn stitch<S: Scalar, C: ChunkStore<S>>(store: &mut C) -> Result<Mesh<S>> {
+    let mut positions: BTreeMap<GlobalId, Position<S>> = BTreeMap::new();
+    let mut polygons: Vec<Vec<GlobalId>> = Vec::new();
+    for_each_chunk(store, |_, chunk| {
+        for (point, id) in chunk.ids.iter() {
+            if let Some(position) = chunk.mesh.position(point) {
+                positions.entry(*id).or_insert(position);
+            }
+        }
+        for face in chunk.mesh.faces() {
+            polygons.push(
+                face.vertices()
+                    .filter_map(|v| v.element())
+                    .filter_map(|v| chunk.ids.get(v.point_index).copied())
+                    .collect(),
+            );
+        }
+        Ok(())
+    })?;
+    let dense: HashMap<GlobalId, usize> = positions
+        .keys()
+        .enumerate()
+        .map(|(i, id)| (*id, i))
+        .collect();
+    let positions: Vec<Position<S>> = positions.into_values().collect();
+    let polygons: Vec<Vec<usize>> = polygons
+        .iter()
+        .map(|polygon| polygon.iter().map(|id| dense[id]).collect())
+        .collect();
+    Ok(Mesh::from_polygons(&positions, &polygons))
+}
+
+/// Laplacian smoothing of a chunk which leaves shared points in place and
+/// honours the constraints of the chunk's mesh.
+pub fn smooth<S: Scalar>(chunk: &mut Chunk<S>, iterations: usize, strength: f64) {
+    let free: Vec<VertexIndex> = chunk
+        .mesh
+        .vertices()
+        .map(|v| v.index)
+        .filter(|v| !chunk.is_locked(*v) && !chunk.mesh.constraints.is_fixed(&chunk.mesh, *v))
+        .collect();
+    for _ in 0..iterations {
+        let moved: Vec<(PointIndex, Vec3)> = free
+            .iter()
+            .filter_map(|vertex| {
+                let mesh = &chunk.mesh;
+                let point = mesh.vertex(*vertex).element()?.point_index;
+                let neighbors: Vec<Vec3> = mesh
+                    .vertex(*vertex)
+                    .edges()
+                    .map(|e| mesh.vertex_position(e.twin().vertex().index))
+                    .collect();
+                if neighbors.is_empty() {
+                    return None;
+                }
+                let average = math::scale(
+                    neighbors.iter().fold([0.0; 3], |sum, n| math::add(sum, *n)),
+                    1.0 / neighbors.len() as f64,
+                );
+                let position = mesh.vertex_position(*vertex);
+                let target = math::lerp(position, average, strength);
+                Some((point, mesh.constraints.constrain(mesh, *vertex, target)))
+            })
+            .collect();
+        for (point, position) in moved {
+            if let Some(p) = chunk.mesh.get_element_mut(point) {
+                p.position = scalar::from_f64(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps track of how many chunks are out at once.
+    struct CountingStore {
+        inner: MemoryStore<f64>,
+        loaded: usize,
+        most_loaded: usize,
+    }
+
+    impl ChunkStore<f64> for CountingStore {
+        fn chunk_count(&self) -> usize {
+            self.inner.chunk_count()
+        }
+
+        fn load(&mut self, chunk: usize) -> Result<Chunk<f64>> {
+            self.loaded += 1;
+            self.most_loaded = self.most_loaded.max(self.loaded);
+            self.inner.load(chunk)
+        }
+
+        fn unload(&mut self, chunk: usize, data: Chunk<f64>) -> Result<()> {
+            self.loaded -= 1;
+            self.inner.unload(chunk, data)
+        }
+    }
+
+    #[test]
+    fn partition_and_stitch_round_trip() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh<f64> = builder::grid(4, 4);
+        let chunks = partition(&mesh, 2.0).unwrap();
+        assert_eq!(chunks.len(), 4);
+        for chunk in &chunks {
+            assert_eq!(chunk.mesh.face_count(), 4);
+            // The middle row and column of the grid are shared.
+            assert_eq!(chunk.shared.len(), 5);
+        }
+        let mut store = MemoryStore::new(chunks);
+        let stitched = stitch(&mut store).unwrap();
+        builder::assert_connectivity(&stitched);
+        assert_eq!(stitched.face_count(), 16);
+        assert_eq!(stitched.point_count(), 25);
+        assert_eq!(
+            stitched.edges().filter(|e| !e.face().is_valid()).count(),
+            16
+        );
+        assert!(partition(&mesh, 0.0).is_err());
+    }
+
+    #[test]
+    fn chunks_are_streamed_one_at_a_time() {
+        let mut mesh: Mesh<f64> = builder::grid(6, 6);
+        let center = mesh
+            .points
+            .iter()
+            .find(|(_, p)| p.position == [4.0, 4.0, 0.0])
+            .map(|(index, _)| index)
+            .unwrap();
+        if let Some(point) = mesh.get_element_mut(center) {
+            point.position[2] = 1.0;
+        }
+        let mut store = CountingStore {
+            inner: MemoryStore::new(partition(&mesh, 3.0).unwrap()),
+            loaded: 0,
+            most_loaded: 0,
+        };
+        for_each_chunk(&mut store, |_, chunk| {
+            smooth(chunk, 4, 0.5);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(store.most_loaded, 1);
+
+        let smoothed = stitch(&mut store).unwrap();
+        assert_eq!(smoothed.point_count(), 49);
+        let heights: Vec<[f64; 3]> = smoothed.points.iter().map(|(_, p)| p.position).collect();
+        // The bump spread out, points on the tile borders stayed put.
+        assert!(heights.iter().all(|p| p[2] < 1.0));
+        assert!(heights.iter().filter(|p| p[2] > 0.0).count() > 1);
+        assert!(heights
+            .iter()
+            .filter(|p| p[0] == 3.0 || p[1] == 3.0)
+            .all(|p| p[2] == 0.0));
+    }
+}
@@ -0,0 +1,239 @@
+//! Comparing meshes regardless of how their elements are numbered.
+//!
+//! Fixing where one half-edge goes determines where its whole connected
+//! piece goes, by following `next` and `twin`. Each piece is tried against
+//! the unmatched half-edges of the other mesh until one fits.
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+
+impl<S: Scalar> Mesh<S> {
+    /// True when both meshes have the same connectivity up to renumbering
+    /// of their elements, positions are ignored.
+    pub fn topologically_equals<T: Scalar>(&self, other: &Mesh<T>) -> bool {
+        Matcher::new(self, other, None).matches()
+    }
+
+    /// Like `topologically_equals`, also requiring every matched point to
+    /// lie within `eps` of its counterpart.
+    pub fn geometrically_equals<T: Scalar>(&self, other: &Mesh<T>, eps: f64) -> bool {
+        Matcher::new(self, other, Some(eps)).matches()
+    }
+}
+
+/// Which element of the other mesh each element was matched with, and the
+/// reverse so no element is used twice.
+#[derive(Clone, Default)]
+struct Mapping<K> {
+    forward: HashMap<Handle<K>, Handle<K>>,
+    used: HashSet<Handle<K>>,
+}
+
+impl<K> Mapping<K> {
+    /// Records a pair, failing when either side is already paired up with
+    /// something else.
+    fn pair(&mut self, from: Handle<K>, to: Handle<K>) -> Option<bool> {
+        match self.forward.get(&from) {
+            Some(existing) if *existing == to => Some(false),
+            Some(_) => None,
+            None if self.used.contains(&to) => None,
+            None => {
+                self.forward.insert(from, to);
+                self.used.insert(to);
+                Some(true)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct State {
+    edges: Mapping<Edge>,
+    vertices: Mapping<Vertex>,
+    faces: Mapping<Face>,
+    points: Mapping<Point>,
+}
+
+struct Matcher<'a, S: Scalar, T: Scalar> {
+    a: &'a Mesh<S>,
+    b: &'a Mesh<T>,
+    eps: Option<f64>,
+}
+
+impl<'a, S: Scalar, T: Scalar> Matcher<'a, S, T> {
+    fn new(a: &'a Mesh<S>, b: &'a Mesh<T>, eps: Option<f64>) -> Self {
+        Matcher { a, b, eps }
+    }
+
+    fn matches(&self) -> bool {
+        let (a, b) = (self.a, self.b);
+        if a.edge_count() != b.edge_count()
+            || a.vertex_count() != b.vertex_count()
+            || a.face_count() != b.face_count()
+            || a.point_count() != b.point_count()
+        {
+            return false;
+        }
+        let mut state = State::default();
+        for (root, _) in a.edges.iter() {
+            if state.edges.forward.contains_key(&root) {
+                continue;
+            }
+            let found = b
+                .edges
+                .iter()
+                .filter(|(candidate, _)| !state.edges.used.contains(candidate))
+                .find_map(|(candidate, _)| self.extend(&state, root, candidate));
+            match found {
+                Some(extended) => state = extended,
+                None => return false,
+            }
+        }
+        self.match_leftovers(&state)
+    }
+
+    fn close(&self, a: PointIndex, b: PointIndex) -> bool {
+        let Some(eps) = self.eps else {
+            return true;
+        };
+        match (self.a.points.get(a), self.b.points.get(b)) {
+            (Some(p), Some(q)) => {
+                let (p, q) = (scalar::to_f64(p.position), scalar::to_f64(q.position));
+                math::distance(p, q) <= eps
+            }
+            _ => false,
+        }
+    }
+
+    /// Matches the piece of `a` around `root` with the piece of `b` around
+    /// `candidate`, returning the grown mapping when everything lines up.
+    fn extend(&self, state: &State, root: EdgeIndex, candidate: EdgeIndex) -> Option<State> {
+        let (a, b) = (self.a, self.b);
+        let mut state = state.clone();
+        let mut queue = vec![(root, candidate)];
+        state.edges.pair(root, candidate)?;
+        while let Some((x, y)) = queue.pop() {
+            let (ex, ey) = (a.edges.get(x)?, b.edges.get(y)?);
+            if ex.face_index.is_valid() != ey.face_index.is_valid() {
+                return None;
+            }
+            if ex.face_index.is_valid() {
+                state.faces.pair(ex.face_index, ey.face_index)?;
+            }
+            if state.vertices.pair(ex.vertex_index, ey.vertex_index)? {
+                let (vx, vy) = (
+                    a.vertices.get(ex.vertex_index)?,
+                    b.vertices.get(ey.vertex_index)?,
+                );
+                state.points.pair(vx.point_index, vy.point_index)?;
+                if !self.close(vx.point_index, vy.point_index) {
+                    return None;
+                }
+            }
+            for (nx, ny) in [
+                (ex.next_index, ey.next_index),
+                (ex.prev_index, ey.prev_index),
+                (ex.twin_index, ey.twin_index),
+            ] {
+                if state.edges.pair(nx, ny)? {
+                    queue.push((nx, ny));
+                }
+            }
+        }
+        Some(state)
+    }
+
+    /// Vertices without edges and points without vertices only have to
+    /// agree in number, and in position when comparing geometry.
+    fn match_leftovers(&self, state: &State) -> bool {
+        let (a, b) = (self.a, self.b);
+        let lone = |vertices: &hbuf::ElementBuffer<Vertex>, mapping: &HashSet<VertexIndex>| {
+            vertices
+                .iter()
+                .filter(|(index, _)| !mapping.contains(index))
+                .count()
+        };
+        let reached: HashSet<VertexIndex> = state.vertices.forward.keys().copied().collect();
+        if lone(&a.vertices, &reached) != lone(&b.vertices, &state.vertices.used) {
+            return false;
+        }
+        let mut free: Vec<PointIndex> = b
+            .points
+            .iter()
+            .map(|(index, _)| index)
+            .filter(|index| !state.points.used.contains(index))
+            .collect();
+        for (index, _) in a.points.iter() {
+            if state.points.forward.contains_key(&index) {
+                continue;
+            }
+            match free
+                .iter()
+                .position(|candidate| self.close(index, *candidate))
+            {
+                Some(found) => {
+                    free.swap_remove(found);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renumbered_meshes_compare_equal() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh<f64> = builder::grid(2, 2);
+        // The same grid with its quads listed backwards.
+        let mut positions: Vec<[f64; 3]> = mesh.points.iter().map(|(_, p)| p.position).collect();
+        positions.reverse();
+        let polygons: Vec<Vec<usize>> = [[0, 1, 4, 3], [1, 2, 5, 4], [3, 4, 7, 6], [4, 5, 8, 7]]
+            .iter()
+            .rev()
+            .map(|quad| quad.iter().map(|i| 8 - i).collect())
+            .collect();
+        let renumbered: Mesh<f64> = Mesh::from_polygons(&positions, &polygons);
+        assert!(mesh.topologically_equals(&renumbered));
+        assert!(mesh.geometrically_equals(&renumbered, 0.0));
+
+        let mut moved = renumbered.clone();
+        for (_, point) in moved.points.iter_mut() {
+            point.position[2] += 0.01;
+        }
+        assert!(mesh.topologically_equals(&moved));
+        assert!(mesh.geometrically_equals(&moved, 0.02));
+        assert!(!mesh.geometrically_equals(&moved, 0.001));
+    }
+
+    #[test]
+    fn different_connectivity_is_told_apart() {
+        let quads: Mesh = builder::grid(2, 1);
+        let strip: Mesh = builder::grid(1, 2);
+        // Same counts and shape, only the layout differs.
+        assert!(quads.topologically_equals(&strip));
+        assert!(!quads.geometrically_equals(&strip, 1.0e-6));
+
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+        ];
+        // Two quads touching along an edge against two sharing no edge.
+        let joined: Mesh = Mesh::from_polygons(&positions, &[vec![0, 1, 2, 3], vec![1, 4, 5, 2]]);
+        let apart: Mesh = Mesh::from_polygons(
+            &[positions.to_vec(), positions[1..3].to_vec()].concat(),
+            &[vec![0, 1, 2, 3], vec![6, 4, 5, 7]],
+        );
+        assert!(joined.topologically_equals(&quads));
+        assert!(!joined.topologically_equals(&apart));
+        assert!(!joined.topologically_equals(&builder::cube::<f32>()));
+    }
+}
@@ -0,0 +1,188 @@
+//! Limits on how vertices may move.
+//!
+//! Passes which move vertices, like smoothing or remeshing, ask the
+//! constraints of the mesh where a vertex may go instead of taking pinning
+//! parameters of their own, so feature vertices stay where they belong
+//! through every pass.
+
+use crate::attributes::SparseElementMap;
+use crate::math::{self, Vec3};
+use crate::ops::Plane;
+use crate::*;
+use std::collections::HashMap;
+
+/// How a single vertex may move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constraint {
+    /// The vertex stays where it is.
+    Pinned,
+    /// The vertex slides within a plane.
+    Plane(Plane),
+    /// The vertex slides along the line through `origin`.
+    Line {
+        origin: [f64; 3],
+        direction: [f64; 3],
+    },
+}
+
+impl Constraint {
+    /// The closest position to `position` the constraint allows, `current`
+    /// being where the vertex is now.
+    fn apply(&self, current: Vec3, position: Vec3) -> Vec3 {
+        let project = |origin: Vec3, axis: Vec3, along: bool| {
+            let length = math::dot(axis, axis);
+            if length == 0.0 || !length.is_finite() {
+                return current;
+            }
+            let offset = math::sub(position, origin);
+            let t = math::dot(offset, axis) / length;
+            if along {
+                math::add(origin, math::scale(axis, t))
+            } else {
+                math::sub(position, math::scale(axis, t))
+            }
+        };
+        match self {
+            Constraint::Pinned => current,
+            Constraint::Plane(plane) => project(plane.origin, plane.normal, false),
+            Constraint::Line { origin, direction } => project(*origin, *direction, true),
+        }
+    }
+}
+
+/// The constraints of the vertices of a mesh.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Constraints {
+    vertices: SparseElementMap<Vertex, Constraint>,
+    /// Keeps every boundary vertex in place.
+    pub lock_boundary: bool,
+}
+
+impl Constraints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constrains a vertex, returning the constraint it replaces.
+    pub fn set(&mut self, vertex: VertexIndex, constraint: Constraint) -> Option<Constraint> {
+        self.vertices.insert(vertex, constraint)
+    }
+
+    pub fn pin(&mut self, vertex: VertexIndex) -> Option<Constraint> {
+        self.set(vertex, Constraint::Pinned)
+    }
+
+    /// Lets a vertex move freely again.
+    pub fn release(&mut self, vertex: VertexIndex) -> Option<Constraint> {
+        self.vertices.remove(vertex)
+    }
+
+    pub fn get(&self, vertex: VertexIndex) -> Option<&Constraint> {
+        self.vertices.get(vertex)
+    }
+
+    /// Constrained vertices in no particular order, not counting the
+    /// boundary lock.
+    pub fn iter(&self) -> impl Iterator<Item = (VertexIndex, &Constraint)> {
+        self.vertices.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty() && !self.lock_boundary
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.lock_boundary = false;
+    }
+
+    /// True when the vertex can't move at all.
+    pub fn is_fixed<S: Scalar>(&self, mesh: &Mesh<S>, vertex: VertexIndex) -> bool {
+        self.get(vertex) == Some(&Constraint::Pinned) || self.is_locked(mesh, vertex)
+    }
+
+    /// True when the vertex can't move freely.
+    pub fn is_constrained<S: Scalar>(&self, mesh: &Mesh<S>, vertex: VertexIndex) -> bool {
+        self.get(vertex).is_some() || self.is_locked(mesh, vertex)
+    }
+
+    /// The closest position to `position` the vertex is allowed to move to.
+    pub fn constrain<S: Scalar>(
+        &self,
+        mesh: &Mesh<S>,
+        vertex: VertexIndex,
+        position: [f64; 3],
+    ) -> [f64; 3] {
+        let current = mesh.vertex_position(vertex);
+        if self.is_locked(mesh, vertex) {
+            return current;
+        }
+        match self.get(vertex) {
+            Some(constraint) => constraint.apply(current, position),
+            None => position,
+        }
+    }
+
+    /// Moves every constraint to the handle its vertex was renumbered to.
+    pub fn remap(&mut self, vertices: &HashMap<VertexIndex, VertexIndex>) {
+        self.vertices.remap(vertices);
+    }
+
+    fn is_locked<S: Scalar>(&self, mesh: &Mesh<S>, vertex: VertexIndex) -> bool {
+        self.lock_boundary && mesh.vertex(vertex).edges().any(|e| e.is_boundary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex_at(mesh: &Mesh, position: Position) -> VertexIndex {
+        mesh.vertices()
+            .find(|v| v.position() == Some(position))
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn constraints_limit_where_vertices_go() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let middle = vertex_at(&mesh, [1.0, 1.0, 0.0]);
+        let corner = vertex_at(&mesh, [0.0, 0.0, 0.0]);
+        let target = [3.0, 2.0, 1.0];
+        assert!(mesh.constraints.is_empty());
+        assert_eq!(mesh.constraints.constrain(&mesh, middle, target), target);
+
+        let plane = Plane::new([0.0; 3], [0.0, 0.0, 2.0]);
+        mesh.constraints.set(middle, Constraint::Plane(plane));
+        assert_eq!(
+            mesh.constraints.constrain(&mesh, middle, target),
+            [3.0, 2.0, 0.0]
+        );
+        let line = Constraint::Line {
+            origin: [1.0, 1.0, 0.0],
+            direction: [1.0, 0.0, 0.0],
+        };
+        assert!(mesh.constraints.set(middle, line).is_some());
+        assert_eq!(
+            mesh.constraints.constrain(&mesh, middle, target),
+            [3.0, 1.0, 0.0]
+        );
+        mesh.constraints.pin(middle);
+        assert!(mesh.constraints.is_fixed(&mesh, middle));
+        assert_eq!(
+            mesh.constraints.constrain(&mesh, middle, target),
+            [1.0, 1.0, 0.0]
+        );
+        assert_eq!(mesh.constraints.release(middle), Some(Constraint::Pinned));
+
+        assert!(!mesh.constraints.is_constrained(&mesh, corner));
+        mesh.constraints.lock_boundary = true;
+        assert!(mesh.constraints.is_fixed(&mesh, corner));
+        assert!(!mesh.constraints.is_fixed(&mesh, middle));
+        assert_eq!(mesh.constraints.constrain(&mesh, corner, target), [0.0; 3]);
+    }
+}
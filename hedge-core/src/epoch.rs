@@ -0,0 +1,166 @@
+//! Immutable published states of a mesh for caching handles across frames.
+//!
+//! The thread editing a mesh publishes a copy of it as a new `Epoch` at the
+//! end of each frame. Workers grab the latest epoch once and then read it
+//! without taking any locks, since an epoch never changes. Handles remember
+//! the layout they were created in through `Stamped`, so a worker can keep
+//! them across frames and cheaply check whether they still refer to a live
+//! element of a newer epoch: removing an element bumps the generation of
+//! its cell, which the stale handle no longer matches.
+//!
+//! Moving elements between cells, as `ElementBuffer::defragment` does,
+//! voids every handle. Such meshes have to be published through
+//! `Epochs::publish_relayout`.
+
+use crate::*;
+use std::sync::{Arc, Mutex};
+
+/// One published state of a mesh.
+#[derive(Debug)]
+pub struct Epoch<S: Scalar = f32> {
+    mesh: Mesh<S>,
+    number: u64,
+    layout: u64,
+}
+
+/// A handle along with the layout of the epoch it came from.
+#[derive(Debug)]
+pub struct Stamped<K> {
+    pub handle: Handle<K>,
+    layout: u64,
+}
+
+impl<K> Clone for Stamped<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for Stamped<K> {}
+
+impl<K> PartialEq for Stamped<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle && self.layout == other.layout
+    }
+}
+
+impl<S: Scalar> Epoch<S> {
+    /// Counts up by one for every published epoch.
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn mesh(&self) -> &Mesh<S> {
+        &self.mesh
+    }
+
+    pub fn view(&self) -> MeshView<'_, S> {
+        self.mesh.read_view()
+    }
+
+    pub fn stamp<K>(&self, handle: Handle<K>) -> Stamped<K> {
+        Stamped {
+            handle,
+            layout: self.layout,
+        }
+    }
+
+    /// True when the handle still refers to the element it was stamped for.
+    pub fn is_live<K>(&self, stamped: &Stamped<K>) -> bool
+    where
+        Mesh<S>: GetElement<Handle<K>>,
+    {
+        stamped.layout == self.layout && self.mesh.get_element(stamped.handle).is_some()
+    }
+}
+
+/// The latest epoch of a mesh, shared between the editing thread and workers.
+#[derive(Debug)]
+pub struct Epochs<S: Scalar = f32> {
+    latest: Mutex<Arc<Epoch<S>>>,
+}
+
+impl<S: Scalar> Epochs<S> {
+    pub fn new(mesh: Mesh<S>) -> Self {
+        Epochs {
+            latest: Mutex::new(Arc::new(Epoch {
+                mesh,
+                number: 0,
+                layout: 0,
+            })),
+        }
+    }
+
+    /// The most recently published epoch, which stays valid for as long as
+    /// the caller holds on to it.
+    pub fn latest(&self) -> Arc<Epoch<S>> {
+        self.lock().clone()
+    }
+
+    /// Publishes a new state in which handles of earlier epochs stay valid
+    /// unless their element was removed.
+    pub fn publish(&self, mesh: Mesh<S>) -> u64 {
+        self.replace(mesh, false)
+    }
+
+    /// Publishes a new state in which elements may have moved between
+    /// cells, voiding every handle stamped before.
+    pub fn publish_relayout(&self, mesh: Mesh<S>) -> u64 {
+        self.replace(mesh, true)
+    }
+
+    fn replace(&self, mesh: Mesh<S>, relayout: bool) -> u64 {
+        let mut latest = self.lock();
+        let number = latest.number + 1;
+        let layout = latest.layout + relayout as u64;
+        *latest = Arc::new(Epoch {
+            mesh,
+            number,
+            layout,
+        });
+        number
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Arc<Epoch<S>>> {
+        // An epoch is replaced in a single assignment, so a panic while
+        // holding the lock can't leave a partial state behind.
+        self.latest
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_handles_are_checked_against_newer_epochs() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let epochs = Epochs::new(mesh.clone());
+
+        let first = epochs.latest();
+        let cached: Vec<Stamped<Face>> = std::thread::scope(|scope| {
+            scope
+                .spawn(|| first.view().faces().map(|f| first.stamp(f.index)).collect())
+                .join()
+                .unwrap()
+        });
+        assert!(cached.iter().all(|s| first.is_live(s)));
+
+        ops::remove_face(&mut mesh, cached[0].handle).unwrap();
+        assert_eq!(epochs.publish(mesh.clone()), 1);
+        let second = epochs.latest();
+        assert!(!second.is_live(&cached[0]));
+        assert!(second.is_live(&cached[1]));
+        // Workers still holding the first epoch can keep reading it.
+        assert!(first.is_live(&cached[0]));
+        assert_eq!(first.view().face_count(), 2);
+
+        epochs.publish_relayout(mesh);
+        assert!(!epochs.latest().is_live(&cached[1]));
+        let fresh = epochs.latest().stamp(cached[1].handle);
+        assert!(epochs.latest().is_live(&fresh));
+    }
+}
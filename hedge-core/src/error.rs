@@ -0,0 +1,53 @@
+//! The error type shared by fallible operations.
+
+use crate::{EdgeIndex, FaceIndex, PointIndex, VertexIndex};
+use std::fmt;
+
+/// Errors reported by mesh construction and operators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The handle doesn't refer to a live edge of the mesh.
+    InvalidEdge(EdgeIndex),
+    /// The handle doesn't refer to a live vertex of the mesh.
+    InvalidVertex(VertexIndex),
+    /// The handle doesn't refer to a live face of the mesh.
+    InvalidFace(FaceIndex),
+    /// The handle doesn't refer to a live point of the mesh.
+    InvalidPoint(PointIndex),
+    /// Following `next` from this edge doesn't lead back to it.
+    OpenEdgeLoop(EdgeIndex),
+    /// Links between elements disagree, such as an edge whose twin belongs
+    /// to another edge.
+    Inconsistent(String),
+    /// An operation would leave the mesh without a valid 2-manifold surface.
+    NonManifold(String),
+    /// Two inputs which are expected to pair up have different lengths.
+    LengthMismatch { expected: usize, found: usize },
+    /// A parameter is outside of the range the operation accepts.
+    InvalidArgument(String),
+    /// The operation was stopped through its `Progress`.
+    Cancelled,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidEdge(index) => write!(f, "invalid edge {:?}", index),
+            Error::InvalidVertex(index) => write!(f, "invalid vertex {:?}", index),
+            Error::InvalidFace(index) => write!(f, "invalid face {:?}", index),
+            Error::InvalidPoint(index) => write!(f, "invalid point {:?}", index),
+            Error::OpenEdgeLoop(index) => write!(f, "edge loop from {:?} is not closed", index),
+            Error::Inconsistent(reason) => write!(f, "inconsistent connectivity: {}", reason),
+            Error::NonManifold(reason) => write!(f, "not manifold: {}", reason),
+            Error::LengthMismatch { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)
+            }
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::Cancelled => write!(f, "the operation was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,253 @@
+//! Facades over a mesh and component index to enable fluent adjcency traversals.
+
+use super::*;
+
+pub trait FunctionSet<'mesh, S: Scalar, I: Default>: Sized {
+    type Element: 'mesh;
+
+    fn new(index: I, mesh: &'mesh Mesh<S>) -> Self;
+    fn element(&self) -> Option<&'mesh Self::Element>;
+
+    fn maybe(index: Option<I>, mesh: &'mesh Mesh<S>) -> Self {
+        Self::new(index.unwrap_or_default(), mesh)
+    }
+}
+
+/// Function set for operations related to the Face struct
+#[derive(Debug, Copy, Clone)]
+pub struct FaceFn<'mesh, S: Scalar = f32> {
+    pub(crate) mesh: &'mesh Mesh<S>,
+    pub index: FaceIndex,
+}
+
+impl<'mesh, S: Scalar> FunctionSet<'mesh, S, FaceIndex> for FaceFn<'mesh, S> {
+    type Element = Face;
+
+    fn new(index: FaceIndex, mesh: &'mesh Mesh<S>) -> Self {
+        FaceFn { mesh, index }
+    }
+
+    fn element(&self) -> Option<&'mesh Face> {
+        self.mesh.faces.get(self.index)
+    }
+}
+
+impl<'mesh, S: Scalar> FaceFn<'mesh, S> {
+    /// Convert this `FaceFn` to an `EdgeFn`.
+    pub fn edge(&self) -> EdgeFn<'mesh, S> {
+        let edge_index = self.element().map(|face| face.edge_index);
+        EdgeFn::maybe(edge_index, self.mesh)
+    }
+
+    pub fn edges(&self) -> FaceEdges<'mesh, S> {
+        FaceEdges::new(*self)
+    }
+
+    pub fn vertices(&self) -> FaceVertices<'mesh, S> {
+        FaceVertices::new(*self)
+    }
+
+    /// Unit normal of the face, `None` for invalid or degenerate faces.
+    pub fn normal(&self) -> Option<Position<S>> {
+        let normal = self.mesh.face_normal(self.index);
+        (self.is_valid() && normal != [0.0; 3]).then(|| scalar::from_f64(normal))
+    }
+
+    /// Area of the face, measured from the plane that fits it best.
+    pub fn area(&self) -> S {
+        S::from_f64(crate::math::length(self.mesh.face_area_vector(self.index)))
+    }
+}
+
+impl<'mesh, S: Scalar> IsValid for FaceFn<'mesh, S> {
+    fn is_valid(&self) -> bool {
+        self.element().is_some()
+    }
+}
+
+/// Function set for operations related to the Edge struct
+#[derive(Debug, Copy, Clone)]
+pub struct EdgeFn<'mesh, S: Scalar = f32> {
+    pub(crate) mesh: &'mesh Mesh<S>,
+    pub index: EdgeIndex,
+}
+
+impl<'mesh, S: Scalar> FunctionSet<'mesh, S, EdgeIndex> for EdgeFn<'mesh, S> {
+    type Element = Edge;
+
+    fn new(index: EdgeIndex, mesh: &'mesh Mesh<S>) -> Self {
+        EdgeFn { mesh, index }
+    }
+
+    fn element(&self) -> Option<&'mesh Edge> {
+        self.mesh.edges.get(self.index)
+    }
+}
+
+impl<'mesh, S: Scalar> EdgeFn<'mesh, S> {
+    pub fn is_boundary(&self) -> bool {
+        !self.face().is_valid() || !self.twin().face().is_valid()
+    }
+
+    /// Convert this `EdgeFn` to an `EdgeFn` of it's next edge
+    pub fn next(&self) -> EdgeFn<'mesh, S> {
+        let next_index = self.element().map(|edge| edge.next_index);
+        EdgeFn::maybe(next_index, self.mesh)
+    }
+
+    /// Convert this `EdgeFn` to an `EdgeFn` of it's prev edge
+    pub fn prev(&self) -> EdgeFn<'mesh, S> {
+        let prev_index = self.element().map(|edge| edge.prev_index);
+        EdgeFn::maybe(prev_index, self.mesh)
+    }
+
+    /// Convert this `EdgeFn` to an `EdgeFn` of it's twin edge
+    pub fn twin(&self) -> EdgeFn<'mesh, S> {
+        let twin_index = self.element().map(|edge| edge.twin_index);
+        EdgeFn::maybe(twin_index, self.mesh)
+    }
+
+    /// Convert this `EdgeFn` to an `FaceFn`
+    pub fn face(&self) -> FaceFn<'mesh, S> {
+        let face_index = self.element().map(|edge| edge.face_index);
+        FaceFn::maybe(face_index, self.mesh)
+    }
+
+    /// Convert this `EdgeFn` to an `VertexFn`
+    pub fn vertex(&self) -> VertexFn<'mesh, S> {
+        let vertex_index = self.element().map(|edge| edge.vertex_index);
+        VertexFn::maybe(vertex_index, self.mesh)
+    }
+}
+
+impl<'mesh, S: Scalar> IsValid for EdgeFn<'mesh, S> {
+    fn is_valid(&self) -> bool {
+        self.element().is_some()
+    }
+}
+
+/// Function set for operations related to the Vertex struct
+#[derive(Debug, Copy, Clone)]
+pub struct VertexFn<'mesh, S: Scalar = f32> {
+    pub(crate) mesh: &'mesh Mesh<S>,
+    pub index: VertexIndex,
+}
+
+impl<'mesh, S: Scalar> FunctionSet<'mesh, S, VertexIndex> for VertexFn<'mesh, S> {
+    type Element = Vertex;
+
+    fn new(index: VertexIndex, mesh: &'mesh Mesh<S>) -> Self {
+        VertexFn { mesh, index }
+    }
+
+    fn element(&self) -> Option<&'mesh Vertex> {
+        self.mesh.vertices.get(self.index)
+    }
+}
+
+impl<'mesh, S: Scalar> VertexFn<'mesh, S> {
+    /// Convert this `VertexFn` to an `EdgeFn`
+    pub fn edge(&self) -> EdgeFn<'mesh, S> {
+        let edge_index = self.element().map(|vertex| vertex.edge_index);
+        EdgeFn::maybe(edge_index, self.mesh)
+    }
+
+    pub fn edges(&self) -> VertexCirculator<'mesh, S> {
+        VertexCirculator::new(*self)
+    }
+
+    pub fn point(&self) -> Option<&'mesh Point<S>> {
+        self.element()
+            .and_then(|vertex| self.mesh.points.get(vertex.point_index))
+    }
+
+    pub fn position(&self) -> Option<Position<S>> {
+        self.point().map(|point| point.position)
+    }
+}
+
+impl<'mesh, S: Scalar> IsValid for VertexFn<'mesh, S> {
+    fn is_valid(&self) -> bool {
+        self.element().is_some()
+    }
+}
+
+/// Function set for operations related to the Point struct
+#[derive(Debug, Copy, Clone)]
+pub struct PointFn<'mesh, S: Scalar = f32> {
+    pub(crate) mesh: &'mesh Mesh<S>,
+    pub index: PointIndex,
+}
+
+impl<'mesh, S: Scalar> FunctionSet<'mesh, S, PointIndex> for PointFn<'mesh, S> {
+    type Element = Point<S>;
+
+    fn new(index: PointIndex, mesh: &'mesh Mesh<S>) -> Self {
+        PointFn { mesh, index }
+    }
+
+    fn element(&self) -> Option<&'mesh Point<S>> {
+        self.mesh.points.get(self.index)
+    }
+}
+
+impl<'mesh, S: Scalar> PointFn<'mesh, S> {
+    pub fn position(&self) -> Option<Position<S>> {
+        self.element().map(|point| point.position)
+    }
+
+    /// Vertices placed at this point, found by scanning all vertices.
+    pub fn vertices(&self) -> impl Iterator<Item = VertexFn<'mesh, S>> + 'mesh {
+        let (mesh, index) = (self.mesh, self.index);
+        mesh.vertices
+            .iter()
+            .filter(move |(_, vertex)| vertex.point_index == index)
+            .map(move |(vertex, _)| VertexFn::new(vertex, mesh))
+    }
+}
+
+impl<'mesh, S: Scalar> IsValid for PointFn<'mesh, S> {
+    fn is_valid(&self) -> bool {
+        self.element().is_some()
+    }
+}
+
+/// Index types which have a function set, so generic code can go from any
+/// index to its facade through `Mesh::fns`.
+pub trait ElementIndex: Copy {
+    type Fn<'mesh, S: Scalar>;
+
+    fn fns<S: Scalar>(self, mesh: &Mesh<S>) -> Self::Fn<'_, S>;
+}
+
+impl ElementIndex for FaceIndex {
+    type Fn<'mesh, S: Scalar> = FaceFn<'mesh, S>;
+
+    fn fns<S: Scalar>(self, mesh: &Mesh<S>) -> FaceFn<'_, S> {
+        FaceFn::new(self, mesh)
+    }
+}
+
+impl ElementIndex for EdgeIndex {
+    type Fn<'mesh, S: Scalar> = EdgeFn<'mesh, S>;
+
+    fn fns<S: Scalar>(self, mesh: &Mesh<S>) -> EdgeFn<'_, S> {
+        EdgeFn::new(self, mesh)
+    }
+}
+
+impl ElementIndex for VertexIndex {
+    type Fn<'mesh, S: Scalar> = VertexFn<'mesh, S>;
+
+    fn fns<S: Scalar>(self, mesh: &Mesh<S>) -> VertexFn<'_, S> {
+        VertexFn::new(self, mesh)
+    }
+}
+
+impl ElementIndex for PointIndex {
+    type Fn<'mesh, S: Scalar> = PointFn<'mesh, S>;
+
+    fn fns<S: Scalar>(self, mesh: &Mesh<S>) -> PointFn<'_, S> {
+        PointFn::new(self, mesh)
+    }
+}
@@ -0,0 +1,467 @@
+//! Procedural mesh generation.
+
+use crate::*;
+use std::collections::HashMap;
+
+/// Optional extras for `heightfield_with`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeightfieldOptions {
+    /// Depth of a vertical skirt hanging below the border, hiding cracks
+    /// between neighboring terrain tiles.
+    pub skirt: Option<f32>,
+    /// Merges flat regions into larger faces as long as no sample deviates
+    /// from the face by more than this.
+    pub simplify: Option<f32>,
+}
+
+/// Builds a quad grid with one vertex per sample, displaced along `+z`.
+///
+/// Samples are spaced one unit apart on the XY plane and faces wind counter
+/// clockwise when seen from above. Fails with fewer than 2x2 samples.
+pub fn heightfield<S, F>(width: usize, height: usize, f: F) -> Result<Mesh<S>>
+where
+    S: Scalar,
+    F: Fn(usize, usize) -> f32,
+{
+    heightfield_with(width, height, &HeightfieldOptions::default(), f)
+}
+
+/// Builds a heightfield grid with an optional skirt and simplification.
+///
+/// Simplified regions become polygons which include every vertex of their
+/// smaller neighbors along their sides, so no cracks or T-junctions appear.
+pub fn heightfield_with<S, F>(
+    width: usize,
+    height: usize,
+    options: &HeightfieldOptions,
+    f: F,
+) -> Result<Mesh<S>>
+where
+    S: Scalar,
+    F: Fn(usize, usize) -> f32,
+{
+    if width < 2 || height < 2 {
+        return Err(Error::InvalidArgument(format!(
+            "a heightfield needs at least 2x2 samples, got {}x{}",
+            width, height
+        )));
+    }
+    let heights: Vec<f32> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| f(x, y))
+        .collect();
+    let field = Field {
+        width,
+        height,
+        heights,
+    };
+
+    let mut blocks = Vec::new();
+    match options.simplify {
+        Some(tolerance) => {
+            let cells = (width - 1).max(height - 1);
+            let size = cells.next_power_of_two();
+            field.subdivide(0, 0, size, tolerance, &mut blocks);
+        }
+        None => {
+            for y in 0..height - 1 {
+                for x in 0..width - 1 {
+                    blocks.push((x, y, 1));
+                }
+            }
+        }
+    }
+
+    let mut used = vec![false; width * height];
+    for (x, y, size) in &blocks {
+        for (cx, cy) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+            used[field.sample(x + cx * size, y + cy * size)] = true;
+        }
+    }
+
+    let mut remap: HashMap<usize, usize> = HashMap::new();
+    let mut positions: Vec<[f64; 3]> = Vec::new();
+    let mut index_of = |sample: usize, positions: &mut Vec<[f64; 3]>| {
+        *remap.entry(sample).or_insert_with(|| {
+            positions.push(field.position(sample));
+            positions.len() - 1
+        })
+    };
+
+    let mut polygons: Vec<Vec<usize>> = blocks
+        .iter()
+        .map(|(x, y, size)| {
+            field
+                .perimeter(*x, *y, *x + size, *y + size)
+                .into_iter()
+                .filter(|sample| used[*sample])
+                .map(|sample| index_of(sample, &mut positions))
+                .collect()
+        })
+        .collect();
+
+    if let Some(depth) = options.skirt {
+        let border: Vec<usize> = field
+            .perimeter(0, 0, width - 1, height - 1)
+            .into_iter()
+            .filter(|sample| used[*sample])
+            .collect();
+        let lowered: Vec<usize> = border
+            .iter()
+            .map(|sample| {
+                let mut p = field.position(*sample);
+                p[2] -= depth as f64;
+                positions.push(p);
+                positions.len() - 1
+            })
+            .collect();
+        for i in 0..border.len() {
+            let j = (i + 1) % border.len();
+            let a = index_of(border[i], &mut positions);
+            let b = index_of(border[j], &mut positions);
+            polygons.push(vec![b, a, lowered[i], lowered[j]]);
+        }
+    }
+
+    Ok(builder::from_polygons(&positions, &polygons))
+}
+
+/// A line to rotate around, turning counter clockwise when looking down
+/// `direction` towards `origin`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axis {
+    pub origin: [f64; 3],
+    pub direction: [f64; 3],
+}
+
+impl Axis {
+    pub fn new(origin: [f64; 3], direction: [f64; 3]) -> Self {
+        Axis { origin, direction }
+    }
+
+    /// Rotates a point by `angle` radians around the axis, which has to
+    /// have a unit direction.
+    pub(crate) fn rotate(&self, point: [f64; 3], angle: f64) -> [f64; 3] {
+        let d = math::sub(point, self.origin);
+        let k = self.direction;
+        let (sin, cos) = angle.sin_cos();
+        let along = math::scale(k, math::dot(k, d) * (1.0 - cos));
+        let rotated = math::add(
+            math::add(math::scale(d, cos), math::scale(math::cross(k, d), sin)),
+            along,
+        );
+        math::add(self.origin, rotated)
+    }
+}
+
+/// Sweeps a chain of boundary edges around an axis, bridging each copy of
+/// the chain to the previous one with quads.
+///
+/// Over the whole `angle` the copies also move `offset` along the axis,
+/// giving the helix of a thread or spring. The first ring of quads is
+/// connected to the faces the chain borders. A full turn without offset
+/// would wrap back onto the chain and is refused. Returns the new faces.
+pub fn spin<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    profile_edges: &[EdgeIndex],
+    axis: &Axis,
+    angle: f64,
+    steps: usize,
+    offset: f64,
+) -> Result<Vec<FaceIndex>> {
+    let mut span = trace::span!(mesh, "spin");
+    let mesh = span.mesh();
+    let length = math::length(axis.direction);
+    if length == 0.0 || !length.is_finite() || steps == 0 {
+        return Err(Error::InvalidArgument(format!(
+            "spinning needs an axis direction and steps, got {:?} and {}",
+            axis.direction, steps
+        )));
+    }
+    if angle.abs() >= std::f64::consts::TAU - 1.0e-9 && offset == 0.0 {
+        return Err(Error::InvalidArgument(
+            "a full turn without offset wraps onto the profile".to_string(),
+        ));
+    }
+    let axis = Axis::new(axis.origin, math::scale(axis.direction, 1.0 / length));
+
+    let mut profile = Vec::with_capacity(profile_edges.len() + 1);
+    for (i, index) in profile_edges.iter().enumerate() {
+        let edge = mesh.edge(*index);
+        if !edge.is_valid() || edge.face().is_valid() {
+            return Err(Error::InvalidArgument(format!(
+                "profiles are made of boundary edges, {:?} isn't one",
+                index
+            )));
+        }
+        if i > 0 && edge.vertex().index != mesh.edge(profile_edges[i - 1]).next().vertex().index {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} doesn't continue the profile",
+                index
+            )));
+        }
+        profile.push(edge.vertex().index);
+    }
+    let Some(last) = profile_edges.last() else {
+        return Ok(Vec::new());
+    };
+    let end = mesh.edge(*last).next().vertex().index;
+    let closed = profile.first() == Some(&end);
+    if !closed {
+        profile.push(end);
+    }
+
+    let mut rings: Vec<Vec<PointIndex>> = vec![profile
+        .iter()
+        .map(|v| {
+            mesh.vertex(*v)
+                .element()
+                .map(|v| v.point_index)
+                .unwrap_or_default()
+        })
+        .collect()];
+    for step in 1..=steps {
+        let t = step as f64 / steps as f64;
+        let shift = math::scale(axis.direction, offset * t);
+        let ring = profile
+            .iter()
+            .map(|v| {
+                let position = axis.rotate(mesh.vertex_position(*v), angle * t);
+                let point = Point::from_position(scalar::from_f64(math::add(position, shift)));
+                mesh.add_element(point)
+            })
+            .collect();
+        rings.push(ring);
+    }
+    let count = profile_edges.len();
+    let polygons: Vec<Vec<PointIndex>> = rings
+        .windows(2)
+        .flat_map(|pair| {
+            (0..count).map(move |i| {
+                let j = (i + 1) % pair[0].len();
+                vec![pair[0][i], pair[0][j], pair[1][j], pair[1][i]]
+            })
+        })
+        .collect();
+    let faces = builder::add_polygons(mesh, &polygons);
+
+    // The sweep starts on the profile's points with vertices of its own,
+    // welding joins it to the faces along the profile.
+    let start = mesh.face(faces[0]).edge().twin().index;
+    ops::weld_boundaries(mesh, profile_edges[0], start, 0.0)?;
+    Ok(faces)
+}
+
+struct Field {
+    width: usize,
+    height: usize,
+    heights: Vec<f32>,
+}
+
+impl Field {
+    fn sample(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    fn position(&self, sample: usize) -> [f64; 3] {
+        let (x, y) = (sample % self.width, sample / self.width);
+        [x as f64, y as f64, self.heights[sample] as f64]
+    }
+
+    /// Samples along the border of a rectangle, counter clockwise from `(x0, y0)`.
+    fn perimeter(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> Vec<usize> {
+        let bottom = (x0..x1).map(|x| self.sample(x, y0));
+        let right = (y0..y1).map(|y| self.sample(x1, y));
+        let top = (x0 + 1..=x1).rev().map(|x| self.sample(x, y1));
+        let left = (y0 + 1..=y1).rev().map(|y| self.sample(x0, y));
+        bottom.chain(right).chain(top).chain(left).collect()
+    }
+
+    /// Whether every sample of the block is close to the bilinear patch over its corners.
+    fn is_flat(&self, x0: usize, y0: usize, size: usize, tolerance: f32) -> bool {
+        let h = |x, y| self.heights[self.sample(x, y)];
+        let corners = [
+            h(x0, y0),
+            h(x0 + size, y0),
+            h(x0, y0 + size),
+            h(x0 + size, y0 + size),
+        ];
+        (y0..=y0 + size).all(|y| {
+            (x0..=x0 + size).all(|x| {
+                let u = (x - x0) as f32 / size as f32;
+                let v = (y - y0) as f32 / size as f32;
+                let bottom = corners[0] + (corners[1] - corners[0]) * u;
+                let top = corners[2] + (corners[3] - corners[2]) * u;
+                (h(x, y) - (bottom + (top - bottom) * v)).abs() <= tolerance
+            })
+        })
+    }
+
+    /// Quadtree split of the cells into square blocks that are flat or a single cell.
+    fn subdivide(
+        &self,
+        x: usize,
+        y: usize,
+        size: usize,
+        tolerance: f32,
+        blocks: &mut Vec<(usize, usize, usize)>,
+    ) {
+        if x >= self.width - 1 || y >= self.height - 1 {
+            return;
+        }
+        let inside = x + size < self.width && y + size < self.height;
+        if inside && (size == 1 || self.is_flat(x, y, size, tolerance)) {
+            blocks.push((x, y, size));
+            return;
+        }
+        let half = size / 2;
+        for (dx, dy) in [(0, 0), (half, 0), (0, half), (half, half)] {
+            self.subdivide(x + dx, y + dy, half, tolerance, blocks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary_edge_count<S: Scalar>(mesh: &Mesh<S>) -> usize {
+        mesh.edges().filter(|e| !e.face().is_valid()).count()
+    }
+
+    #[test]
+    fn builds_a_displaced_grid() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = heightfield(4, 3, |x, y| (x * y) as f32).unwrap();
+        assert!(heightfield::<f32, _>(1, 3, |_, _| 0.0).is_err());
+        assert_eq!(mesh.vertex_count(), 12);
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(boundary_edge_count(&mesh), 10);
+        assert!(mesh
+            .vertices()
+            .filter_map(|v| v.position())
+            .all(|p| p[2] == p[0] * p[1]));
+        for face in mesh.faces() {
+            assert!(mesh.face_normal(face.index)[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn skirts_hang_below_the_border() {
+        let options = HeightfieldOptions {
+            skirt: Some(2.0),
+            ..Default::default()
+        };
+        let mesh: Mesh = heightfield_with(3, 3, &options, |_, _| 1.0).unwrap();
+        assert_eq!(mesh.vertex_count(), 9 + 8);
+        assert_eq!(mesh.face_count(), 4 + 8);
+        assert_eq!(boundary_edge_count(&mesh), 8);
+        assert!(mesh.edges().filter(|e| !e.face().is_valid()).all(|e| e
+            .vertex()
+            .position()
+            .unwrap()[2]
+            == -1.0));
+    }
+
+    #[test]
+    fn flat_regions_are_merged_without_cracks() {
+        let options = HeightfieldOptions {
+            simplify: Some(0.01),
+            ..Default::default()
+        };
+        let flat: Mesh = heightfield_with(5, 5, &options, |_, _| 0.0).unwrap();
+        assert_eq!(flat.face_count(), 1);
+        assert_eq!(flat.vertex_count(), 4);
+
+        // A single raised sample forces refinement around it.
+        let bump: Mesh = heightfield_with(
+            9,
+            9,
+            &options,
+            |x, y| {
+                if (x, y) == (2, 2) {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+        )
+        .unwrap();
+        assert!(bump.face_count() < 64);
+        assert!(bump.face_count() > 4);
+        assert_eq!(
+            bump.vertex_count() + bump.face_count() - bump.edge_count() / 2,
+            1
+        );
+        // The border of the grid is the only boundary.
+        let border = bump
+            .edges()
+            .filter(|e| !e.face().is_valid())
+            .filter(|e| {
+                let p = e.vertex().position().unwrap();
+                p[0] == 0.0 || p[0] == 8.0 || p[1] == 0.0 || p[1] == 8.0
+            })
+            .count();
+        assert_eq!(border, boundary_edge_count(&bump));
+    }
+
+    #[test]
+    fn spinning_a_boundary_edge_sweeps_quads() {
+        let mut mesh: Mesh<f64> = builder::grid(1, 1);
+        let top = mesh
+            .edges()
+            .find(|e| !e.face().is_valid() && e.vertex().position() == Some([0.0, 1.0, 0.0]))
+            .unwrap()
+            .index;
+        let axis = Axis::new([0.0; 3], [2.0, 0.0, 0.0]);
+        let quarter = std::f64::consts::FRAC_PI_2;
+        let faces = spin(&mut mesh, &[top], &axis, quarter, 3, 0.0).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(faces.len(), 3);
+        assert_eq!(mesh.vertex_count(), 10);
+        assert_eq!(mesh.point_count(), 10);
+        assert_eq!(boundary_edge_count(&mesh), 3 + 2 * 3 + 1);
+        // The last copy of the edge ends up straight above the axis.
+        let last = mesh.points.iter().map(|(_, p)| p.position);
+        assert_eq!(
+            last.filter(|p| math::distance(*p, [1.0, 0.0, 1.0]) < 1.0e-12)
+                .count(),
+            1
+        );
+        let tau = std::f64::consts::TAU;
+        assert!(spin(&mut mesh, &[top], &axis, tau, 8, 0.0).is_err());
+    }
+
+    #[test]
+    fn helical_spins_keep_climbing() {
+        let mut mesh: Mesh<f64> = builder::grid(1, 1);
+        let start = mesh.edges().find(|e| !e.face().is_valid()).unwrap();
+        let rim: Vec<EdgeIndex> = (0..4)
+            .scan(start, |edge, _| {
+                let current = edge.index;
+                *edge = edge.next();
+                Some(current)
+            })
+            .collect();
+        let axis = Axis::new([3.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let faces = spin(
+            &mut mesh,
+            &rim,
+            &axis,
+            2.0 * std::f64::consts::TAU,
+            16,
+            10.0,
+        )
+        .unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(faces.len(), 64);
+        assert_eq!(mesh.point_count(), 4 + 64);
+        let highest = mesh
+            .points
+            .iter()
+            .map(|(_, p)| p.position[1])
+            .fold(0.0, f64::max);
+        assert!((highest - 11.0).abs() < 1.0e-9);
+        assert_eq!(boundary_edge_count(&mesh), 4);
+    }
+}
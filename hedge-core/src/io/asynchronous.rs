@@ -0,0 +1,183 @@
+//! Runtime independent plumbing for the asynchronous loaders.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Bytes which arrive asynchronously.
+///
+/// The signature matches `futures::io::AsyncRead`, which async-std readers
+/// implement. With the `futures` or `tokio` features `FuturesReader` and
+/// `TokioReader` wrap readers of those crates.
+pub trait AsyncRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+impl AsyncRead for &[u8] {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Read::read(&mut *self, buf))
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for &mut R {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+/// Reads from a `futures::io::AsyncRead`, and so from async-std.
+#[cfg(feature = "futures")]
+#[derive(Debug)]
+pub struct FuturesReader<R>(pub R);
+
+#[cfg(feature = "futures")]
+impl<R: futures_io::AsyncRead + Unpin> AsyncRead for FuturesReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+/// Reads from a `tokio::io::AsyncRead`.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct TokioReader<R>(pub R);
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> AsyncRead for TokioReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        match Pin::new(&mut self.0).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Size of the chunks loaders read before handing control back.
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+struct ReadChunk<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadChunk<'_, R> {
+    type Output = io::Result<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.reader).poll_read(cx, this.buf)
+    }
+}
+
+/// Reads the next chunk, zero once the reader is exhausted.
+pub(crate) async fn read_chunk<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    loop {
+        match (ReadChunk {
+            reader: &mut *reader,
+            buf: &mut *buf,
+        })
+        .await
+        {
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Gives other tasks on the executor a chance to run.
+pub(crate) async fn yield_now() {
+    YieldNow(false).await
+}
+
+/// Drives a future to completion on the current thread, used by tests.
+#[cfg(test)]
+pub(crate) fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    let mut pending = 0;
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return (output, pending),
+            Poll::Pending => {
+                pending += 1;
+                std::thread::park();
+            }
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "futures", feature = "tokio")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn futures_readers_load() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let reader = FuturesReader(text.as_bytes());
+        let (mesh, _) = block_on(crate::io::obj::load_async::<f32, _>(reader));
+        assert_eq!(mesh.unwrap().face_count(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_readers_load() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let (mesh, _) = block_on(crate::io::obj::load_async::<f32, _>(TokioReader(
+            text.as_bytes(),
+        )));
+        assert_eq!(mesh.unwrap().face_count(), 1);
+    }
+}
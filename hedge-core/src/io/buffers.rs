@@ -0,0 +1,183 @@
+//! Vertex and index arrays ready for upload to the GPU.
+//!
+//! Faces are ear clipped into triangles and every distinct combination of
+//! point and corner attributes becomes a vertex, so points are duplicated
+//! where a hard edge or a UV seam gives their corners different values.
+
+use super::quantized::CornerChannels;
+use crate::*;
+use std::collections::HashMap;
+
+/// Tightly packed vertex attributes and triangle list indices.
+///
+/// `normals` and `uvs` are either empty or hold one value per position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshBuffers {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    /// Builds the buffers from the given corner attributes, or the vertex
+    /// normals of the mesh when no normals are given. Corners missing from
+    /// a channel get a normal along +z or a UV at the origin.
+    pub fn new<S: Scalar>(mesh: &Mesh<S>, channels: &CornerChannels) -> Self {
+        MeshBuffers::with_points(mesh, channels).0
+    }
+
+    /// Like `new`, also returning the point each vertex was made for.
+    pub(crate) fn with_points<S: Scalar>(
+        mesh: &Mesh<S>,
+        channels: &CornerChannels,
+    ) -> (Self, Vec<PointIndex>) {
+        let stored = match channels.normals {
+            Some(_) => None,
+            None => mesh.stored_corner_normals(),
+        };
+        let normals = channels.normals.or(stored.as_ref());
+        let mut buffers = MeshBuffers::default();
+        let mut points = Vec::new();
+        let mut vertices: HashMap<(PointIndex, [u32; 3], [u32; 2]), u32> = HashMap::new();
+        for face in mesh.faces() {
+            let Some(corners) = face
+                .edges()
+                .map(|e| e.vertex().element().map(|v| (e.index, v.point_index)))
+                .collect::<Option<Vec<(EdgeIndex, PointIndex)>>>()
+            else {
+                log::warn!("Skipping face {:?} with a dangling vertex.", face.index);
+                continue;
+            };
+            let mut face_vertices = Vec::with_capacity(corners.len());
+            for (corner, point) in &corners {
+                let normal = normals.map(|c| c.get(*corner).copied().unwrap_or([0.0, 0.0, 1.0]));
+                let uv = channels
+                    .uvs
+                    .map(|c| c.get(*corner).copied().unwrap_or_default());
+                let key = (
+                    *point,
+                    normal.unwrap_or_default().map(f32::to_bits),
+                    uv.unwrap_or_default().map(f32::to_bits),
+                );
+                let index = *vertices.entry(key).or_insert_with(|| {
+                    let position = mesh.vertex_position(mesh.edge(*corner).vertex().index);
+                    buffers.positions.push(position.map(|v| v as f32));
+                    buffers.normals.extend(normal);
+                    buffers.uvs.extend(uv);
+                    points.push(*point);
+                    buffers.positions.len() as u32 - 1
+                });
+                face_vertices.push(index);
+            }
+            let triangles = if corners.len() == 3 {
+                vec![[0, 1, 2]]
+            } else {
+                let points: Vec<[f64; 3]> = face
+                    .vertices()
+                    .map(|v| mesh.vertex_position(v.index))
+                    .collect();
+                ops::ear_clipping(&points)
+            };
+            for triangle in triangles {
+                buffers.indices.extend(triangle.map(|i| face_vertices[i]));
+            }
+        }
+        (buffers, points)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Smallest and largest position along each axis, zeros when empty.
+    pub fn bounds(&self) -> [[f32; 3]; 2] {
+        if self.positions.is_empty() {
+            return [[0.0; 3]; 2];
+        }
+        let mut bounds = [[f32::INFINITY; 3], [f32::NEG_INFINITY; 3]];
+        for p in &self.positions {
+            for axis in 0..3 {
+                bounds[0][axis] = bounds[0][axis].min(p[axis]);
+                bounds[1][axis] = bounds[1][axis].max(p[axis]);
+            }
+        }
+        bounds
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Triangulated buffers with the vertex normals of the mesh, or if it
+    /// has none normals smoothed across edges below the default feature
+    /// angle. See `MeshBuffers::new` for other attributes.
+    pub fn to_buffers(&self) -> MeshBuffers {
+        if !self.vertex_normals.is_empty() {
+            return MeshBuffers::new(self, &CornerChannels::default());
+        }
+        let normals = self.compute_auto_smooth_normals(tolerance::DEFAULT_FEATURE_ANGLE);
+        let channels = CornerChannels {
+            normals: Some(&normals),
+            uvs: None,
+        };
+        MeshBuffers::new(self, &channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_edges_and_seams_split_vertices() {
+        let _ = env_logger::try_init();
+        let cube: Mesh = builder::cube();
+        let buffers = cube.to_buffers();
+        // Every corner of the cube sits on three hard edges.
+        assert_eq!(buffers.vertex_count(), 24);
+        assert_eq!(buffers.normals.len(), 24);
+        assert!(buffers.uvs.is_empty());
+        assert_eq!(buffers.triangle_count(), 12);
+        assert!(buffers.indices.iter().all(|i| (*i as usize) < 24));
+        assert_eq!(buffers.bounds(), [[0.0; 3], [1.0; 3]]);
+
+        let mut grid: Mesh = builder::grid(2, 1);
+        assert_eq!(grid.to_buffers().vertex_count(), 6);
+        // Give the two quads UVs of their own, splitting the shared edge.
+        let mut uvs = crate::uv::UvChannel::new();
+        for edge in grid.edges().filter(|e| e.face().is_valid()) {
+            let p = edge.vertex().position().unwrap();
+            let u = if p[0] == 1.0
+                && edge
+                    .face()
+                    .edges()
+                    .any(|e| e.vertex().position().unwrap()[0] == 2.0)
+            {
+                0.0
+            } else {
+                p[0]
+            };
+            uvs.set(edge.index, [u, p[1]]);
+        }
+        let channels = CornerChannels {
+            normals: None,
+            uvs: Some(&uvs),
+        };
+        let buffers = MeshBuffers::new(&grid, &channels);
+        assert_eq!(buffers.vertex_count(), 8);
+        assert_eq!(buffers.uvs.len(), 8);
+        assert!(buffers.normals.is_empty());
+        assert_eq!(buffers.indices.len(), 12);
+
+        // Stored vertex normals take over from the smoothed ones.
+        grid.compute_vertex_normals(normals::NormalWeighting::Angle);
+        let buffers = grid.to_buffers();
+        assert_eq!(buffers.vertex_count(), 6);
+        assert!(buffers.normals.iter().all(|n| *n == [0.0, 0.0, 1.0]));
+        let buffers = MeshBuffers::new(&grid, &channels);
+        assert_eq!(buffers.normals.len(), 8);
+    }
+}
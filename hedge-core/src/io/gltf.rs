@@ -0,0 +1,910 @@
+//! glTF 2.0 export for previewing meshes in standard viewers, and import
+//! of scenes.
+//!
+//! Faces are ear clipped into triangles as they are written and every
+//! distinct combination of point and corner attributes becomes a glTF
+//! vertex. A `.gltf` file embeds its buffer as a base64 data URI, a `.glb`
+//! file carries it in a binary chunk. UVs are flipped vertically, as glTF
+//! puts their origin in the top left corner.
+//!
+//! Morph targets become targets of the primitive holding their position
+//! offsets, with zero default weights and their names in the mesh's
+//! `extras.targetNames` as most importers expect.
+//!
+//! Scenes are written with a node per object. Reading gives an object per
+//! node with a mesh, placed by the transforms of the node and its parents.
+//! Only positions and triangle indices are read, glTF vertices at the same
+//! position are merged to recover shared edges.
+
+use super::buffers::MeshBuffers;
+use super::invalid_data;
+use super::json::{self, Value};
+use super::merge::{self, MergePolicy};
+use super::quantized::CornerChannels;
+use crate::scene::{Object, Scene, Transform};
+use crate::*;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+const TRIANGLES: usize = 4;
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const JSON_CHUNK: u32 = 0x4E4F534A;
+const BIN_CHUNK: u32 = 0x004E4942;
+
+/// Writes a `.gltf` file with its buffer embedded.
+pub fn write<S: Scalar, W: Write>(
+    writer: W,
+    mesh: &Mesh<S>,
+    channels: &CornerChannels,
+) -> io::Result<()> {
+    let mut document = Document::default();
+    let index = document.add_mesh(mesh, channels, None)?;
+    document.nodes.push(format!(r#"{{"mesh":{}}}"#, index));
+    document.write(writer)
+}
+
+/// Writes a binary `.glb` file.
+pub fn write_glb<S: Scalar, W: Write>(
+    writer: W,
+    mesh: &Mesh<S>,
+    channels: &CornerChannels,
+) -> io::Result<()> {
+    let mut document = Document::default();
+    let index = document.add_mesh(mesh, channels, None)?;
+    document.nodes.push(format!(r#"{{"mesh":{}}}"#, index));
+    document.write_glb(writer)
+}
+
+/// Writes every object of a scene to a `.gltf` file with its buffer
+/// embedded.
+///
+/// Each object becomes a node named after it, carrying its transform and
+/// a mesh of its own. Materials are written by name only and referenced
+/// from the primitives of the objects using them.
+pub fn write_scene<S: Scalar, W: Write>(writer: W, scene: &Scene<S>) -> io::Result<()> {
+    scene_document(scene)?.write(writer)
+}
+
+/// Like `write_scene`, writing a binary `.glb` file.
+pub fn write_scene_glb<S: Scalar, W: Write>(writer: W, scene: &Scene<S>) -> io::Result<()> {
+    scene_document(scene)?.write_glb(writer)
+}
+
+fn scene_document<S: Scalar>(scene: &Scene<S>) -> io::Result<Document> {
+    let mut document = Document::default();
+    for object in &scene.objects {
+        let material = object.material.filter(|id| *id < scene.materials.len());
+        let index = document.add_mesh(&object.mesh, &CornerChannels::default(), material)?;
+        // glTF matrices are column major.
+        let matrix = scene.transform_of(object).matrix;
+        let columns: Vec<String> = (0..16)
+            .map(|i| format!("{}", matrix[i % 4][i / 4]))
+            .collect();
+        document.nodes.push(format!(
+            r#"{{"name":{},"mesh":{},"matrix":[{}]}}"#,
+            escape(&object.name),
+            index,
+            columns.join(",")
+        ));
+    }
+    document.materials = scene
+        .materials
+        .iter()
+        .map(|name| format!(r#"{{"name":{}}}"#, escape(name)))
+        .collect();
+    Ok(document)
+}
+
+/// Reads a `.gltf` file with embedded buffers or a `.glb` file into a
+/// scene.
+///
+/// Nodes with equal transforms share a transform of the scene, nodes which
+/// aren't moved get none. Materials keep their names, unnamed ones are
+/// named after their index. Buffers in external files aren't supported.
+pub fn read_scene<S: Scalar, R: Read>(mut reader: R) -> io::Result<Scene<S>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let (text, binary) = if bytes.starts_with(GLB_MAGIC) {
+        glb_chunks(&bytes)?
+    } else {
+        (&bytes[..], None)
+    };
+    let text = std::str::from_utf8(text).map_err(invalid_data)?;
+    let root = json::parse(text)?;
+    let buffers = root
+        .get("buffers")
+        .elements()
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| match (buffer.get("uri").as_str(), binary) {
+            (Some(uri), _) => match uri.split_once(";base64,") {
+                Some((kind, data)) if kind.starts_with("data:") => unbase64(data),
+                _ => Err(invalid_data("Buffers in external files aren't supported.")),
+            },
+            (None, Some(binary)) if i == 0 => Ok(binary.to_vec()),
+            (None, _) => Err(invalid_data(format!("Buffer {} has no data.", i))),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let document = Reading {
+        root: &root,
+        buffers,
+    };
+
+    let mut scene = Scene::new();
+    scene.materials = root
+        .get("materials")
+        .elements()
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            m.get("name")
+                .as_str()
+                .map_or_else(|| format!("material{}", i), String::from)
+        })
+        .collect();
+    let roots = match root
+        .get("scenes")
+        .elements()
+        .get(root.get("scene").as_usize().unwrap_or(0))
+    {
+        Some(gltf_scene) => gltf_scene.get("nodes").elements().to_vec(),
+        None => (0..root.get("nodes").elements().len())
+            .map(|i| Value::Number(i as f64))
+            .collect(),
+    };
+    let mut pending: Vec<(usize, Transform)> = roots
+        .iter()
+        .rev()
+        .map(|node| Ok((index_of(node, "node")?, Transform::IDENTITY)))
+        .collect::<io::Result<_>>()?;
+    let mut visited = vec![false; root.get("nodes").elements().len()];
+    while let Some((node_index, parent)) = pending.pop() {
+        let node = document.element("nodes", node_index)?;
+        if std::mem::replace(&mut visited[node_index], true) {
+            return Err(invalid_data(format!(
+                "Node {} has several parents.",
+                node_index
+            )));
+        }
+        let transform = node_transform(node)?.then(&parent);
+        for child in node.get("children").elements().iter().rev() {
+            pending.push((index_of(child, "child")?, transform));
+        }
+        if node.get("mesh").is_null() {
+            continue;
+        }
+        let gltf_mesh = document.element("meshes", index_of(node.get("mesh"), "mesh")?)?;
+        let (mesh, material) = document.mesh(gltf_mesh)?;
+        let name = node
+            .get("name")
+            .as_str()
+            .or_else(|| gltf_mesh.get("name").as_str())
+            .map_or_else(|| format!("object{}", scene.objects.len()), String::from);
+        let mut object = Object::new(name, mesh);
+        object.material = material.filter(|m| *m < scene.materials.len());
+        if transform != Transform::IDENTITY {
+            object.transform = Some(
+                match scene.transforms.iter().position(|t| *t == transform) {
+                    Some(id) => id,
+                    None => scene.add_transform(transform),
+                },
+            );
+        }
+        scene.add_object(object);
+    }
+    Ok(scene)
+}
+
+/// The JSON and binary chunk of a `.glb` file.
+fn glb_chunks(bytes: &[u8]) -> io::Result<(&[u8], Option<&[u8]>)> {
+    let word = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()) as usize)
+            .ok_or_else(|| invalid_data("Truncated .glb file."))
+    };
+    let mut chunks = (None, None);
+    let mut at = 12;
+    while at < bytes.len().min(word(8)?) {
+        let (length, kind) = (word(at)?, word(at + 4)? as u32);
+        let chunk = bytes
+            .get(at + 8..at + 8 + length)
+            .ok_or_else(|| invalid_data("Truncated .glb chunk."))?;
+        match kind {
+            JSON_CHUNK if chunks.0.is_none() => chunks.0 = Some(chunk),
+            BIN_CHUNK if chunks.1.is_none() => chunks.1 = Some(chunk),
+            _ => {}
+        }
+        at += 8 + length;
+    }
+    let json = chunks
+        .0
+        .ok_or_else(|| invalid_data("No JSON chunk in .glb file."))?;
+    Ok((json, chunks.1))
+}
+
+/// A node's own transform, from its matrix or its translation, rotation
+/// and scale.
+fn node_transform(node: &Value) -> io::Result<Transform> {
+    let numbers = |key: &str, count: usize| -> io::Result<Option<Vec<f64>>> {
+        let value = node.get(key);
+        if value.is_null() {
+            return Ok(None);
+        }
+        let numbers: Option<Vec<f64>> = value.elements().iter().map(Value::as_f64).collect();
+        match numbers {
+            Some(numbers) if numbers.len() == count => Ok(Some(numbers)),
+            _ => Err(invalid_data(format!("Node has a malformed {}.", key))),
+        }
+    };
+    if let Some(columns) = numbers("matrix", 16)? {
+        let mut transform = Transform::IDENTITY;
+        for (i, value) in columns.iter().enumerate().filter(|(i, _)| i % 4 != 3) {
+            transform.matrix[i % 4][i / 4] = *value;
+        }
+        return Ok(transform);
+    }
+    let scale = numbers("scale", 3)?.map_or([1.0; 3], |s| [s[0], s[1], s[2]]);
+    let mut transform = Transform::scaling(scale);
+    if let Some(q) = numbers("rotation", 4)? {
+        let [x, y, z, w] = [q[0], q[1], q[2], q[3]];
+        let mut rotation = Transform::IDENTITY;
+        rotation.matrix[0][..3].copy_from_slice(&[
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ]);
+        rotation.matrix[1][..3].copy_from_slice(&[
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ]);
+        rotation.matrix[2][..3].copy_from_slice(&[
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ]);
+        transform = transform.then(&rotation);
+    }
+    if let Some(t) = numbers("translation", 3)? {
+        transform = transform.then(&Transform::translation([t[0], t[1], t[2]]));
+    }
+    Ok(transform)
+}
+
+fn index_of(value: &Value, what: &str) -> io::Result<usize> {
+    value
+        .as_usize()
+        .ok_or_else(|| invalid_data(format!("Invalid {} index.", what)))
+}
+
+/// The parsed JSON of a file being read along with its decoded buffers.
+struct Reading<'a> {
+    root: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Reading<'_> {
+    fn element(&self, array: &str, index: usize) -> io::Result<&Value> {
+        self.root
+            .get(array)
+            .elements()
+            .get(index)
+            .ok_or_else(|| invalid_data(format!("There is no {} {}.", array, index)))
+    }
+
+    /// Builds the triangles of every primitive into one mesh, returning the
+    /// material of the first primitive which has one.
+    fn mesh<S: Scalar>(&self, gltf_mesh: &Value) -> io::Result<(Mesh<S>, Option<usize>)> {
+        let mut mesh = Mesh::new();
+        let mut polygons = Vec::new();
+        let mut material = None;
+        for primitive in gltf_mesh.get("primitives").elements() {
+            let mode = primitive.get("mode");
+            if !mode.is_null() && mode.as_usize() != Some(TRIANGLES) {
+                return Err(invalid_data("Only triangle primitives can be read."));
+            }
+            if material.is_none() && !primitive.get("material").is_null() {
+                material = Some(index_of(primitive.get("material"), "material")?);
+            }
+            let positions = index_of(primitive.get("attributes").get("POSITION"), "position")?;
+            let points: Vec<PointIndex> = self
+                .accessor(positions, &[FLOAT], "VEC3")?
+                .chunks(3)
+                .map(|p| {
+                    let position = [p[0], p[1], p[2]].map(f32::from_bits);
+                    mesh.add_element(Point::from_position(scalar::from_f64(
+                        position.map(f64::from),
+                    )))
+                })
+                .collect();
+            let corners: Vec<u32> = match primitive.get("indices") {
+                Value::Null => (0..points.len() as u32).collect(),
+                indices => self.accessor(
+                    index_of(indices, "indices")?,
+                    &[UNSIGNED_BYTE, UNSIGNED_SHORT, UNSIGNED_INT],
+                    "SCALAR",
+                )?,
+            };
+            if !corners.len().is_multiple_of(3) {
+                return Err(invalid_data("Triangle indices don't come in threes."));
+            }
+            for triangle in corners.chunks(3) {
+                let triangle = triangle
+                    .iter()
+                    .map(|i| points.get(*i as usize).copied())
+                    .collect::<Option<Vec<PointIndex>>>()
+                    .ok_or_else(|| invalid_data("Index refers to a missing vertex."))?;
+                polygons.push(triangle);
+            }
+        }
+        merge::merge_points(&mut mesh, &mut polygons, MergePolicy::Exact);
+        builder::add_polygons(&mut mesh, &polygons);
+        Ok((mesh, material))
+    }
+
+    /// The components of an accessor widened to `u32`, floats by their
+    /// bits, checking its component type against `types` and its type
+    /// against `shape`.
+    fn accessor(&self, index: usize, types: &[u32], shape: &str) -> io::Result<Vec<u32>> {
+        let accessor = self.element("accessors", index)?;
+        let kind = accessor.get("componentType").as_usize().unwrap_or(0) as u32;
+        let width = if shape == "VEC3" { 3 } else { 1 };
+        if !types.contains(&kind) || accessor.get("type").as_str() != Some(shape) {
+            return Err(invalid_data(format!(
+                "Accessor {} has the wrong type.",
+                index
+            )));
+        }
+        let size = match kind {
+            UNSIGNED_BYTE => 1,
+            UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+        let count = accessor.get("count").as_usize().unwrap_or(0);
+        let view = self.element("bufferViews", index_of(accessor.get("bufferView"), "view")?)?;
+        let buffer = self
+            .buffers
+            .get(index_of(view.get("buffer"), "buffer")?)
+            .ok_or_else(|| invalid_data("View refers to a missing buffer."))?;
+        let start = view.get("byteOffset").as_usize().unwrap_or(0)
+            + accessor.get("byteOffset").as_usize().unwrap_or(0);
+        let stride = view.get("byteStride").as_usize().unwrap_or(size * width);
+        let length = view.get("byteLength").as_usize().unwrap_or(0);
+        let end = view.get("byteOffset").as_usize().unwrap_or(0) + length;
+        let needed = match count {
+            0 => Some(0),
+            n => (n - 1)
+                .checked_mul(stride)
+                .and_then(|n| n.checked_add(size * width)),
+        };
+        let fits = needed
+            .and_then(|n| n.checked_add(start))
+            .is_some_and(|last| last <= end.min(buffer.len()));
+        if !fits {
+            return Err(invalid_data(format!(
+                "Accessor {} runs past its view.",
+                index
+            )));
+        }
+        let mut values = Vec::with_capacity(count * width);
+        for element in 0..count {
+            for component in 0..width {
+                let at = start + element * stride + component * size;
+                let bytes = &buffer[at..at + size];
+                values.push(match size {
+                    1 => bytes[0] as u32,
+                    2 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+                    _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+                });
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// The binary data and JSON objects of a file being written, all meshes
+/// sharing one buffer.
+#[derive(Default)]
+struct Document {
+    bytes: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+    meshes: Vec<String>,
+    nodes: Vec<String>,
+    materials: Vec<String>,
+}
+
+impl Document {
+    /// Adds the buffers of a mesh and returns the index of its glTF mesh.
+    fn add_mesh<S: Scalar>(
+        &mut self,
+        mesh: &Mesh<S>,
+        channels: &CornerChannels,
+        material: Option<usize>,
+    ) -> io::Result<usize> {
+        if let Some(face) = mesh
+            .faces()
+            .find(|f| f.edges().any(|e| e.vertex().element().is_none()))
+        {
+            return Err(invalid_data(format!(
+                "Face {:?} has a dangling vertex.",
+                face.index
+            )));
+        }
+        let (mut buffers, points) = MeshBuffers::with_points(mesh, channels);
+        for uv in &mut buffers.uvs {
+            uv[1] = 1.0 - uv[1];
+        }
+        let bounds = buffers.bounds();
+        let count = buffers.vertex_count();
+        let mut attributes = Vec::new();
+        for (name, values) in [
+            ("POSITION", &buffers.positions),
+            ("NORMAL", &buffers.normals),
+        ] {
+            if !values.is_empty() {
+                let bounds = (name == "POSITION").then_some(bounds);
+                let accessor = self.vec3(values, bounds);
+                attributes.push(format!(r#""{}":{}"#, name, accessor));
+            }
+        }
+        if !buffers.uvs.is_empty() {
+            let accessor = self.accessor(
+                buffers.uvs.concat().iter().flat_map(|v| v.to_le_bytes()),
+                ARRAY_BUFFER,
+                format!(
+                    r#""componentType":{},"count":{},"type":"VEC2""#,
+                    FLOAT, count
+                ),
+            );
+            attributes.push(format!(r#""TEXCOORD_0":{}"#, accessor));
+        }
+        let mut targets = Vec::with_capacity(mesh.morph_targets.len());
+        let mut names = Vec::with_capacity(mesh.morph_targets.len());
+        for target in &mesh.morph_targets {
+            // Bounds are required for positions, offsets included.
+            let deltas = MeshBuffers {
+                positions: points
+                    .iter()
+                    .map(|p| {
+                        let delta = target.deltas.get(*p).copied();
+                        delta.map_or([0.0; 3], |d| scalar::to_f64(d).map(|v| v as f32))
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            let accessor = self.vec3(&deltas.positions, Some(deltas.bounds()));
+            targets.push(format!(r#"{{"POSITION":{}}}"#, accessor));
+            names.push(escape(&target.name));
+        }
+        let indices = self.accessor(
+            buffers.indices.iter().flat_map(|i| i.to_le_bytes()),
+            ELEMENT_ARRAY_BUFFER,
+            format!(
+                r#""componentType":{},"count":{},"type":"SCALAR""#,
+                UNSIGNED_INT,
+                buffers.indices.len()
+            ),
+        );
+
+        let mut primitive = format!(
+            r#"{{"attributes":{{{}}},"indices":{},"mode":4"#,
+            attributes.join(","),
+            indices
+        );
+        if let Some(material) = material {
+            let _ = write!(primitive, r#","material":{}"#, material);
+        }
+        if targets.is_empty() {
+            self.meshes
+                .push(format!(r#"{{"primitives":[{}}}]}}"#, primitive));
+        } else {
+            self.meshes.push(format!(
+                concat!(
+                    r#"{{"primitives":[{},"targets":[{}]}}],"#,
+                    r#""weights":[{}],"extras":{{"targetNames":[{}]}}}}"#
+                ),
+                primitive,
+                targets.join(","),
+                vec!["0"; names.len()].join(","),
+                names.join(",")
+            ));
+        }
+        Ok(self.meshes.len() - 1)
+    }
+
+    /// Adds an accessor for three floats per vertex, with the bounds that
+    /// positions have to give.
+    fn vec3(&mut self, values: &[[f32; 3]], bounds: Option<[[f32; 3]; 2]>) -> usize {
+        let mut description = format!(
+            r#""componentType":{},"count":{},"type":"VEC3""#,
+            FLOAT,
+            values.len()
+        );
+        if let Some(bounds) = bounds {
+            let [min, max] = bounds.map(|b| format!("[{},{},{}]", b[0], b[1], b[2]));
+            let _ = write!(description, r#","min":{},"max":{}"#, min, max);
+        }
+        self.accessor(
+            values.concat().iter().flat_map(|v| v.to_le_bytes()),
+            ARRAY_BUFFER,
+            description,
+        )
+    }
+
+    /// Appends the data to the buffer under a view of its own and adds an
+    /// accessor over it, returning the accessor's index.
+    fn accessor(
+        &mut self,
+        data: impl Iterator<Item = u8>,
+        target: u32,
+        description: String,
+    ) -> usize {
+        let offset = self.bytes.len();
+        self.bytes.extend(data);
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            offset,
+            self.bytes.len() - offset,
+            target
+        ));
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},{}}}"#,
+            self.views.len() - 1,
+            description
+        ));
+        self.accessors.len() - 1
+    }
+
+    /// The JSON document, pointing its buffer at `uri` when the data isn't
+    /// in a binary chunk.
+    fn json(&self, uri: Option<&str>) -> String {
+        let buffer = match uri {
+            Some(uri) => format!(r#"{{"byteLength":{},"uri":"{}"}}"#, self.bytes.len(), uri),
+            None => format!(r#"{{"byteLength":{}}}"#, self.bytes.len()),
+        };
+        let roots: Vec<String> = (0..self.nodes.len()).map(|i| i.to_string()).collect();
+        let mut json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0","generator":"hedge"}},"#,
+                r#""scene":0,"scenes":[{{"nodes":[{}]}}],"nodes":[{}],"meshes":[{}],"#
+            ),
+            roots.join(","),
+            self.nodes.join(","),
+            self.meshes.join(",")
+        );
+        if !self.materials.is_empty() {
+            let _ = write!(json, r#""materials":[{}],"#, self.materials.join(","));
+        }
+        let _ = write!(
+            json,
+            r#""buffers":[{}],"bufferViews":[{}],"accessors":[{}]}}"#,
+            buffer,
+            self.views.join(","),
+            self.accessors.join(",")
+        );
+        json
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&self.bytes)
+        );
+        writer.write_all(self.json(Some(&uri)).as_bytes())
+    }
+
+    fn write_glb<W: Write>(self, mut writer: W) -> io::Result<()> {
+        let mut json = self.json(None).into_bytes();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = self.bytes;
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let length =
+            u32::try_from(length).map_err(|_| invalid_data("Too large for a .glb file."))?;
+        writer.write_all(GLB_MAGIC)?;
+        writer.write_all(&2u32.to_le_bytes())?;
+        writer.write_all(&length.to_le_bytes())?;
+        for (kind, chunk) in [(JSON_CHUNK, &json), (BIN_CHUNK, &bin)] {
+            writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+            writer.write_all(&kind.to_le_bytes())?;
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// A JSON string literal holding `text`.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn unbase64(text: &str) -> io::Result<Vec<u8>> {
+    let digit = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let digits = chunk
+            .iter()
+            .map(|c| digit(*c))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|d| d.len() > 1)
+            .ok_or_else(|| invalid_data("Invalid base64 data."))?;
+        let n = digits
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, d)| n | (*d as u32) << (18 - 6 * i));
+        out.extend(n.to_be_bytes()[1..digits.len()].iter());
+    }
+    Ok(out)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_files_hold_triangulated_corners() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::cube();
+        let normals = mesh.compute_auto_smooth_normals(0.0);
+        let channels = CornerChannels {
+            normals: Some(&normals),
+            uvs: None,
+        };
+        let mut bytes = Vec::new();
+        write_glb(&mut bytes, &mesh, &channels).unwrap();
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[..4], GLB_MAGIC);
+        assert_eq!(word(8), bytes.len());
+        let json_length = word(12);
+        assert_eq!(json_length % 4, 0);
+        let json = std::str::from_utf8(&bytes[20..20 + json_length]).unwrap();
+        // Flat normals give every face corners of its own.
+        assert!(json.contains(r#""count":24,"type":"VEC3","min":[0,0,0],"max":[1,1,1]"#));
+        assert!(json.contains(r#""count":36,"type":"SCALAR""#));
+        assert!(json.contains(r#""NORMAL":1"#));
+        assert!(!json.contains("uri"));
+        assert_eq!(word(20 + json_length), 24 * 12 * 2 + 36 * 4);
+    }
+
+    #[test]
+    fn text_files_embed_their_buffer() {
+        assert_eq!(base64(b"hedge"), "aGVkZ2U=");
+        assert_eq!(base64(b"mesh"), "bWVzaA==");
+        let mesh: Mesh = builder::grid(2, 1);
+        let mut uvs = crate::uv::UvChannel::new();
+        for edge in mesh.edges().filter(|e| e.face().is_valid()) {
+            let p = edge.vertex().position().unwrap();
+            uvs.set(edge.index, [p[0] / 2.0, p[1]]);
+        }
+        let channels = CornerChannels {
+            normals: None,
+            uvs: Some(&uvs),
+        };
+        let mut bytes = Vec::new();
+        write(&mut bytes, &mesh, &channels).unwrap();
+        let json = String::from_utf8(bytes).unwrap();
+        assert!(json.contains(r#""TEXCOORD_0":1"#));
+        assert!(json.contains(r#""count":12,"type":"SCALAR""#));
+        let start = json.find("base64,").unwrap() + "base64,".len();
+        let data = &json[start..json[start..].find('"').unwrap() + start];
+        // Corners with the same point and UV share a glTF vertex.
+        let length: usize = 6 * 3 * 4 + 6 * 2 * 4 + 12 * 4;
+        assert_eq!(data.len(), length.div_ceil(3) * 4);
+    }
+
+    #[test]
+    fn morph_targets_become_primitive_targets() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let corner = mesh
+            .points
+            .iter()
+            .find(|(_, p)| p.position == [2.0, 1.0, 0.0])
+            .unwrap()
+            .0;
+        mesh.add_morph_target("lift")
+            .deltas
+            .set(corner, [0.0, 0.0, 1.0]);
+        mesh.add_morph_target("say \"hi\"");
+        let mut bytes = Vec::new();
+        write(&mut bytes, &mesh, &CornerChannels::default()).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let gltf_mesh = &json["meshes"][0];
+        let targets = gltf_mesh["primitives"][0]["targets"].as_array().unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(gltf_mesh["weights"], serde_json::json!([0, 0]));
+        assert_eq!(
+            gltf_mesh["extras"]["targetNames"],
+            serde_json::json!(["lift", "say \"hi\""])
+        );
+        let lift = &json["accessors"][targets[0]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(lift["count"], 6);
+        assert_eq!(lift["max"], serde_json::json!([0, 0, 1]));
+        let indices = &json["accessors"][json["meshes"][0]["primitives"][0]["indices"]
+            .as_u64()
+            .unwrap() as usize];
+        assert_eq!(indices["type"], "SCALAR");
+    }
+
+    #[test]
+    fn scenes_get_a_node_per_object() {
+        let mut scene: Scene = Scene::new();
+        let moved = scene.add_transform(scene::Transform::translation([1.0, 2.0, 3.0]));
+        let red = scene.material("red");
+        let mut cube = scene::Object::new("cube", builder::cube());
+        cube.transform = Some(moved);
+        cube.material = Some(red);
+        scene.add_object(cube);
+        scene.add_object(scene::Object::new("floor", builder::grid(2, 2)));
+
+        let mut bytes = Vec::new();
+        write_scene(&mut bytes, &scene).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["scenes"][0]["nodes"], serde_json::json!([0, 1]));
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["name"], "cube");
+        assert_eq!(nodes[1]["name"], "floor");
+        let matrix = nodes[0]["matrix"].as_array().unwrap();
+        assert_eq!(matrix[12..15], [1, 2, 3]);
+        assert_eq!(nodes[1]["matrix"][12], 0);
+        assert_ne!(nodes[0]["mesh"], nodes[1]["mesh"]);
+
+        assert_eq!(json["materials"], serde_json::json!([{ "name": "red" }]));
+        let primitive = |node: &serde_json::Value| {
+            json["meshes"][node["mesh"].as_u64().unwrap() as usize]["primitives"][0].clone()
+        };
+        assert_eq!(primitive(&nodes[0])["material"], 0);
+        assert!(primitive(&nodes[1]).get("material").is_none());
+
+        let mut glb = Vec::new();
+        write_scene_glb(&mut glb, &scene).unwrap();
+        assert_eq!(&glb[..4], GLB_MAGIC);
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let _ = env_logger::try_init();
+        let mut scene: Scene<f64> = Scene::new();
+        let lift = scene.add_transform(
+            scene::Transform::scaling([2.0; 3])
+                .then(&scene::Transform::translation([0.0, 0.0, 5.0])),
+        );
+        let (red, blue) = (scene.material("red"), scene.material("blue"));
+        for (name, mesh, transform, material) in [
+            ("cube", builder::cube(), Some(lift), Some(red)),
+            ("also lifted", builder::octahedron(), Some(lift), Some(blue)),
+            ("floor", builder::grid(3, 2), None, None),
+        ] {
+            let mut object = scene::Object::new(name, mesh);
+            object.transform = transform;
+            object.material = material;
+            scene.add_object(object);
+        }
+
+        let mut text = Vec::new();
+        write_scene(&mut text, &scene).unwrap();
+        let mut glb = Vec::new();
+        write_scene_glb(&mut glb, &scene).unwrap();
+        for bytes in [text, glb] {
+            let read: Scene<f64> = read_scene(bytes.as_slice()).unwrap();
+            assert_eq!(read.materials, scene.materials);
+            assert_eq!(read.transforms, scene.transforms);
+            assert_eq!(read.objects.len(), scene.objects.len());
+            for (a, b) in scene.objects.iter().zip(&read.objects) {
+                assert_eq!(a.name, b.name);
+                assert_eq!(a.transform, b.transform);
+                assert_eq!(a.material, b.material);
+                builder::assert_connectivity(&b.mesh);
+                // glTF holds triangles, which share the points of the faces.
+                let mut triangles = a.mesh.clone();
+                ops::triangulate(&mut triangles, ops::TriangulationMethod::EarClipping).unwrap();
+                assert_eq!(b.mesh.face_count(), triangles.face_count());
+                assert_eq!(b.mesh.edge_count(), triangles.edge_count());
+                let positions = |m: &Mesh<f64>| {
+                    let mut p: Vec<[f64; 3]> = m.points.iter().map(|(_, p)| p.position).collect();
+                    p.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    p
+                };
+                assert_eq!(positions(&b.mesh), positions(&a.mesh));
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_place_their_children() {
+        let mut mesh = Vec::new();
+        write(
+            &mut mesh,
+            &builder::grid::<f32>(1, 1),
+            &CornerChannels::default(),
+        )
+        .unwrap();
+        let mut json = String::from_utf8(mesh).unwrap();
+        // A parent moving a rotated and scaled child holding the mesh.
+        let nodes = concat!(
+            r#""nodes":[{"translation":[0,0,3],"children":[1]},"#,
+            r#"{"name":"child","mesh":0,"scale":[2,2,2],"rotation":[0,0,0.7071067811865476,0.7071067811865476]}],"#
+        );
+        let start = json.find(r#""nodes":[{"#).unwrap();
+        let end = json.find(r#""meshes":"#).unwrap();
+        json.replace_range(start..end, nodes);
+        let scene: Scene = read_scene(json.as_bytes()).unwrap();
+        assert_eq!(scene.objects.len(), 1);
+        let child = scene.object("child").unwrap();
+        let placed = scene.placed_mesh(child);
+        let far = placed
+            .points
+            .iter()
+            .map(|(_, p)| p.position)
+            .find(|p| p[0] < -1.0)
+            .unwrap();
+        assert!(math::distance(far.map(f64::from), [-2.0, 2.0, 3.0]) < 1.0e-6);
+
+        for broken in [
+            &b"{}"[..],
+            b"glTF",
+            b"{\"buffers\":[{\"uri\":\"mesh.bin\"}]}",
+            b"[1",
+        ] {
+            let result: io::Result<Scene> = read_scene(broken);
+            assert!(result.map(|s| s.objects.is_empty()).unwrap_or(true));
+        }
+        let cyclic = r#"{"scenes":[{"nodes":[0]}],"nodes":[{"children":[0]}]}"#;
+        assert!(read_scene::<f32, _>(cyclic.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,517 @@
+//! Gmsh `.msh` import and export.
+//!
+//! ASCII files in format 2.2 and 4.1 can be read. Only surface elements
+//! (triangles and quads) are imported, volume and line elements are ignored.
+//! Files are written in format 2.2 which every Gmsh-aware tool understands.
+
+use super::asynchronous::{self, AsyncRead};
+use super::invalid_data;
+use super::merge::{self, MergePolicy, MergeStats};
+use crate::*;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+const TRIANGLE: u32 = 2;
+const QUAD: u32 = 3;
+const SURFACE: u32 = 2;
+
+/// A named physical group as declared in the `$PhysicalNames` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhysicalGroup {
+    pub dimension: u32,
+    pub tag: Tag,
+    pub name: String,
+}
+
+/// A mesh along with the Gmsh physical group of each face.
+#[derive(Debug, Clone, Default)]
+pub struct GmshMesh<S: Scalar = f32> {
+    pub mesh: Mesh<S>,
+    pub physical_groups: Vec<PhysicalGroup>,
+    pub face_tags: HashMap<FaceIndex, Tag>,
+}
+
+impl<S: Scalar> From<Mesh<S>> for GmshMesh<S> {
+    fn from(mesh: Mesh<S>) -> Self {
+        GmshMesh {
+            mesh,
+            physical_groups: Vec::new(),
+            face_tags: HashMap::new(),
+        }
+    }
+}
+
+struct Section<'a> {
+    name: &'a str,
+    lines: Vec<&'a str>,
+}
+
+fn sections(text: &str) -> io::Result<Vec<Section<'_>>> {
+    let mut out = Vec::new();
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        let name = line
+            .strip_prefix('$')
+            .ok_or_else(|| invalid_data(format!("Expected a section header, found {:?}", line)))?;
+        let end = format!("$End{}", name);
+        let mut body = Vec::new();
+        loop {
+            match lines.next() {
+                Some(l) if l == end => break,
+                Some(l) => body.push(l),
+                None => return Err(invalid_data(format!("Unterminated section ${}", name))),
+            }
+        }
+        out.push(Section { name, lines: body });
+    }
+    Ok(out)
+}
+
+fn parse<T: FromStr>(token: Option<&str>) -> io::Result<T> {
+    token
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| invalid_data(format!("Unable to parse token {:?}", token)))
+}
+
+fn parse_line<T: FromStr>(line: &str) -> io::Result<Vec<T>> {
+    line.split_whitespace().map(|t| parse(Some(t))).collect()
+}
+
+fn parse_physical_names(lines: &[&str]) -> io::Result<Vec<PhysicalGroup>> {
+    lines
+        .iter()
+        .skip(1)
+        .map(|line| {
+            let mut tokens = line.splitn(3, char::is_whitespace);
+            let dimension = parse(tokens.next())?;
+            let tag = parse(tokens.next())?;
+            let name = tokens
+                .next()
+                .unwrap_or("")
+                .trim()
+                .trim_matches('"')
+                .to_owned();
+            Ok(PhysicalGroup {
+                dimension,
+                tag,
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Accumulates nodes and surface elements independently of the file version.
+struct Contents<S: Scalar> {
+    mesh: Mesh<S>,
+    nodes: HashMap<u64, PointIndex>,
+    polygons: Vec<Vec<PointIndex>>,
+    tags: Vec<Tag>,
+}
+
+impl<S: Scalar> Contents<S> {
+    fn add_node(&mut self, tag: u64, position: [f64; 3]) {
+        let point = self
+            .mesh
+            .add_element(Point::from_position(scalar::from_f64(position)));
+        self.nodes.insert(tag, point);
+    }
+
+    fn add_element(&mut self, element_type: u32, nodes: &[u64], tag: Tag) -> io::Result<()> {
+        let count = match element_type {
+            TRIANGLE => 3,
+            QUAD => 4,
+            _ => return Ok(()),
+        };
+        if nodes.len() < count {
+            return Err(invalid_data("Element has too few nodes."));
+        }
+        let polygon = nodes[..count]
+            .iter()
+            .map(|n| {
+                self.nodes
+                    .get(n)
+                    .copied()
+                    .ok_or_else(|| invalid_data(format!("Element references unknown node {}", n)))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        self.polygons.push(polygon);
+        self.tags.push(tag);
+        Ok(())
+    }
+}
+
+fn read_v2<S: Scalar>(sections: &[Section], contents: &mut Contents<S>) -> io::Result<()> {
+    for section in sections {
+        match section.name {
+            "Nodes" => {
+                for line in section.lines.iter().skip(1) {
+                    let mut tokens = line.split_whitespace();
+                    let tag = parse(tokens.next())?;
+                    let x = parse(tokens.next())?;
+                    let y = parse(tokens.next())?;
+                    let z = parse(tokens.next())?;
+                    contents.add_node(tag, [x, y, z]);
+                }
+            }
+            "Elements" => {
+                for line in section.lines.iter().skip(1) {
+                    let values: Vec<u64> = parse_line(line)?;
+                    if values.len() < 3 {
+                        return Err(invalid_data("Truncated element."));
+                    }
+                    let tag_count = values[2] as usize;
+                    let tag = if tag_count > 0 {
+                        values.get(3).copied().unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    let nodes = values.get(3 + tag_count..).unwrap_or(&[]);
+                    contents.add_element(values[1] as u32, nodes, tag as Tag)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read_v4<S: Scalar>(sections: &[Section], contents: &mut Contents<S>) -> io::Result<()> {
+    let mut surface_tags: HashMap<u64, Tag> = HashMap::new();
+    for section in sections {
+        match section.name {
+            "Entities" => {
+                let counts: Vec<usize> = parse_line(section.lines.first().unwrap_or(&""))?;
+                if counts.len() < 4 {
+                    return Err(invalid_data("Truncated entity counts."));
+                }
+                let surface_start = 1 + counts[0] + counts[1];
+                for line in section.lines.iter().skip(surface_start).take(counts[2]) {
+                    let values: Vec<f64> = parse_line(line)?;
+                    if values.len() < 8 {
+                        return Err(invalid_data("Truncated surface entity."));
+                    }
+                    if let (true, Some(tag)) = (values[7] > 0.0, values.get(8)) {
+                        surface_tags.insert(values[0] as u64, *tag as Tag);
+                    }
+                }
+            }
+            "Nodes" => {
+                let mut lines = section.lines.iter().skip(1);
+                while let Some(header) = lines.next() {
+                    let header: Vec<usize> = parse_line(header)?;
+                    let count = *header
+                        .get(3)
+                        .ok_or_else(|| invalid_data("Bad node block."))?;
+                    let tags = lines
+                        .by_ref()
+                        .take(count)
+                        .map(|l| parse(Some(l.trim())))
+                        .collect::<io::Result<Vec<u64>>>()?;
+                    for tag in tags {
+                        let line = lines.next().ok_or_else(|| invalid_data("Missing node."))?;
+                        let xyz: Vec<f64> = parse_line(line)?;
+                        if xyz.len() < 3 {
+                            return Err(invalid_data("Truncated node coordinates."));
+                        }
+                        contents.add_node(tag, [xyz[0], xyz[1], xyz[2]]);
+                    }
+                }
+            }
+            "Elements" => {
+                let mut lines = section.lines.iter().skip(1);
+                while let Some(header) = lines.next() {
+                    let header: Vec<u64> = parse_line(header)?;
+                    if header.len() < 4 {
+                        return Err(invalid_data("Bad element block."));
+                    }
+                    let tag = if header[0] as u32 == SURFACE {
+                        surface_tags.get(&header[1]).copied().unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    for line in lines.by_ref().take(header[3] as usize) {
+                        let values: Vec<u64> = parse_line(line)?;
+                        contents.add_element(
+                            header[2] as u32,
+                            values.get(1..).unwrap_or(&[]),
+                            tag,
+                        )?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads an ASCII `.msh` file, keeping every node as a separate point.
+pub fn read<S: Scalar, R: BufRead>(reader: R) -> io::Result<GmshMesh<S>> {
+    read_with(reader, MergePolicy::Never).map(|(gmsh, _)| gmsh)
+}
+
+/// Reads an ASCII `.msh` file, merging duplicate nodes according to `policy`.
+pub fn read_with<S: Scalar, R: BufRead>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(GmshMesh<S>, MergeStats)> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let sections = sections(&text)?;
+
+    let format = sections
+        .iter()
+        .find(|s| s.name == "MeshFormat")
+        .and_then(|s| s.lines.first())
+        .ok_or_else(|| invalid_data("Missing $MeshFormat section."))?;
+    let mut format = format.split_whitespace();
+    let version: String = parse(format.next())?;
+    let file_type: u32 = parse(format.next())?;
+    if file_type != 0 {
+        return Err(invalid_data("Binary .msh files are not supported."));
+    }
+
+    let mut contents = Contents {
+        mesh: Mesh::new(),
+        nodes: HashMap::new(),
+        polygons: Vec::new(),
+        tags: Vec::new(),
+    };
+    if version.starts_with('2') {
+        read_v2(&sections, &mut contents)?;
+    } else if version.starts_with('4') {
+        read_v4(&sections, &mut contents)?;
+    } else {
+        return Err(invalid_data(format!(
+            "Unsupported .msh version {}",
+            version
+        )));
+    }
+
+    let physical_groups = match sections.iter().find(|s| s.name == "PhysicalNames") {
+        Some(section) => parse_physical_names(&section.lines)?,
+        None => Vec::new(),
+    };
+
+    let Contents {
+        mut mesh,
+        mut polygons,
+        tags,
+        ..
+    } = contents;
+    let stats = merge::merge_points(&mut mesh, &mut polygons, policy);
+    let faces = builder::add_polygons(&mut mesh, &polygons);
+    let face_tags = faces
+        .into_iter()
+        .zip(tags)
+        .filter(|(face, tag)| face.is_valid() && *tag != 0)
+        .collect();
+
+    Ok((
+        GmshMesh {
+            mesh,
+            physical_groups,
+            face_tags,
+        },
+        stats,
+    ))
+}
+
+/// Reads an ASCII `.msh` file from an asynchronous source.
+///
+/// Control goes back to the executor after every chunk that is read. The
+/// sections of a `.msh` file refer to each other, so parsing only starts
+/// once the whole file has arrived.
+pub async fn load_async<S: Scalar, R: AsyncRead + Unpin>(reader: R) -> io::Result<GmshMesh<S>> {
+    load_async_with(reader, MergePolicy::Never)
+        .await
+        .map(|(gmsh, _)| gmsh)
+}
+
+/// Like `load_async`, merging duplicate nodes according to `policy`.
+pub async fn load_async_with<S: Scalar, R: AsyncRead + Unpin>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(GmshMesh<S>, MergeStats)> {
+    let mut contents = Vec::new();
+    let mut buf = vec![0; asynchronous::CHUNK_SIZE];
+    loop {
+        let count = asynchronous::read_chunk(&mut reader, &mut buf).await?;
+        if count == 0 {
+            break;
+        }
+        contents.extend_from_slice(&buf[..count]);
+        asynchronous::yield_now().await;
+    }
+    read_with(contents.as_slice(), policy)
+}
+
+/// Writes an ASCII `.msh` file in format 2.2.
+///
+/// Quads are written as quads, larger polygons are split into triangle fans.
+pub fn write<S: Scalar, W: Write>(mut writer: W, gmsh: &GmshMesh<S>) -> io::Result<()> {
+    let mesh = &gmsh.mesh;
+    writeln!(writer, "$MeshFormat\n2.2 0 8\n$EndMeshFormat")?;
+
+    if !gmsh.physical_groups.is_empty() {
+        writeln!(writer, "$PhysicalNames\n{}", gmsh.physical_groups.len())?;
+        for group in &gmsh.physical_groups {
+            writeln!(
+                writer,
+                "{} {} \"{}\"",
+                group.dimension, group.tag, group.name
+            )?;
+        }
+        writeln!(writer, "$EndPhysicalNames")?;
+    }
+
+    let mut node_ids = HashMap::new();
+    writeln!(writer, "$Nodes\n{}", mesh.point_count())?;
+    for (id, (index, point)) in mesh.points.iter().enumerate() {
+        let [x, y, z] = scalar::to_f64(point.position);
+        writeln!(writer, "{} {} {} {}", id + 1, x, y, z)?;
+        node_ids.insert(index, id + 1);
+    }
+    writeln!(writer, "$EndNodes")?;
+
+    let mut elements = Vec::new();
+    for face in mesh.faces() {
+        let nodes = face
+            .vertices()
+            .map(|v| {
+                v.element()
+                    .and_then(|v| node_ids.get(&v.point_index))
+                    .copied()
+                    .ok_or_else(|| {
+                        invalid_data(format!("Face {:?} has a dangling vertex.", face.index))
+                    })
+            })
+            .collect::<io::Result<Vec<usize>>>()?;
+        let tag = gmsh.face_tags.get(&face.index).copied().unwrap_or(0);
+        match nodes.len() {
+            3 => elements.push((TRIANGLE, tag, nodes)),
+            4 => elements.push((QUAD, tag, nodes)),
+            n if n > 4 => {
+                for i in 1..n - 1 {
+                    elements.push((TRIANGLE, tag, vec![nodes[0], nodes[i], nodes[i + 1]]));
+                }
+            }
+            _ => log::warn!("Skipping degenerate face {:?}", face.index),
+        }
+    }
+
+    writeln!(writer, "$Elements\n{}", elements.len())?;
+    for (id, (element_type, tag, nodes)) in elements.iter().enumerate() {
+        let entity = if *tag == 0 { 1 } else { *tag };
+        write!(writer, "{} {} 2 {} {}", id + 1, element_type, tag, entity)?;
+        for node in nodes {
+            write!(writer, " {}", node)?;
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer, "$EndElements")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE_V4: &str = r#"$MeshFormat
+4.1 0 8
+$EndMeshFormat
+$PhysicalNames
+1
+2 7 "plate"
+$EndPhysicalNames
+$Entities
+0 0 1 0
+1 0 0 0 1 1 0 1 7 0
+$EndEntities
+$Nodes
+1 4 1 4
+2 1 0 4
+1
+2
+3
+4
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+$EndNodes
+$Elements
+1 2 1 2
+2 1 2 2
+1 1 2 3
+2 1 3 4
+$EndElements
+"#;
+
+    #[test]
+    fn reads_version_4_surfaces() {
+        let gmsh: GmshMesh = read(SQUARE_V4.as_bytes()).expect("Failed to read mesh.");
+        assert_eq!(gmsh.mesh.point_count(), 4);
+        assert_eq!(gmsh.mesh.face_count(), 2);
+        assert_eq!(gmsh.physical_groups[0].name, "plate");
+        assert!(gmsh.face_tags.values().all(|tag| *tag == 7));
+        assert_eq!(gmsh.face_tags.len(), 2);
+        assert_eq!(gmsh.mesh.edges().filter(|e| !e.is_boundary()).count(), 2);
+    }
+
+    #[test]
+    fn round_trips_through_version_2() {
+        let gmsh: GmshMesh = read(SQUARE_V4.as_bytes()).expect("Failed to read mesh.");
+        let mut buffer = Vec::new();
+        write(&mut buffer, &gmsh).expect("Failed to write mesh.");
+
+        let text = String::from_utf8(buffer.clone()).unwrap();
+        assert!(text.starts_with("$MeshFormat\n2.2 0 8"));
+
+        let copy: GmshMesh = read(buffer.as_slice()).expect("Failed to read mesh.");
+        assert_eq!(copy.mesh.point_count(), 4);
+        assert_eq!(copy.mesh.face_count(), 2);
+        assert_eq!(copy.physical_groups, gmsh.physical_groups);
+        assert_eq!(copy.face_tags.len(), 2);
+        assert!(copy.face_tags.values().all(|tag| *tag == 7));
+    }
+
+    #[test]
+    fn duplicate_nodes_can_be_merged() {
+        // Two triangles written with their own copies of the shared nodes.
+        let text = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Nodes\n6\n\
+                    1 0 0 0\n2 1 0 0\n3 1 1 0\n4 0 0 0\n5 1 1 0\n6 0 1 0\n$EndNodes\n\
+                    $Elements\n2\n1 2 0 1 2 3\n2 2 0 4 5 6\n$EndElements\n";
+        let (split, stats) = read_with::<f64, _>(text.as_bytes(), MergePolicy::Never).unwrap();
+        assert_eq!(stats.merged_points, 0);
+        assert_eq!(split.mesh.point_count(), 6);
+        assert!(split.mesh.edges().all(|e| e.is_boundary()));
+
+        let (joined, stats) = read_with::<f64, _>(text.as_bytes(), MergePolicy::Exact).unwrap();
+        assert_eq!(stats.merged_points, 2);
+        assert_eq!(joined.mesh.point_count(), 4);
+        assert_eq!(joined.mesh.edges().filter(|e| !e.is_boundary()).count(), 2);
+    }
+
+    #[test]
+    fn loads_asynchronously() {
+        let (result, yields) = asynchronous::block_on(load_async::<f64, _>(SQUARE_V4.as_bytes()));
+        let gmsh = result.unwrap();
+        assert_eq!(gmsh.mesh.face_count(), 2);
+        assert_eq!(yields, 1);
+    }
+
+    #[test]
+    fn rejects_binary_files() {
+        let text = "$MeshFormat\n4.1 1 8\n$EndMeshFormat\n";
+        assert!(read::<f64, _>(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_nodes() {
+        let text = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Nodes\n1\n1 0 0 0\n$EndNodes\n\
+                    $Elements\n1\n1 2 2 0 1 1 2 3\n$EndElements\n";
+        assert!(read::<f32, _>(text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! A small JSON reader for the documents of text based formats.
+
+use super::invalid_data;
+use std::io;
+
+/// Nesting deeper than this is rejected rather than risking the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order of the document.
+    Object(Vec<(String, Value)>),
+}
+
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// The member called `key`, or null when there is none.
+    pub(crate) fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    pub(crate) fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Numbers which are whole and not negative.
+    pub(crate) fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|n| n.fract() == 0.0 && *n >= 0.0 && *n <= u32::MAX as f64)
+            .map(|n| n as usize)
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The elements of an array, nothing for other values.
+    pub(crate) fn elements(&self) -> &[Value] {
+        match self {
+            Value::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+}
+
+/// Parses a complete document.
+pub(crate) fn parse(text: &str) -> io::Result<Value> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        at: 0,
+    };
+    let value = parser.value(0)?;
+    parser.whitespace();
+    if parser.at != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> io::Error {
+        invalid_data(format!("Invalid JSON at byte {}: {}.", self.at, what))
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.bytes.get(self.at), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.at += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> io::Result<()> {
+        if self.bytes[self.at..].starts_with(literal.as_bytes()) {
+            self.at += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", literal)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.whitespace();
+        match self.bytes.get(self.at) {
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                self.at += 1;
+                let mut elements = Vec::new();
+                self.whitespace();
+                if self.bytes.get(self.at) == Some(&b']') {
+                    self.at += 1;
+                    return Ok(Value::Array(elements));
+                }
+                loop {
+                    elements.push(self.value(depth + 1)?);
+                    self.whitespace();
+                    match self.bytes.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b']') => {
+                            self.at += 1;
+                            return Ok(Value::Array(elements));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.at += 1;
+                let mut members = Vec::new();
+                self.whitespace();
+                if self.bytes.get(self.at) == Some(&b'}') {
+                    self.at += 1;
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.whitespace();
+                    match self.bytes.get(self.at) {
+                        Some(b',') => self.at += 1,
+                        Some(b'}') => {
+                            self.at += 1;
+                            return Ok(Value::Object(members));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn number(&mut self) -> io::Result<Value> {
+        let start = self.at;
+        while matches!(
+            self.bytes.get(self.at),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.at += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.at])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("malformed number"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            let start = self.at;
+            while !matches!(self.bytes.get(self.at), None | Some(b'"' | b'\\')) {
+                self.at += 1;
+            }
+            // The input is a `str` and runs end on ASCII, so they are valid.
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.at]).unwrap());
+            match self.bytes.get(self.at) {
+                Some(b'"') => {
+                    self.at += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.at += 1;
+                    let escaped = match self.bytes.get(self.at) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let code = self.code_unit()?;
+                            let code = if (0xD800..0xDC00).contains(&code) {
+                                self.at += 1;
+                                self.expect("\\u")?;
+                                self.at -= 1;
+                                let low = self.code_unit()?;
+                                0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF)
+                            } else {
+                                code
+                            };
+                            char::from_u32(code).ok_or_else(|| self.error("invalid escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.at += 1;
+                    out.push(escaped);
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// The four hex digits after a `\u`, leaving `at` on the last of them.
+    fn code_unit(&mut self) -> io::Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.at + 1..self.at + 5)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid escape"))?;
+        self.at += 4;
+        Ok(digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_parse_into_values() {
+        let value = parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"é😀"}} "#).unwrap();
+        let numbers: Vec<Option<f64>> = value
+            .get("a")
+            .elements()
+            .iter()
+            .map(Value::as_f64)
+            .collect();
+        assert_eq!(numbers, vec![Some(1.0), Some(-25.0), None, None]);
+        assert_eq!(value.get("b").get("c").as_str(), Some("x\"é😀"));
+        let escaped = parse(r#""\u00e9\ud83d\ude00\n""#).unwrap();
+        assert_eq!(escaped.as_str(), Some("é😀\n"));
+        assert!(value.get("missing").is_null());
+        assert_eq!(value.get("a").elements()[0].as_usize(), Some(1));
+        assert_eq!(value.get("a").elements()[1].as_usize(), None);
+
+        for broken in ["", "[1,]", "{\"a\" 1}", "\"open", "[1] 2", "tru"] {
+            assert!(parse(broken).is_err(), "{}", broken);
+        }
+        assert!(parse(&"[".repeat(MAX_DEPTH + 2)).is_err());
+    }
+}
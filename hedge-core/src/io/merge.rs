@@ -0,0 +1,184 @@
+//! Merging of duplicate points while importing.
+
+use crate::math::Vec3;
+use crate::spatial::PointGrid;
+use crate::*;
+use std::collections::HashMap;
+
+/// How importers treat points that share a position.
+///
+/// Keeping duplicates preserves seams authored in the file, merging them
+/// connects faces that only touch through coincident points.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MergePolicy {
+    /// Every point in the file is kept.
+    #[default]
+    Never,
+    /// Points with bit for bit identical positions are merged.
+    Exact,
+    /// Points closer than the distance are merged into the first of them.
+    Weld(f64),
+}
+
+impl MergePolicy {
+    /// Welds points which `tolerances` considers coincident.
+    pub fn weld(tolerances: &Tolerances) -> Self {
+        MergePolicy::Weld(tolerances.weld)
+    }
+}
+
+/// What merging did to the imported points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub input_points: usize,
+    pub merged_points: usize,
+    /// Polygons left with fewer than three distinct corners, these can't be
+    /// built and are skipped.
+    pub collapsed_polygons: usize,
+}
+
+impl MergeStats {
+    pub fn output_points(&self) -> usize {
+        self.input_points - self.merged_points
+    }
+}
+
+/// Merges the points of a mesh that has no connectivity yet according to
+/// `policy`, rewriting the polygons to refer to the remaining points.
+pub(crate) fn merge_points<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    polygons: &mut [Vec<PointIndex>],
+    policy: MergePolicy,
+) -> MergeStats {
+    let points: Vec<(PointIndex, Vec3)> = mesh
+        .points
+        .iter()
+        .map(|(index, point)| (index, scalar::to_f64(point.position)))
+        .collect();
+    let mut stats = MergeStats {
+        input_points: points.len(),
+        ..MergeStats::default()
+    };
+    let replacements = match policy {
+        MergePolicy::Never => return stats,
+        MergePolicy::Exact => exact_duplicates(&points),
+        MergePolicy::Weld(distance) if distance > 0.0 => welded(&points, distance),
+        MergePolicy::Weld(_) => exact_duplicates(&points),
+    };
+    if replacements.is_empty() {
+        return stats;
+    }
+
+    for point in replacements.keys() {
+        mesh.remove_element(*point);
+    }
+    stats.merged_points = replacements.len();
+    for polygon in polygons.iter_mut() {
+        for point in polygon.iter_mut() {
+            if let Some(kept) = replacements.get(point) {
+                *point = *kept;
+            }
+        }
+        polygon.dedup();
+        while polygon.len() > 1 && polygon.first() == polygon.last() {
+            polygon.pop();
+        }
+        if polygon.len() < 3 {
+            stats.collapsed_polygons += 1;
+        }
+    }
+    stats
+}
+
+fn exact_duplicates(points: &[(PointIndex, Vec3)]) -> HashMap<PointIndex, PointIndex> {
+    let mut first: HashMap<[u64; 3], PointIndex> = HashMap::new();
+    let mut replacements = HashMap::new();
+    for (index, position) in points {
+        // Adding zero turns -0.0 into 0.0 so both hash alike.
+        let key = position.map(|v| (v + 0.0).to_bits());
+        match first.get(&key) {
+            Some(kept) => {
+                replacements.insert(*index, *kept);
+            }
+            None => {
+                first.insert(key, *index);
+            }
+        }
+    }
+    replacements
+}
+
+fn welded(points: &[(PointIndex, Vec3)], distance: f64) -> HashMap<PointIndex, PointIndex> {
+    let mut grid = PointGrid::new(distance);
+    let mut kept: Vec<(PointIndex, Vec3)> = Vec::new();
+    let mut replacements = HashMap::new();
+    for (index, position) in points {
+        let target = grid
+            .nearby(*position)
+            .filter(|k| math::distance(kept[*k].1, *position) <= distance)
+            .min();
+        match target {
+            Some(k) => {
+                replacements.insert(*index, kept[k].0);
+            }
+            None => {
+                grid.insert(kept.len(), *position);
+                kept.push((*index, *position));
+            }
+        }
+    }
+    replacements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soup(positions: &[[f64; 3]]) -> (Mesh<f64>, Vec<PointIndex>) {
+        let mut mesh = Mesh::new();
+        let points = positions
+            .iter()
+            .map(|p| mesh.add_element(Point::from_position(*p)))
+            .collect();
+        (mesh, points)
+    }
+
+    #[test]
+    fn policies_merge_as_configured() {
+        let _ = env_logger::try_init();
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0 + 1.0e-9, 0.0],
+        ];
+        for (policy, merged) in [
+            (MergePolicy::Never, 0),
+            (MergePolicy::Exact, 1),
+            (MergePolicy::Weld(1.0e-6), 2),
+        ] {
+            let (mut mesh, p) = soup(&positions);
+            let mut polygons = vec![vec![p[0], p[1], p[2]], vec![p[3], p[4], p[5]]];
+            let stats = merge_points(&mut mesh, &mut polygons, policy);
+            assert_eq!(stats.merged_points, merged);
+            assert_eq!(stats.output_points(), mesh.point_count());
+            assert_eq!(stats.collapsed_polygons, 0);
+            let faces = builder::add_polygons(&mut mesh, &polygons);
+            assert!(faces.iter().all(|f| f.is_valid()));
+            let interior = mesh.edges().filter(|e| !e.is_boundary()).count();
+            assert_eq!(interior, if merged == 2 { 2 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn welding_reports_collapsed_polygons() {
+        let (mut mesh, p) = soup(&[[0.0; 3], [1.0e-4, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        let mut polygons = vec![vec![p[0], p[1], p[2]]];
+        let stats = merge_points(&mut mesh, &mut polygons, MergePolicy::Weld(1.0e-3));
+        assert_eq!(stats.merged_points, 1);
+        assert_eq!(stats.collapsed_polygons, 1);
+        assert_eq!(polygons[0], vec![p[0], p[2]]);
+    }
+}
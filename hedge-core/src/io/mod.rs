@@ -0,0 +1,30 @@
+//! Reading and writing meshes in external file formats.
+
+mod asynchronous;
+pub mod buffers;
+pub mod gltf;
+pub mod gmsh;
+mod json;
+mod merge;
+pub mod obj;
+pub mod packed;
+pub mod ply;
+pub mod quantized;
+pub mod stl;
+pub mod stream;
+pub mod threemf;
+
+pub use self::asynchronous::AsyncRead;
+#[cfg(feature = "futures")]
+pub use self::asynchronous::FuturesReader;
+#[cfg(feature = "tokio")]
+pub use self::asynchronous::TokioReader;
+pub use self::merge::{MergePolicy, MergeStats};
+
+use std::io;
+
+pub(crate) fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+    error: E,
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,711 @@
+//! Wavefront `.obj` import and export.
+//!
+//! Vertex positions and faces are read, texture coordinates, normals and
+//! groups are skipped. Writing can add per-corner normals and UVs, and
+//! sort faces into `g`/`usemtl` sections along with a `.mtl` library.
+//! Faces may refer to positions with negative, relative indices. Objects
+//! and their materials are kept when reading into a `Scene`, where a new
+//! object starts at every `o` statement and wherever the material changes.
+
+use super::asynchronous::{self, AsyncRead};
+use super::invalid_data;
+use super::merge::{self, MergePolicy, MergeStats};
+use crate::attributes::Channel;
+use crate::normals::NormalChannel;
+use crate::scene::{Object, Scene};
+use crate::uv::UvChannel;
+use crate::*;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// Line based parser which can be fed the contents of a file in arbitrary
+/// chunks, for callers that receive the file piece by piece.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    positions: Vec<[f64; 3]>,
+    polygons: Vec<Vec<usize>>,
+    objects: Vec<Part>,
+    pending: Vec<u8>,
+    line: usize,
+}
+
+/// Polygons from `first` up to the next part belong to the named object.
+#[derive(Debug, Clone)]
+struct Part {
+    name: String,
+    material: Option<String>,
+    first: usize,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser::default()
+    }
+
+    /// Parses every complete line in `bytes`, keeping a trailing partial
+    /// line until more bytes arrive.
+    pub fn feed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let Some(end) = self.pending.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        let rest = self.pending.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        for line in complete.split(|b| *b == b'\n') {
+            self.parse_line(line)?;
+        }
+        Ok(())
+    }
+
+    /// Parses what is left and builds the mesh.
+    pub fn finish<S: Scalar>(mut self, policy: MergePolicy) -> io::Result<(Mesh<S>, MergeStats)> {
+        let rest = std::mem::take(&mut self.pending);
+        self.parse_line(&rest)?;
+        Ok(self.build(&self.polygons, policy))
+    }
+
+    /// Parses what is left and builds one object per part of the file.
+    ///
+    /// Each object only gets the points its faces use. Faces before the first
+    /// `o` statement go to an object without a name.
+    pub fn finish_scene<S: Scalar>(
+        mut self,
+        policy: MergePolicy,
+    ) -> io::Result<(Scene<S>, MergeStats)> {
+        let rest = std::mem::take(&mut self.pending);
+        self.parse_line(&rest)?;
+
+        let mut scene = Scene::new();
+        let mut stats = MergeStats::default();
+        for (i, part) in self.objects.iter().enumerate() {
+            let end = self
+                .objects
+                .get(i + 1)
+                .map_or(self.polygons.len(), |next| next.first);
+            if part.first == end {
+                continue;
+            }
+            let mut used = HashMap::new();
+            let mut positions = Vec::new();
+            let polygons: Vec<Vec<usize>> = self.polygons[part.first..end]
+                .iter()
+                .map(|polygon| {
+                    polygon
+                        .iter()
+                        .map(|p| {
+                            *used.entry(*p).or_insert_with(|| {
+                                positions.push(self.positions[*p]);
+                                positions.len() - 1
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+            let local = Parser {
+                positions,
+                ..Parser::default()
+            };
+            let (mesh, part_stats) = local.build(&polygons, policy);
+            stats.input_points += part_stats.input_points;
+            stats.merged_points += part_stats.merged_points;
+            stats.collapsed_polygons += part_stats.collapsed_polygons;
+            let mut object = Object::new(part.name.clone(), mesh);
+            object.material = part.material.as_deref().map(|m| scene.material(m));
+            scene.add_object(object);
+        }
+        Ok((scene, stats))
+    }
+
+    fn build<S: Scalar>(
+        &self,
+        polygons: &[Vec<usize>],
+        policy: MergePolicy,
+    ) -> (Mesh<S>, MergeStats) {
+        let mut mesh = Mesh::new();
+        let points: Vec<PointIndex> = self
+            .positions
+            .iter()
+            .map(|p| mesh.add_element(Point::from_position(scalar::from_f64(*p))))
+            .collect();
+        let mut polygons: Vec<Vec<PointIndex>> = polygons
+            .iter()
+            .map(|polygon| polygon.iter().map(|i| points[*i]).collect())
+            .collect();
+        let stats = merge::merge_points(&mut mesh, &mut polygons, policy);
+        builder::add_polygons(&mut mesh, &polygons);
+        (mesh, stats)
+    }
+
+    /// Starts a new part unless the current one has no faces yet, in which
+    /// case it is renamed instead.
+    fn start_part(&mut self, name: String, material: Option<String>) {
+        match self.objects.last_mut() {
+            Some(part) if part.first == self.polygons.len() => {
+                part.name = name;
+                part.material = material;
+            }
+            _ => self.objects.push(Part {
+                name,
+                material,
+                first: self.polygons.len(),
+            }),
+        }
+    }
+
+    fn parse_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.line += 1;
+        let text = std::str::from_utf8(line)
+            .map_err(|_| invalid_data(format!("Line {} is not valid UTF-8", self.line)))?;
+        let text = text.split('#').next().unwrap_or("");
+        let mut tokens = text.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let mut position = [0.0; 3];
+                for value in &mut position {
+                    *value = tokens
+                        .next()
+                        .and_then(|t| t.parse().ok())
+                        .ok_or_else(|| self.error("Expected three coordinates"))?;
+                }
+                self.positions.push(position);
+            }
+            Some("f") => {
+                let polygon = tokens
+                    .map(|corner| self.position_index(corner))
+                    .collect::<io::Result<Vec<usize>>>()?;
+                if polygon.len() < 3 {
+                    return Err(self.error("Faces need at least three corners"));
+                }
+                if self.objects.is_empty() {
+                    self.start_part(String::new(), None);
+                }
+                self.polygons.push(polygon);
+            }
+            Some("o") => {
+                // Materials carry over into the next object.
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = self.objects.last().and_then(|p| p.material.clone());
+                self.start_part(name, material);
+            }
+            Some("usemtl") => {
+                let material = tokens.next().map(str::to_string);
+                let name = self.objects.last().map(|p| p.name.clone());
+                self.start_part(name.unwrap_or_default(), material);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Resolves the position part of a `v/vt/vn` corner.
+    fn position_index(&self, corner: &str) -> io::Result<usize> {
+        let index: i64 = corner
+            .split('/')
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| self.error(&format!("Unable to parse corner {:?}", corner)))?;
+        let count = self.positions.len() as i64;
+        let resolved = if index < 0 { count + index } else { index - 1 };
+        if resolved < 0 || resolved >= count {
+            return Err(self.error(&format!("Position {} doesn't exist", index)));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        invalid_data(format!("{} on line {}", message, self.line))
+    }
+}
+
+/// Reads an `.obj` file, keeping every position as a separate point.
+pub fn read<S: Scalar, R: BufRead>(reader: R) -> io::Result<Mesh<S>> {
+    read_with(reader, MergePolicy::Never).map(|(mesh, _)| mesh)
+}
+
+/// Reads an `.obj` file, merging duplicate positions according to `policy`.
+pub fn read_with<S: Scalar, R: BufRead>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(Mesh<S>, MergeStats)> {
+    let mut parser = Parser::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        parser.feed(chunk)?;
+        let consumed = chunk.len();
+        reader.consume(consumed);
+    }
+    parser.finish(policy)
+}
+
+/// Reads an `.obj` file into one object per object or material.
+pub fn read_scene<S: Scalar, R: BufRead>(reader: R) -> io::Result<Scene<S>> {
+    read_scene_with(reader, MergePolicy::Never).map(|(scene, _)| scene)
+}
+
+/// Like `read_scene`, merging duplicate positions within each object.
+pub fn read_scene_with<S: Scalar, R: BufRead>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(Scene<S>, MergeStats)> {
+    let mut parser = Parser::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        parser.feed(chunk)?;
+        let consumed = chunk.len();
+        reader.consume(consumed);
+    }
+    parser.finish_scene(policy)
+}
+
+/// Writes the faces of a mesh over its points, numbered densely whatever
+/// the removals left behind, along with the vertex normals of the mesh.
+pub fn write<S: Scalar, W: Write>(writer: W, mesh: &Mesh<S>) -> io::Result<()> {
+    write_with(writer, mesh, None, None)
+}
+
+/// Like `write`, adding the per-corner normals and UVs of the channels.
+///
+/// Equal values are written once and corners refer to them from the face
+/// statements, a face only uses a channel when all of its corners have a
+/// value in it. Without a normal channel the vertex normals of the mesh
+/// are written.
+pub fn write_with<S: Scalar, W: Write>(
+    mut writer: W,
+    mesh: &Mesh<S>,
+    normals: Option<&NormalChannel>,
+    uvs: Option<&UvChannel>,
+) -> io::Result<()> {
+    let stored = match normals {
+        Some(_) => None,
+        None => mesh.stored_corner_normals(),
+    };
+    let normals = normals.or(stored.as_ref());
+    write_mesh(
+        &mut writer,
+        mesh,
+        normals,
+        uvs,
+        None,
+        &mut Written::default(),
+    )
+}
+
+/// A section of faces, written as a `g` statement followed by a `usemtl`
+/// when it has a material.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub material: Option<String>,
+}
+
+/// Sections to sort the faces of a mesh into, see `write_groups`.
+#[derive(Debug, Clone, Copy)]
+pub struct Groups<'a> {
+    /// The group id of every face, faces without one are in group zero.
+    pub faces: &'a Channel<Face, u32>,
+    /// The group for each id, ids past the end are named after their id.
+    pub groups: &'a [Group],
+    /// The `.mtl` file the materials are defined in, see `write_mtl`.
+    pub library: Option<&'a str>,
+}
+
+impl Groups<'_> {
+    fn id(&self, face: FaceIndex) -> u32 {
+        self.faces.get(face).copied().unwrap_or(0)
+    }
+
+    fn write_header<W: Write>(&self, writer: &mut W, id: u32) -> io::Result<()> {
+        match self.groups.get(id as usize) {
+            Some(group) => {
+                writeln!(writer, "g {}", group.name)?;
+                if let Some(material) = &group.material {
+                    writeln!(writer, "usemtl {}", material)?;
+                }
+                Ok(())
+            }
+            None => writeln!(writer, "g group{}", id),
+        }
+    }
+}
+
+/// Like `write_with`, writing the faces in one section per group in
+/// increasing id order. Faces keep their order within a section.
+pub fn write_groups<S: Scalar, W: Write>(
+    mut writer: W,
+    mesh: &Mesh<S>,
+    normals: Option<&NormalChannel>,
+    uvs: Option<&UvChannel>,
+    groups: &Groups,
+) -> io::Result<()> {
+    if let Some(library) = groups.library {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+    let stored = match normals {
+        Some(_) => None,
+        None => mesh.stored_corner_normals(),
+    };
+    let normals = normals.or(stored.as_ref());
+    let mut written = Written::default();
+    write_mesh(&mut writer, mesh, normals, uvs, Some(groups), &mut written)
+}
+
+/// A material of a `.mtl` library, only its diffuse color is written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: [f32; 3],
+}
+
+/// Writes a `.mtl` library defining the materials groups refer to.
+pub fn write_mtl<W: Write>(mut writer: W, materials: &[Material]) -> io::Result<()> {
+    for material in materials {
+        let [r, g, b] = material.diffuse;
+        writeln!(writer, "newmtl {}", material.name)?;
+        writeln!(writer, "Kd {} {} {}", r, g, b)?;
+    }
+    Ok(())
+}
+
+/// Writes every object of a scene with its transform applied, as `.obj`
+/// has no notion of transforms.
+pub fn write_scene<S: Scalar, W: Write>(mut writer: W, scene: &Scene<S>) -> io::Result<()> {
+    let mut written = Written::default();
+    for object in &scene.objects {
+        writeln!(writer, "o {}", object.name)?;
+        if let Some(material) = scene.material_of(object) {
+            writeln!(writer, "usemtl {}", material)?;
+        }
+        let placed = scene.placed_mesh(object);
+        write_mesh(&mut writer, &placed, None, None, None, &mut written)?;
+    }
+    Ok(())
+}
+
+/// How many of each statement earlier meshes of the file wrote, as indices
+/// run on across objects.
+#[derive(Default)]
+struct Written {
+    points: usize,
+    uvs: usize,
+    normals: usize,
+}
+
+fn write_mesh<S: Scalar, W: Write>(
+    writer: &mut W,
+    mesh: &Mesh<S>,
+    normals: Option<&NormalChannel>,
+    uvs: Option<&UvChannel>,
+    groups: Option<&Groups>,
+    written: &mut Written,
+) -> io::Result<()> {
+    let mut dense = HashMap::new();
+    for (index, point) in mesh.points.iter() {
+        let [x, y, z] = scalar::to_f64(point.position);
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+        dense.insert(index, written.points + dense.len() + 1);
+    }
+    written.points += dense.len();
+
+    // Values are keyed by their bits so equal ones share a statement.
+    let mut uv_indices: HashMap<[u32; 2], usize> = HashMap::new();
+    let mut normal_indices: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut corners = Vec::new();
+    for face in mesh.faces() {
+        let edges: Vec<EdgeIndex> = face.edges().map(|e| e.index).collect();
+        let face_uvs = uvs.and_then(|c| {
+            edges
+                .iter()
+                .map(|e| c.get(*e).copied())
+                .collect::<Option<Vec<_>>>()
+        });
+        let face_normals = normals.and_then(|c| {
+            edges
+                .iter()
+                .map(|e| c.get(*e).copied())
+                .collect::<Option<Vec<_>>>()
+        });
+        let mut face_corners = Vec::with_capacity(edges.len());
+        for (i, edge) in edges.iter().enumerate() {
+            let point = mesh.edge(*edge).vertex().element().map(|v| v.point_index);
+            let Some(point) = point.and_then(|point| dense.get(&point)) else {
+                continue;
+            };
+            let uv = match &face_uvs {
+                Some(values) => {
+                    let uv = values[i];
+                    let key = uv.map(f32::to_bits);
+                    if !uv_indices.contains_key(&key) {
+                        writeln!(writer, "vt {} {}", uv[0], uv[1])?;
+                        uv_indices.insert(key, written.uvs + uv_indices.len() + 1);
+                    }
+                    uv_indices.get(&key).copied()
+                }
+                None => None,
+            };
+            let normal = match &face_normals {
+                Some(values) => {
+                    let n = values[i];
+                    let key = n.map(f32::to_bits);
+                    if !normal_indices.contains_key(&key) {
+                        writeln!(writer, "vn {} {} {}", n[0], n[1], n[2])?;
+                        normal_indices.insert(key, written.normals + normal_indices.len() + 1);
+                    }
+                    normal_indices.get(&key).copied()
+                }
+                None => None,
+            };
+            face_corners.push((*point, uv, normal));
+        }
+        corners.push((face.index, face_corners));
+    }
+    written.uvs += uv_indices.len();
+    written.normals += normal_indices.len();
+
+    if let Some(groups) = groups {
+        corners.sort_by_key(|(face, _)| groups.id(*face));
+    }
+    let mut section = None;
+    for (face, face_corners) in corners {
+        if let Some(groups) = groups {
+            let id = groups.id(face);
+            if section != Some(id) {
+                groups.write_header(writer, id)?;
+                section = Some(id);
+            }
+        }
+        write!(writer, "f")?;
+        for corner in face_corners {
+            match corner {
+                (point, None, None) => write!(writer, " {}", point)?,
+                (point, Some(uv), None) => write!(writer, " {}/{}", point, uv)?,
+                (point, None, Some(normal)) => write!(writer, " {}//{}", point, normal)?,
+                (point, Some(uv), Some(normal)) => write!(writer, " {}/{}/{}", point, uv, normal)?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Reads an `.obj` file from an asynchronous source.
+///
+/// The file is parsed as it arrives and control goes back to the executor
+/// after every chunk, so loading a large file doesn't stall other tasks.
+pub async fn load_async<S: Scalar, R: AsyncRead + Unpin>(reader: R) -> io::Result<Mesh<S>> {
+    load_async_with(reader, MergePolicy::Never)
+        .await
+        .map(|(mesh, _)| mesh)
+}
+
+/// Like `load_async`, merging duplicate positions according to `policy`.
+pub async fn load_async_with<S: Scalar, R: AsyncRead + Unpin>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(Mesh<S>, MergeStats)> {
+    let mut parser = Parser::new();
+    let mut buf = vec![0; asynchronous::CHUNK_SIZE];
+    loop {
+        let count = asynchronous::read_chunk(&mut reader, &mut buf).await?;
+        if count == 0 {
+            break;
+        }
+        parser.feed(&buf[..count])?;
+        asynchronous::yield_now().await;
+    }
+    parser.finish(policy)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Writes the mesh as `.obj`, see `io::obj::write`.
+    pub fn write_obj<W: Write>(&self, writer: W) -> io::Result<()> {
+        write(writer, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUADS: &str = "# two quads sharing an edge\n\
+                         v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 2 0 0\nv 2 1 0\n\
+                         vt 0 0\n\
+                         f 1/1 2/1 3/1 4/1\n\
+                         f -5 5 -1 3\n";
+
+    #[test]
+    fn reads_positions_and_faces() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = read(QUADS.as_bytes()).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.point_count(), 6);
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.edges().filter(|e| !e.is_boundary()).count(), 2);
+
+        assert!(read::<f32, _>("v 0 0\n".as_bytes()).is_err());
+        assert!(read::<f32, _>("v 0 0 0\nf 1 2 3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn chunked_input_gives_the_same_mesh() {
+        let mut parser = Parser::new();
+        for chunk in QUADS.as_bytes().chunks(5) {
+            parser.feed(chunk).unwrap();
+        }
+        let (mesh, _): (Mesh, _) = parser.finish(MergePolicy::Never).unwrap();
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.point_count(), 6);
+    }
+
+    #[test]
+    fn scenes_round_trip_objects_and_materials() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                    o first part\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\n\
+                    o second\nusemtl red\nf 1 2 3 4\n";
+        let scene: Scene<f64> = read_scene(text.as_bytes()).unwrap();
+        let names: Vec<&str> = scene.objects.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["first part", "first part", "second"]);
+        assert_eq!(scene.materials, ["red", "blue"]);
+        assert_eq!(scene.objects[2].material, scene.objects[0].material);
+        assert_eq!(scene.objects[0].mesh.point_count(), 3);
+
+        let mut moved = scene.clone();
+        let up = moved.add_transform(scene::Transform::translation([0.0, 0.0, 2.0]));
+        moved.objects[2].transform = Some(up);
+        let mut bytes = Vec::new();
+        write_scene(&mut bytes, &moved).unwrap();
+        let again: Scene<f64> = read_scene(bytes.as_slice()).unwrap();
+        assert_eq!(again.objects.len(), 3);
+        assert_eq!(again.materials, scene.materials);
+        let second = again.object("second").unwrap();
+        assert_eq!(again.material_of(second), Some("red"));
+        assert!(second.mesh.points.iter().all(|(_, p)| p.position[2] == 2.0));
+        assert_eq!(again.flatten().face_count(), 3);
+    }
+
+    #[test]
+    fn async_loading_yields_between_chunks() {
+        let mut text = String::new();
+        for i in 0..20_000 {
+            text.push_str(&format!("v {} 0 0\nv {} 1 0\n", i, i));
+        }
+        for i in 0..19_999 {
+            let a = 2 * i + 1;
+            text.push_str(&format!("f {} {} {} {}\n", a, a + 2, a + 3, a + 1));
+        }
+        let (mesh, yields) = asynchronous::block_on(load_async::<f32, _>(text.as_bytes()));
+        let mesh = mesh.unwrap();
+        assert_eq!(mesh.face_count(), 19_999);
+        assert!(yields >= text.len() / asynchronous::CHUNK_SIZE);
+    }
+
+    #[test]
+    fn written_meshes_are_densely_numbered() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let first = mesh.faces().next().unwrap().index;
+        ops::remove_face(&mut mesh, first).unwrap();
+        let mut bytes = Vec::new();
+        mesh.write_obj(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(
+            text.lines().filter(|l| l.starts_with("v ")).count(),
+            mesh.point_count()
+        );
+        assert_eq!(text.lines().filter(|l| l.starts_with("f ")).count(), 1);
+        let again: Mesh = read(text.as_bytes()).unwrap();
+        builder::assert_connectivity(&again);
+        assert_eq!(again.face_count(), 1);
+
+        let normals = mesh.compute_auto_smooth_normals(0.0);
+        let mut uvs = UvChannel::new();
+        for edge in mesh.edges().filter(|e| e.face().is_valid()) {
+            let p = edge.vertex().position().unwrap();
+            uvs.set(edge.index, [p[0], p[1]]);
+        }
+        let mut bytes = Vec::new();
+        write_with(&mut bytes, &mesh, Some(&normals), Some(&uvs)).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text.lines().filter(|l| l.starts_with("vn ")).count(), 1);
+        assert_eq!(text.lines().filter(|l| l.starts_with("vt ")).count(), 4);
+        let face = text.lines().find(|l| l.starts_with("f ")).unwrap();
+        assert!(face.split(' ').skip(1).all(|corner| corner.ends_with("/1")));
+        let again: Mesh = read(text.as_bytes()).unwrap();
+        assert_eq!(again.face_count(), 1);
+
+        mesh.compute_vertex_normals(normals::NormalWeighting::Area);
+        let mut bytes = Vec::new();
+        mesh.write_obj(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text.lines().filter(|l| l.starts_with("vn ")).count(), 1);
+        let face = text.lines().find(|l| l.starts_with("f ")).unwrap();
+        assert!(face
+            .split(' ')
+            .skip(1)
+            .all(|corner| corner.ends_with("//1")));
+        let again: Mesh = read(text.as_bytes()).unwrap();
+        assert_eq!(again.vertex_count(), 4);
+    }
+
+    #[test]
+    fn groups_become_sections_with_materials() {
+        let mesh: Mesh = builder::grid(3, 1);
+        let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let mut ids = Channel::new();
+        ids.set(faces[0], 1);
+        ids.set(faces[2], 1);
+        let named = [
+            Group {
+                name: "plain".to_string(),
+                material: None,
+            },
+            Group {
+                name: "painted".to_string(),
+                material: Some("red".to_string()),
+            },
+        ];
+        let groups = Groups {
+            faces: &ids,
+            groups: &named,
+            library: Some("grid.mtl"),
+        };
+        let mut bytes = Vec::new();
+        write_groups(&mut bytes, &mesh, None, None, &groups).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let statements: Vec<&str> = text
+            .lines()
+            .filter(|l| !l.starts_with("v "))
+            .map(|l| l.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(statements, ["mtllib", "g", "f", "g", "usemtl", "f", "f"]);
+        let scene: Scene<f32> = read_scene(text.as_bytes()).unwrap();
+        assert_eq!(scene.materials, ["red"]);
+        assert_eq!(scene.objects.len(), 2);
+        assert_eq!(scene.objects[1].mesh.face_count(), 2);
+
+        ids.set(faces[1], 7);
+        let groups = Groups {
+            faces: &ids,
+            groups: &named,
+            library: None,
+        };
+        let mut bytes = Vec::new();
+        write_groups(&mut bytes, &mesh, None, None, &groups).unwrap();
+        assert!(String::from_utf8(bytes).unwrap().contains("g group7\nf "));
+
+        let mut bytes = Vec::new();
+        let red = Material {
+            name: "red".to_string(),
+            diffuse: [1.0, 0.0, 0.0],
+        };
+        write_mtl(&mut bytes, &[red]).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), "newmtl red\nKd 1 0 0\n");
+    }
+}
@@ -0,0 +1,628 @@
+//! A fixed layout binary format which can be used without parsing.
+//!
+//! A file starts with a header and a table of contents, followed by the
+//! sections it lists. Every section is a dense little endian array aligned
+//! to 16 bytes, so a file can be memory mapped and read in place through
+//! `PackedMesh`. Opening one scans the connectivity once to check it, after
+//! that only the values that are asked for are decoded.
+//!
+//! ```text
+//! header   magic "HEDGEPAK", version: u32, section count: u32
+//! entry    name: [u8; 24], element: u32, component: u32, width: u32,
+//!          reserved: u32, offset: u64, count: u64
+//! ```
+//!
+//! Connectivity refers to elements by their position in the file, with
+//! `u32::MAX` standing in for a missing element. Attribute channels are
+//! stored as `f32` values with `NaN` for elements without a value.
+
+use super::invalid_data;
+use crate::attributes::Channel;
+use crate::*;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const MAGIC: &[u8; 8] = b"HEDGEPAK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 56;
+const ALIGNMENT: usize = 16;
+const NAME_SIZE: usize = 24;
+const MISSING: u32 = u32::MAX;
+
+const POSITIONS: &str = "positions";
+const EDGES: &str = "edges";
+const VERTICES: &str = "vertices";
+const FACES: &str = "faces";
+
+/// The kind of element a section stores values for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementKind {
+    Point,
+    Vertex,
+    Face,
+    Edge,
+}
+
+impl ElementKind {
+    fn code(self) -> u32 {
+        match self {
+            ElementKind::Point => 1,
+            ElementKind::Vertex => 2,
+            ElementKind::Face => 3,
+            ElementKind::Edge => 4,
+        }
+    }
+
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(ElementKind::Point),
+            2 => Some(ElementKind::Vertex),
+            3 => Some(ElementKind::Face),
+            4 => Some(ElementKind::Edge),
+            _ => None,
+        }
+    }
+}
+
+/// The type of every value in a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    F32,
+    F64,
+    U32,
+}
+
+impl Component {
+    fn code(self) -> u32 {
+        match self {
+            Component::F32 => 1,
+            Component::F64 => 2,
+            Component::U32 => 3,
+        }
+    }
+
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Component::F32),
+            2 => Some(Component::F64),
+            3 => Some(Component::U32),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Component::F64 => 8,
+            Component::F32 | Component::U32 => 4,
+        }
+    }
+}
+
+/// An attribute channel which can be written to a packed file.
+pub trait PackedChannel<K> {
+    /// Number of `f32` values per element.
+    fn width(&self) -> usize;
+    /// Appends `width()` values for the element, `NaN` when it has no value.
+    fn write(&self, element: Handle<K>, out: &mut Vec<f32>);
+}
+
+impl<K> PackedChannel<K> for Channel<K, f32> {
+    fn width(&self) -> usize {
+        1
+    }
+
+    fn write(&self, element: Handle<K>, out: &mut Vec<f32>) {
+        out.push(self.get(element).copied().unwrap_or(f32::NAN));
+    }
+}
+
+impl<K, const N: usize> PackedChannel<K> for Channel<K, [f32; N]> {
+    fn width(&self) -> usize {
+        N
+    }
+
+    fn write(&self, element: Handle<K>, out: &mut Vec<f32>) {
+        out.extend_from_slice(self.get(element).unwrap_or(&[f32::NAN; N]));
+    }
+}
+
+/// Named attribute channels to store along with a mesh.
+#[derive(Default)]
+pub struct PackedChannels<'a> {
+    pub vertices: Vec<(&'a str, &'a dyn PackedChannel<Vertex>)>,
+    pub faces: Vec<(&'a str, &'a dyn PackedChannel<Face>)>,
+    pub edges: Vec<(&'a str, &'a dyn PackedChannel<Edge>)>,
+}
+
+struct Pending {
+    name: String,
+    element: ElementKind,
+    component: Component,
+    width: usize,
+    count: usize,
+    bytes: Vec<u8>,
+}
+
+/// Writes a mesh without any attribute channels.
+pub fn write<S: Scalar, W: Write>(writer: W, mesh: &Mesh<S>) -> io::Result<()> {
+    write_with(writer, mesh, &PackedChannels::default())
+}
+
+/// Writes a mesh along with attribute channels.
+///
+/// Positions are stored with the precision of the mesh scalar type.
+pub fn write_with<S: Scalar, W: Write>(
+    mut writer: W,
+    mesh: &Mesh<S>,
+    channels: &PackedChannels,
+) -> io::Result<()> {
+    fn dense<K>(handles: impl Iterator<Item = Handle<K>>) -> HashMap<Handle<K>, u32> {
+        handles.enumerate().map(|(i, h)| (h, i as u32)).collect()
+    }
+    let points = dense(mesh.points.iter().map(|(h, _)| h));
+    let vertices = dense(mesh.vertices.iter().map(|(h, _)| h));
+    let faces = dense(mesh.faces.iter().map(|(h, _)| h));
+    let edges = dense(mesh.edges.iter().map(|(h, _)| h));
+    fn lookup<K>(map: &HashMap<Handle<K>, u32>, handle: Handle<K>) -> u32 {
+        map.get(&handle).copied().unwrap_or(MISSING)
+    }
+
+    let mut sections = Vec::new();
+    let wide = std::mem::size_of::<S>() == 8;
+    let mut bytes = Vec::new();
+    for (_, point) in mesh.points.iter() {
+        for value in scalar::to_f64(point.position) {
+            if wide {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+    }
+    sections.push(Pending {
+        name: POSITIONS.into(),
+        element: ElementKind::Point,
+        component: if wide { Component::F64 } else { Component::F32 },
+        width: 3,
+        count: points.len(),
+        bytes,
+    });
+
+    let words = |name: &str, element, width, count, values: Vec<u32>| Pending {
+        name: name.into(),
+        element,
+        component: Component::U32,
+        width,
+        count,
+        bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+    };
+    let edge_words = mesh
+        .edges
+        .iter()
+        .flat_map(|(_, e)| {
+            [
+                lookup(&edges, e.twin_index),
+                lookup(&edges, e.next_index),
+                lookup(&edges, e.prev_index),
+                lookup(&faces, e.face_index),
+                lookup(&vertices, e.vertex_index),
+            ]
+        })
+        .collect();
+    sections.push(words(EDGES, ElementKind::Edge, 5, edges.len(), edge_words));
+    let vertex_words = mesh
+        .vertices
+        .iter()
+        .flat_map(|(_, v)| [lookup(&edges, v.edge_index), lookup(&points, v.point_index)])
+        .collect();
+    sections.push(words(
+        VERTICES,
+        ElementKind::Vertex,
+        2,
+        vertices.len(),
+        vertex_words,
+    ));
+    let face_words = mesh
+        .faces
+        .iter()
+        .map(|(_, f)| lookup(&edges, f.edge_index))
+        .collect();
+    sections.push(words(FACES, ElementKind::Face, 1, faces.len(), face_words));
+
+    fn attribute<K>(
+        name: &str,
+        element: ElementKind,
+        channel: &dyn PackedChannel<K>,
+        handles: impl Iterator<Item = Handle<K>>,
+    ) -> Pending {
+        let mut values = Vec::new();
+        let mut count = 0;
+        for handle in handles {
+            channel.write(handle, &mut values);
+            count += 1;
+        }
+        Pending {
+            name: name.into(),
+            element,
+            component: Component::F32,
+            width: channel.width(),
+            count,
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+    for (name, channel) in &channels.vertices {
+        let handles = mesh.vertices.iter().map(|(h, _)| h);
+        sections.push(attribute(name, ElementKind::Vertex, *channel, handles));
+    }
+    for (name, channel) in &channels.faces {
+        let handles = mesh.faces.iter().map(|(h, _)| h);
+        sections.push(attribute(name, ElementKind::Face, *channel, handles));
+    }
+    for (name, channel) in &channels.edges {
+        let handles = mesh.edges.iter().map(|(h, _)| h);
+        sections.push(attribute(name, ElementKind::Edge, *channel, handles));
+    }
+
+    for section in &sections {
+        if section.name.len() > NAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Section name {:?} is too long", section.name),
+            ));
+        }
+    }
+
+    let align = |offset: usize| offset.div_ceil(ALIGNMENT) * ALIGNMENT;
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    let mut offset = align(HEADER_SIZE + ENTRY_SIZE * sections.len());
+    for section in &sections {
+        let mut name = [0u8; NAME_SIZE];
+        name[..section.name.len()].copy_from_slice(section.name.as_bytes());
+        header.extend_from_slice(&name);
+        for word in [
+            section.element.code(),
+            section.component.code(),
+            section.width as u32,
+            0,
+        ] {
+            header.extend_from_slice(&word.to_le_bytes());
+        }
+        header.extend_from_slice(&(offset as u64).to_le_bytes());
+        header.extend_from_slice(&(section.count as u64).to_le_bytes());
+        offset = align(offset + section.bytes.len());
+    }
+    header.resize(align(header.len()), 0);
+    writer.write_all(&header)?;
+    for section in &sections {
+        writer.write_all(&section.bytes)?;
+        let padding = align(section.bytes.len()) - section.bytes.len();
+        writer.write_all(&[0; ALIGNMENT][..padding])?;
+    }
+    Ok(())
+}
+
+/// A dense array of values inside a packed file.
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub element: ElementKind,
+    pub component: Component,
+    /// Number of values per element.
+    pub width: usize,
+    /// Number of elements.
+    pub count: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    fn word(&self, index: usize, size: usize) -> &'a [u8] {
+        &self.bytes[index * size..(index + 1) * size]
+    }
+
+    /// Value `component` of element `index`, converted to `f64`.
+    pub fn value(&self, index: usize, component: usize) -> f64 {
+        let at = index * self.width + component;
+        match self.component {
+            Component::F32 => f32::from_le_bytes(self.word(at, 4).try_into().unwrap()) as f64,
+            Component::F64 => f64::from_le_bytes(self.word(at, 8).try_into().unwrap()),
+            Component::U32 => u32::from_le_bytes(self.word(at, 4).try_into().unwrap()) as f64,
+        }
+    }
+
+    fn index(&self, index: usize, component: usize) -> Option<u32> {
+        let at = index * self.width + component;
+        let value = u32::from_le_bytes(self.word(at, 4).try_into().unwrap());
+        (value != MISSING).then_some(value)
+    }
+}
+
+/// Connectivity of a half-edge, as indices into the packed arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedEdge {
+    pub twin: Option<u32>,
+    pub next: Option<u32>,
+    pub prev: Option<u32>,
+    pub face: Option<u32>,
+    pub vertex: Option<u32>,
+}
+
+/// A packed file read in place.
+#[derive(Debug, Clone)]
+pub struct PackedMesh<'a> {
+    sections: Vec<Section<'a>>,
+    positions: Section<'a>,
+    edges: Section<'a>,
+    vertices: Section<'a>,
+    faces: Section<'a>,
+}
+
+impl<'a> PackedMesh<'a> {
+    /// Checks the header, the table of contents and that every index in the
+    /// connectivity sections refers to an element of the file. This reads
+    /// all of the connectivity once, positions and attribute channels are
+    /// only touched when values are read.
+    pub fn parse(bytes: &'a [u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+            return Err(invalid_data("Not a packed hedge mesh."));
+        }
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let long = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        if word(8) != VERSION {
+            return Err(invalid_data(format!("Unsupported version {}", word(8))));
+        }
+        let count = word(12) as usize;
+        if bytes.len() < HEADER_SIZE + count * ENTRY_SIZE {
+            return Err(invalid_data("Truncated table of contents."));
+        }
+
+        let mut sections = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = HEADER_SIZE + i * ENTRY_SIZE;
+            let name = &bytes[entry..entry + NAME_SIZE];
+            let name = std::str::from_utf8(
+                &name[..name.iter().position(|b| *b == 0).unwrap_or(NAME_SIZE)],
+            )
+            .map_err(|_| invalid_data("Section names have to be UTF-8."))?;
+            let at = entry + NAME_SIZE;
+            let element = ElementKind::from_code(word(at))
+                .ok_or_else(|| invalid_data(format!("Unknown element kind in {:?}", name)))?;
+            let component = Component::from_code(word(at + 4))
+                .ok_or_else(|| invalid_data(format!("Unknown component type in {:?}", name)))?;
+            let width = word(at + 8) as usize;
+            let (offset, elements) = (long(at + 16) as usize, long(at + 24) as usize);
+            let size = elements
+                .checked_mul(width)
+                .and_then(|n| n.checked_mul(component.size()))
+                .ok_or_else(|| invalid_data(format!("Section {:?} is too large", name)))?;
+            let section = offset
+                .checked_add(size)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| invalid_data(format!("Section {:?} is out of bounds", name)))?;
+            sections.push(Section {
+                name,
+                element,
+                component,
+                width,
+                count: elements,
+                bytes: section,
+            });
+        }
+
+        let find = |name: &str, width: usize| {
+            sections
+                .iter()
+                .find(|s| s.name == name && s.width == width)
+                .copied()
+                .ok_or_else(|| invalid_data(format!("Missing section {:?}", name)))
+        };
+        let (positions, edges) = (find(POSITIONS, 3)?, find(EDGES, 5)?);
+        let (vertices, faces) = (find(VERTICES, 2)?, find(FACES, 1)?);
+        if edges.component != Component::U32
+            || vertices.component != Component::U32
+            || faces.component != Component::U32
+            || positions.component == Component::U32
+        {
+            return Err(invalid_data("Unexpected component types."));
+        }
+        let in_range = |section: &Section, column: usize, limit: usize| {
+            (0..section.count).all(|i| {
+                section
+                    .index(i, column)
+                    .is_none_or(|v| (v as usize) < limit)
+            })
+        };
+        let valid = (0..3).all(|c| in_range(&edges, c, edges.count))
+            && in_range(&edges, 3, faces.count)
+            && in_range(&edges, 4, vertices.count)
+            && in_range(&vertices, 0, edges.count)
+            && in_range(&vertices, 1, positions.count)
+            && in_range(&faces, 0, edges.count);
+        if !valid {
+            return Err(invalid_data("Connectivity refers to missing elements."));
+        }
+        Ok(PackedMesh {
+            sections,
+            positions,
+            edges,
+            vertices,
+            faces,
+        })
+    }
+
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// An attribute channel or any other section by name.
+    pub fn section(&self, name: &str) -> Option<Section<'a>> {
+        self.sections.iter().find(|s| s.name == name).copied()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.positions.count
+    }
+
+    pub fn position(&self, point: usize) -> [f64; 3] {
+        [0, 1, 2].map(|c| self.positions.value(point, c))
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.count
+    }
+
+    pub fn edge(&self, edge: usize) -> PackedEdge {
+        let column = |c| self.edges.index(edge, c);
+        PackedEdge {
+            twin: column(0),
+            next: column(1),
+            prev: column(2),
+            face: column(3),
+            vertex: column(4),
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.count
+    }
+
+    /// The outgoing edge and point of a vertex.
+    pub fn vertex(&self, vertex: usize) -> (Option<u32>, Option<u32>) {
+        (
+            self.vertices.index(vertex, 0),
+            self.vertices.index(vertex, 1),
+        )
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.count
+    }
+
+    /// The first edge of a face.
+    pub fn face(&self, face: usize) -> Option<u32> {
+        self.faces.index(face, 0)
+    }
+
+    /// Copies the contents into a mesh which can be edited.
+    pub fn to_mesh<S: Scalar>(&self) -> Mesh<S> {
+        let mut mesh = Mesh::new();
+        let points: Vec<PointIndex> = (0..self.point_count())
+            .map(|p| mesh.add_element(Point::from_position(scalar::from_f64(self.position(p)))))
+            .collect();
+        let vertices: Vec<VertexIndex> = (0..self.vertex_count())
+            .map(|_| mesh.add_element(Vertex::default()))
+            .collect();
+        let faces: Vec<FaceIndex> = (0..self.face_count())
+            .map(|_| mesh.add_element(Face::default()))
+            .collect();
+        let edges: Vec<EdgeIndex> = (0..self.edge_count())
+            .map(|_| mesh.add_element(Edge::default()))
+            .collect();
+        fn resolve<K: Default>(handles: &[Handle<K>], index: Option<u32>) -> Handle<K> {
+            index.map(|i| handles[i as usize]).unwrap_or_default()
+        }
+        for (i, handle) in edges.iter().enumerate() {
+            let packed = self.edge(i);
+            mesh.edges[*handle] = Edge {
+                twin_index: resolve(&edges, packed.twin),
+                next_index: resolve(&edges, packed.next),
+                prev_index: resolve(&edges, packed.prev),
+                face_index: resolve(&faces, packed.face),
+                vertex_index: resolve(&vertices, packed.vertex),
+            };
+        }
+        for (i, handle) in vertices.iter().enumerate() {
+            let (edge, point) = self.vertex(i);
+            mesh.vertices[*handle] = Vertex::new(resolve(&edges, edge), resolve(&points, point));
+        }
+        for (i, handle) in faces.iter().enumerate() {
+            mesh.faces[*handle] = Face::new(resolve(&edges, self.face(i)));
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_files_are_read_in_place() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(3, 2);
+        // Leave a gap in the buffers so file order differs from handles.
+        let first = mesh.faces().next().unwrap().index;
+        ops::remove_face(&mut mesh, first).unwrap();
+
+        let mut heights: Channel<Vertex, f32> = Channel::new();
+        for vertex in mesh.vertices() {
+            heights.set(vertex.index, vertex.position().unwrap()[0]);
+        }
+        let missing = mesh.vertices().next().unwrap().index;
+        heights.remove(missing);
+        let channels = PackedChannels {
+            vertices: vec![("height", &heights)],
+            ..PackedChannels::default()
+        };
+        let mut bytes = Vec::new();
+        write_with(&mut bytes, &mesh, &channels).unwrap();
+        assert_eq!(bytes.len() % ALIGNMENT, 0);
+
+        let packed = PackedMesh::parse(&bytes).unwrap();
+        assert_eq!(packed.face_count(), 5);
+        assert_eq!(packed.vertex_count(), mesh.vertex_count());
+        assert_eq!(packed.point_count(), mesh.point_count());
+        assert_eq!(packed.edge_count(), mesh.edge_count());
+        let boundary = (0..packed.edge_count())
+            .filter(|e| packed.edge(*e).face.is_none())
+            .count();
+        assert_eq!(
+            boundary,
+            mesh.edges().filter(|e| !e.face().is_valid()).count()
+        );
+
+        let height = packed.section("height").unwrap();
+        assert_eq!(height.element, ElementKind::Vertex);
+        assert!(height.value(0, 0).is_nan());
+        for v in 1..packed.vertex_count() {
+            let point = packed.vertex(v).1.unwrap() as usize;
+            assert_eq!(height.value(v, 0), packed.position(point)[0]);
+        }
+
+        let copy: Mesh = packed.to_mesh();
+        builder::assert_connectivity(&copy);
+        assert_eq!(copy.face_count(), 5);
+        let corners = |m: &Mesh| -> Vec<Vec<Position>> {
+            m.faces()
+                .map(|f| f.vertices().filter_map(|v| v.position()).collect())
+                .collect()
+        };
+        assert_eq!(corners(&copy), corners(&mesh));
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let mesh: Mesh<f64> = builder::cube();
+        let mut bytes = Vec::new();
+        write(&mut bytes, &mesh).unwrap();
+        let packed = PackedMesh::parse(&bytes).unwrap();
+        assert_eq!(
+            packed.section("positions").unwrap().component,
+            Component::F64
+        );
+
+        assert!(PackedMesh::parse(&bytes[..bytes.len() - 16]).is_err());
+        let mut wrong = bytes.clone();
+        wrong[0] = b'X';
+        assert!(PackedMesh::parse(&wrong).is_err());
+        // Point the first edge's twin past the end of the edges.
+        let edges = packed.section("edges").unwrap();
+        let offset = edges.bytes.as_ptr() as usize - bytes.as_ptr() as usize;
+        let mut broken = bytes.clone();
+        broken[offset..offset + 4].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(PackedMesh::parse(&broken).is_err());
+    }
+}
@@ -0,0 +1,610 @@
+//! Stanford `.ply` import and export.
+//!
+//! ASCII and binary little endian files can be read and written. Vertices
+//! become points, with their normals and colors kept per point when the
+//! file has them, and faces are read from the `vertex_indices` list.
+//! Unknown elements and properties are skipped.
+
+use super::invalid_data;
+use super::merge::{self, MergePolicy, MergeStats};
+use crate::attributes::Channel;
+use crate::*;
+use std::io::{self, BufRead, Write};
+
+/// An 8-bit RGB vertex color.
+pub type Color = [u8; 3];
+
+/// How the body of a file is encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// A mesh along with the per-point attributes of its `.ply` vertices.
+#[derive(Debug, Clone, Default)]
+pub struct PlyMesh<S: Scalar = f32> {
+    pub mesh: Mesh<S>,
+    pub normals: Option<Channel<Point, Normal>>,
+    pub colors: Option<Channel<Point, Color>>,
+}
+
+impl<S: Scalar> From<Mesh<S>> for PlyMesh<S> {
+    fn from(mesh: Mesh<S>) -> Self {
+        PlyMesh {
+            mesh,
+            normals: None,
+            colors: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(name: &str) -> io::Result<Type> {
+        Ok(match name {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            _ => return Err(invalid_data(format!("Unknown property type {:?}", name))),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::I8 => "char",
+            Type::U8 => "uchar",
+            Type::I16 => "short",
+            Type::U16 => "ushort",
+            Type::I32 => "int",
+            Type::U32 => "uint",
+            Type::F32 => "float",
+            Type::F64 => "double",
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(Type),
+    /// The type of the length and the type of the items.
+    List(Type, Type),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+impl Element {
+    fn find(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|(n, _)| n == name)
+    }
+}
+
+fn parse_header<R: BufRead>(reader: &mut R) -> io::Result<(Format, Vec<Element>)> {
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> io::Result<()> {
+        line.clear();
+        if reader.read_line(line)? == 0 {
+            return Err(invalid_data("Unterminated .ply header."));
+        }
+        Ok(())
+    };
+    next_line(&mut line)?;
+    if line.trim() != "ply" {
+        return Err(invalid_data("Not a .ply file."));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        next_line(&mut line)?;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            Some("format") => {
+                format = Some(match tokens.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    other => {
+                        return Err(invalid_data(format!("Unsupported .ply format {:?}", other)))
+                    }
+                });
+            }
+            Some("element") => {
+                let name = tokens.next().unwrap_or_default().to_string();
+                let count = tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| invalid_data(format!("Bad element count in {:?}", line)))?;
+                elements.push(Element {
+                    name,
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_data("Property outside of an element."))?;
+                let tokens: Vec<&str> = tokens.collect();
+                let (property, name) = match tokens.as_slice() {
+                    ["list", length, item, name] => (
+                        Property::List(Type::parse(length)?, Type::parse(item)?),
+                        name,
+                    ),
+                    [kind, name] => (Property::Scalar(Type::parse(kind)?), name),
+                    _ => return Err(invalid_data(format!("Bad property {:?}", line.trim()))),
+                };
+                element.properties.push((name.to_string(), property));
+            }
+            Some(other) => {
+                return Err(invalid_data(format!(
+                    "Unknown .ply header keyword {:?}",
+                    other
+                )))
+            }
+        }
+    }
+    let format = format.ok_or_else(|| invalid_data("Missing .ply format."))?;
+    Ok((format, elements))
+}
+
+/// Values of the body, read one at a time in the order of the header.
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn value(&mut self, kind: Type) -> io::Result<f64> {
+        match self {
+            Body::Ascii(tokens) => tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| invalid_data("Truncated or malformed .ply body.")),
+            Body::Binary(bytes) => {
+                if bytes.len() < kind.size() {
+                    return Err(invalid_data("Truncated .ply body."));
+                }
+                let (value, rest) = bytes.split_at(kind.size());
+                *bytes = rest;
+                Ok(match kind {
+                    Type::I8 => value[0] as i8 as f64,
+                    Type::U8 => value[0] as f64,
+                    Type::I16 => i16::from_le_bytes([value[0], value[1]]) as f64,
+                    Type::U16 => u16::from_le_bytes([value[0], value[1]]) as f64,
+                    Type::I32 => i32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Type::U32 => u32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Type::F32 => f32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    Type::F64 => f64::from_le_bytes(value.try_into().unwrap()),
+                })
+            }
+        }
+    }
+
+    /// Every property of one record, lists as their items.
+    fn record(&mut self, element: &Element) -> io::Result<Vec<Vec<f64>>> {
+        element
+            .properties
+            .iter()
+            .map(|(_, property)| match property {
+                Property::Scalar(kind) => Ok(vec![self.value(*kind)?]),
+                Property::List(length, item) => {
+                    let length = self.value(*length)?;
+                    if length < 0.0 {
+                        return Err(invalid_data("Negative .ply list length."));
+                    }
+                    (0..length as usize).map(|_| self.value(*item)).collect()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads a `.ply` file, keeping every vertex as a separate point.
+pub fn read<S: Scalar, R: BufRead>(reader: R) -> io::Result<PlyMesh<S>> {
+    read_with(reader, MergePolicy::Never).map(|(ply, _)| ply)
+}
+
+/// Reads a `.ply` file, merging duplicate vertices according to `policy`.
+/// Merged points keep the normal and color of the first of them.
+pub fn read_with<S: Scalar, R: BufRead>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(PlyMesh<S>, MergeStats)> {
+    let (format, elements) = parse_header(&mut reader)?;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut body = match format {
+        Format::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes)
+                .map_err(invalid_data)?
+                .split_ascii_whitespace(),
+        ),
+        Format::BinaryLittleEndian => Body::Binary(&bytes),
+    };
+
+    let mut mesh = Mesh::new();
+    let mut points = Vec::new();
+    let mut normals = None;
+    let mut colors = None;
+    let mut corners: Vec<Vec<usize>> = Vec::new();
+    for element in &elements {
+        let find_all = |names: [&str; 3]| -> Option<[usize; 3]> {
+            let [a, b, c] = names.map(|name| element.find(name));
+            Some([a?, b?, c?])
+        };
+        match element.name.as_str() {
+            "vertex" => {
+                let position = find_all(["x", "y", "z"])
+                    .ok_or_else(|| invalid_data("Vertices without positions."))?;
+                let normal = find_all(["nx", "ny", "nz"]);
+                let color = find_all(["red", "green", "blue"]);
+                let color_is_float = color.map(|c| match &element.properties[c[0]].1 {
+                    Property::Scalar(kind) => kind.is_float(),
+                    Property::List(..) => false,
+                });
+                let mut normal_values = Vec::new();
+                let mut color_values = Vec::new();
+                for _ in 0..element.count {
+                    let record = body.record(element)?;
+                    let scalar = |i: usize| record[i].first().copied().unwrap_or_default();
+                    let point = mesh
+                        .add_element(Point::from_position(scalar::from_f64(position.map(scalar))));
+                    points.push(point);
+                    if let Some(normal) = normal {
+                        normal_values.push((point, normal.map(|i| scalar(i) as f32)));
+                    }
+                    if let (Some(color), Some(float)) = (color, color_is_float) {
+                        let channel = |i: usize| {
+                            let value = if float { scalar(i) * 255.0 } else { scalar(i) };
+                            value.round().clamp(0.0, 255.0) as u8
+                        };
+                        color_values.push((point, color.map(channel)));
+                    }
+                }
+                normals = normal.map(|_| normal_values);
+                colors = color.map(|_| color_values);
+            }
+            "face" => {
+                let indices = element
+                    .find("vertex_indices")
+                    .or_else(|| element.find("vertex_index"))
+                    .ok_or_else(|| invalid_data("Faces without vertex indices."))?;
+                for _ in 0..element.count {
+                    let record = body.record(element)?;
+                    corners.push(record[indices].iter().map(|i| *i as usize).collect());
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    body.record(element)?;
+                }
+            }
+        }
+    }
+
+    let mut polygons = corners
+        .iter()
+        .map(|corners| {
+            corners
+                .iter()
+                .map(|i| {
+                    points
+                        .get(*i)
+                        .copied()
+                        .ok_or_else(|| invalid_data(format!("Face refers to missing vertex {}", i)))
+                })
+                .collect::<io::Result<Vec<PointIndex>>>()
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let stats = merge::merge_points(&mut mesh, &mut polygons, policy);
+    builder::add_polygons(&mut mesh, &polygons);
+
+    let normals = normals.map(|values| live_channel(&mesh, values));
+    let colors = colors.map(|values| live_channel(&mesh, values));
+    Ok((
+        PlyMesh {
+            mesh,
+            normals,
+            colors,
+        },
+        stats,
+    ))
+}
+
+/// The values of the points merging left in the mesh.
+fn live_channel<S: Scalar, T>(mesh: &Mesh<S>, values: Vec<(PointIndex, T)>) -> Channel<Point, T> {
+    let mut channel = Channel::new();
+    for (point, value) in values {
+        if mesh.get_element(point).is_some() {
+            channel.set(point, value);
+        }
+    }
+    channel
+}
+
+/// The vertex normals of a mesh moved onto their points, when it has any.
+fn point_normals<S: Scalar>(mesh: &Mesh<S>) -> Option<Channel<Point, Normal>> {
+    if mesh.vertex_normals.is_empty() {
+        return None;
+    }
+    let mut normals = Channel::new();
+    for (vertex, normal) in mesh.vertex_normals.iter() {
+        if let Some(vertex) = mesh.vertices.get(vertex) {
+            if !normals.contains(vertex.point_index) {
+                normals.set(vertex.point_index, *normal);
+            }
+        }
+    }
+    Some(normals)
+}
+
+/// Writes values in the encoding of the body.
+struct Out<W> {
+    writer: W,
+    format: Format,
+    separate: bool,
+}
+
+impl<W: Write> Out<W> {
+    fn value(&mut self, kind: Type, value: f64) -> io::Result<()> {
+        match self.format {
+            Format::Ascii => {
+                if self.separate {
+                    write!(self.writer, " ")?;
+                }
+                self.separate = true;
+                match kind {
+                    Type::F32 => write!(self.writer, "{}", value as f32),
+                    Type::F64 => write!(self.writer, "{}", value),
+                    _ => write!(self.writer, "{}", value as i64),
+                }
+            }
+            Format::BinaryLittleEndian => match kind {
+                Type::U8 => self.writer.write_all(&[value as u8]),
+                Type::I32 => self.writer.write_all(&(value as i32).to_le_bytes()),
+                Type::F32 => self.writer.write_all(&(value as f32).to_le_bytes()),
+                Type::F64 => self.writer.write_all(&value.to_le_bytes()),
+                _ => unreachable!("only written types are encoded"),
+            },
+        }
+    }
+
+    fn end_record(&mut self) -> io::Result<()> {
+        self.separate = false;
+        match self.format {
+            Format::Ascii => writeln!(self.writer),
+            Format::BinaryLittleEndian => Ok(()),
+        }
+    }
+}
+
+/// Writes a `.ply` file with one vertex per point, numbered densely.
+///
+/// Positions are written as doubles for `f64` meshes and floats otherwise.
+/// Normals and colors are written when the channels are present, points
+/// missing from a channel get zeros. Without a normal channel each point
+/// gets the normal of one of its vertices, if the mesh has vertex normals.
+pub fn write<S: Scalar, W: Write>(
+    mut writer: W,
+    ply: &PlyMesh<S>,
+    format: Format,
+) -> io::Result<()> {
+    let mesh = &ply.mesh;
+    let stored = match ply.normals {
+        Some(_) => None,
+        None => point_normals(mesh),
+    };
+    let normals = ply.normals.as_ref().or(stored.as_ref());
+    let position = if std::mem::size_of::<S>() == 8 {
+        Type::F64
+    } else {
+        Type::F32
+    };
+    writeln!(writer, "ply")?;
+    match format {
+        Format::Ascii => writeln!(writer, "format ascii 1.0")?,
+        Format::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(writer, "element vertex {}", mesh.point_count())?;
+    for axis in ["x", "y", "z"] {
+        writeln!(writer, "property {} {}", position.name(), axis)?;
+    }
+    if normals.is_some() {
+        for axis in ["nx", "ny", "nz"] {
+            writeln!(writer, "property float {}", axis)?;
+        }
+    }
+    if ply.colors.is_some() {
+        for channel in ["red", "green", "blue"] {
+            writeln!(writer, "property uchar {}", channel)?;
+        }
+    }
+    writeln!(writer, "element face {}", mesh.face_count())?;
+    writeln!(writer, "property list uchar int vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    let mut out = Out {
+        writer,
+        format,
+        separate: false,
+    };
+    let mut dense = std::collections::HashMap::new();
+    for (i, (index, point)) in mesh.points.iter().enumerate() {
+        dense.insert(index, i);
+        for value in scalar::to_f64(point.position) {
+            out.value(position, value)?;
+        }
+        if let Some(normals) = normals {
+            for value in normals.get(index).copied().unwrap_or_default() {
+                out.value(Type::F32, value as f64)?;
+            }
+        }
+        if let Some(colors) = &ply.colors {
+            for value in colors.get(index).copied().unwrap_or_default() {
+                out.value(Type::U8, value as f64)?;
+            }
+        }
+        out.end_record()?;
+    }
+    for face in mesh.faces() {
+        let corners = face
+            .vertices()
+            .map(|v| {
+                v.element()
+                    .and_then(|v| dense.get(&v.point_index))
+                    .copied()
+                    .ok_or_else(|| {
+                        invalid_data(format!("Face {:?} has a dangling vertex.", face.index))
+                    })
+            })
+            .collect::<io::Result<Vec<usize>>>()?;
+        if corners.len() > u8::MAX as usize {
+            return Err(invalid_data(format!(
+                "Face {:?} has more than 255 corners.",
+                face.index
+            )));
+        }
+        out.value(Type::U8, corners.len() as f64)?;
+        for corner in corners {
+            out.value(Type::I32, corner as f64)?;
+        }
+        out.end_record()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_ascii_files_skipping_unknown_data() {
+        let _ = env_logger::try_init();
+        let text = "ply\nformat ascii 1.0\ncomment two triangles\n\
+                    element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                    property float confidence\nproperty uchar red\nproperty uchar green\n\
+                    property uchar blue\n\
+                    element face 2\nproperty list uchar int vertex_indices\n\
+                    element edge 1\nproperty int vertex1\nproperty int vertex2\n\
+                    end_header\n\
+                    0 0 0 0.5 255 0 0\n1 0 0 0.5 0 255 0\n1 1 0 0.5 0 0 255\n0 1 0 0.5 9 9 9\n\
+                    3 0 1 2\n3 0 2 3\n0 1\n";
+        let ply: PlyMesh = read(text.as_bytes()).unwrap();
+        builder::assert_connectivity(&ply.mesh);
+        assert_eq!(ply.mesh.point_count(), 4);
+        assert_eq!(ply.mesh.face_count(), 2);
+        assert!(ply.normals.is_none());
+        let colors = ply.colors.unwrap();
+        assert_eq!(colors.len(), 4);
+        let green = ply
+            .mesh
+            .points
+            .iter()
+            .find(|(_, p)| p.position == [1.0, 0.0, 0.0])
+            .unwrap()
+            .0;
+        assert_eq!(colors.get(green), Some(&[0, 255, 0]));
+
+        assert!(
+            read::<f32, _>("ply\nformat binary_big_endian 1.0\nend_header\n".as_bytes()).is_err()
+        );
+        assert!(read::<f32, _>("obj\n".as_bytes()).is_err());
+        let missing = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                       property float y\nproperty float z\nelement face 1\n\
+                       property list uchar int vertex_indices\nend_header\n0 0 0\n3 0 1 2\n";
+        assert!(read::<f32, _>(missing.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn both_formats_round_trip_attributes() {
+        let mesh: Mesh<f64> = builder::cube();
+        let mut normals = Channel::new();
+        let mut colors = Channel::new();
+        for (index, point) in mesh.points.iter() {
+            let p = point.position;
+            normals.set(index, [p[0] as f32, p[1] as f32, p[2] as f32]);
+            colors.set(index, [(p[0] * 200.0) as u8, 7, 0]);
+        }
+        let ply = PlyMesh {
+            mesh,
+            normals: Some(normals),
+            colors: Some(colors),
+        };
+        for format in [Format::Ascii, Format::BinaryLittleEndian] {
+            let mut bytes = Vec::new();
+            write(&mut bytes, &ply, format).unwrap();
+            let again: PlyMesh<f64> = read(bytes.as_slice()).unwrap();
+            builder::assert_connectivity(&again.mesh);
+            assert_eq!(again.mesh.face_count(), 6);
+            assert_eq!(again.mesh.point_count(), 8);
+            assert!(analysis::watertight_report(&again.mesh, 1.0e-9).is_closed());
+            let normals = again.normals.as_ref().unwrap();
+            let colors = again.colors.as_ref().unwrap();
+            for (index, point) in again.mesh.points.iter() {
+                let p = point.position;
+                assert_eq!(
+                    normals.get(index),
+                    Some(&[p[0] as f32, p[1] as f32, p[2] as f32])
+                );
+                assert_eq!(colors.get(index), Some(&[(p[0] * 200.0) as u8, 7, 0]));
+            }
+        }
+
+        // Unwelded corners of a triangle soup join up when merged.
+        let mut soup = Mesh::<f32>::new();
+        for p in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ] {
+            soup.add_element(Point::from_position(p));
+        }
+        let points: Vec<PointIndex> = soup.points.iter().map(|(index, _)| index).collect();
+        builder::add_polygons(&mut soup, &[points[..3].to_vec(), points[3..].to_vec()]);
+        let mut bytes = Vec::new();
+        write(&mut bytes, &PlyMesh::from(soup), Format::BinaryLittleEndian).unwrap();
+        let (merged, stats): (PlyMesh, _) =
+            read_with(bytes.as_slice(), MergePolicy::Exact).unwrap();
+        assert_eq!(stats.merged_points, 2);
+        assert_eq!(merged.mesh.edges().filter(|e| !e.is_boundary()).count(), 2);
+    }
+}
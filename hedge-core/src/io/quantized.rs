@@ -0,0 +1,744 @@
+//! A compact lossy format for shipping meshes over the network.
+//!
+//! Positions are snapped to a grid over their bounding box, normals are
+//! mapped onto an octahedron and snapped on its unfolded square, UVs are
+//! snapped to a grid over their bounds. Each stream of integers is stored
+//! as zigzag varints of the difference to the previous value and the bytes
+//! are compressed with an adaptive binary range coder, one model per
+//! stream.
+//!
+//! ```text
+//! header   magic "HEDGEQNT", version: u32,
+//!          position bits: u8, normal bits: u8, uv bits: u8, flags: u8,
+//!          points: u32, faces: u32, corners: u32,
+//!          position bounds: [f64; 6], uv bounds: [f64; 4] when flagged
+//! payload  face sizes, corner points, positions, normals, uvs
+//! ```
+//!
+//! Faces keep their corner order, so corner attributes come back on the
+//! same corners. Each point gets a single vertex when reading, vertices
+//! without faces are dropped. Corners without a normal are written as `+z`
+//! and corners without a UV as the lower corner of the UV bounds.
+
+use super::invalid_data;
+use crate::normals::NormalChannel;
+use crate::uv::UvChannel;
+use crate::*;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 8] = b"HEDGEQNT";
+const VERSION: u32 = 1;
+const HAS_NORMALS: u8 = 1;
+const HAS_UVS: u8 = 2;
+
+/// Bits per component kept by the quantization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quantization {
+    pub position_bits: u8,
+    pub normal_bits: u8,
+    pub uv_bits: u8,
+}
+
+impl Default for Quantization {
+    fn default() -> Self {
+        Quantization {
+            position_bits: 14,
+            normal_bits: 10,
+            uv_bits: 12,
+        }
+    }
+}
+
+/// Corner attributes to store along with a mesh.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CornerChannels<'a> {
+    pub normals: Option<&'a NormalChannel>,
+    pub uvs: Option<&'a UvChannel>,
+}
+
+/// A mesh read back along with the corner attributes that were stored.
+#[derive(Debug, Clone)]
+pub struct Decoded<S: Scalar = f32> {
+    pub mesh: Mesh<S>,
+    pub normals: Option<NormalChannel>,
+    pub uvs: Option<UvChannel>,
+}
+
+/// Writes a mesh with just its vertex normals at the default quantization.
+pub fn write<S: Scalar, W: Write>(writer: W, mesh: &Mesh<S>) -> io::Result<()> {
+    write_with(
+        writer,
+        mesh,
+        &CornerChannels::default(),
+        &Quantization::default(),
+    )
+}
+
+/// Writes a mesh along with corner attributes, using the vertex normals of
+/// the mesh when no normals are given.
+pub fn write_with<S: Scalar, W: Write>(
+    mut writer: W,
+    mesh: &Mesh<S>,
+    channels: &CornerChannels,
+    quantization: &Quantization,
+) -> io::Result<()> {
+    for bits in [
+        quantization.position_bits,
+        quantization.normal_bits,
+        quantization.uv_bits,
+    ] {
+        if !(2..=30).contains(&bits) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Quantization to {} bits is not supported", bits),
+            ));
+        }
+    }
+    let stored = match channels.normals {
+        Some(_) => None,
+        None => mesh.stored_corner_normals(),
+    };
+    let channels = &CornerChannels {
+        normals: channels.normals.or(stored.as_ref()),
+        ..*channels
+    };
+    let points: HashMap<PointIndex, u32> = mesh
+        .points
+        .iter()
+        .enumerate()
+        .map(|(i, (h, _))| (h, i as u32))
+        .collect();
+    let positions: Vec<[f64; 3]> = mesh
+        .points
+        .iter()
+        .map(|(_, p)| scalar::to_f64(p.position))
+        .collect();
+    let corners: Vec<EdgeIndex> = mesh
+        .faces()
+        .flat_map(|face| face.edges().map(|e| e.index))
+        .collect();
+    let position_bounds = bounds(&positions);
+    let uv_values: Vec<[f64; 2]> = match channels.uvs {
+        Some(uvs) => corners
+            .iter()
+            .map(|c| uvs.get(*c).copied().unwrap_or_default().map(f64::from))
+            .collect(),
+        None => Vec::new(),
+    };
+    let uv_bounds = bounds(&uv_values);
+
+    let mut flags = 0;
+    if channels.normals.is_some() {
+        flags |= HAS_NORMALS;
+    }
+    if channels.uvs.is_some() {
+        flags |= HAS_UVS;
+    }
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&[
+        quantization.position_bits,
+        quantization.normal_bits,
+        quantization.uv_bits,
+        flags,
+    ]);
+    for count in [positions.len(), mesh.face_count(), corners.len()] {
+        header.extend_from_slice(&(count as u32).to_le_bytes());
+    }
+    for value in position_bounds.iter().flatten() {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    if channels.uvs.is_some() {
+        for value in uv_bounds.iter().flatten() {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    let mut encoder = Encoder::default();
+    let mut models = Models::default();
+    let mut previous = 0;
+    for face in mesh.faces() {
+        let sides = face.edges().count() as i64;
+        encoder.varint(&mut models.topology, sides - previous);
+        previous = sides;
+    }
+    let mut previous = 0;
+    for corner in &corners {
+        let vertex = mesh.edge(*corner).vertex();
+        let point = vertex
+            .element()
+            .and_then(|v| points.get(&v.point_index))
+            .copied()
+            .unwrap_or_default() as i64;
+        encoder.varint(&mut models.topology, point - previous);
+        previous = point;
+    }
+    let position_steps = steps(quantization.position_bits);
+    encoder.deltas(
+        &mut models.positions,
+        positions
+            .iter()
+            .map(|p| snap(*p, &position_bounds, position_steps))
+            .collect(),
+    );
+    if let Some(normals) = channels.normals {
+        let steps = steps(quantization.normal_bits);
+        encoder.deltas(
+            &mut models.normals,
+            corners
+                .iter()
+                .map(|c| {
+                    let normal = normals
+                        .get(*c)
+                        .map_or([0.0, 0.0, 1.0], |n| n.map(f64::from));
+                    snap(octahedral(normal), &[[-1.0; 2], [1.0; 2]], steps)
+                })
+                .collect(),
+        );
+    }
+    if channels.uvs.is_some() {
+        let steps = steps(quantization.uv_bits);
+        encoder.deltas(
+            &mut models.uvs,
+            uv_values
+                .iter()
+                .map(|uv| snap(*uv, &uv_bounds, steps))
+                .collect(),
+        );
+    }
+    let payload = encoder.finish();
+    log::debug!(
+        "Quantized {} points and {} corners into {} bytes.",
+        positions.len(),
+        corners.len(),
+        header.len() + payload.len()
+    );
+    writer.write_all(&header)?;
+    writer.write_all(&payload)
+}
+
+/// Reads a mesh written by `write` or `write_with`.
+pub fn read<S: Scalar, R: Read>(mut reader: R) -> io::Result<Decoded<S>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < 28 || &bytes[..8] != MAGIC {
+        return Err(invalid_data("Not a quantized hedge mesh."));
+    }
+    let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    if word(8) != VERSION {
+        return Err(invalid_data(format!("Unsupported version {}", word(8))));
+    }
+    let [position_bits, normal_bits, uv_bits, flags] = [12, 13, 14, 15].map(|at| bytes[at]);
+    let [point_count, face_count, corner_count] = [16, 20, 24].map(|at| word(at) as usize);
+    let mut at = 28;
+    let mut float = || {
+        let value = bytes
+            .get(at..at + 8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()));
+        at += 8;
+        value.ok_or_else(|| invalid_data("Truncated header."))
+    };
+    let mut read_bounds = |dimensions: usize| -> io::Result<[Vec<f64>; 2]> {
+        let min = (0..dimensions)
+            .map(|_| float())
+            .collect::<io::Result<_>>()?;
+        let max = (0..dimensions)
+            .map(|_| float())
+            .collect::<io::Result<_>>()?;
+        Ok([min, max])
+    };
+    let position_bounds = read_bounds(3)?;
+    let uv_bounds = if flags & HAS_UVS != 0 {
+        Some(read_bounds(2)?)
+    } else {
+        None
+    };
+
+    let mut decoder = Decoder::new(&bytes[at..]);
+    let mut models = Models::default();
+    // The counts come from the header, so nothing is allocated up front.
+    // A corrupted count runs the decoder past the payload instead.
+    let mut sides = Vec::new();
+    let (mut previous, mut total) = (0i64, 0usize);
+    for _ in 0..face_count {
+        previous = previous.wrapping_add(decoder.varint(&mut models.topology));
+        if previous < 3 || previous as u64 > (corner_count - total) as u64 {
+            return Err(invalid_data("Face sizes don't add up to the corners."));
+        }
+        total += previous as usize;
+        sides.push(previous as usize);
+        decoder.check()?;
+    }
+    if total != corner_count {
+        return Err(invalid_data("Face sizes don't add up to the corners."));
+    }
+    let mut corners = Vec::new();
+    let mut previous = 0i64;
+    for _ in 0..corner_count {
+        previous = previous.wrapping_add(decoder.varint(&mut models.topology));
+        if previous < 0 || previous as u64 >= point_count as u64 {
+            return Err(invalid_data("Corner refers to a missing point."));
+        }
+        corners.push(previous as usize);
+        decoder.check()?;
+    }
+    let steps_of = |bits: u8| {
+        if (2..=30).contains(&bits) {
+            Ok(steps(bits))
+        } else {
+            Err(invalid_data(format!(
+                "Unsupported quantization to {} bits",
+                bits
+            )))
+        }
+    };
+    let position_steps = steps_of(position_bits)?;
+    let positions: Vec<[f64; 3]> = decoder
+        .deltas(&mut models.positions, point_count)?
+        .into_iter()
+        .map(|q| unsnap(q, &to_array(&position_bounds), position_steps))
+        .collect();
+    let normals = if flags & HAS_NORMALS != 0 {
+        let steps = steps_of(normal_bits)?;
+        let values: Vec<[i64; 2]> = decoder.deltas(&mut models.normals, corner_count)?;
+        Some(
+            values
+                .into_iter()
+                .map(|q| from_octahedral(unsnap(q, &[[-1.0; 2], [1.0; 2]], steps)))
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        None
+    };
+    let uvs = match uv_bounds {
+        Some(uv_bounds) => {
+            let steps = steps_of(uv_bits)?;
+            let values: Vec<[i64; 2]> = decoder.deltas(&mut models.uvs, corner_count)?;
+            Some(
+                values
+                    .into_iter()
+                    .map(|q| unsnap(q, &to_array(&uv_bounds), steps))
+                    .collect::<Vec<_>>(),
+            )
+        }
+        None => None,
+    };
+
+    let mut polygons = Vec::with_capacity(sides.len());
+    let mut start = 0;
+    for count in sides {
+        polygons.push(corners[start..start + count].to_vec());
+        start += count;
+    }
+    let mut mesh = Mesh::new();
+    let points: Vec<PointIndex> = positions
+        .iter()
+        .map(|p| mesh.add_element(Point::from_position(scalar::from_f64(*p))))
+        .collect();
+    let polygons: Vec<Vec<PointIndex>> = polygons
+        .iter()
+        .map(|polygon| polygon.iter().map(|i| points[*i]).collect())
+        .collect();
+    let faces = builder::add_polygons(&mut mesh, &polygons);
+    if faces.iter().any(|f| !f.is_valid()) {
+        return Err(invalid_data("Faces don't form a manifold mesh."));
+    }
+    let order: Vec<EdgeIndex> = faces
+        .iter()
+        .flat_map(|f| mesh.face(*f).edges().map(|e| e.index).collect::<Vec<_>>())
+        .collect();
+    let normals = normals.map(|values| {
+        let mut channel = NormalChannel::new();
+        for (corner, normal) in order.iter().zip(values) {
+            channel.set(*corner, normal.map(|c| c as f32));
+        }
+        channel
+    });
+    let uvs = uvs.map(|values| {
+        let mut channel = UvChannel::new();
+        for (corner, uv) in order.iter().zip(values) {
+            channel.set(*corner, uv.map(|c| c as f32));
+        }
+        channel
+    });
+    Ok(Decoded { mesh, normals, uvs })
+}
+
+/// Lower and upper corner of the box around the values.
+fn bounds<const N: usize>(values: &[[f64; N]]) -> [[f64; N]; 2] {
+    let mut bounds = [[f64::INFINITY; N], [f64::NEG_INFINITY; N]];
+    for value in values {
+        for k in 0..N {
+            bounds[0][k] = bounds[0][k].min(value[k]);
+            bounds[1][k] = bounds[1][k].max(value[k]);
+        }
+    }
+    if values.is_empty() {
+        [[0.0; N]; 2]
+    } else {
+        bounds
+    }
+}
+
+fn to_array<const N: usize>(bounds: &[Vec<f64>; 2]) -> [[f64; N]; 2] {
+    bounds
+        .clone()
+        .map(|b| std::array::from_fn(|k| b.get(k).copied().unwrap_or_default()))
+}
+
+fn steps(bits: u8) -> f64 {
+    ((1u64 << bits) - 1) as f64
+}
+
+fn snap<const N: usize>(value: [f64; N], bounds: &[[f64; N]; 2], steps: f64) -> [i64; N] {
+    std::array::from_fn(|k| {
+        let extent = bounds[1][k] - bounds[0][k];
+        if extent > 0.0 {
+            ((value[k] - bounds[0][k]) / extent * steps)
+                .round()
+                .clamp(0.0, steps) as i64
+        } else {
+            0
+        }
+    })
+}
+
+fn unsnap<const N: usize>(value: [i64; N], bounds: &[[f64; N]; 2], steps: f64) -> [f64; N] {
+    std::array::from_fn(|k| {
+        let extent = bounds[1][k] - bounds[0][k];
+        bounds[0][k] + value[k] as f64 / steps * extent
+    })
+}
+
+/// Maps a unit vector onto the square `[-1, 1]^2` by way of an octahedron.
+fn octahedral(normal: [f64; 3]) -> [f64; 2] {
+    let sum = normal[0].abs() + normal[1].abs() + normal[2].abs();
+    if sum == 0.0 {
+        return [0.0, 0.0];
+    }
+    let [x, y, z] = normal.map(|c| c / sum);
+    if z >= 0.0 {
+        [x, y]
+    } else {
+        let fold = |a: f64, b: f64| (1.0 - b.abs()) * if a >= 0.0 { 1.0 } else { -1.0 };
+        [fold(x, y), fold(y, x)]
+    }
+}
+
+fn from_octahedral([x, y]: [f64; 2]) -> [f64; 3] {
+    let z = 1.0 - x.abs() - y.abs();
+    let t = (-z).max(0.0);
+    let unfold = |a: f64| if a >= 0.0 { a - t } else { a + t };
+    math::normalize([unfold(x), unfold(y), z])
+}
+
+/// Adaptive probabilities for the bytes of one stream.
+struct Model {
+    probabilities: [u16; 256],
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model {
+            probabilities: [1 << (PROBABILITY_BITS - 1); 256],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Models {
+    topology: Model,
+    positions: Model,
+    normals: Model,
+    uvs: Model,
+}
+
+const PROBABILITY_BITS: u32 = 11;
+const ADAPTATION: u32 = 5;
+const TOP: u32 = 1 << 24;
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Range coder in the style of LZMA, carrying overflow into bytes it has
+/// already produced.
+struct Encoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    pending: u64,
+    out: Vec<u8>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            pending: 1,
+            out: Vec::new(),
+        }
+    }
+}
+
+impl Encoder {
+    fn bit(&mut self, probability: &mut u16, bit: bool) {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION;
+        } else {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPTATION;
+        }
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift();
+        }
+    }
+
+    fn shift(&mut self) {
+        if self.low < 0xFF00_0000 || self.low >> 32 != 0 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            while self.pending > 0 {
+                self.out.push(byte.wrapping_add(carry));
+                byte = 0xFF;
+                self.pending -= 1;
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.pending += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    fn byte(&mut self, model: &mut Model, byte: u8) {
+        let mut node = 1;
+        for i in (0..8).rev() {
+            let bit = (byte >> i) & 1 == 1;
+            self.bit(&mut model.probabilities[node], bit);
+            node = (node << 1) | bit as usize;
+        }
+    }
+
+    fn varint(&mut self, model: &mut Model, value: i64) {
+        let mut value = zigzag(value);
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.byte(model, low);
+                return;
+            }
+            self.byte(model, low | 0x80);
+        }
+    }
+
+    fn deltas<const N: usize>(&mut self, model: &mut Model, values: Vec<[i64; N]>) {
+        let mut previous = [0; N];
+        for value in values {
+            for k in 0..N {
+                self.varint(model, value[k] - previous[k]);
+            }
+            previous = value;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift();
+        }
+        self.out
+    }
+}
+
+struct Decoder<'a> {
+    code: u32,
+    range: u32,
+    bytes: std::slice::Iter<'a, u8>,
+    overrun: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        let mut decoder = Decoder {
+            code: 0,
+            range: u32::MAX,
+            bytes: bytes.iter(),
+            overrun: 0,
+        };
+        for _ in 0..5 {
+            decoder.code = (decoder.code << 8) | decoder.next() as u32;
+        }
+        decoder
+    }
+
+    /// Reading past the end gives zeros, callers check what they decode.
+    fn next(&mut self) -> u8 {
+        self.bytes.next().copied().unwrap_or_else(|| {
+            self.overrun += 1;
+            0
+        })
+    }
+
+    /// Fails once the decoder ran past the payload, which a valid stream
+    /// never does since the encoder flushes every byte the decoder needs.
+    fn check(&self) -> io::Result<()> {
+        if self.overrun > 0 {
+            return Err(invalid_data("Truncated payload."));
+        }
+        Ok(())
+    }
+
+    fn bit(&mut self, probability: &mut u16) -> bool {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+        let bit = self.code >= bound;
+        if bit {
+            self.code -= bound;
+            self.range -= bound;
+            *probability -= *probability >> ADAPTATION;
+        } else {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPTATION;
+        }
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next() as u32;
+        }
+        bit
+    }
+
+    fn byte(&mut self, model: &mut Model) -> u8 {
+        let mut node = 1;
+        for _ in 0..8 {
+            node = (node << 1) | self.bit(&mut model.probabilities[node]) as usize;
+        }
+        (node & 0xFF) as u8
+    }
+
+    fn varint(&mut self, model: &mut Model) -> i64 {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte(model);
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        unzigzag(value)
+    }
+
+    fn deltas<const N: usize>(
+        &mut self,
+        model: &mut Model,
+        count: usize,
+    ) -> io::Result<Vec<[i64; N]>> {
+        let mut previous = [0i64; N];
+        let mut values = Vec::new();
+        for _ in 0..count {
+            for value in previous.iter_mut() {
+                *value = value.wrapping_add(self.varint(model));
+            }
+            values.push(previous);
+            self.check()?;
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantized_meshes_round_trip() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh<f64> = testing::random_terrain(11);
+        let normals = mesh.compute_auto_smooth_normals(0.5);
+        let uvs = uv::unwrap_lscm(&mesh, &[]);
+        let channels = CornerChannels {
+            normals: Some(&normals),
+            uvs: Some(&uvs),
+        };
+        let mut bytes = Vec::new();
+        write_with(&mut bytes, &mesh, &channels, &Quantization::default()).unwrap();
+        let mut packed = Vec::new();
+        crate::io::packed::write(&mut packed, &mesh).unwrap();
+        assert!(bytes.len() * 4 < packed.len());
+
+        let decoded: Decoded<f64> = read(bytes.as_slice()).unwrap();
+        builder::assert_connectivity(&decoded.mesh);
+        assert!(decoded.mesh.topologically_equals(&mesh));
+        // Half a grid step of the largest extent.
+        let [min, max] = bounds(
+            &mesh
+                .points
+                .iter()
+                .map(|(_, p)| p.position)
+                .collect::<Vec<_>>(),
+        );
+        let extent = (0..3).map(|k| max[k] - min[k]).fold(0.0, f64::max);
+        let step = extent / steps(14);
+        assert!(decoded.mesh.geometrically_equals(&mesh, step));
+
+        let corners = |m: &Mesh<f64>| -> Vec<EdgeIndex> {
+            m.faces()
+                .flat_map(|f| f.edges().map(|e| e.index).collect::<Vec<_>>())
+                .collect()
+        };
+        let (decoded_normals, decoded_uvs) = (decoded.normals.unwrap(), decoded.uvs.unwrap());
+        let [low, high] = bounds(
+            &uvs.iter()
+                .map(|(_, uv)| uv.map(f64::from))
+                .collect::<Vec<_>>(),
+        );
+        let uv_step = (0..2).map(|k| high[k] - low[k]).fold(0.0, f64::max) / steps(12);
+        for (a, b) in corners(&mesh).into_iter().zip(corners(&decoded.mesh)) {
+            let (n, m) = (normals.get(a).unwrap(), decoded_normals.get(b).unwrap());
+            let dot: f32 = (0..3).map(|k| n[k] * m[k]).sum();
+            assert!(dot > 0.999);
+            let (u, v) = (uvs.get(a).unwrap(), decoded_uvs.get(b).unwrap());
+            assert!((0..2).all(|k| ((u[k] - v[k]).abs() as f64) < uv_step));
+        }
+    }
+
+    #[test]
+    fn octahedral_mapping_is_invertible() {
+        for normal in [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+            [-0.48, 0.6, -0.64],
+        ] {
+            let back = from_octahedral(octahedral(normal));
+            assert!(math::distance(back, normal) < 1.0e-12);
+        }
+        assert!(read::<f32, _>(&b"HEDGEQNT\x02"[..]).is_err());
+    }
+
+    #[test]
+    fn corrupted_counts_are_errors() {
+        let mesh: Mesh = builder::grid(2, 2);
+        let mut bytes = Vec::new();
+        write(&mut bytes, &mesh).unwrap();
+        for at in [16, 20, 24] {
+            for count in [u32::MAX, 1 << 28, 10] {
+                let mut corrupted = bytes.clone();
+                corrupted[at..at + 4].copy_from_slice(&count.to_le_bytes());
+                assert!(read::<f32, _>(corrupted.as_slice()).is_err());
+            }
+        }
+        let truncated = &bytes[..bytes.len() - 6];
+        assert!(read::<f32, _>(truncated).is_err());
+        assert!(read::<f32, _>(bytes.as_slice()).is_ok());
+    }
+}
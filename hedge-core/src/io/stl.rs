@@ -0,0 +1,224 @@
+//! STL import and export.
+//!
+//! Both binary and ASCII files are read, telling them apart by whether the
+//! size matches the triangle count of a binary header. STL stores every
+//! triangle with corners of its own, so reading merges coincident corners
+//! to recover the shared edges. Facet normals are ignored when reading and
+//! recomputed when writing.
+
+use super::invalid_data;
+use super::merge::{self, MergePolicy, MergeStats};
+use crate::*;
+use std::io::{self, Read, Write};
+
+/// How a file is encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Binary,
+    Ascii,
+}
+
+const HEADER: usize = 80;
+const TRIANGLE: usize = 50;
+
+/// Reads an STL file, merging corners at bit for bit identical positions.
+pub fn read<S: Scalar, R: Read>(reader: R) -> io::Result<Mesh<S>> {
+    read_with(reader, MergePolicy::Exact).map(|(mesh, _)| mesh)
+}
+
+/// Reads an STL file, merging corners according to `policy`.
+///
+/// Exporters often write corners with slightly different rounding, welding
+/// within a tolerance joins those as well. With `MergePolicy::Never` every
+/// triangle stays on its own.
+pub fn read_with<S: Scalar, R: Read>(
+    mut reader: R,
+    policy: MergePolicy,
+) -> io::Result<(Mesh<S>, MergeStats)> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let triangles = if is_binary(&bytes) {
+        read_binary(&bytes)
+    } else {
+        let text = std::str::from_utf8(&bytes).map_err(invalid_data)?;
+        read_ascii(text)?
+    };
+
+    let mut mesh = Mesh::new();
+    let mut polygons: Vec<Vec<PointIndex>> = triangles
+        .iter()
+        .map(|corners| {
+            corners
+                .iter()
+                .map(|p| mesh.add_element(Point::from_position(scalar::from_f64(*p))))
+                .collect()
+        })
+        .collect();
+    let stats = merge::merge_points(&mut mesh, &mut polygons, policy);
+    builder::add_polygons(&mut mesh, &polygons);
+    log::debug!(
+        "Read {} triangles onto {} points.",
+        triangles.len(),
+        mesh.point_count()
+    );
+    Ok((mesh, stats))
+}
+
+/// Binary files have exactly the size their triangle count calls for, ASCII
+/// files can't as their text would have to spell out that count.
+fn is_binary(bytes: &[u8]) -> bool {
+    match bytes.get(HEADER..HEADER + 4) {
+        Some(count) => {
+            let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+            bytes.len() == HEADER + 4 + count * TRIANGLE
+        }
+        None => false,
+    }
+}
+
+fn read_binary(bytes: &[u8]) -> Vec<[[f64; 3]; 3]> {
+    let float = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as f64;
+    bytes[HEADER + 4..]
+        .chunks_exact(TRIANGLE)
+        .enumerate()
+        .map(|(i, _)| {
+            // Corners follow the 12 bytes of the facet normal.
+            let start = HEADER + 4 + i * TRIANGLE + 12;
+            [0, 1, 2].map(|corner| {
+                let at = start + corner * 12;
+                [float(at), float(at + 4), float(at + 8)]
+            })
+        })
+        .collect()
+}
+
+fn read_ascii(text: &str) -> io::Result<Vec<[[f64; 3]; 3]>> {
+    let mut tokens = text.split_whitespace();
+    if tokens.next() != Some("solid") {
+        return Err(invalid_data("Not an STL file."));
+    }
+    let mut triangles = Vec::new();
+    let mut corners = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "vertex" => {
+                let mut corner = [0.0; 3];
+                for value in &mut corner {
+                    *value = tokens
+                        .next()
+                        .and_then(|t| t.parse().ok())
+                        .ok_or_else(|| invalid_data("Malformed STL vertex."))?;
+                }
+                corners.push(corner);
+            }
+            "endfacet" => {
+                if corners.len() != 3 {
+                    return Err(invalid_data(format!(
+                        "STL facet with {} corners",
+                        corners.len()
+                    )));
+                }
+                triangles.push([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+/// Writes every face as triangles of an STL file, splitting larger
+/// polygons into fans. Each triangle gets the normal of its face.
+pub fn write<S: Scalar, W: Write>(mut writer: W, mesh: &Mesh<S>, format: Format) -> io::Result<()> {
+    let mut triangles = Vec::new();
+    for face in mesh.faces() {
+        let corners: Vec<[f64; 3]> = face
+            .vertices()
+            .map(|v| mesh.vertex_position(v.index))
+            .collect();
+        let normal = mesh.face_normal(face.index);
+        for i in 1..corners.len().saturating_sub(1) {
+            triangles.push((normal, [corners[0], corners[i], corners[i + 1]]));
+        }
+    }
+
+    match format {
+        Format::Binary => {
+            let mut header = [0u8; HEADER];
+            let name = b"hedge";
+            header[..name.len()].copy_from_slice(name);
+            writer.write_all(&header)?;
+            let count = u32::try_from(triangles.len())
+                .map_err(|_| invalid_data("Too many triangles for STL."))?;
+            writer.write_all(&count.to_le_bytes())?;
+            for (normal, corners) in &triangles {
+                for value in std::iter::once(normal).chain(corners).flatten() {
+                    writer.write_all(&(*value as f32).to_le_bytes())?;
+                }
+                writer.write_all(&[0, 0])?;
+            }
+        }
+        Format::Ascii => {
+            writeln!(writer, "solid hedge")?;
+            for ([nx, ny, nz], corners) in &triangles {
+                writeln!(writer, "facet normal {} {} {}", nx, ny, nz)?;
+                writeln!(writer, "outer loop")?;
+                for [x, y, z] in corners {
+                    writeln!(writer, "vertex {} {} {}", x, y, z)?;
+                }
+                writeln!(writer, "endloop")?;
+                writeln!(writer, "endfacet")?;
+            }
+            writeln!(writer, "endsolid hedge")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_formats_weld_back_into_closed_meshes() {
+        let _ = env_logger::try_init();
+        let cube: Mesh = builder::cube();
+        for format in [Format::Binary, Format::Ascii] {
+            let mut bytes = Vec::new();
+            write(&mut bytes, &cube, format).unwrap();
+            assert_eq!(is_binary(&bytes), format == Format::Binary);
+            let mesh: Mesh = read(bytes.as_slice()).unwrap();
+            builder::assert_connectivity(&mesh);
+            assert_eq!(mesh.face_count(), 12);
+            assert_eq!(mesh.point_count(), 8);
+            let report = analysis::watertight_report(&mesh, 1.0e-9);
+            assert!(report.is_closed());
+            assert!(report.is_consistently_oriented());
+
+            let (soup, stats): (Mesh, _) = read_with(bytes.as_slice(), MergePolicy::Never).unwrap();
+            assert_eq!(stats.merged_points, 0);
+            assert_eq!(soup.point_count(), 36);
+            assert!(soup.edges().all(|e| e.is_boundary()));
+        }
+        assert!(read::<f32, _>("facet normal 0 0 1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn close_corners_weld_within_the_tolerance() {
+        let text = "solid pair\n\
+                    facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nendloop\nendfacet\n\
+                    facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1.0000001 1 0\nvertex 0 1 0\nendloop\nendfacet\n\
+                    endsolid pair\n";
+        let exact: Mesh<f64> = read(text.as_bytes()).unwrap();
+        assert_eq!(exact.point_count(), 5);
+        let (welded, stats): (Mesh<f64>, _) =
+            read_with(text.as_bytes(), MergePolicy::Weld(1.0e-5)).unwrap();
+        assert_eq!(stats.merged_points, 2);
+        assert_eq!(welded.point_count(), 4);
+        assert_eq!(welded.edges().filter(|e| !e.is_boundary()).count(), 2);
+
+        let broken = "solid broken\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\nendsolid\n";
+        assert!(read::<f32, _>(broken.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Interleaved, indexed vertex streams for exporters.
+//!
+//! A stream starts out with one record per face corner so that every corner
+//! attribute can be represented. `VertexStream::deduplicate` then merges
+//! records which are bit-for-bit identical.
+
+use crate::attributes::Channel;
+use crate::*;
+use std::collections::HashMap;
+
+/// A per-corner attribute which can be written into a vertex record.
+pub trait CornerAttribute {
+    /// Number of `f32` components written per corner.
+    fn width(&self) -> usize;
+    /// Appends `width()` components for the corner, zeros when it has no value.
+    fn write(&self, corner: EdgeIndex, out: &mut Vec<f32>);
+}
+
+impl<const N: usize> CornerAttribute for Channel<Edge, [f32; N]> {
+    fn width(&self) -> usize {
+        N
+    }
+
+    fn write(&self, corner: EdgeIndex, out: &mut Vec<f32>) {
+        out.extend_from_slice(self.get(corner).unwrap_or(&[0.0; N]));
+    }
+}
+
+/// Triangulated vertex records, `stride` floats each, starting with the position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VertexStream {
+    pub stride: usize,
+    pub vertices: Vec<f32>,
+    pub triangles: Vec<[u32; 3]>,
+    /// The face each triangle was cut from.
+    pub faces: Vec<FaceIndex>,
+}
+
+/// A run of `count` triangles starting at `start` which share a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRange {
+    pub bucket: u32,
+    pub start: usize,
+    pub count: usize,
+}
+
+/// Outcome of deduplicating a vertex stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    pub input_vertices: usize,
+    pub output_vertices: usize,
+    pub stride: usize,
+}
+
+impl DedupStats {
+    pub fn removed_vertices(&self) -> usize {
+        self.input_vertices - self.output_vertices
+    }
+
+    pub fn bytes_saved(&self) -> usize {
+        self.removed_vertices() * self.stride * std::mem::size_of::<f32>()
+    }
+
+    /// Output size relative to the input, `1.0` when nothing was merged.
+    pub fn ratio(&self) -> f64 {
+        if self.input_vertices == 0 {
+            1.0
+        } else {
+            self.output_vertices as f64 / self.input_vertices as f64
+        }
+    }
+}
+
+impl VertexStream {
+    /// Writes a record for every face corner and ear clips the faces into
+    /// triangles, like `MeshBuffers::new`.
+    pub fn from_mesh<S: Scalar>(mesh: &Mesh<S>, attributes: &[&dyn CornerAttribute]) -> Self {
+        let stride = 3 + attributes.iter().map(|a| a.width()).sum::<usize>();
+        let mut stream = VertexStream {
+            stride,
+            ..Default::default()
+        };
+        for face in mesh.faces() {
+            let first = stream.len() as u32;
+            let mut positions = Vec::new();
+            for corner in face.edges() {
+                let position = mesh.vertex_position(corner.vertex().index);
+                stream.vertices.extend(position.iter().map(|v| *v as f32));
+                for attribute in attributes {
+                    attribute.write(corner.index, &mut stream.vertices);
+                }
+                positions.push(position);
+            }
+            let triangles = if positions.len() == 3 {
+                vec![[0, 1, 2]]
+            } else {
+                ops::ear_clipping(&positions)
+            };
+            for triangle in triangles {
+                stream.triangles.push(triangle.map(|i| first + i as u32));
+                stream.faces.push(face.index);
+            }
+        }
+        stream
+    }
+
+    /// Reorders the triangles so that each bucket is contiguous and returns
+    /// the range to draw for each, in increasing bucket order.
+    ///
+    /// Faces take their bucket from `buckets`, typically a material or group
+    /// id, and land in bucket zero without a value. Within a bucket triangles
+    /// keep their order unless `view` is given, in which case they are sorted
+    /// back to front looking along that direction, by their centroids.
+    pub fn bucket_triangles(
+        &mut self,
+        buckets: &Channel<Face, u32>,
+        view: Option<[f64; 3]>,
+    ) -> Vec<DrawRange> {
+        let depth = |triangle: &[u32; 3]| {
+            view.map_or(0.0, |direction| {
+                triangle
+                    .iter()
+                    .map(|i| {
+                        let p = self.vertex(*i);
+                        (0..3).map(|k| p[k] as f64 * direction[k]).sum::<f64>()
+                    })
+                    .sum::<f64>()
+            })
+        };
+        let mut order: Vec<(u32, f64, usize)> = self
+            .triangles
+            .iter()
+            .zip(&self.faces)
+            .enumerate()
+            .map(|(i, (triangle, face))| {
+                let bucket = buckets.get(*face).copied().unwrap_or(0);
+                (bucket, depth(triangle), i)
+            })
+            .collect();
+        // Farthest along the view first, the sort is stable for ties.
+        order.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+        self.triangles = order.iter().map(|(_, _, i)| self.triangles[*i]).collect();
+        self.faces = order.iter().map(|(_, _, i)| self.faces[*i]).collect();
+
+        let mut ranges: Vec<DrawRange> = Vec::new();
+        for (start, (bucket, _, _)) in order.iter().enumerate() {
+            match ranges.last_mut() {
+                Some(range) if range.bucket == *bucket => range.count += 1,
+                _ => ranges.push(DrawRange {
+                    bucket: *bucket,
+                    start,
+                    count: 1,
+                }),
+            }
+        }
+        ranges
+    }
+
+    /// Number of vertex records.
+    pub fn len(&self) -> usize {
+        self.vertices.len().checked_div(self.stride).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn vertex(&self, index: u32) -> &[f32] {
+        let start = index as usize * self.stride;
+        &self.vertices[start..start + self.stride]
+    }
+
+    /// Merges identical records and remaps the triangles onto the survivors.
+    ///
+    /// Records are compared by their bit patterns so no tolerance is applied,
+    /// except that `-0.0` and `0.0` are considered equal. Surviving records
+    /// keep their first-seen order.
+    pub fn deduplicate(&mut self) -> DedupStats {
+        let input_vertices = self.len();
+        let mut unique: HashMap<Vec<u32>, u32> = HashMap::with_capacity(input_vertices);
+        let mut remap = Vec::with_capacity(input_vertices);
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        for index in 0..input_vertices as u32 {
+            let record = self.vertex(index);
+            let key: Vec<u32> = record.iter().map(|v| (v + 0.0).to_bits()).collect();
+            let next = unique.len() as u32;
+            let target = *unique.entry(key).or_insert_with(|| {
+                vertices.extend_from_slice(record);
+                next
+            });
+            remap.push(target);
+        }
+        self.vertices = vertices;
+        for triangle in &mut self.triangles {
+            *triangle = triangle.map(|i| remap[i as usize]);
+        }
+        let stats = DedupStats {
+            input_vertices,
+            output_vertices: self.len(),
+            stride: self.stride,
+        };
+        log::debug!(
+            "Deduplicated {} of {} vertices ({} bytes saved).",
+            stats.removed_vertices(),
+            stats.input_vertices,
+            stats.bytes_saved()
+        );
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uv;
+
+    #[test]
+    fn shared_corners_are_merged() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(2, 2);
+        let mut stream = VertexStream::from_mesh(&mesh, &[]);
+        assert_eq!(stream.len(), 16);
+        assert_eq!(stream.triangles.len(), 8);
+
+        let stats = stream.deduplicate();
+        assert_eq!(stats.input_vertices, 16);
+        assert_eq!(stats.output_vertices, 9);
+        assert_eq!(stats.bytes_saved(), 7 * 3 * 4);
+        assert_eq!(stream.len(), 9);
+        for triangle in &stream.triangles {
+            assert!(triangle.iter().all(|i| (*i as usize) < stream.len()));
+            assert_eq!(stream.vertex(triangle[0])[2], 0.0);
+        }
+    }
+
+    #[test]
+    fn differing_channels_are_kept_apart() {
+        let mesh: Mesh = builder::grid(2, 1);
+        let seam = mesh
+            .edges()
+            .find(|e| !e.is_boundary() && !e.twin().is_boundary())
+            .expect("Expected an interior edge.");
+        let charts = uv::split_charts_along(&mesh, &[seam.index].into());
+        let uvs = uv::unwrap_charts_lscm(&mesh, &charts, &[]);
+
+        let mut stream = VertexStream::from_mesh(&mesh, &[&uvs]);
+        assert_eq!(stream.stride, 5);
+        let stats = stream.deduplicate();
+        // The two vertices on the seam keep one record per chart.
+        assert_eq!(stats.output_vertices, 8);
+        assert_eq!(stats.removed_vertices(), 0);
+    }
+
+    #[test]
+    fn triangles_are_grouped_by_bucket() {
+        let mesh: Mesh = builder::grid(3, 1);
+        let mut stream = VertexStream::from_mesh(&mesh, &[]);
+        let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let mut buckets = Channel::new();
+        buckets.set(faces[0], 2);
+        buckets.set(faces[2], 2);
+
+        let ranges = stream.bucket_triangles(&buckets, Some([1.0, 0.0, 0.0]));
+        let expected = [
+            DrawRange {
+                bucket: 0,
+                start: 0,
+                count: 2,
+            },
+            DrawRange {
+                bucket: 2,
+                start: 2,
+                count: 4,
+            },
+        ];
+        assert_eq!(ranges, expected);
+        assert!(stream.faces[..2].iter().all(|f| *f == faces[1]));
+        // Looking along +x the cell farthest in x comes first.
+        assert!(stream.faces[2..4].iter().all(|f| *f == faces[2]));
+        assert!(stream.faces[4..].iter().all(|f| *f == faces[0]));
+    }
+
+    #[test]
+    fn concave_faces_are_ear_clipped() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [0.0, 2.0, 0.0],
+        ];
+        // Start the face on every corner, fans from some of them fold over.
+        for start in 0..6 {
+            let polygon: Vec<usize> = (0..6).map(|i| (start + i) % 6).collect();
+            let mesh: Mesh = Mesh::from_polygons(&positions, &[polygon]);
+            let stream = VertexStream::from_mesh(&mesh, &[]);
+            assert_eq!(stream.triangles.len(), 4);
+            let mut total = 0.0;
+            for triangle in &stream.triangles {
+                let [a, b, c] = triangle.map(|i| stream.vertex(i).to_vec());
+                let area = ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])) / 2.0;
+                assert!(area > 0.0);
+                total += area;
+            }
+            assert_eq!(total, 3.0);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! 3MF export for printing workflows.
+//!
+//! A 3MF file is a zip package holding an XML model along with the content
+//! types and relationships pointing at it. The package is written with its
+//! parts stored rather than compressed, which every reader accepts. Colors
+//! are written as base materials, either one for the whole object or one
+//! for every distinct face color.
+
+use super::invalid_data;
+use super::ply::Color;
+use crate::attributes::Channel;
+use crate::*;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+const MODEL_PATH: &str = "3D/3dmodel.model";
+
+const CONTENT_TYPES: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+    r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+    r#"<Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>"#,
+    r#"</Types>"#
+);
+
+const RELATIONSHIPS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Target="/3D/3dmodel.model" Id="rel0" "#,
+    r#"Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>"#,
+    r#"</Relationships>"#
+);
+
+/// How the object is colored.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Colors<'a> {
+    #[default]
+    None,
+    /// One color for the whole object.
+    Object(Color),
+    /// A color per face, faces without one get `default`.
+    Faces {
+        colors: &'a Channel<Face, Color>,
+        default: Color,
+    },
+}
+
+/// Writes a 3MF package holding the mesh as a single object, in millimeters.
+///
+/// The mesh has to be closed and made of triangles, as printers expect, see
+/// `ops::triangulate` and `analysis::watertight_report`.
+pub fn write<S: Scalar, W: Write>(
+    mut writer: W,
+    mesh: &Mesh<S>,
+    colors: &Colors,
+) -> io::Result<()> {
+    let model = model(mesh, colors)?;
+    let mut package = Package::default();
+    package.add("[Content_Types].xml", CONTENT_TYPES.as_bytes())?;
+    package.add("_rels/.rels", RELATIONSHIPS.as_bytes())?;
+    package.add(MODEL_PATH, model.as_bytes())?;
+    writer.write_all(&package.finish()?)
+}
+
+fn model<S: Scalar>(mesh: &Mesh<S>, colors: &Colors) -> io::Result<String> {
+    if let Some(face) = mesh.faces().find(|f| f.edges().count() != 3) {
+        return Err(invalid_data(format!(
+            "Face {:?} isn't a triangle.",
+            face.index
+        )));
+    }
+    if let Some(edge) = mesh.edges().find(|e| e.is_boundary()) {
+        return Err(invalid_data(format!(
+            "The mesh is open along {:?}.",
+            edge.index
+        )));
+    }
+
+    let mut points: HashMap<PointIndex, usize> = HashMap::new();
+    let mut vertices = String::new();
+    let mut triangles = String::new();
+    let mut palette: Vec<Color> = Vec::new();
+    let mut color_index = |color: Color| match palette.iter().position(|c| *c == color) {
+        Some(index) => index,
+        None => {
+            palette.push(color);
+            palette.len() - 1
+        }
+    };
+    if let Colors::Object(color) | Colors::Faces { default: color, .. } = colors {
+        color_index(*color);
+    }
+    for face in mesh.faces() {
+        let mut corners = [0; 3];
+        for (corner, vertex) in corners.iter_mut().zip(face.vertices()) {
+            let Some(point) = vertex.element().map(|v| v.point_index) else {
+                return Err(invalid_data(format!(
+                    "Face {:?} has a dangling vertex.",
+                    face.index
+                )));
+            };
+            let next = points.len();
+            *corner = *points.entry(point).or_insert_with(|| {
+                let [x, y, z] = mesh.vertex_position(vertex.index);
+                let _ = write!(vertices, r#"<vertex x="{}" y="{}" z="{}"/>"#, x, y, z);
+                next
+            });
+        }
+        let [v1, v2, v3] = corners;
+        let _ = write!(
+            triangles,
+            r#"<triangle v1="{}" v2="{}" v3="{}""#,
+            v1, v2, v3
+        );
+        if let Colors::Faces { colors, .. } = colors {
+            if let Some(color) = colors.get(face.index) {
+                let _ = write!(triangles, r#" pid="1" p1="{}""#, color_index(*color));
+            }
+        }
+        triangles.push_str("/>");
+    }
+
+    let mut model = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<model unit="millimeter" xml:lang="en-US" "#,
+        r#"xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#,
+        "<resources>"
+    ));
+    let object = if palette.is_empty() {
+        r#"<object id="2" type="model">"#.to_string()
+    } else {
+        model.push_str(r#"<basematerials id="1">"#);
+        for (i, [r, g, b]) in palette.iter().enumerate() {
+            let _ = write!(
+                model,
+                r##"<base name="color {}" displaycolor="#{:02X}{:02X}{:02X}"/>"##,
+                i, r, g, b
+            );
+        }
+        model.push_str("</basematerials>");
+        r#"<object id="2" type="model" pid="1" pindex="0">"#.to_string()
+    };
+    let _ = write!(
+        model,
+        "{}<mesh><vertices>{}</vertices><triangles>{}</triangles></mesh></object>",
+        object, vertices, triangles
+    );
+    model.push_str(r#"</resources><build><item objectid="2"/></build></model>"#);
+    log::debug!(
+        "Wrote a 3MF model with {} vertices, {} triangles and {} colors.",
+        points.len(),
+        mesh.face_count(),
+        palette.len()
+    );
+    Ok(model)
+}
+
+/// A zip archive of stored, uncompressed files.
+#[derive(Default)]
+struct Package {
+    bytes: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Package {
+    /// DOS date of the first of January 1980, the earliest a zip can hold.
+    const DATE: u16 = 0x21;
+
+    fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let offset = self.offset(self.bytes.len())?;
+        let size = self.offset(data.len())?;
+        let crc = crc32(data);
+        // Version needed, flags, method, time and date, then the sizes.
+        let mut fields = Vec::with_capacity(26);
+        for value in [20u16, 0, 0, 0, Self::DATE] {
+            fields.extend(value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            fields.extend(value.to_le_bytes());
+        }
+        fields.extend((name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+
+        self.bytes.extend(0x04034b50u32.to_le_bytes());
+        self.bytes.extend(&fields);
+        self.bytes.extend(name.as_bytes());
+        self.bytes.extend(data);
+
+        self.directory.extend(0x02014b50u32.to_le_bytes());
+        self.directory.extend(20u16.to_le_bytes());
+        self.directory.extend(&fields);
+        // Comment length, disk, internal and external attributes.
+        self.directory.extend([0; 10]);
+        self.directory.extend(offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<Vec<u8>> {
+        let start = self.offset(self.bytes.len())?;
+        let size = self.offset(self.directory.len())?;
+        self.bytes.append(&mut self.directory);
+        self.bytes.extend(0x06054b50u32.to_le_bytes());
+        self.bytes.extend([0; 4]);
+        self.bytes.extend(self.entries.to_le_bytes());
+        self.bytes.extend(self.entries.to_le_bytes());
+        self.bytes.extend(size.to_le_bytes());
+        self.bytes.extend(start.to_le_bytes());
+        self.bytes.extend(0u16.to_le_bytes());
+        Ok(self.bytes)
+    }
+
+    fn offset(&self, value: usize) -> io::Result<u32> {
+        u32::try_from(value).map_err(|_| invalid_data("Too large for a 3MF package."))
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The stored files of a package, by name.
+    fn unpack(bytes: &[u8]) -> HashMap<String, String> {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        let half = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap()) as usize;
+        let mut files = HashMap::new();
+        let mut at = 0;
+        while word(at) == 0x04034b50 {
+            let size = word(at + 18);
+            let name_length = half(at + 26);
+            let name = &bytes[at + 30..at + 30 + name_length];
+            let data = &bytes[at + 30 + name_length..at + 30 + name_length + size];
+            assert_eq!(word(at + 14) as u32, crc32(data));
+            files.insert(
+                String::from_utf8(name.to_vec()).unwrap(),
+                String::from_utf8(data.to_vec()).unwrap(),
+            );
+            at += 30 + name_length + size;
+        }
+        assert_eq!(word(at), 0x02014b50);
+        let end = bytes.len() - 22;
+        assert_eq!(word(end), 0x06054b50);
+        assert_eq!(half(end + 10), files.len());
+        assert_eq!(word(end + 16), at);
+        files
+    }
+
+    #[test]
+    fn packages_hold_colored_models() {
+        let _ = env_logger::try_init();
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        let mesh: Mesh = builder::octahedron();
+        let mut colors = Channel::new();
+        let red = mesh.faces().next().unwrap().index;
+        colors.set(red, [255, 0, 0]);
+        let mut bytes = Vec::new();
+        let faces = Colors::Faces {
+            colors: &colors,
+            default: [128, 128, 128],
+        };
+        write(&mut bytes, &mesh, &faces).unwrap();
+        let files = unpack(&bytes);
+        assert_eq!(files.len(), 3);
+        assert!(files["_rels/.rels"].contains(MODEL_PATH));
+        let model = &files[MODEL_PATH];
+        assert_eq!(model.matches("<vertex ").count(), 6);
+        assert_eq!(model.matches("<triangle ").count(), 8);
+        assert!(model.contains(r##"displaycolor="#808080""##));
+        assert!(model.contains(r##"displaycolor="#FF0000""##));
+        assert_eq!(model.matches(r#"p1="1""#).count(), 1);
+
+        let mut plain = Vec::new();
+        write(&mut plain, &mesh, &Colors::None).unwrap();
+        assert!(!unpack(&plain)[MODEL_PATH].contains("basematerials"));
+    }
+
+    #[test]
+    fn printers_need_closed_triangle_meshes() {
+        let mut bytes = Vec::new();
+        let cube: Mesh = builder::cube();
+        assert!(write(&mut bytes, &cube, &Colors::None).is_err());
+        let sheet: Mesh = builder::triangle_grid(1, 1);
+        assert!(write(&mut bytes, &sheet, &Colors::Object([0, 0, 255])).is_err());
+        assert!(bytes.is_empty());
+    }
+}
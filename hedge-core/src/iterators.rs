@@ -0,0 +1,326 @@
+//! Iterators for simple or common mesh traversal patterns.
+//!
+//! Every iterator is bounded by the number of edges in the mesh so that
+//! broken connectivity can't trap a traversal in an endless loop.
+
+use super::*;
+use std::collections::HashSet;
+
+/// Iterates over the outgoing edges of a vertex.
+pub struct VertexCirculator<'mesh, S: Scalar = f32> {
+    vert: VertexFn<'mesh, S>,
+    last_edge: Option<EdgeFn<'mesh, S>>,
+    remaining: usize,
+}
+
+impl<'mesh, S: Scalar> VertexCirculator<'mesh, S> {
+    pub fn new(vert: VertexFn<'mesh, S>) -> Self {
+        VertexCirculator {
+            vert,
+            last_edge: None,
+            remaining: vert.mesh.edge_count(),
+        }
+    }
+}
+
+impl<'mesh, S: Scalar> Iterator for VertexCirculator<'mesh, S> {
+    type Item = EdgeFn<'mesh, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            log::warn!("Vertex circulator exceeded the edge count of the mesh.");
+            return None;
+        }
+        self.remaining -= 1;
+
+        let root_edge = self.vert.edge();
+        self.last_edge = if let Some(last_edge) = self.last_edge {
+            let next_edge = last_edge.prev().twin();
+            if !next_edge.is_valid() {
+                log::debug!("Vertex circulator terminated due to disconnected edge.");
+                None
+            } else if next_edge.index == root_edge.index {
+                None
+            } else {
+                Some(next_edge)
+            }
+        } else if root_edge.is_valid() {
+            Some(root_edge)
+        } else {
+            None
+        };
+        if self.last_edge.is_none() {
+            self.remaining = 0;
+        }
+        self.last_edge
+    }
+}
+
+/// Iterates over the edges of a face loop starting with the root edge.
+pub struct FaceEdges<'mesh, S: Scalar = f32> {
+    root_edge: EdgeFn<'mesh, S>,
+    last_edge: Option<EdgeFn<'mesh, S>>,
+    remaining: usize,
+}
+
+impl<'mesh, S: Scalar> FaceEdges<'mesh, S> {
+    pub fn new(face: FaceFn<'mesh, S>) -> Self {
+        Self::from_edge(face.edge())
+    }
+
+    /// Iterates the loop containing an arbitrary edge.
+    pub fn from_edge(root_edge: EdgeFn<'mesh, S>) -> Self {
+        FaceEdges {
+            root_edge,
+            last_edge: None,
+            remaining: root_edge.mesh.edge_count(),
+        }
+    }
+}
+
+impl<'mesh, S: Scalar> Iterator for FaceEdges<'mesh, S> {
+    type Item = EdgeFn<'mesh, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        self.last_edge = if let Some(last_edge) = self.last_edge {
+            let next_edge = last_edge.next();
+            if !next_edge.is_valid() || next_edge.index == self.root_edge.index {
+                None
+            } else {
+                Some(next_edge)
+            }
+        } else if self.root_edge.is_valid() {
+            Some(self.root_edge)
+        } else {
+            None
+        };
+        if self.last_edge.is_none() {
+            self.remaining = 0;
+        }
+        self.last_edge
+    }
+}
+
+/// Iterates over the vertices of a face loop.
+pub struct FaceVertices<'mesh, S: Scalar = f32> {
+    inner_iter: FaceEdges<'mesh, S>,
+}
+
+impl<'mesh, S: Scalar> FaceVertices<'mesh, S> {
+    pub fn new(face: FaceFn<'mesh, S>) -> Self {
+        FaceVertices {
+            inner_iter: FaceEdges::new(face),
+        }
+    }
+}
+
+impl<'mesh, S: Scalar> Iterator for FaceVertices<'mesh, S> {
+    type Item = VertexFn<'mesh, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner_iter.next().map(|edge| edge.vertex())
+    }
+}
+
+/// Iterates over the loops running along the border of a set of faces.
+///
+/// Each loop lists, in order, the half-edges of selected faces whose twin
+/// lies outside the selection or on the open boundary of the mesh. A
+/// region with holes has a loop for every hole.
+pub struct RegionBoundary<'mesh, S: Scalar = f32> {
+    mesh: &'mesh Mesh<S>,
+    region: &'mesh Selection,
+    sides: Vec<EdgeIndex>,
+    visited: HashSet<EdgeIndex>,
+}
+
+impl<'mesh, S: Scalar> RegionBoundary<'mesh, S> {
+    pub fn new(mesh: &'mesh Mesh<S>, region: &'mesh Selection) -> Self {
+        let mut sides = region.boundary(mesh);
+        sides.sort_by_key(|e| std::cmp::Reverse(e.offset));
+        RegionBoundary {
+            mesh,
+            region,
+            sides,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// The side following `edge`, turning around its end through the
+    /// selected faces.
+    fn next_side(&self, edge: EdgeFn<'mesh, S>) -> Option<EdgeFn<'mesh, S>> {
+        let mut candidate = edge.next();
+        for _ in 0..self.mesh.edge_count() {
+            if !candidate.is_valid() {
+                return None;
+            }
+            if !self.region.contains(candidate.twin().face().index) {
+                return Some(candidate);
+            }
+            candidate = candidate.twin().next();
+        }
+        None
+    }
+}
+
+impl<'mesh, S: Scalar> Iterator for RegionBoundary<'mesh, S> {
+    type Item = Vec<EdgeFn<'mesh, S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let root = loop {
+            let side = self.sides.pop()?;
+            if !self.visited.contains(&side) {
+                break self.mesh.edge(side);
+            }
+        };
+        let mut sides = vec![root];
+        self.visited.insert(root.index);
+        let mut current = root;
+        while let Some(next) = self.next_side(current) {
+            if next.index == root.index || !self.visited.insert(next.index) {
+                break;
+            }
+            sides.push(next);
+            current = next;
+        }
+        Some(sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn build_triangle(mesh: &mut Mesh) -> (FaceIndex, [VertexIndex; 3], [EdgeIndex; 3]) {
+        let p0 = mesh.add_element(Point::new(-1.0, 0.0, 0.0));
+        let p1 = mesh.add_element(Point::new(1.0, 0.0, 0.0));
+        let p2 = mesh.add_element(Point::new(0.0, 1.0, 0.0));
+
+        let v0 = mesh.add_element(Vertex::at_point(p0));
+        let v1 = mesh.add_element(Vertex::at_point(p1));
+        let v2 = mesh.add_element(Vertex::at_point(p2));
+
+        let e0 = utils::build_full_edge(mesh, v0, v1).unwrap();
+        let e1 = utils::build_full_edge_from(mesh, e0, v2).unwrap();
+        let e2 = utils::close_edge_loop(mesh, e1, e0).unwrap();
+
+        let f0 = mesh.add_element(Face::default());
+        utils::assign_face_to_loop(mesh, e0, f0).unwrap();
+
+        (f0, [v0, v1, v2], [e0, e1, e2])
+    }
+
+    #[test]
+    fn can_iterate_over_edges_of_face() {
+        let _ = env_logger::try_init();
+        let mut mesh = Mesh::default();
+        let (f0, _, edges) = build_triangle(&mut mesh);
+
+        let mut iter_count = 0;
+        for edge in mesh.face(f0).edges() {
+            assert!(iter_count < 3);
+            assert!(edges.contains(&edge.index));
+            iter_count += 1;
+        }
+        assert_eq!(iter_count, 3);
+    }
+
+    #[test]
+    fn can_iterate_over_vertices_of_face() {
+        let _ = env_logger::try_init();
+        let mut mesh = Mesh::default();
+        let (f0, verts, _) = build_triangle(&mut mesh);
+
+        let mut iter_count = 0;
+        for vert in mesh.face(f0).vertices() {
+            assert!(iter_count < 3);
+            assert!(verts.contains(&vert.index));
+            iter_count += 1;
+        }
+        assert_eq!(iter_count, 3);
+    }
+
+    /// Builds four triangles around a shared center vertex.
+    fn build_fan(points: [PointIndex; 5], mesh: &mut Mesh) -> VertexIndex {
+        let center = mesh.add_element(Vertex::at_point(points[4]));
+        let rim: Vec<VertexIndex> = points[..4]
+            .iter()
+            .map(|p| mesh.add_element(Vertex::at_point(*p)))
+            .collect();
+
+        // Spokes from the center out to each rim vertex.
+        let spokes: Vec<EdgeIndex> = rim
+            .iter()
+            .map(|v| utils::build_full_edge(mesh, center, *v).unwrap())
+            .collect();
+
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+            let out_edge = spokes[i];
+            let in_edge = mesh.edge(spokes[j]).twin().index;
+            let rim_edge = utils::build_full_edge(mesh, rim[i], rim[j]).unwrap();
+            utils::connect_edges(mesh, out_edge, rim_edge).unwrap();
+            utils::connect_edges(mesh, rim_edge, in_edge).unwrap();
+            utils::connect_edges(mesh, in_edge, out_edge).unwrap();
+            let face = mesh.add_element(Face::default());
+            utils::assign_face_to_loop(mesh, out_edge, face).unwrap();
+        }
+        if let Some(vertex) = mesh.get_element_mut(center) {
+            vertex.edge_index = spokes[0];
+        }
+
+        center
+    }
+
+    #[test]
+    fn can_iterate_around_vertex() {
+        let _ = env_logger::try_init();
+        let mut mesh = Mesh::default();
+
+        let points = [
+            mesh.add_element(Point::new(-1.0, 0.0, 0.0)),
+            mesh.add_element(Point::new(0.0, -1.0, 0.0)),
+            mesh.add_element(Point::new(1.0, 0.0, 0.0)),
+            mesh.add_element(Point::new(0.0, 1.0, 0.0)),
+            mesh.add_element(Point::new(0.0, 0.0, 0.0)),
+        ];
+
+        let root_vert = build_fan(points, &mut mesh);
+
+        let mut iter_count = 0;
+        for edge in mesh.vertex(root_vert).edges() {
+            assert!(iter_count < 4);
+            assert_eq!(edge.vertex().index, root_vert);
+            iter_count += 1;
+        }
+        assert_eq!(iter_count, 4);
+    }
+
+    #[test]
+    fn region_boundaries_come_in_loops() {
+        let mesh: Mesh = builder::grid(3, 3);
+        let center = |f: &FaceFn| f.edges().all(|e| !e.is_boundary());
+        let inner: Selection = mesh.faces().filter(center).map(|f| f.index).collect();
+        let loops: Vec<_> = inner.boundary_loops(&mesh).collect();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 4);
+        for (i, side) in loops[0].iter().enumerate() {
+            let next = &loops[0][(i + 1) % 4];
+            assert_eq!(side.twin().vertex().index, next.vertex().index);
+        }
+
+        let ring: Selection = mesh
+            .faces()
+            .filter(|f| !center(f))
+            .map(|f| f.index)
+            .collect();
+        let mut lengths: Vec<usize> = ring.boundary_loops(&mesh).map(|l| l.len()).collect();
+        lengths.sort();
+        assert_eq!(lengths, vec![4, 12]);
+    }
+}
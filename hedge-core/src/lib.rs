@@ -1,42 +1,1008 @@
-use hedge_element_buffer as hbuf;
+//!
+//! An index based half-edge mesh implementation.
+//!
 
-#[derive(Default)]
-pub struct Vertex;
+use std::fmt;
 
-#[derive(Default)]
-pub struct Edge;
+pub use crate::error::{Error, Result};
+pub use crate::function_sets::*;
+pub use crate::iterators::*;
+pub use crate::progress::Progress;
+pub use crate::scalar::Scalar;
+pub use crate::selection::Selection;
+pub use crate::tolerance::Tolerances;
+pub use crate::view::MeshView;
 
-#[derive(Default)]
-pub struct Face;
+pub mod analysis;
+pub mod attributes;
+pub mod boolean;
+mod builder;
+pub mod bvh;
+pub mod canonical;
+pub mod chunked;
+mod compare;
+pub mod constraints;
+pub mod epoch;
+pub mod error;
+pub mod function_sets;
+pub mod generate;
+pub mod io;
+pub mod iterators;
+mod linalg;
+mod math;
+pub mod morph;
+pub mod normals;
+pub mod ops;
+mod parallel;
+pub mod pool;
+pub mod predicates;
+pub mod primitives;
+pub mod progress;
+pub mod progressive;
+pub mod properties;
+mod random;
+pub mod reconstruct;
+pub mod sample;
+pub mod scalar;
+pub mod scene;
+pub mod selection;
+mod spatial;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tolerance;
+mod trace;
+pub mod utils;
+pub mod uv;
+pub mod view;
+pub mod voxel;
 
-pub struct Mesh {
+pub use hbuf::{Generation, Handle, HandleKind, Offset, ParseHandleError, Tag};
+pub use hedge_element_buffer as hbuf;
+
+pub type Position<S = f32> = [S; 3];
+pub type Normal = [f32; 3];
+
+/// Interface for checking that an element or facade refers to something usable.
+pub trait IsValid {
+    fn is_valid(&self) -> bool;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// TODO: Documentation
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    /// The adjacent or 'twin' half-edge
+    pub twin_index: EdgeIndex,
+    /// The index of the next edge in the loop
+    pub next_index: EdgeIndex,
+    /// The index of the previous edge in the loop
+    pub prev_index: EdgeIndex,
+    /// The index of the face this edge loop defines
+    pub face_index: FaceIndex,
+    /// The index of the Vertex for this edge.
+    pub vertex_index: VertexIndex,
+}
+pub type EdgeIndex = Handle<Edge>;
+impl HandleKind for Edge {
+    const KIND: &'static str = "e";
+}
+impl Edge {
+    /// Returns true when this edge has a previous and next edge.
+    pub fn is_connected(&self) -> bool {
+        self.next_index.is_valid() && self.prev_index.is_valid()
+    }
+}
+impl IsValid for Edge {
+    /// An Edge is valid when it has a valid twin index, a valid vertex index
+    /// and `is_connected`
+    fn is_valid(&self) -> bool {
+        self.vertex_index.is_valid() && self.twin_index.is_valid() && self.is_connected()
+    }
+}
+
+/// TODO: Documentation
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vertex {
+    /// Index of the outgoing edge
+    pub edge_index: EdgeIndex,
+    /// Index of point this vertex belongs to
+    pub point_index: PointIndex,
+}
+pub type VertexIndex = Handle<Vertex>;
+impl HandleKind for Vertex {
+    const KIND: &'static str = "v";
+}
+impl Vertex {
+    pub fn new(edge_index: EdgeIndex, point_index: PointIndex) -> Self {
+        Vertex {
+            edge_index,
+            point_index,
+        }
+    }
+
+    pub fn for_edge(edge_index: EdgeIndex) -> Self {
+        Vertex {
+            edge_index,
+            ..Vertex::default()
+        }
+    }
+
+    pub fn at_point(point_index: PointIndex) -> Self {
+        Vertex {
+            point_index,
+            ..Vertex::default()
+        }
+    }
+}
+impl IsValid for Vertex {
+    /// A vertex is considered "valid" as long as it has a valid edge index.
+    fn is_valid(&self) -> bool {
+        self.edge_index.is_valid()
+    }
+}
+
+/// TODO: Documentation
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Face {
+    /// The "root" of an edge loop that defines this face.
+    pub edge_index: EdgeIndex,
+}
+pub type FaceIndex = Handle<Face>;
+impl HandleKind for Face {
+    const KIND: &'static str = "f";
+}
+impl Face {
+    pub fn new(edge_index: EdgeIndex) -> Self {
+        Face { edge_index }
+    }
+}
+impl IsValid for Face {
+    /// A face is considered "valid" as long as it has an edge index
+    /// other than `INVALID_COMPONENT_INDEX`
+    fn is_valid(&self) -> bool {
+        self.edge_index.is_valid()
+    }
+}
+
+/// The geometric payload shared by one or more vertices.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point<S: Scalar = f32> {
+    pub position: Position<S>,
+}
+/// Point handles don't carry the scalar type of the mesh so that
+/// connectivity is expressed the same way regardless of precision.
+pub type PointIndex = Handle<Point>;
+impl<S: Scalar> HandleKind for Point<S> {
+    const KIND: &'static str = "p";
+}
+impl<S: Scalar> Point<S> {
+    pub fn new(x: S, y: S, z: S) -> Self {
+        Point {
+            position: [x, y, z],
+        }
+    }
+
+    pub fn from_position(position: Position<S>) -> Self {
+        Point { position }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Interface for adding elements to a `Mesh`.
+pub trait AddElement<E> {
+    type Index;
+    fn add_element(&mut self, element: E) -> Self::Index;
+}
+
+/// Interface for removing elements to a `Mesh`.
+pub trait RemoveElement<I> {
+    fn remove_element(&mut self, index: I);
+}
+
+/// Interface for getting an element reference.
+pub trait GetElement<I> {
+    type Element;
+    fn get_element(&self, index: I) -> Option<&Self::Element>;
+    fn get_element_mut(&mut self, index: I) -> Option<&mut Self::Element>;
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mesh<S: Scalar = f32> {
     pub edges: hbuf::ElementBuffer<Edge>,
     pub vertices: hbuf::ElementBuffer<Vertex>,
     pub faces: hbuf::ElementBuffer<Face>,
+    pub points: pool::PointPool<S>,
+    /// Blend shapes over the points, see `morph`.
+    pub morph_targets: Vec<morph::MorphTarget<S>>,
+    /// Limits on how vertices may move, see `constraints`.
+    pub constraints: constraints::Constraints,
+    /// Shading normals of the vertices, see `Mesh::compute_vertex_normals`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub vertex_normals: attributes::Channel<Vertex, Normal>,
+    /// Values kept in step with the elements, see `properties`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub properties: properties::Properties,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    point_vertices: builder::PointVertices,
+}
+
+impl<S: Scalar> fmt::Debug for Mesh<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Mesh {{ {} points, {} vertices, {} edges, {} faces }}",
+            self.point_count(),
+            self.vertex_count(),
+            self.edge_count(),
+            self.face_count()
+        )
+    }
 }
 
-impl Default for Mesh {
+impl<S: Scalar> Default for Mesh<S> {
     fn default() -> Self {
         Mesh::new()
     }
 }
 
-impl Mesh {
+impl<S: Scalar> Mesh<S> {
     pub fn new() -> Self {
         Mesh {
             edges: Default::default(),
             vertices: Default::default(),
             faces: Default::default(),
+            points: Default::default(),
+            morph_targets: Vec::new(),
+            constraints: Default::default(),
+            vertex_normals: Default::default(),
+            properties: Default::default(),
+            point_vertices: Default::default(),
         }
     }
+
+    /// Returns a `FaceFn` for the given index.
+    pub fn face(&self, index: FaceIndex) -> FaceFn<'_, S> {
+        FaceFn::new(index, self)
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = FaceFn<'_, S>> {
+        self.faces
+            .iter()
+            .map(move |(index, _)| FaceFn::new(index, self))
+    }
+
+    /// Returns an `EdgeFn` for the given index.
+    pub fn edge(&self, index: EdgeIndex) -> EdgeFn<'_, S> {
+        EdgeFn::new(index, self)
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = EdgeFn<'_, S>> {
+        self.edges
+            .iter()
+            .map(move |(index, _)| EdgeFn::new(index, self))
+    }
+
+    /// Returns a `VertexFn` for the given index.
+    pub fn vertex(&self, index: VertexIndex) -> VertexFn<'_, S> {
+        VertexFn::new(index, self)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = VertexFn<'_, S>> {
+        self.vertices
+            .iter()
+            .map(move |(index, _)| VertexFn::new(index, self))
+    }
+
+    /// Returns a `PointFn` for the given index.
+    pub fn point(&self, index: PointIndex) -> PointFn<'_, S> {
+        PointFn::new(index, self)
+    }
+
+    /// Returns the function set matching the kind of `index`.
+    pub fn fns<I: ElementIndex>(&self, index: I) -> I::Fn<'_, S> {
+        index.fns(self)
+    }
+
+    /// Changes whenever the edges, vertices or faces may have changed, for
+    /// caches of data derived from the connectivity.
+    pub fn topology_version(&self) -> [hbuf::Version; 3] {
+        [
+            self.edges.version(),
+            self.vertices.version(),
+            self.faces.version(),
+        ]
+    }
+
+    /// Changes whenever the points may have moved or been added or removed.
+    pub fn geometry_version(&self) -> hbuf::Version {
+        self.points.version()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns the position of a point if the index is still valid.
+    pub fn position(&self, index: PointIndex) -> Option<Position<S>> {
+        self.points.get(index).map(|p| p.position)
+    }
+
+    /// Checks that every live element is wired up consistently.
+    ///
+    /// Twins pair up, `next` and `prev` are inverses, face loops close with
+    /// at least three edges and every handle stored on an element is live.
+    pub fn validate(&self) -> Result<()> {
+        let inconsistent = |reason: String| Err(Error::Inconsistent(reason));
+        for (index, edge) in self.edges.iter() {
+            let twin = self.edges.get(edge.twin_index);
+            if twin.map(|t| t.twin_index) != Some(index) {
+                return inconsistent(format!("{:?} and its twin don't pair up", index));
+            }
+            let next = self.edges.get(edge.next_index);
+            let prev = self.edges.get(edge.prev_index);
+            if next.map(|n| n.prev_index) != Some(index)
+                || prev.map(|p| p.next_index) != Some(index)
+            {
+                return inconsistent(format!("{:?} is not linked both ways", index));
+            }
+            if self.vertices.get(edge.vertex_index).is_none() {
+                return Err(Error::InvalidVertex(edge.vertex_index));
+            }
+            if next.map(|n| n.vertex_index) != twin.map(|t| t.vertex_index) {
+                return inconsistent(format!("{:?} doesn't end where its next starts", index));
+            }
+            if next.map(|n| n.face_index) != Some(edge.face_index) {
+                return inconsistent(format!("{:?} and its next have different faces", index));
+            }
+            if edge.face_index.is_valid() && self.faces.get(edge.face_index).is_none() {
+                return Err(Error::InvalidFace(edge.face_index));
+            }
+        }
+        for (index, vertex) in self.vertices.iter() {
+            if self.points.get(vertex.point_index).is_none() {
+                return Err(Error::InvalidPoint(vertex.point_index));
+            }
+            match self.edges.get(vertex.edge_index) {
+                Some(edge) if edge.vertex_index == index => {}
+                _ => return inconsistent(format!("{:?} has no outgoing edge", index)),
+            }
+        }
+        for (index, face) in self.faces.iter() {
+            match self.edges.get(face.edge_index) {
+                Some(edge) if edge.face_index == index => {}
+                _ => return Err(Error::InvalidEdge(face.edge_index)),
+            }
+            let mut edge = face.edge_index;
+            let mut sides = 0;
+            loop {
+                sides += 1;
+                edge = self
+                    .edges
+                    .get(edge)
+                    .map(|e| e.next_index)
+                    .unwrap_or_default();
+                if edge == face.edge_index {
+                    break;
+                }
+                if sides > self.edge_count() {
+                    return Err(Error::OpenEdgeLoop(face.edge_index));
+                }
+            }
+            if sides < 3 {
+                return inconsistent(format!("{:?} has only {} sides", index, sides));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reverses the winding of every face.
+    ///
+    /// Each half-edge swaps `next` and `prev` and moves to the other end of
+    /// its edge, keeping its face and twin. Values stored per half-edge stay
+    /// with it and so end up on the corner its old `next` started from.
+    /// The vertex normals of the mesh are negated, normals stored in other
+    /// channels are flipped with `Channel::flip_normals`.
+    pub fn flip_all_faces(&mut self) {
+        let edges: Vec<EdgeIndex> = self.edges.iter().map(|(index, _)| index).collect();
+        self.reverse_edges(&edges);
+        self.vertex_normals.flip_normals();
+    }
+
+    /// Reverses half-edges in place, the set has to contain the twin, next
+    /// and previous edge of every member.
+    pub(crate) fn reverse_edges(&mut self, edges: &[EdgeIndex]) {
+        let mut outgoing = Vec::new();
+        let mut origins = Vec::with_capacity(edges.len());
+        for index in edges {
+            let Some(edge) = self.edges.get(*index) else {
+                continue;
+            };
+            let origin = self.edges.get(edge.twin_index).map(|t| t.vertex_index);
+            origins.push((*index, origin.unwrap_or_default()));
+            // Incoming boundary edges leave the vertex once reversed, which
+            // keeps circulation starting on the boundary.
+            let vertex = edge.vertex_index;
+            if self.vertices.get(vertex).map(|v| v.edge_index) == Some(*index) {
+                let replacement = if edge.face_index.is_valid() {
+                    edge.twin_index
+                } else {
+                    edge.prev_index
+                };
+                outgoing.push((vertex, replacement));
+            }
+        }
+        for (index, origin) in origins {
+            if let Some(edge) = self.edges.get_mut(index) {
+                std::mem::swap(&mut edge.next_index, &mut edge.prev_index);
+                edge.vertex_index = origin;
+            }
+        }
+        for (vertex, edge) in outgoing {
+            if let Some(v) = self.vertices.get_mut(vertex) {
+                v.edge_index = edge;
+            }
+        }
+    }
+
+    /// Position of a vertex in `f64`, or the origin for dangling vertices.
+    pub(crate) fn vertex_position(&self, index: VertexIndex) -> math::Vec3 {
+        self.vertex(index)
+            .position()
+            .map(scalar::to_f64)
+            .unwrap_or_default()
+    }
+
+    fn face_positions(&self, index: FaceIndex) -> Vec<math::Vec3> {
+        self.face(index)
+            .vertices()
+            .map(|v| self.vertex_position(v.index))
+            .collect()
+    }
+
+    /// Vector area of a face using Newell's method.
+    ///
+    /// Points along the face normal with a length equal to the face area.
+    pub(crate) fn face_area_vector(&self, index: FaceIndex) -> math::Vec3 {
+        math::polygon_area_vector(&self.face_positions(index))
+    }
+
+    /// Unit normal of a face, zero for degenerate faces.
+    pub(crate) fn face_normal(&self, index: FaceIndex) -> math::Vec3 {
+        math::polygon_normal(&self.face_positions(index))
+    }
+
+    /// Area weighted average of the normals of the faces around a vertex.
+    pub(crate) fn vertex_normal(&self, index: VertexIndex) -> math::Vec3 {
+        let sum = self
+            .vertex(index)
+            .edges()
+            .map(|edge| edge.face())
+            .filter(|face| face.is_valid())
+            .fold([0.0; 3], |sum, face| {
+                math::add(sum, self.face_area_vector(face.index))
+            });
+        math::normalize(sum)
+    }
+}
+
+impl<S: Scalar> AddElement<Edge> for Mesh<S> {
+    type Index = EdgeIndex;
+    fn add_element(&mut self, element: Edge) -> EdgeIndex {
+        trace::added();
+        let index = self.edges.push(element);
+        self.properties.added(index);
+        index
+    }
+}
+
+impl<S: Scalar> AddElement<Vertex> for Mesh<S> {
+    type Index = VertexIndex;
+    fn add_element(&mut self, element: Vertex) -> VertexIndex {
+        trace::added();
+        let index = self.vertices.push(element);
+        self.properties.added(index);
+        index
+    }
+}
+
+impl<S: Scalar> AddElement<Face> for Mesh<S> {
+    type Index = FaceIndex;
+    fn add_element(&mut self, element: Face) -> FaceIndex {
+        trace::added();
+        let index = self.faces.push(element);
+        self.properties.added(index);
+        index
+    }
+}
+
+impl<S: Scalar> AddElement<Point<S>> for Mesh<S> {
+    type Index = PointIndex;
+    fn add_element(&mut self, element: Point<S>) -> PointIndex {
+        trace::added();
+        let index = self.points.push(element);
+        self.properties.added(index);
+        index
+    }
+}
+
+impl<S: Scalar> RemoveElement<EdgeIndex> for Mesh<S> {
+    fn remove_element(&mut self, index: EdgeIndex) {
+        trace::removed();
+        self.properties.removed(index);
+        self.edges.remove(index)
+    }
+}
+
+impl<S: Scalar> RemoveElement<VertexIndex> for Mesh<S> {
+    fn remove_element(&mut self, index: VertexIndex) {
+        trace::removed();
+        self.properties.removed(index);
+        self.vertices.remove(index)
+    }
+}
+
+impl<S: Scalar> RemoveElement<FaceIndex> for Mesh<S> {
+    fn remove_element(&mut self, index: FaceIndex) {
+        trace::removed();
+        self.properties.removed(index);
+        self.faces.remove(index)
+    }
+}
+
+impl<S: Scalar> RemoveElement<PointIndex> for Mesh<S> {
+    fn remove_element(&mut self, index: PointIndex) {
+        trace::removed();
+        self.properties.removed(index);
+        self.points.remove(index)
+    }
+}
+
+impl<S: Scalar> GetElement<EdgeIndex> for Mesh<S> {
+    type Element = Edge;
+    fn get_element(&self, index: EdgeIndex) -> Option<&Edge> {
+        self.edges.get(index)
+    }
+    fn get_element_mut(&mut self, index: EdgeIndex) -> Option<&mut Edge> {
+        self.edges.get_mut(index)
+    }
+}
+
+impl<S: Scalar> GetElement<VertexIndex> for Mesh<S> {
+    type Element = Vertex;
+    fn get_element(&self, index: VertexIndex) -> Option<&Vertex> {
+        self.vertices.get(index)
+    }
+    fn get_element_mut(&mut self, index: VertexIndex) -> Option<&mut Vertex> {
+        self.vertices.get_mut(index)
+    }
+}
+
+impl<S: Scalar> GetElement<FaceIndex> for Mesh<S> {
+    type Element = Face;
+    fn get_element(&self, index: FaceIndex) -> Option<&Face> {
+        self.faces.get(index)
+    }
+    fn get_element_mut(&mut self, index: FaceIndex) -> Option<&mut Face> {
+        self.faces.get_mut(index)
+    }
+}
+
+impl<S: Scalar> GetElement<PointIndex> for Mesh<S> {
+    type Element = Point<S>;
+    fn get_element(&self, index: PointIndex) -> Option<&Point<S>> {
+        self.points.get(index)
+    }
+    fn get_element_mut(&mut self, index: PointIndex) -> Option<&mut Point<S>> {
+        self.points.get_mut(index)
+    }
+}
+
+pub mod prelude {
+    pub use super::{
+        AddElement, Edge, EdgeFn, EdgeIndex, ElementIndex, Face, FaceFn, FaceIndex, FunctionSet,
+        GetElement, IsValid, Mesh, MeshView, Point, PointFn, PointIndex, Position, RemoveElement,
+        Scalar, Vertex, VertexFn, VertexIndex,
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use log::*;
+
+    #[test]
+    fn basic_debug_printing() {
+        let _ = env_logger::try_init();
+
+        let edge = Edge::default();
+        debug!("{:?}", edge);
+
+        let vertex = Vertex::default();
+        debug!("{:?}", vertex);
+
+        let face = Face::default();
+        debug!("{:?}", face);
+
+        let point = Point::<f32>::default();
+        debug!("{:?}", point);
+
+        let mesh = Mesh::<f32>::default();
+        debug!("{:?}", mesh);
+    }
+
+    #[test]
+    fn index_types_are_invalid_by_default() {
+        let vert = VertexIndex::default();
+        assert!(!vert.is_valid());
+
+        let edge = EdgeIndex::default();
+        assert!(!edge.is_valid());
+
+        let point = PointIndex::default();
+        assert!(!point.is_valid());
+
+        let face = FaceIndex::default();
+        assert!(!face.is_valid());
+    }
+
+    #[test]
+    fn indices_print_with_their_kind() {
+        let face = FaceIndex::new(12, 3);
+        assert_eq!(face.to_string(), "f:12@3");
+        assert_eq!("f:12@3".parse::<FaceIndex>(), Ok(face));
+        assert_eq!(PointIndex::new(1, 1).to_string(), "p:1@1");
+        assert!("v:12@3".parse::<EdgeIndex>().is_err());
+    }
+
+    #[test]
+    fn versions_track_edits() {
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let (topology, geometry) = (mesh.topology_version(), mesh.geometry_version());
+        let _ = mesh.faces().map(|f| f.area()).sum::<f32>();
+        assert_eq!(mesh.topology_version(), topology);
+
+        let point = mesh.points.iter().next().unwrap().0;
+        mesh.points[point].position[2] = 1.0;
+        assert_eq!(mesh.topology_version(), topology);
+        assert_ne!(mesh.geometry_version(), geometry);
+
+        let face = mesh.faces().next().unwrap().index;
+        ops::remove_face(&mut mesh, face).unwrap();
+        assert_ne!(mesh.topology_version(), topology);
+    }
+
+    #[test]
+    fn default_elements_are_invalid() {
+        assert!(!Edge::default().is_valid());
+        assert!(!Vertex::default().is_valid());
+        assert!(!Face::default().is_valid());
+    }
 
     #[test]
-    fn it_works() {
-        let mut mesh = Mesh::new();
+    fn any_index_reaches_its_function_set() {
+        fn valid<I: ElementIndex>(mesh: &Mesh, index: I) -> bool
+        where
+            for<'m> I::Fn<'m, f32>: IsValid,
+        {
+            mesh.fns(index).is_valid()
+        }
+
+        let mesh: Mesh = builder::grid(1, 1);
+        let face = mesh.faces().next().unwrap().index;
+        assert_eq!(mesh.fns(face).edge().index, mesh.face(face).edge().index);
+        let vertex = mesh.fns(face).edge().vertex().index;
+        let point = mesh.vertices[vertex].point_index;
+        assert_eq!(mesh.fns(point).position(), mesh.fns(vertex).position());
+        assert_eq!(mesh.point(point).vertices().count(), 1);
+        assert!(valid(&mesh, face) && valid(&mesh, vertex) && valid(&mesh, point));
+        assert!(!valid(&mesh, EdgeIndex::default()));
+    }
+
+    #[test]
+    fn initial_mesh_has_no_elements() {
+        let mesh: Mesh = Mesh::default();
+
+        assert_eq!(mesh.edge_count(), 0);
+        assert!(mesh.get_element(EdgeIndex::default()).is_none());
+        assert_eq!(mesh.face_count(), 0);
+        assert!(mesh.get_element(FaceIndex::default()).is_none());
+        assert_eq!(mesh.vertex_count(), 0);
+        assert!(mesh.get_element(VertexIndex::default()).is_none());
+        assert_eq!(mesh.point_count(), 0);
+        assert!(mesh.get_element(PointIndex::default()).is_none());
+    }
+
+    #[test]
+    fn can_add_and_remove_elements() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = Mesh::default();
+
+        let v0 = mesh.add_element(Vertex::default());
+        let e0 = mesh.add_element(Edge::default());
+        let f0 = mesh.add_element(Face::default());
+        let p0 = mesh.add_element(Point::default());
+        assert_eq!(mesh.vertex_count(), 1);
+        assert_eq!(mesh.edge_count(), 1);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.point_count(), 1);
+
+        mesh.remove_element(v0);
+        mesh.remove_element(e0);
+        mesh.remove_element(f0);
+        mesh.remove_element(p0);
+        assert_eq!(mesh.vertex_count(), 0);
+        assert_eq!(mesh.edge_count(), 0);
+        assert_eq!(mesh.face_count(), 0);
+        assert_eq!(mesh.point_count(), 0);
+        assert!(mesh.get_element(p0).is_none());
+    }
+
+    #[test]
+    fn double_precision_positions_are_preserved() {
+        let mut mesh: Mesh<f64> = Mesh::new();
+        let x = 1.0 + 1.0e-12;
+        let p0 = mesh.add_element(Point::new(x, 0.0, -x));
+        assert_eq!(mesh.position(p0), Some([x, 0.0, -x]));
+
+        let v0 = mesh.add_element(Vertex::at_point(p0));
+        assert_eq!(mesh.vertex(v0).position(), Some([x, 0.0, -x]));
+    }
+
+    #[test]
+    fn can_build_a_simple_mesh_manually() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = Mesh::default();
+
+        let p0 = mesh.add_element(Point::new(-1.0, 0.0, 0.0));
+        let p1 = mesh.add_element(Point::new(1.0, 0.0, 0.0));
+        let p2 = mesh.add_element(Point::new(0.0, 1.0, 0.0));
+
+        let v0 = mesh.add_element(Vertex::at_point(p0));
+        let v1 = mesh.add_element(Vertex::at_point(p1));
+        let v2 = mesh.add_element(Vertex::at_point(p2));
+
+        let e0 = utils::build_full_edge(&mut mesh, v0, v1).unwrap();
+        let e1 = utils::build_full_edge_from(&mut mesh, e0, v2).unwrap();
+        let e2 = utils::close_edge_loop(&mut mesh, e1, e0).unwrap();
+
+        let f0 = mesh.add_element(Face::default());
+        utils::assign_face_to_loop(&mut mesh, e0, f0).unwrap();
+
+        assert!(mesh.edge(e0).is_boundary());
+        assert!(mesh.edge(e1).is_boundary());
+        assert!(mesh.edge(e2).is_boundary());
+        assert_eq!(mesh.edge(e0).face().index, f0);
+        assert_eq!(mesh.edge(e1).face().index, f0);
+        assert_eq!(mesh.edge(e2).face().index, f0);
+
+        assert_eq!(mesh.edge(e0).vertex().index, v0);
+        assert_eq!(mesh.edge(e1).vertex().index, v1);
+        assert_eq!(mesh.edge(e2).vertex().index, v2);
+
+        assert_eq!(mesh.edge(e0).twin().vertex().index, v1);
+        assert_eq!(mesh.edge(e1).twin().vertex().index, v2);
+        assert_eq!(mesh.edge(e2).twin().vertex().index, v0);
+    }
+
+    #[test]
+    fn invalid_handles_are_reported() {
+        let mut mesh: Mesh = Mesh::default();
+        let v0 = mesh.add_element(Vertex::default());
+        let v1 = mesh.add_element(Vertex::default());
+        let e0 = utils::build_full_edge(&mut mesh, v0, v1).unwrap();
+
+        let missing = EdgeIndex::default();
+        assert_eq!(
+            utils::close_edge_loop(&mut mesh, e0, missing),
+            Err(Error::InvalidEdge(missing))
+        );
+        let stale = mesh.add_element(Vertex::default());
+        mesh.remove_element(stale);
+        assert_eq!(
+            utils::build_full_edge_from(&mut mesh, e0, stale),
+            Err(Error::InvalidVertex(stale))
+        );
+        // Failed calls don't leave partial connectivity behind.
+        assert_eq!(mesh.edge_count(), 2);
+        assert!(!mesh.edge(e0).next().is_valid());
+        assert_eq!(mesh.vertex(v1).edge().twin().index, e0);
+        let face = FaceIndex::default();
+        assert_eq!(
+            utils::assign_face_to_loop(&mut mesh, e0, face),
+            Err(Error::InvalidFace(face))
+        );
+        let face = mesh.add_element(Face::default());
+        assert_eq!(
+            utils::assign_face_to_loop(&mut mesh, e0, face),
+            Err(Error::OpenEdgeLoop(e0))
+        );
+        assert!(!mesh.edge(e0).face().is_valid());
+    }
+
+    #[test]
+    fn stitching_joins_borders_across_a_gap() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.5, 0.0, 0.0],
+            [2.5, 0.0, 0.0],
+            [2.5, 1.0, 0.0],
+            [1.5, 1.0, 0.0],
+        ];
+        let mut mesh: Mesh = Mesh::from_polygons(&positions, &[vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        let border = |mesh: &Mesh, from: Position, to: Position| {
+            mesh.edges()
+                .find(|e| {
+                    !e.face().is_valid()
+                        && e.vertex().position() == Some(from)
+                        && e.twin().vertex().position() == Some(to)
+                })
+                .unwrap()
+                .index
+        };
+        let a = border(&mesh, [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        let b = border(&mesh, [1.5, 0.0, 0.0], [1.5, 1.0, 0.0]);
+        assert!(utils::stitch_boundaries(&mut mesh, &[a], &[]).is_err());
+        assert!(utils::stitch_boundaries(&mut mesh, &[a], &[a]).is_err());
+        let inner = mesh.edge(a).twin().index;
+        assert!(utils::stitch_boundaries(&mut mesh, &[a], &[inner]).is_err());
+        assert_eq!(mesh.vertex_count(), 8);
+
+        assert_eq!(utils::stitch_boundaries(&mut mesh, &[a], &[b]).unwrap(), 1);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.point_count(), 6);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 6);
+        assert!(mesh.vertices().all(|v| v.position().unwrap()[0] != 1.5));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_meshes_keep_their_handles() {
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let removed = mesh.faces().next().unwrap().index;
+        ops::remove_face(&mut mesh, removed).unwrap();
+        let kept: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let corner = mesh.vertices().next().unwrap().index;
+        mesh.constraints.pin(corner);
+        let point = mesh.points.iter().next().unwrap().0;
+        mesh.add_morph_target("lift")
+            .deltas
+            .set(point, [0.0, 0.0, 1.0]);
+
+        let json = serde_json::to_string(&mesh).unwrap();
+        let loaded: Mesh = serde_json::from_str(&json).unwrap();
+        builder::assert_connectivity(&loaded);
+        assert_eq!(loaded.face_count(), 3);
+        assert!(!loaded.face(removed).is_valid());
+        for face in kept {
+            let positions = |mesh: &Mesh| -> Vec<Position> {
+                mesh.face(face)
+                    .vertices()
+                    .filter_map(|v| v.position())
+                    .collect()
+            };
+            assert_eq!(positions(&loaded), positions(&mesh));
+        }
+        assert!(loaded.constraints.is_fixed(&loaded, corner));
+        let lift = loaded.morph_target("lift").unwrap();
+        assert_eq!(lift.deltas.get(point), Some(&[0.0, 0.0, 1.0]));
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        // Dropping the generation of the reserved cell leaves one short.
+        let broken = json.replacen("\"generations\":[0,", "\"generations\":[", 1);
+        assert_ne!(broken, json);
+        assert!(serde_json::from_str::<Mesh>(&broken).is_err());
+    }
+
+    #[test]
+    fn can_iterate_over_faces() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = Mesh::default();
+
+        let e0 = mesh.add_element(Edge::default());
+        mesh.add_element(Face::new(e0));
+        mesh.add_element(Face::new(e0));
+        let f2 = mesh.add_element(Face::new(e0));
+        mesh.remove_element(f2);
+
+        assert_eq!(mesh.face_count(), 2);
+        let mut faces_iterated_over = 0;
+        for face in mesh.faces() {
+            assert!(face.is_valid());
+            assert_ne!(face.index, f2);
+            faces_iterated_over += 1;
+        }
+        assert_eq!(faces_iterated_over, mesh.face_count());
+    }
+
+    #[test]
+    fn face_normals_survive_awkward_polygons() {
+        // A concave L shape far away from the origin.
+        let offset = 1.0e6;
+        let outline = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ];
+        let positions: Vec<[f64; 3]> = outline
+            .iter()
+            .map(|[x, y]| [x + offset, y + offset, offset])
+            .collect();
+        let mesh: Mesh<f64> = builder::from_polygons(&positions, &[(0..6).collect()]);
+        let face = mesh.faces().next().unwrap();
+        assert_eq!(face.normal(), Some([0.0, 0.0, 1.0]));
+        assert_eq!(face.area(), 3.0);
+
+        // A skewed quad gets the average of its two halves.
+        let skewed = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.1],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.1],
+        ];
+        let mesh: Mesh<f64> = builder::from_polygons(&skewed, &[vec![0, 1, 2, 3]]);
+        let normal = mesh.faces().next().unwrap().normal().unwrap();
+        assert!(normal[2] > 0.99 && normal[0].abs() < 1.0e-12 && normal[1].abs() < 1.0e-12);
+
+        // Slivers have no meaningful normal.
+        let sliver = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 1.0e-17, 0.0]];
+        let mesh: Mesh<f64> = builder::from_polygons(&sliver, &[vec![0, 1, 2]]);
+        assert_eq!(mesh.faces().next().unwrap().normal(), None);
+    }
+
+    #[test]
+    fn flipping_all_faces_turns_the_mesh_inside_out() {
+        let mut mesh: Mesh<f64> = builder::cube();
+        let mut normals: attributes::Channel<Face, [f64; 3]> = attributes::Channel::new();
+        for face in mesh.faces() {
+            normals.set(face.index, mesh.face_normal(face.index));
+        }
+        mesh.flip_all_faces();
+        normals.flip_normals();
+        builder::assert_connectivity(&mesh);
+        for face in mesh.faces() {
+            assert_eq!(normals.get(face.index), Some(&mesh.face_normal(face.index)));
+        }
+        let report = analysis::watertight_report(&mesh, 1.0e-9);
+        assert!(report.is_closed() && report.volume < 0.0);
+
+        let mut open: Mesh = builder::grid(2, 2);
+        open.compute_vertex_normals(normals::NormalWeighting::Area);
+        open.flip_all_faces();
+        assert!(open
+            .vertices()
+            .all(|v| open.vertex_normals.get(v.index) == Some(&[0.0, 0.0, -1.0])));
+        builder::assert_connectivity(&open);
+        let mut rim = open
+            .vertices()
+            .filter(|v| v.position() != Some([1.0, 1.0, 0.0]));
+        assert!(rim.all(|v| !v.edge().face().is_valid()));
+        assert!(open.faces().all(|f| open.face_normal(f.index)[2] < -0.99));
     }
 }
@@ -0,0 +1,181 @@
+//! Minimal sparse linear algebra for the parameterization and fairing code.
+
+/// Compressed sparse row matrix.
+#[derive(Debug, Clone)]
+pub(crate) struct SparseMatrix {
+    rows: usize,
+    cols: usize,
+    row_starts: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl SparseMatrix {
+    /// Builds a matrix from `(row, column, value)` triplets, summing duplicates.
+    pub fn from_triplets(rows: usize, cols: usize, mut triplets: Vec<(usize, usize, f64)>) -> Self {
+        triplets.sort_by_key(|(row, col, _)| (*row, *col));
+        let mut row_starts = vec![0; rows + 1];
+        let mut columns = Vec::with_capacity(triplets.len());
+        let mut values: Vec<f64> = Vec::with_capacity(triplets.len());
+        let mut last = None;
+        for (row, col, value) in triplets {
+            if last == Some((row, col)) {
+                if let Some(v) = values.last_mut() {
+                    *v += value;
+                }
+                continue;
+            }
+            last = Some((row, col));
+            row_starts[row + 1] += 1;
+            columns.push(col);
+            values.push(value);
+        }
+        for row in 0..rows {
+            row_starts[row + 1] += row_starts[row];
+        }
+        SparseMatrix {
+            rows,
+            cols,
+            row_starts,
+            columns,
+            values,
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn mul(&self, x: &[f64]) -> Vec<f64> {
+        (0..self.rows)
+            .map(|row| {
+                (self.row_starts[row]..self.row_starts[row + 1])
+                    .map(|i| self.values[i] * x[self.columns[i]])
+                    .sum()
+            })
+            .collect()
+    }
+
+    pub fn mul_transpose(&self, y: &[f64]) -> Vec<f64> {
+        let mut out = vec![0.0; self.cols];
+        for (row, yv) in y.iter().enumerate().take(self.rows) {
+            for i in self.row_starts[row]..self.row_starts[row + 1] {
+                out[self.columns[i]] += self.values[i] * yv;
+            }
+        }
+        out
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Solves `min |Ax - b|` with conjugate gradients on the normal equations.
+pub(crate) fn least_squares(
+    a: &SparseMatrix,
+    b: &[f64],
+    tolerance: f64,
+    max_iterations: usize,
+) -> Vec<f64> {
+    let mut x = vec![0.0; a.cols()];
+    let mut r = b.to_vec();
+    let mut s = a.mul_transpose(&r);
+    let mut p = s.clone();
+    let mut gamma = dot(&s, &s);
+    let threshold = tolerance * tolerance * gamma.max(f64::MIN_POSITIVE);
+
+    for _ in 0..max_iterations {
+        if gamma <= threshold {
+            break;
+        }
+        let q = a.mul(&p);
+        let qq = dot(&q, &q);
+        if qq <= f64::MIN_POSITIVE {
+            break;
+        }
+        let alpha = gamma / qq;
+        x.iter_mut().zip(&p).for_each(|(x, p)| *x += alpha * p);
+        r.iter_mut().zip(&q).for_each(|(r, q)| *r -= alpha * q);
+        s = a.mul_transpose(&r);
+        let next_gamma = dot(&s, &s);
+        let beta = next_gamma / gamma;
+        gamma = next_gamma;
+        p.iter_mut().zip(&s).for_each(|(p, s)| *p = s + beta * *p);
+    }
+    x
+}
+
+/// Solves `Ax = b` for a symmetric positive definite `A` with conjugate gradients.
+#[cfg(feature = "poisson")]
+pub(crate) fn conjugate_gradient(
+    a: &SparseMatrix,
+    b: &[f64],
+    initial: Vec<f64>,
+    tolerance: f64,
+    max_iterations: usize,
+) -> Vec<f64> {
+    let mut x = initial;
+    let ax = a.mul(&x);
+    let mut r: Vec<f64> = b.iter().zip(&ax).map(|(b, ax)| b - ax).collect();
+    let mut p = r.clone();
+    let mut rr = dot(&r, &r);
+    let threshold = tolerance * tolerance * dot(b, b).max(f64::MIN_POSITIVE);
+
+    for _ in 0..max_iterations {
+        if rr <= threshold {
+            break;
+        }
+        let ap = a.mul(&p);
+        let pap = dot(&p, &ap);
+        if pap.abs() <= f64::MIN_POSITIVE {
+            break;
+        }
+        let alpha = rr / pap;
+        x.iter_mut().zip(&p).for_each(|(x, p)| *x += alpha * p);
+        r.iter_mut().zip(&ap).for_each(|(r, ap)| *r -= alpha * ap);
+        let next_rr = dot(&r, &r);
+        let beta = next_rr / rr;
+        rr = next_rr;
+        p.iter_mut().zip(&r).for_each(|(p, r)| *p = r + beta * *p);
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_duplicate_triplets() {
+        let m = SparseMatrix::from_triplets(2, 2, vec![(0, 0, 1.0), (1, 1, 2.0), (0, 0, 1.0)]);
+        assert_eq!(m.mul(&[1.0, 1.0]), vec![2.0, 2.0]);
+        assert_eq!(m.mul_transpose(&[1.0, 0.0]), vec![2.0, 0.0]);
+    }
+
+    #[test]
+    fn solves_overdetermined_system() {
+        // x = 1, y = 2, x + y = 3
+        let a = SparseMatrix::from_triplets(
+            3,
+            2,
+            vec![(0, 0, 1.0), (1, 1, 1.0), (2, 0, 1.0), (2, 1, 1.0)],
+        );
+        let x = least_squares(&a, &[1.0, 2.0, 3.0], 1.0e-12, 100);
+        assert!((x[0] - 1.0).abs() < 1.0e-9);
+        assert!((x[1] - 2.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "poisson")]
+    fn solves_spd_system() {
+        let a = SparseMatrix::from_triplets(
+            2,
+            2,
+            vec![(0, 0, 4.0), (0, 1, 1.0), (1, 0, 1.0), (1, 1, 3.0)],
+        );
+        let x = conjugate_gradient(&a, &[1.0, 2.0], vec![0.0; 2], 1.0e-12, 100);
+        assert!((x[0] - 1.0 / 11.0).abs() < 1.0e-9);
+        assert!((x[1] - 7.0 / 11.0).abs() < 1.0e-9);
+    }
+}
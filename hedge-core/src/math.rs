@@ -0,0 +1,106 @@
+//! Small `f64` vector helpers used by the geometry code.
+
+pub(crate) type Vec3 = [f64; 3];
+
+#[inline]
+pub(crate) fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[inline]
+pub(crate) fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+#[inline]
+pub(crate) fn scale(a: Vec3, s: f64) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+#[inline]
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline]
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[inline]
+pub(crate) fn length(a: Vec3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[inline]
+pub(crate) fn distance(a: Vec3, b: Vec3) -> f64 {
+    length(sub(a, b))
+}
+
+/// Returns the unit vector or zero when the input is degenerate.
+#[inline]
+pub(crate) fn normalize(a: Vec3) -> Vec3 {
+    let len = length(a);
+    if len > f64::EPSILON {
+        scale(a, 1.0 / len)
+    } else {
+        [0.0; 3]
+    }
+}
+
+#[inline]
+pub(crate) fn lerp(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    add(a, scale(sub(b, a), t))
+}
+
+/// Vector area of a closed polygon using Newell's method.
+///
+/// Points along the best fit normal with a length equal to the projected
+/// area. Unlike the cross product of two sides this is well defined for
+/// concave and non-planar loops, and coordinates are taken relative to the
+/// centroid so that polygons far from the origin don't lose precision.
+pub(crate) fn polygon_area_vector(points: &[Vec3]) -> Vec3 {
+    if points.len() < 3 {
+        return [0.0; 3];
+    }
+    let centroid = scale(
+        points.iter().fold([0.0; 3], |sum, p| add(sum, *p)),
+        1.0 / points.len() as f64,
+    );
+    let mut normal = [0.0; 3];
+    for (i, a) in points.iter().enumerate() {
+        let a = sub(*a, centroid);
+        let b = sub(points[(i + 1) % points.len()], centroid);
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    scale(normal, 0.5)
+}
+
+/// Unit normal of a polygon, zero when its area vanishes relative to the
+/// size of the polygon. The test is scale invariant so that small but well
+/// shaped faces still get a normal while slivers don't get a noisy one.
+pub(crate) fn polygon_normal(points: &[Vec3]) -> Vec3 {
+    let area_vector = polygon_area_vector(points);
+    let area = length(area_vector);
+    let perimeter: f64 = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| distance(*p, points[(i + 1) % points.len()]))
+        .sum();
+    if area > 0.0 && area > f64::EPSILON * perimeter * perimeter {
+        scale(area_vector, 1.0 / area)
+    } else {
+        [0.0; 3]
+    }
+}
+
+#[inline]
+pub(crate) fn triangle_area(a: Vec3, b: Vec3, c: Vec3) -> f64 {
+    length(polygon_area_vector(&[a, b, c]))
+}
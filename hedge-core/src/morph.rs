@@ -0,0 +1,125 @@
+//! Morph targets, also known as blend shapes.
+//!
+//! A target is a named offset for some of the points of a mesh. Blending
+//! adds each target's offsets scaled by its weight to the base positions,
+//! points a target has no offset for are left alone by it. `io::gltf`
+//! writes the targets along with the mesh.
+
+use crate::attributes::Channel;
+use crate::*;
+
+/// A named set of per-point position offsets.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MorphTarget<S: Scalar = f32> {
+    pub name: String,
+    pub deltas: Channel<Point, [S; 3]>,
+}
+
+impl<S: Scalar> MorphTarget<S> {
+    pub fn new(name: &str) -> Self {
+        MorphTarget {
+            name: name.to_string(),
+            deltas: Channel::new(),
+        }
+    }
+
+    /// Stores the offset which moves `point` from its base position to `position`.
+    pub fn set_position(&mut self, mesh: &Mesh<S>, point: PointIndex, position: Position<S>) {
+        if let Some(base) = mesh.points.get(point) {
+            let delta = [0, 1, 2].map(|k| position[k] - base.position[k]);
+            self.deltas.set(point, delta);
+        }
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Returns the target called `name`, adding an empty one at the end of
+    /// `morph_targets` when there is none.
+    pub fn add_morph_target(&mut self, name: &str) -> &mut MorphTarget<S> {
+        let index = match self.morph_targets.iter().position(|t| t.name == name) {
+            Some(index) => index,
+            None => {
+                self.morph_targets.push(MorphTarget::new(name));
+                self.morph_targets.len() - 1
+            }
+        };
+        &mut self.morph_targets[index]
+    }
+
+    pub fn morph_target(&self, name: &str) -> Option<&MorphTarget<S>> {
+        self.morph_targets.iter().find(|t| t.name == name)
+    }
+
+    /// Positions of every point with the targets blended in, one weight per
+    /// target in the order of `morph_targets`.
+    pub fn morphed_positions(&self, weights: &[f32]) -> Result<Channel<Point, Position<S>>> {
+        if weights.len() != self.morph_targets.len() {
+            return Err(Error::InvalidArgument(format!(
+                "{} weights for {} morph targets",
+                weights.len(),
+                self.morph_targets.len()
+            )));
+        }
+        let mut positions = Channel::new();
+        for (index, point) in self.points.iter() {
+            let mut position = scalar::to_f64(point.position);
+            for (target, weight) in self.morph_targets.iter().zip(weights) {
+                if let Some(delta) = target.deltas.get(index) {
+                    let delta = scalar::to_f64(*delta);
+                    position = math::add(position, math::scale(delta, *weight as f64));
+                }
+            }
+            positions.set(index, scalar::from_f64(position));
+        }
+        Ok(positions)
+    }
+
+    /// Moves the points to their blended positions, making the blend the
+    /// new base shape. The targets are kept and stay relative to the points.
+    pub fn apply_morph(&mut self, weights: &[f32]) -> Result<()> {
+        let positions = self.morphed_positions(weights)?;
+        for (index, point) in self.points.iter_mut() {
+            if let Some(position) = positions.get(index) {
+                point.position = *position;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_blend_targets() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(1, 1);
+        let points: Vec<PointIndex> = mesh.points.iter().map(|(index, _)| index).collect();
+        let copy = mesh.clone();
+        let raise = mesh.add_morph_target("raise");
+        raise.set_position(&copy, points[0], [0.0, 0.0, 2.0]);
+        mesh.add_morph_target("shift")
+            .deltas
+            .set(points[0], [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.add_morph_target("raise").deltas.len(), 1);
+        assert_eq!(mesh.morph_targets.len(), 2);
+
+        let positions = mesh.morphed_positions(&[0.5, 1.0]).unwrap();
+        assert_eq!(positions.get(points[0]), Some(&[1.0, 0.0, 1.0]));
+        assert_eq!(
+            positions.get(points[1]).copied(),
+            mesh.points.get(points[1]).map(|p| p.position)
+        );
+        assert!(mesh.morphed_positions(&[1.0]).is_err());
+
+        mesh.apply_morph(&[1.0, 0.0]).unwrap();
+        assert_eq!(
+            mesh.points.get(points[0]).unwrap().position,
+            [0.0, 0.0, 2.0]
+        );
+        assert!(mesh.morph_target("raise").is_some());
+        assert!(mesh.morph_target("smile").is_none());
+    }
+}
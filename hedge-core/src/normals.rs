@@ -0,0 +1,242 @@
+//! Normals for shading, per vertex or per corner.
+//!
+//! Vertex normals are kept on the mesh in `Mesh::vertex_normals` and used
+//! by the exporters and GPU buffers whenever no other normals are given.
+//!
+//! Like UVs, corner normals are written per corner, identified by the
+//! half-edge leaving the corner's vertex inside the face. Corners of a
+//! vertex share a normal when they are joined by smooth edges, so sharp
+//! edges and the boundary split a vertex into several shading groups.
+
+use crate::attributes::Channel;
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::HashMap;
+
+pub use crate::Normal;
+pub type NormalChannel = Channel<Edge, Normal>;
+
+/// How much each face around a vertex contributes to its normal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// By the area of the face, favouring large faces.
+    #[default]
+    Area,
+    /// By the angle of the face's corner at the vertex, which doesn't
+    /// change when a face is split into triangles.
+    Angle,
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Replaces `vertex_normals` with the average of the normals of the
+    /// faces around each vertex.
+    ///
+    /// Vertices without faces, or whose faces cancel out, get no normal.
+    pub fn compute_vertex_normals(&mut self, weighting: NormalWeighting) {
+        let mut sums: HashMap<VertexIndex, Vec3> = HashMap::new();
+        for face in self.faces() {
+            let normal = self.face_normal(face.index);
+            let area = self.face_area_vector(face.index);
+            for corner in face.edges() {
+                let weighted = match weighting {
+                    NormalWeighting::Area => area,
+                    NormalWeighting::Angle => {
+                        let origin = self.vertex_position(corner.vertex().index);
+                        let next = self.vertex_position(corner.next().vertex().index);
+                        let prev = self.vertex_position(corner.prev().vertex().index);
+                        let angle = corner_angle(math::sub(next, origin), math::sub(prev, origin));
+                        math::scale(normal, angle)
+                    }
+                };
+                let sum = sums.entry(corner.vertex().index).or_default();
+                *sum = math::add(*sum, weighted);
+            }
+        }
+        self.vertex_normals.clear();
+        for (vertex, sum) in sums {
+            let normal = math::normalize(sum);
+            if normal != [0.0; 3] {
+                self.vertex_normals.set(vertex, normal.map(|c| c as f32));
+            }
+        }
+    }
+
+    /// The vertex normals written to every corner of the faces, for the
+    /// APIs taking corner normals.
+    pub fn corner_vertex_normals(&self) -> NormalChannel {
+        let mut normals = NormalChannel::new();
+        for edge in self.edges().filter(|edge| edge.face().is_valid()) {
+            if let Some(normal) = self.vertex_normals.get(edge.vertex().index) {
+                normals.set(edge.index, *normal);
+            }
+        }
+        normals
+    }
+
+    /// The corner vertex normals, when the mesh has any.
+    pub(crate) fn stored_corner_normals(&self) -> Option<NormalChannel> {
+        (!self.vertex_normals.is_empty()).then(|| self.corner_vertex_normals())
+    }
+
+    /// Area weighted normals averaged across edges whose dihedral angle is
+    /// below `angle`, in radians, and kept flat across sharper ones.
+    ///
+    /// An angle of zero gives flat shading, anything above pi smooths every
+    /// interior edge. Corners of degenerate faces get no normal.
+    pub fn compute_auto_smooth_normals(&self, angle: f64) -> NormalChannel {
+        let sharp = uv::feature_edges(self, angle);
+        let mut groups = Groups::default();
+        for edge in self.edges() {
+            let twin = edge.twin();
+            if edge.face().is_valid() && twin.face().is_valid() && !sharp.contains(&edge.index) {
+                // Both corners leave the origin of `edge` across it.
+                groups.join(edge.index, twin.next().index);
+            }
+        }
+        let mut sums: HashMap<EdgeIndex, Vec3> = HashMap::new();
+        for edge in self.edges().filter(|edge| edge.face().is_valid()) {
+            let area = self.face_area_vector(edge.face().index);
+            let sum = sums.entry(groups.root(edge.index)).or_default();
+            *sum = math::add(*sum, area);
+        }
+        let mut normals = NormalChannel::new();
+        for edge in self.edges().filter(|edge| edge.face().is_valid()) {
+            if self.face_normal(edge.face().index) == [0.0; 3] {
+                continue;
+            }
+            let normal = math::normalize(sums[&groups.root(edge.index)]);
+            if normal != [0.0; 3] {
+                normals.set(edge.index, normal.map(|c| c as f32));
+            }
+        }
+        normals
+    }
+}
+
+/// Angle between two vectors, zero when either has no length.
+fn corner_angle(a: Vec3, b: Vec3) -> f64 {
+    let (a, b) = (math::normalize(a), math::normalize(b));
+    if a == [0.0; 3] || b == [0.0; 3] {
+        return 0.0;
+    }
+    math::dot(a, b).clamp(-1.0, 1.0).acos()
+}
+
+/// Disjoint sets of corners.
+#[derive(Default)]
+struct Groups {
+    parents: HashMap<EdgeIndex, EdgeIndex>,
+}
+
+impl Groups {
+    fn root(&mut self, mut corner: EdgeIndex) -> EdgeIndex {
+        while let Some(parent) = self.parents.get(&corner).copied() {
+            if let Some(grandparent) = self.parents.get(&parent).copied() {
+                self.parents.insert(corner, grandparent);
+            }
+            corner = parent;
+        }
+        corner
+    }
+
+    fn join(&mut self, a: EdgeIndex, b: EdgeIndex) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parents.insert(a, b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharp_edges_split_shading_groups() {
+        let _ = env_logger::try_init();
+        let cube: Mesh = builder::cube();
+        let flat = cube.compute_auto_smooth_normals(std::f64::consts::FRAC_PI_4);
+        assert_eq!(flat.len(), 24);
+        for edge in cube.edges() {
+            let face = scalar::to_f64(edge.face().normal().unwrap());
+            let normal = flat.get(edge.index).unwrap().map(f64::from);
+            assert!(math::distance(face, normal) < 1.0e-6);
+        }
+
+        let smooth = cube.compute_auto_smooth_normals(std::f64::consts::PI);
+        let third = 1.0 / 3.0f64.sqrt();
+        for edge in cube.edges() {
+            let normal = smooth.get(edge.index).unwrap();
+            assert!(normal
+                .iter()
+                .all(|c| (c.abs() as f64 - third).abs() < 1.0e-6));
+        }
+    }
+
+    #[test]
+    fn gentle_folds_are_smoothed() {
+        // Two quads folded by 30 degrees along their shared edge.
+        let (s, c) = (
+            std::f64::consts::FRAC_PI_6.sin(),
+            std::f64::consts::FRAC_PI_6.cos(),
+        );
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0 + c, 0.0, s],
+            [1.0 + c, 1.0, s],
+        ];
+        let mesh: Mesh<f64> =
+            Mesh::from_polygons(&positions, &[vec![0, 1, 2, 3], vec![1, 4, 5, 2]]);
+        let shared = mesh
+            .edges()
+            .find(|e| e.face().is_valid() && e.twin().face().is_valid())
+            .unwrap();
+        let across = shared.twin().next();
+        for (angle, joined) in [(0.6, true), (0.4, false)] {
+            let normals = mesh.compute_auto_smooth_normals(angle);
+            let (a, b) = (normals.get(shared.index), normals.get(across.index));
+            assert_eq!(a == b, joined);
+            assert_eq!(normals.len(), 8);
+        }
+    }
+
+    #[test]
+    fn vertex_normals_follow_the_weighting() {
+        // A 2x1 quad along +z folded up against a 1x1 quad along +x.
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let mut mesh: Mesh<f64> =
+            Mesh::from_polygons(&positions, &[vec![0, 1, 2, 3], vec![0, 3, 4, 5]]);
+        let origin = mesh
+            .vertices()
+            .find(|v| v.position() == Some([0.0; 3]))
+            .unwrap()
+            .index;
+
+        mesh.compute_vertex_normals(NormalWeighting::Area);
+        assert_eq!(mesh.vertex_normals.len(), 6);
+        let normal = mesh.vertex_normals.get(origin).unwrap().map(f64::from);
+        let expected = math::normalize([1.0, 0.0, 2.0]);
+        assert!(math::distance(normal, expected) < 1.0e-6);
+
+        mesh.compute_vertex_normals(NormalWeighting::Angle);
+        let normal = mesh.vertex_normals.get(origin).unwrap().map(f64::from);
+        let expected = math::normalize([1.0, 0.0, 1.0]);
+        assert!(math::distance(normal, expected) < 1.0e-6);
+
+        let corners = mesh.corner_vertex_normals();
+        assert_eq!(corners.len(), 8);
+        let renumbering = mesh.canonicalize();
+        let moved = mesh.vertex_normals.get(renumbering.vertices[&origin]);
+        assert_eq!(moved.map(|n| n.map(f64::from)), Some(normal));
+    }
+}
@@ -0,0 +1,137 @@
+use super::*;
+
+/// Cuts a corner off the mesh, replacing a vertex by a face.
+///
+/// Every edge at the vertex is split `distance` away from it and each face
+/// around it loses the corner between the two splits. The pieces cut off
+/// are merged into one face whose corners are the splits, which is
+/// returned. A vertex on the boundary needs at least two faces and leaves
+/// the new face on the boundary. `distance` has to be shorter than every
+/// edge at the vertex.
+pub fn bevel_vertex<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vertex: VertexIndex,
+    distance: f64,
+) -> Result<FaceIndex> {
+    let mut span = trace::span!(mesh, "bevel_vertex");
+    let mesh = span.mesh();
+    if !mesh.vertex(vertex).is_valid() {
+        return Err(Error::InvalidVertex(vertex));
+    }
+    let spokes: Vec<EdgeIndex> = mesh.vertex(vertex).edges().map(|e| e.index).collect();
+    let faces: Vec<FaceIndex> = mesh
+        .vertex(vertex)
+        .edges()
+        .map(|e| e.face().index)
+        .filter(|f| f.is_valid())
+        .collect();
+    let boundary = is_boundary_vertex(mesh, vertex);
+    if faces.len() < if boundary { 2 } else { 3 } {
+        return Err(Error::NonManifold(format!(
+            "{:?} has too few faces around it to be bevelled",
+            vertex
+        )));
+    }
+    let lengths: Vec<f64> = spokes
+        .iter()
+        .map(|e| {
+            let far = mesh.edge(*e).twin().vertex().index;
+            math::distance(mesh.vertex_position(vertex), mesh.vertex_position(far))
+        })
+        .collect();
+    let shortest = lengths.iter().copied().fold(f64::INFINITY, f64::min);
+    if distance <= 0.0 || distance >= shortest || !distance.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "bevels need a distance between 0 and {}, got {}",
+            shortest, distance
+        )));
+    }
+
+    for (spoke, length) in spokes.iter().zip(lengths) {
+        split_edge(mesh, *spoke, distance / length)?;
+    }
+    for face in faces {
+        let corner = mesh
+            .face(face)
+            .edges()
+            .find(|e| e.vertex().index == vertex)
+            .ok_or(Error::InvalidFace(face))?;
+        let (next, prev) = (corner.next().vertex().index, corner.prev().vertex().index);
+        split_face(mesh, face, next, prev)?;
+    }
+
+    if !boundary {
+        return dissolve_vertex(mesh, vertex);
+    }
+    // Merge the corners cut off around a boundary vertex, then drop the
+    // vertex from the merged face.
+    let inner: Vec<EdgeIndex> = mesh
+        .vertex(vertex)
+        .edges()
+        .filter(|e| !e.is_boundary())
+        .map(|e| e.index)
+        .collect();
+    let mut merged = FaceIndex::default();
+    for spoke in inner {
+        merged = dissolve_edge(mesh, spoke)?;
+    }
+    dissolve_vertex(mesh, vertex)?;
+    Ok(merged)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Replaces a vertex by a face, see `ops::bevel_vertex`.
+    pub fn bevel_vertex(&mut self, vertex: VertexIndex, distance: f64) -> Result<FaceIndex> {
+        bevel_vertex(self, vertex, distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex_at(mesh: &Mesh, position: Position) -> VertexIndex {
+        mesh.vertices()
+            .find(|v| v.position() == Some(position))
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn cube_corners_become_triangles() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::cube();
+        let corner = vertex_at(&mesh, [1.0, 1.0, 1.0]);
+        assert!(mesh.bevel_vertex(corner, 1.0).is_err());
+        let face = mesh.bevel_vertex(corner, 0.25).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 7);
+        assert_eq!(mesh.vertex_count(), 10);
+        assert!(!mesh.vertex(corner).is_valid());
+        assert_eq!(mesh.face(face).edges().count(), 3);
+        assert!(mesh
+            .faces()
+            .all(|f| f.edges().count() != 4 || f.index != face));
+        let normal = mesh.face_normal(face);
+        assert!(math::dot(normal, math::normalize([1.0; 3])) > 0.99);
+        for v in mesh.face(face).vertices() {
+            let p = mesh.vertex_position(v.index);
+            assert!((p[0] + p[1] + p[2] - 2.75).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn boundary_vertices_leave_an_open_bevel() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let middle = vertex_at(&mesh, [1.0, 0.0, 0.0]);
+        let face = mesh.bevel_vertex(middle, 0.5).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 3);
+        assert_eq!(mesh.face(face).edges().count(), 3);
+        assert!(mesh.face(face).edges().any(|e| !e.twin().face().is_valid()));
+        assert!(mesh.face_normal(face)[2] > 0.99);
+
+        let corner = vertex_at(&mesh, [0.0, 0.0, 0.0]);
+        assert!(mesh.bevel_vertex(corner, 0.5).is_err());
+    }
+}
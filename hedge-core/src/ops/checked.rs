@@ -0,0 +1,295 @@
+//! Operators which refuse to break the manifold.
+//!
+//! Each operator first verifies that applying it leaves every edge with at
+//! most two faces and every vertex with a single fan. When that isn't the
+//! case `Error::NonManifold` describes why and the mesh is left untouched.
+
+use super::*;
+use std::collections::HashSet;
+
+fn non_manifold<T>(reason: String) -> Result<T> {
+    Err(Error::NonManifold(reason))
+}
+
+/// Checked `ops::split_edge`, `t` has to lie strictly inside the edge.
+pub fn split_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex, t: f64) -> Result<VertexIndex> {
+    edge_pair(mesh, edge)?;
+    if !(t > 0.0 && t < 1.0) {
+        return Err(Error::InvalidArgument(format!(
+            "edges are split strictly between their ends, got {}",
+            t
+        )));
+    }
+    super::split_edge(mesh, edge, t)
+}
+
+/// Checked `ops::flip_edge`.
+pub fn flip_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<()> {
+    let (e, twin) = edge_pair(mesh, edge)?;
+    for face in [e.face_index, twin.face_index] {
+        if !mesh.face(face).is_valid() {
+            return non_manifold(format!("{:?} lies on the boundary", edge));
+        }
+        if mesh.face(face).edges().count() != 3 {
+            return non_manifold(format!("{:?} is not a triangle", face));
+        }
+    }
+    let c = edge_data(mesh, e.prev_index)?.vertex_index;
+    let d = edge_data(mesh, twin.prev_index)?.vertex_index;
+    if c == d {
+        return non_manifold(format!(
+            "both sides of {:?} share their opposite corner",
+            edge
+        ));
+    }
+    if neighbors(mesh, c).contains(&d) {
+        return non_manifold(format!(
+            "flipping {:?} would duplicate the edge between {:?} and {:?}",
+            edge, c, d
+        ));
+    }
+    super::flip_edge(mesh, edge)
+}
+
+/// Checked `ops::collapse_edge`.
+///
+/// Uses the link condition: the only vertices adjacent to both ends of the
+/// edge may be the opposite corners of the triangles on either side.
+pub fn collapse_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<VertexIndex> {
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let (a, b) = (e.vertex_index, twin.vertex_index);
+
+    let mut opposite = HashSet::new();
+    for (half, data) in [(edge, &e), (e.twin_index, &twin)] {
+        let face = mesh.face(data.face_index);
+        if !face.is_valid() {
+            if FaceEdges::from_edge(mesh.edge(half)).count() <= 3 {
+                return non_manifold(format!(
+                    "collapsing {:?} would close its boundary loop",
+                    edge
+                ));
+            }
+            continue;
+        }
+        if face.edges().count() != 3 {
+            continue;
+        }
+        let corner = edge_data(mesh, data.prev_index)?.vertex_index;
+        let sides = [data.next_index, data.prev_index];
+        if sides.iter().all(|side| mesh.edge(*side).is_boundary()) {
+            return non_manifold(format!(
+                "collapsing {:?} would leave a dangling edge at {:?}",
+                edge, corner
+            ));
+        }
+        let minimum = if is_boundary_vertex(mesh, corner) {
+            3
+        } else {
+            4
+        };
+        if mesh.vertex(corner).edges().count() < minimum {
+            return non_manifold(format!(
+                "collapsing {:?} would leave {:?} with too few edges",
+                edge, corner
+            ));
+        }
+        opposite.insert(corner);
+    }
+
+    let around_a: HashSet<VertexIndex> = neighbors(mesh, a).into_iter().collect();
+    if let Some(shared) = neighbors(mesh, b)
+        .into_iter()
+        .find(|v| around_a.contains(v) && !opposite.contains(v))
+    {
+        return non_manifold(format!(
+            "collapsing {:?} would pinch the surface at {:?}",
+            edge, shared
+        ));
+    }
+    let on_boundary = !e.face_index.is_valid() || !twin.face_index.is_valid();
+    if !on_boundary && is_boundary_vertex(mesh, a) && is_boundary_vertex(mesh, b) {
+        return non_manifold(format!(
+            "collapsing {:?} would join two boundaries at a single vertex",
+            edge
+        ));
+    }
+    super::collapse_edge(mesh, edge)
+}
+
+/// Checked `ops::remove_face`.
+///
+/// A face in the middle of a boundary vertex's fan can't be removed as that
+/// would split the fan in two.
+pub fn remove_face<S: Scalar>(mesh: &mut Mesh<S>, face: FaceIndex) -> Result<()> {
+    if !mesh.face(face).is_valid() {
+        return Err(Error::InvalidFace(face));
+    }
+    for edge in mesh.face(face).edges() {
+        let vertex = edge.vertex().index;
+        let touches_boundary =
+            !edge.twin().face().is_valid() || !edge.prev().twin().face().is_valid();
+        if is_boundary_vertex(mesh, vertex) && !touches_boundary {
+            return non_manifold(format!(
+                "removing {:?} would split the fan around {:?}",
+                face, vertex
+            ));
+        }
+    }
+    super::remove_face(mesh, face)
+}
+
+/// Checked `ops::dissolve_edge`, the two faces may only share this edge and
+/// neither end may be left with a single edge.
+pub fn dissolve_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<FaceIndex> {
+    super::dissolve::checked_edge(mesh, edge)?;
+    super::dissolve_edge(mesh, edge)
+}
+
+/// Checked `ops::dissolve_vertex`.
+///
+/// Joining two edges must not duplicate an edge or leave a face with two
+/// sides, merging a fan must not make the merged face touch itself.
+pub fn dissolve_vertex<S: Scalar>(mesh: &mut Mesh<S>, vertex: VertexIndex) -> Result<FaceIndex> {
+    super::dissolve::checked_vertex(mesh, vertex)?;
+    super::dissolve_vertex(mesh, vertex)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Merges the ends of an edge into one vertex, which is returned.
+    ///
+    /// Runs `checked::collapse_edge`, so triangles next to the edge are
+    /// removed and the collapse is refused when it would break the manifold.
+    /// The merged vertex sits in the middle of the edge unless one end is
+    /// fixed by the mesh's constraints, it stays in place then and edges
+    /// with both ends fixed aren't collapsed.
+    pub fn collapse_edge(&mut self, edge: EdgeIndex) -> Result<VertexIndex> {
+        let (e, twin) = edge_pair(self, edge)?;
+        let fixed = |v| self.constraints.is_fixed(self, v);
+        let (kept, keep_position) = match (fixed(e.vertex_index), fixed(twin.vertex_index)) {
+            (true, true) => {
+                return Err(Error::InvalidArgument(format!(
+                    "both ends of {:?} are fixed",
+                    edge
+                )))
+            }
+            (true, false) => (edge, true),
+            (false, true) => (e.twin_index, true),
+            (false, false) => (edge, false),
+        };
+        let position = self.vertex_position(self.edge(kept).vertex().index);
+        let vertex = collapse_edge(self, kept)?;
+        if keep_position {
+            if let Some(point) = self.vertex(vertex).element().map(|v| v.point_index) {
+                if let Some(p) = self.get_element_mut(point) {
+                    p.position = scalar::from_f64(position);
+                }
+            }
+        }
+        Ok(vertex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_between(mesh: &Mesh, a: Position, b: Position) -> EdgeIndex {
+        mesh.edges()
+            .find(|e| e.vertex().position() == Some(a) && e.twin().vertex().position() == Some(b))
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn collapses_are_refused_when_they_would_pinch() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::triangle_grid(2, 2);
+        // Both ends are on the boundary but the edge runs through the inside.
+        let edge = edge_between(&mesh, [1.0, 0.0, 0.0], [2.0, 1.0, 0.0]);
+        let before = mesh.edge_count();
+        assert!(matches!(
+            collapse_edge(&mut mesh, edge),
+            Err(Error::NonManifold(_))
+        ));
+        assert_eq!(mesh.edge_count(), before);
+
+        let inner = edge_between(&mesh, [1.0, 1.0, 0.0], [2.0, 2.0, 0.0]);
+        collapse_edge(&mut mesh, inner).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 6);
+    }
+
+    #[test]
+    fn mesh_collapses_keep_fixed_ends() {
+        let mut mesh: Mesh = builder::triangle_grid(2, 2);
+        let edge = edge_between(&mesh, [1.0, 1.0, 0.0], [2.0, 2.0, 0.0]);
+        let pinned = mesh.edge(edge).twin().vertex().index;
+        mesh.constraints.pin(pinned);
+        assert_eq!(mesh.collapse_edge(edge).unwrap(), pinned);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(mesh.vertex(pinned).position(), Some([2.0, 2.0, 0.0]));
+
+        let mut free: Mesh = builder::triangle_grid(2, 2);
+        let edge = edge_between(&free, [1.0, 1.0, 0.0], [2.0, 2.0, 0.0]);
+        let kept = free.collapse_edge(edge).unwrap();
+        assert_eq!(free.vertex(kept).position(), Some([1.5, 1.5, 0.0]));
+        let edge = free.vertex(kept).edge().index;
+        free.constraints.lock_boundary = true;
+        free.constraints.pin(kept);
+        assert!(free.collapse_edge(edge).is_err());
+    }
+
+    #[test]
+    fn closed_meshes_keep_enough_vertices() {
+        let mut mesh: Mesh = builder::octahedron();
+        while let Some(edge) = mesh
+            .edges()
+            .map(|e| e.index)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .find(|e| collapse_edge(&mut mesh.clone(), *e).is_ok())
+        {
+            collapse_edge(&mut mesh, edge).unwrap();
+            builder::assert_connectivity(&mesh);
+        }
+        // A tetrahedron can't be collapsed any further.
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.face_count(), 4);
+    }
+
+    #[test]
+    fn flips_and_removals_are_checked() {
+        let mut mesh: Mesh = builder::triangle_grid(1, 1);
+        let boundary = mesh.edges().find(|e| e.is_boundary()).unwrap().index;
+        assert!(flip_edge(&mut mesh, boundary).is_err());
+        let diagonal = mesh.edges().find(|e| !e.is_boundary()).unwrap().index;
+        flip_edge(&mut mesh, diagonal).unwrap();
+        assert!(split_edge(&mut mesh, diagonal, 1.0).is_err());
+
+        // The middle of the fan around (1, 0) can't be taken out.
+        let mut strip: Mesh = builder::triangle_grid(2, 1);
+        let middle = strip
+            .faces()
+            .find(|f| {
+                let corners: Vec<Position> = f.vertices().filter_map(|v| v.position()).collect();
+                corners.contains(&[1.0, 0.0, 0.0])
+                    && corners.contains(&[2.0, 1.0, 0.0])
+                    && corners.contains(&[1.0, 1.0, 0.0])
+            })
+            .unwrap()
+            .index;
+        assert!(matches!(
+            remove_face(&mut strip, middle),
+            Err(Error::NonManifold(_))
+        ));
+        assert_eq!(strip.face_count(), 4);
+        let end = strip
+            .faces()
+            .find(|f| f.vertices().any(|v| v.position() == Some([0.0, 1.0, 0.0])))
+            .unwrap()
+            .index;
+        remove_face(&mut strip, end).unwrap();
+        builder::assert_connectivity(&strip);
+    }
+}
@@ -0,0 +1,825 @@
+use super::triangulate::{ear_clip, ear_clipping};
+use super::*;
+use crate::math::{self, Vec3};
+use crate::predicates;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// An oriented plane, the side its normal points to is above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plane {
+    pub origin: [f64; 3],
+    pub normal: [f64; 3],
+}
+
+impl Plane {
+    pub fn new(origin: [f64; 3], normal: [f64; 3]) -> Self {
+        Plane { origin, normal }
+    }
+
+    /// Positive above the plane, measured in lengths of the normal.
+    pub fn signed_distance(&self, point: [f64; 3]) -> f64 {
+        math::dot(math::sub(point, self.origin), self.normal)
+    }
+
+    /// Two unit vectors spanning the plane, which must have a unit normal.
+    fn axes(&self) -> (Vec3, Vec3) {
+        let u = math::normalize(math::cross(
+            self.normal,
+            if self.normal[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            },
+        ));
+        (u, math::cross(self.normal, u))
+    }
+
+    /// The same plane with a unit normal, failing for a zero normal.
+    fn normalized(&self) -> Result<Plane> {
+        let length = math::length(self.normal);
+        if length == 0.0 || !length.is_finite() {
+            return Err(Error::InvalidArgument(format!(
+                "planes need a non-zero normal, got {:?}",
+                self.normal
+            )));
+        }
+        Ok(Plane::new(
+            self.origin,
+            math::scale(self.normal, 1.0 / length),
+        ))
+    }
+}
+
+/// Cuts a mesh in two along a plane, returning the parts above and below.
+///
+/// Faces crossing the plane are clipped and the openings left along the cut
+/// are closed with triangulated caps, so a closed mesh gives two closed
+/// meshes. Cuts through holes of the cross section, like slicing a torus,
+/// get caps with matching holes. A face lying in the plane goes to the part
+/// its normal points away from.
+pub fn split_by_plane<S: Scalar>(mesh: &Mesh<S>, plane: &Plane) -> Result<(Mesh<S>, Mesh<S>)> {
+    let plane = plane.normalized()?;
+    let mut cut = Cut::new(mesh, &plane);
+    for face in mesh.faces() {
+        let corners: Vec<usize> = face
+            .vertices()
+            .filter_map(|v| v.element())
+            .map(|v| cut.point(mesh, v.point_index))
+            .collect();
+        if corners.len() >= 3 {
+            cut.add_polygon(&corners);
+        }
+    }
+    let caps = cut.caps();
+    let above: Vec<[usize; 3]> = caps.iter().map(|[a, b, c]| [*c, *b, *a]).collect();
+    Ok((cut.build(&cut.above, &above), cut.build(&cut.below, &caps)))
+}
+
+/// Which side of the plane `bisect` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepSide {
+    Both,
+    /// The side the normal points to.
+    Above,
+    Below,
+}
+
+/// Cuts a mesh along a plane in place, returning a half-edge of every edge
+/// along the cut, on a face that was kept where there is one.
+///
+/// Edges crossing the plane are split and faces crossing it are split
+/// between the new vertices, faces meeting the plane more than twice are
+/// ear clipped first. Faces on the side which isn't kept are removed. With
+/// `cap` the openings along the cut are closed with triangles, where
+/// keeping both sides rips them apart first and gives each their own cap
+/// and points. A cap triangle which can't be added fails the whole call,
+/// so a capped result is never left open.
+pub fn bisect<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    plane: &Plane,
+    keep: KeepSide,
+    cap: bool,
+) -> Result<Vec<EdgeIndex>> {
+    let plane = plane.normalized()?;
+    let mut span = trace::span!(mesh, "bisect");
+    let mesh = span.mesh();
+    let extent = mesh
+        .points
+        .iter()
+        .map(|(_, p)| math::length(math::sub(scalar::to_f64(p.position), plane.origin)))
+        .fold(0.0, f64::max);
+    let eps = 1.0e-12 * extent.max(1.0);
+    let mut on_plane: HashSet<VertexIndex> = mesh
+        .vertices()
+        .filter(|v| plane.signed_distance(mesh.vertex_position(v.index)).abs() <= eps)
+        .map(|v| v.index)
+        .collect();
+    let side = |mesh: &Mesh<S>, on_plane: &HashSet<VertexIndex>, v: VertexIndex| {
+        if on_plane.contains(&v) {
+            0.0
+        } else {
+            plane.signed_distance(mesh.vertex_position(v))
+        }
+    };
+
+    // Concave faces meeting the plane several times become triangles.
+    let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+    for face in faces {
+        let sides: Vec<f64> = mesh
+            .face(face)
+            .vertices()
+            .map(|v| side(mesh, &on_plane, v.index))
+            .collect();
+        let touches = (0..sides.len())
+            .filter(|k| sides[*k] == 0.0 || sides[*k] * sides[(k + 1) % sides.len()] < 0.0)
+            .count();
+        if touches > 2 && sides.len() > 3 {
+            triangulate_face(mesh, face, TriangulationMethod::EarClipping)?;
+        }
+    }
+
+    let crossing: Vec<(EdgeIndex, f64)> = mesh
+        .edges()
+        .filter(|e| e.index < e.twin().index)
+        .filter_map(|e| {
+            let da = side(mesh, &on_plane, e.vertex().index);
+            let db = side(mesh, &on_plane, e.twin().vertex().index);
+            (da * db < 0.0).then(|| (e.index, da / (da - db)))
+        })
+        .collect();
+    for (edge, t) in crossing {
+        on_plane.insert(split_edge(mesh, edge, t)?);
+    }
+
+    let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+    for face in faces {
+        let corners: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        let sides: Vec<f64> = corners.iter().map(|v| side(mesh, &on_plane, *v)).collect();
+        let touching: Vec<VertexIndex> = corners
+            .iter()
+            .zip(&sides)
+            .filter(|(_, d)| **d == 0.0)
+            .map(|(v, _)| *v)
+            .collect();
+        let straddles = sides.iter().any(|d| *d > 0.0) && sides.iter().any(|d| *d < 0.0);
+        if straddles && touching.len() == 2 {
+            split_face(mesh, face, touching[0], touching[1])?;
+        }
+    }
+
+    // Faces take the side of their corners off the plane, faces lying
+    // in it go to the side their normal points away from.
+    let face_side = |mesh: &Mesh<S>, on_plane: &HashSet<VertexIndex>, face: FaceIndex| {
+        let corners = mesh.face(face).vertices();
+        let sides = corners.map(|v| side(mesh, on_plane, v.index));
+        match sides.fold(0.0, |found, d| if found == 0.0 { d } else { found }) {
+            d if d > 0.0 => KeepSide::Above,
+            d if d < 0.0 => KeepSide::Below,
+            _ if math::dot(mesh.face_normal(face), plane.normal) > 0.0 => KeepSide::Below,
+            _ => KeepSide::Above,
+        }
+    };
+    let cut: Vec<EdgeIndex> = mesh
+        .edges()
+        .filter(|e| e.index < e.twin().index)
+        .filter(|e| on_plane.contains(&e.vertex().index))
+        .filter(|e| on_plane.contains(&e.twin().vertex().index))
+        .filter(|e| e.face().is_valid() && e.twin().face().is_valid())
+        .filter(|e| {
+            face_side(mesh, &on_plane, e.face().index)
+                != face_side(mesh, &on_plane, e.twin().face().index)
+        })
+        .map(|e| e.index)
+        .collect();
+
+    if keep == KeepSide::Both {
+        if cap {
+            // Each half gets its own points along the cut.
+            for vertex in rip(mesh, &cut)? {
+                let position = mesh.vertex(vertex).position();
+                if let Some(position) = position {
+                    let point = mesh.add_element(Point::from_position(position));
+                    if let Some(v) = mesh.get_element_mut(vertex) {
+                        v.point_index = point;
+                    }
+                }
+                on_plane.insert(vertex);
+            }
+        }
+    } else {
+        let discarded: Vec<FaceIndex> = mesh
+            .faces()
+            .map(|f| f.index)
+            .filter(|f| face_side(mesh, &on_plane, *f) != keep)
+            .collect();
+        for face in discarded {
+            remove_face(mesh, face)?;
+        }
+    }
+
+    if cap {
+        let mut seen = HashSet::new();
+        let mut groups: HashMap<bool, Vec<Vec<VertexIndex>>> = HashMap::new();
+        let open: Vec<EdgeIndex> = mesh
+            .edges()
+            .filter(|e| !e.face().is_valid())
+            .map(|e| e.index)
+            .collect();
+        for edge in open {
+            if seen.contains(&edge) {
+                continue;
+            }
+            let sides = boundary_loop(mesh, edge)?;
+            seen.extend(sides.iter().copied());
+            let ring: Vec<VertexIndex> =
+                sides.iter().map(|e| mesh.edge(*e).vertex().index).collect();
+            if ring.len() >= 3 && ring.iter().all(|v| on_plane.contains(v)) {
+                let inside = mesh.edge(edge).twin().face().index;
+                let above = face_side(mesh, &on_plane, inside) == KeepSide::Above;
+                groups.entry(above).or_default().push(ring);
+            }
+        }
+        for rings in groups.into_values() {
+            fill_cap(mesh, &plane, &rings)?;
+        }
+    }
+    Ok(cut
+        .into_iter()
+        .filter(|e| mesh.edge(*e).is_valid())
+        .map(|e| {
+            if mesh.edge(e).face().is_valid() {
+                e
+            } else {
+                mesh.edge(e).twin().index
+            }
+        })
+        .collect())
+}
+
+/// Replaces the part of a mesh below a plane with a mirror image of the part
+/// above it, returning the mirrored faces.
+///
+/// The mesh is bisected, vertices on the plane are moved onto it exactly
+/// and the copy is welded to the original along them, so a mesh crossing
+/// the plane stays in one piece. Vertices count as on the plane within the
+/// default weld tolerance scaled to the mesh.
+pub fn symmetrize<S: Scalar>(mesh: &mut Mesh<S>, plane: &Plane) -> Result<Vec<FaceIndex>> {
+    let plane = plane.normalized()?;
+    let mut span = trace::span!(mesh, "symmetrize");
+    let mesh = span.mesh();
+    bisect(mesh, &plane, KeepSide::Above, false)?;
+    let extent = mesh
+        .points
+        .iter()
+        .map(|(_, p)| math::length(math::sub(scalar::to_f64(p.position), plane.origin)))
+        .fold(0.0, f64::max);
+    let eps = Tolerances::relative_to(extent.max(1.0)).weld;
+    for (_, point) in mesh.points.iter_mut() {
+        let position = scalar::to_f64(point.position);
+        let distance = plane.signed_distance(position);
+        if distance.abs() <= eps {
+            let projected = math::sub(position, math::scale(plane.normal, distance));
+            point.position = scalar::from_f64(projected);
+        }
+    }
+
+    let n = plane.normal;
+    let offset = 2.0 * math::dot(n, plane.origin);
+    let mut mirror = scene::Transform::IDENTITY;
+    for (i, row) in mirror.matrix.iter_mut().take(3).enumerate() {
+        for (j, value) in row.iter_mut().take(3).enumerate() {
+            *value -= 2.0 * n[i] * n[j];
+        }
+        row[3] = offset * n[i];
+    }
+    let originals: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+    let copies = mesh.duplicate_transformed(&originals, &mirror)?;
+    let copied: HashSet<FaceIndex> = copies.iter().copied().collect();
+
+    // Each boundary loop with sides on the plane is welded to its mirror
+    // image in a single pass.
+    let on_plane = |mesh: &Mesh<S>, v: VertexIndex| {
+        plane.signed_distance(mesh.vertex_position(v)).abs() <= eps
+    };
+    let open: Vec<EdgeIndex> = mesh
+        .edges()
+        .filter(|e| !e.face().is_valid() && !copied.contains(&e.twin().face().index))
+        .map(|e| e.index)
+        .collect();
+    let mut seen = HashSet::new();
+    for edge in open {
+        if seen.contains(&edge) || !mesh.edge(edge).is_valid() {
+            continue;
+        }
+        let sides = boundary_loop(mesh, edge)?;
+        seen.extend(sides.iter().copied());
+        let ends = |mesh: &Mesh<S>, e: EdgeIndex| {
+            let e = mesh.edge(e);
+            (e.vertex().index, e.next().vertex().index)
+        };
+        let Some(side) = sides.iter().copied().find(|s| {
+            let (from, to) = ends(mesh, *s);
+            on_plane(mesh, from) && on_plane(mesh, to)
+        }) else {
+            continue;
+        };
+        let (from, to) = ends(mesh, side);
+        let (from, to) = (mesh.vertex_position(from), mesh.vertex_position(to));
+        let image = mesh.edges().find(|e| {
+            !e.face().is_valid()
+                && copied.contains(&e.twin().face().index)
+                && math::distance(mesh.vertex_position(e.vertex().index), to) <= eps
+                && math::distance(mesh.vertex_position(e.next().vertex().index), from) <= eps
+        });
+        if let Some(image) = image.map(|e| e.index) {
+            weld_boundaries(mesh, side, image, eps)?;
+        }
+    }
+    Ok(copies)
+}
+
+/// Closes the boundary loops of one side of a cut with triangles.
+fn fill_cap<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    plane: &Plane,
+    rings: &[Vec<VertexIndex>],
+) -> Result<()> {
+    let (u, v) = plane.axes();
+    let mut points = Vec::new();
+    let mut flat = Vec::new();
+    let loops: Vec<Vec<usize>> = rings
+        .iter()
+        .map(|ring| {
+            ring.iter()
+                .map(|vertex| {
+                    let d = math::sub(mesh.vertex_position(*vertex), plane.origin);
+                    flat.push([math::dot(d, u), math::dot(d, v)]);
+                    let point = mesh.vertex(*vertex).element().map(|v| v.point_index);
+                    points.push(point.ok_or(Error::InvalidVertex(*vertex)));
+                    points.len() - 1
+                })
+                .collect()
+        })
+        .collect();
+    for triangle in fill_loops(&loops, &flat) {
+        let corners = triangle
+            .iter()
+            .map(|k| points[*k].clone())
+            .collect::<Result<Vec<PointIndex>>>()?;
+        mesh.add_face(&corners)?;
+    }
+    Ok(())
+}
+
+struct Cut {
+    plane: Plane,
+    positions: Vec<Vec3>,
+    distances: Vec<f64>,
+    points: HashMap<PointIndex, usize>,
+    crossings: HashMap<(usize, usize), usize>,
+    above: Vec<Vec<usize>>,
+    below: Vec<Vec<usize>>,
+    /// Distances this close to zero count as lying on the plane.
+    eps: f64,
+}
+
+impl Cut {
+    fn new<S: Scalar>(mesh: &Mesh<S>, plane: &Plane) -> Self {
+        let extent = mesh
+            .points
+            .iter()
+            .map(|(_, p)| scalar::to_f64(p.position))
+            .map(|p| math::length(math::sub(p, plane.origin)))
+            .fold(0.0, f64::max);
+        Cut {
+            plane: *plane,
+            positions: Vec::new(),
+            distances: Vec::new(),
+            points: HashMap::new(),
+            crossings: HashMap::new(),
+            above: Vec::new(),
+            below: Vec::new(),
+            eps: 1.0e-12 * extent.max(1.0),
+        }
+    }
+
+    fn push(&mut self, position: Vec3, distance: f64) -> usize {
+        self.positions.push(position);
+        self.distances.push(if distance.abs() <= self.eps {
+            0.0
+        } else {
+            distance
+        });
+        self.positions.len() - 1
+    }
+
+    fn point<S: Scalar>(&mut self, mesh: &Mesh<S>, point: PointIndex) -> usize {
+        if let Some(index) = self.points.get(&point) {
+            return *index;
+        }
+        let position = mesh.position(point).map(scalar::to_f64).unwrap_or_default();
+        let index = self.push(position, self.plane.signed_distance(position));
+        self.points.insert(point, index);
+        index
+    }
+
+    /// The point where the segment between two points of opposite sides
+    /// meets the plane, shared by both faces along the edge.
+    fn crossing(&mut self, a: usize, b: usize) -> usize {
+        let key = (a.min(b), a.max(b));
+        if let Some(index) = self.crossings.get(&key) {
+            return *index;
+        }
+        let (da, db) = (self.distances[key.0], self.distances[key.1]);
+        let t = da / (da - db);
+        let position = math::lerp(self.positions[key.0], self.positions[key.1], t);
+        let index = self.push(position, 0.0);
+        self.crossings.insert(key, index);
+        index
+    }
+
+    fn add_polygon(&mut self, corners: &[usize]) {
+        let distances: Vec<f64> = corners.iter().map(|c| self.distances[*c]).collect();
+        if distances.iter().all(|d| *d == 0.0) {
+            let positions: Vec<Vec3> = corners.iter().map(|c| self.positions[*c]).collect();
+            if math::dot(math::polygon_area_vector(&positions), self.plane.normal) > 0.0 {
+                self.below.push(corners.to_vec());
+            } else {
+                self.above.push(corners.to_vec());
+            }
+            return;
+        }
+        let touches = (0..corners.len())
+            .filter(|k| {
+                let next = distances[(k + 1) % corners.len()];
+                distances[*k] == 0.0 || distances[*k] * next < 0.0
+            })
+            .count();
+        if touches > 2 && corners.len() > 3 {
+            // A concave face meeting the plane several times is clipped one
+            // triangle at a time so every piece stays a simple polygon.
+            let positions: Vec<Vec3> = corners.iter().map(|c| self.positions[*c]).collect();
+            for triangle in ear_clipping(&positions) {
+                self.add_polygon(&triangle.map(|k| corners[k]));
+            }
+            return;
+        }
+
+        let (mut above, mut below) = (Vec::new(), Vec::new());
+        for k in 0..corners.len() {
+            let (i, j) = (corners[k], corners[(k + 1) % corners.len()]);
+            let (di, dj) = (self.distances[i], self.distances[j]);
+            if di >= 0.0 {
+                above.push(i);
+            }
+            if di <= 0.0 {
+                below.push(i);
+            }
+            if di * dj < 0.0 {
+                let x = self.crossing(i, j);
+                above.push(x);
+                below.push(x);
+            }
+        }
+        if above.len() >= 3 && distances.iter().any(|d| *d > 0.0) {
+            self.above.push(above);
+        }
+        if below.len() >= 3 && distances.iter().any(|d| *d < 0.0) {
+            self.below.push(below);
+        }
+    }
+
+    /// Triangles closing the part below the plane, wound like its faces.
+    fn caps(&self) -> Vec<[usize; 3]> {
+        let plane = &self.plane;
+        let sides: HashSet<(usize, usize)> = self
+            .below
+            .iter()
+            .flat_map(|polygon| {
+                (0..polygon.len()).map(|k| (polygon[k], polygon[(k + 1) % polygon.len()]))
+            })
+            .collect();
+        // Open sides on the plane, reversed, run around the cap.
+        let mut next: BTreeMap<usize, usize> = sides
+            .iter()
+            .filter(|(a, b)| !sides.contains(&(*b, *a)))
+            .filter(|(a, b)| self.distances[*a] == 0.0 && self.distances[*b] == 0.0)
+            .map(|(a, b)| (*b, *a))
+            .collect();
+        let mut loops = Vec::new();
+        while let Some((&start, _)) = next.iter().next() {
+            let mut ring = vec![start];
+            let mut current = start;
+            let closed = loop {
+                match next.remove(&current) {
+                    Some(following) if following == start => break true,
+                    Some(following) => {
+                        ring.push(following);
+                        current = following;
+                    }
+                    None => break false,
+                }
+            };
+            if closed && ring.len() >= 3 {
+                loops.push(ring);
+            }
+        }
+
+        let (u, v) = plane.axes();
+        let flat: Vec<[f64; 2]> = self
+            .positions
+            .iter()
+            .map(|p| {
+                let d = math::sub(*p, plane.origin);
+                [math::dot(d, u), math::dot(d, v)]
+            })
+            .collect();
+        fill_loops(&loops, &flat)
+    }
+
+    /// Builds a mesh from the polygons of one part and its caps.
+    fn build<S: Scalar>(&self, polygons: &[Vec<usize>], caps: &[[usize; 3]]) -> Mesh<S> {
+        let mut dense = HashMap::new();
+        let mut positions = Vec::new();
+        let polygons: Vec<Vec<usize>> = polygons
+            .iter()
+            .map(|polygon| polygon.as_slice())
+            .chain(caps.iter().map(|triangle| triangle.as_slice()))
+            .map(|polygon| {
+                polygon
+                    .iter()
+                    .map(|i| {
+                        *dense.entry(*i).or_insert_with(|| {
+                            positions.push(scalar::from_f64(self.positions[*i]));
+                            positions.len() - 1
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        Mesh::from_polygons(&positions, &polygons)
+    }
+}
+
+fn signed_area(ring: &[usize], flat: &[[f64; 2]]) -> f64 {
+    (0..ring.len())
+        .map(|k| {
+            let (a, b) = (flat[ring[k]], flat[ring[(k + 1) % ring.len()]]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum::<f64>()
+        * 0.5
+}
+
+fn contains(ring: &[usize], flat: &[[f64; 2]], p: [f64; 2]) -> bool {
+    let mut inside = false;
+    for k in 0..ring.len() {
+        let (a, b) = (flat[ring[k]], flat[ring[(k + 1) % ring.len()]]);
+        if (a[1] > p[1]) != (b[1] > p[1]) {
+            let x = a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+            if p[0] < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn crosses(p: [f64; 2], q: [f64; 2], r: [f64; 2], s: [f64; 2]) -> bool {
+    let sign = |a, b, c| predicates::orient2d(a, b, c).signum();
+    sign(p, q, r) * sign(p, q, s) < 0.0 && sign(r, s, p) * sign(r, s, q) < 0.0
+}
+
+/// Triangulates closed loops, where loops wound against the rest are holes
+/// in the loop around them. Triangles keep the winding of their loop.
+fn fill_loops(loops: &[Vec<usize>], flat: &[[f64; 2]]) -> Vec<[usize; 3]> {
+    let total: f64 = loops.iter().map(|ring| signed_area(ring, flat)).sum();
+    // Ear clipping needs counter clockwise outlines, mirror when they aren't.
+    let flat: Vec<[f64; 2]> = if total < 0.0 {
+        flat.iter().map(|p| [p[0], -p[1]]).collect()
+    } else {
+        flat.to_vec()
+    };
+    let (outlines, holes): (Vec<&Vec<usize>>, Vec<&Vec<usize>>) = loops
+        .iter()
+        .partition(|ring| signed_area(ring, &flat) > 0.0);
+
+    let mut merged: Vec<Vec<usize>> = outlines.iter().map(|ring| ring.to_vec()).collect();
+    let mut assigned: Vec<Vec<&Vec<usize>>> = vec![Vec::new(); outlines.len()];
+    for hole in holes {
+        let inside = flat[hole[0]];
+        let owner = (0..outlines.len())
+            .filter(|o| contains(outlines[*o], &flat, inside))
+            .min_by(|a, b| {
+                signed_area(outlines[*a], &flat).total_cmp(&signed_area(outlines[*b], &flat))
+            });
+        if let Some(owner) = owner {
+            assigned[owner].push(hole);
+        }
+    }
+    let rightmost = |ring: &[usize]| {
+        (0..ring.len())
+            .max_by(|a, b| flat[ring[*a]][0].total_cmp(&flat[ring[*b]][0]))
+            .unwrap_or(0)
+    };
+    for (outline, holes) in merged.iter_mut().zip(&mut assigned) {
+        holes.sort_by(|a, b| flat[b[rightmost(b)]][0].total_cmp(&flat[a[rightmost(a)]][0]));
+        for (h, hole) in holes.iter().enumerate() {
+            let start = rightmost(hole);
+            let from = flat[hole[start]];
+            let edges = |ring: &[usize]| -> Vec<([f64; 2], [f64; 2])> {
+                (0..ring.len())
+                    .map(|k| (flat[ring[k]], flat[ring[(k + 1) % ring.len()]]))
+                    .collect()
+            };
+            let mut blocking = edges(outline);
+            for other in &holes[h..] {
+                blocking.extend(edges(other));
+            }
+            let mut candidates: Vec<usize> = (0..outline.len()).collect();
+            candidates.sort_by(|a, b| {
+                let distance = |k: &usize| {
+                    let p = flat[outline[*k]];
+                    (p[0] - from[0]).powi(2) + (p[1] - from[1]).powi(2)
+                };
+                distance(a).total_cmp(&distance(b))
+            });
+            let target = candidates
+                .iter()
+                .copied()
+                .find(|k| {
+                    let to = flat[outline[*k]];
+                    blocking.iter().all(|(r, s)| {
+                        [*r, *s].contains(&to)
+                            || [*r, *s].contains(&from)
+                            || !crosses(from, to, *r, *s)
+                    })
+                })
+                .unwrap_or(candidates[0]);
+            // Walk into the hole and back out along a doubled bridge.
+            let mut spliced = outline[..=target].to_vec();
+            spliced.extend(hole[start..].iter().chain(&hole[..=start]));
+            spliced.extend(&outline[target..]);
+            *outline = spliced;
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for ring in merged {
+        let points: Vec<[f64; 2]> = ring.iter().map(|i| flat[*i]).collect();
+        triangles.extend(ear_clip(&points).into_iter().map(|t| t.map(|k| ring[k])));
+    }
+    triangles
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Mirrors the part of the mesh above a plane onto the part below it,
+    /// see `ops::symmetrize`.
+    pub fn symmetrize(&mut self, plane: &Plane) -> Result<Vec<FaceIndex>> {
+        symmetrize(self, plane)
+    }
+
+    /// Cuts the mesh along a plane in place, see `ops::bisect`.
+    pub fn bisect(&mut self, plane: &Plane, keep: KeepSide, cap: bool) -> Result<Vec<EdgeIndex>> {
+        bisect(self, plane, keep, cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::voxel::{Voxel, VoxelGrid};
+
+    fn assert_closed(mesh: &Mesh<f64>, volume: f64) {
+        builder::assert_connectivity(mesh);
+        let report = analysis::watertight_report(mesh, 1.0e-9);
+        assert!(report.is_closed(), "{:?}", report);
+        assert!(report.is_consistently_oriented());
+        assert!((report.volume - volume).abs() < 1.0e-9, "{}", report.volume);
+    }
+
+    #[test]
+    fn cutting_a_cube_gives_two_closed_halves() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh<f64> = builder::cube();
+        let plane = Plane::new([0.5, 0.5, 0.25], [0.0, 0.0, 2.0]);
+        let (above, below) = split_by_plane(&mesh, &plane).unwrap();
+        assert_closed(&above, 0.75);
+        assert_closed(&below, 0.25);
+        assert!(below.points.iter().all(|(_, p)| p.position[2] <= 0.25));
+
+        // A slanted cut through the corners and one that misses the mesh.
+        let (above, below) = split_by_plane(&mesh, &Plane::new([0.5; 3], [1.0, 1.0, 0.0])).unwrap();
+        assert_closed(&above, 0.5);
+        assert_closed(&below, 0.5);
+        let (above, below) =
+            split_by_plane(&mesh, &Plane::new([0.0, 0.0, 2.0], [0.0, 0.0, 1.0])).unwrap();
+        assert_eq!(above.face_count(), 0);
+        assert_eq!(below.face_count(), 6);
+        assert!(split_by_plane(&mesh, &Plane::new([0.0; 3], [0.0; 3])).is_err());
+    }
+
+    #[test]
+    fn caps_keep_the_holes_of_the_cross_section() {
+        // A square ring, the cross section at half height has a hole.
+        let mut grid = VoxelGrid::new([0.0; 3], 1.0, [3, 3, 1]);
+        for i in 0..3 {
+            for j in 0..3 {
+                if (i, j) != (1, 1) {
+                    grid.set([i, j, 0], Voxel::Interior);
+                }
+            }
+        }
+        let ring: Mesh<f64> = grid.to_mesh();
+        let (above, below) =
+            split_by_plane(&ring, &Plane::new([0.0, 0.0, 0.5], [0.0, 0.0, 1.0])).unwrap();
+        assert_closed(&above, 4.0);
+        assert_closed(&below, 4.0);
+    }
+
+    #[test]
+    fn bisecting_keeps_and_caps_the_chosen_side() {
+        let plane = Plane::new([0.5, 0.5, 0.25], [0.0, 0.0, 2.0]);
+        let mut both: Mesh<f64> = builder::cube();
+        let cut = both.bisect(&plane, KeepSide::Both, false).unwrap();
+        assert_closed(&both, 1.0);
+        assert_eq!(cut.len(), 4);
+        assert_eq!(both.face_count(), 10);
+
+        let mut below: Mesh<f64> = builder::cube();
+        below.bisect(&plane, KeepSide::Below, true).unwrap();
+        assert_closed(&below, 0.25);
+        assert!(below.points.iter().all(|(_, p)| p.position[2] <= 0.25));
+        let mut above: Mesh<f64> = builder::cube();
+        let cut = above.bisect(&plane, KeepSide::Above, false).unwrap();
+        assert!(cut.iter().all(|e| above.edge(*e).face().is_valid()));
+        assert_eq!(above.edges().filter(|e| !e.face().is_valid()).count(), 4);
+
+        // Keeping both sides with caps separates them.
+        let mut apart: Mesh<f64> = builder::cube();
+        let slant = Plane::new([0.5; 3], [1.0, 1.0, 0.0]);
+        apart.bisect(&slant, KeepSide::Both, true).unwrap();
+        assert_closed(&apart, 1.0);
+        assert_eq!(apart.point_count(), 8 + 4);
+        assert!(apart
+            .bisect(&Plane::new([0.0; 3], [0.0; 3]), KeepSide::Both, true)
+            .is_err());
+    }
+
+    #[test]
+    fn caps_which_cant_be_added_are_errors() {
+        let mut grid: Mesh<f64> = builder::grid(1, 1);
+        let face = grid.faces().next().unwrap().index;
+        let ring: Vec<VertexIndex> = grid.face(face).vertices().map(|v| v.index).collect();
+        let plane = Plane::new([0.0; 3], [0.0, 0.0, 1.0]);
+        assert!(matches!(
+            fill_cap(&mut grid, &plane, &[ring]),
+            Err(Error::NonManifold(_))
+        ));
+        assert_eq!(grid.face_count(), 1);
+    }
+
+    #[test]
+    fn bisected_caps_keep_their_holes() {
+        let mut grid = VoxelGrid::new([0.0; 3], 1.0, [3, 3, 1]);
+        for i in 0..3 {
+            for j in 0..3 {
+                if (i, j) != (1, 1) {
+                    grid.set([i, j, 0], Voxel::Interior);
+                }
+            }
+        }
+        let mut ring: Mesh<f64> = grid.to_mesh();
+        ring.bisect(
+            &Plane::new([0.0, 0.0, 0.5], [0.0, 0.0, 1.0]),
+            KeepSide::Above,
+            true,
+        )
+        .unwrap();
+        assert_closed(&ring, 4.0);
+    }
+
+    #[test]
+    fn symmetrized_halves_are_welded() {
+        let mut grid: Mesh<f64> = builder::grid(3, 2);
+        grid.symmetrize(&Plane::new([2.0, 0.0, 0.0], [1.0, 0.0, 0.0]))
+            .unwrap();
+        builder::assert_connectivity(&grid);
+        assert_eq!(grid.face_count(), 4);
+        assert_eq!(grid.vertex_count(), 9);
+        assert_eq!(grid.edges().filter(|e| !e.face().is_valid()).count(), 8);
+        assert!(grid
+            .points
+            .iter()
+            .all(|(_, p)| p.position[0] == 1.0 || p.position[0] == 2.0 || p.position[0] == 3.0));
+
+        let mut cube: Mesh<f64> = builder::cube();
+        let copies = cube
+            .symmetrize(&Plane::new([0.25, 0.5, 0.5], [1.0, 0.0, 0.0]))
+            .unwrap();
+        assert_eq!(copies.len(), 5);
+        assert_closed(&cube, 1.5);
+    }
+}
@@ -0,0 +1,467 @@
+use super::*;
+use crate::math;
+
+/// Removes an edge and merges the faces on either side of it into the face
+/// of `edge`, which is returned.
+pub fn dissolve_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<FaceIndex> {
+    let mut span = trace::span!(mesh, "dissolve_edge");
+    let mesh = span.mesh();
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let twin_index = e.twin_index;
+    let (f, g) = (e.face_index, twin.face_index);
+    if mesh.get_element(f).is_none() {
+        return Err(Error::InvalidFace(f));
+    }
+    if mesh.get_element(g).is_none() {
+        return Err(Error::InvalidFace(g));
+    }
+    if f == g {
+        return Err(Error::NonManifold(format!(
+            "both sides of {:?} belong to {:?}",
+            edge, f
+        )));
+    }
+    if e.next_index == twin_index || twin.next_index == edge {
+        return Err(Error::NonManifold(format!(
+            "{:?} ends in a vertex without other edges",
+            edge
+        )));
+    }
+
+    let absorbed: Vec<EdgeIndex> = mesh.face(g).edges().map(|e| e.index).collect();
+    for index in absorbed {
+        if let Some(half) = mesh.get_element_mut(index) {
+            half.face_index = f;
+        }
+    }
+    utils::link_unchecked(mesh, e.prev_index, twin.next_index);
+    utils::link_unchecked(mesh, twin.prev_index, e.next_index);
+    mesh.remove_element(edge);
+    mesh.remove_element(twin_index);
+    mesh.remove_element(g);
+    if let Some(face) = mesh.get_element_mut(f) {
+        face.edge_index = e.next_index;
+    }
+    if mesh.vertex(e.vertex_index).edge().index == edge {
+        set_vertex_edge(mesh, e.vertex_index, twin.next_index);
+    }
+    if mesh.vertex(twin.vertex_index).edge().index == twin_index {
+        set_vertex_edge(mesh, twin.vertex_index, e.next_index);
+    }
+    Ok(f)
+}
+
+/// Removes a vertex along with its edges.
+///
+/// A vertex with two edges is the inverse of `split_edge`, the edges on
+/// either side are joined into one and the face of the first outgoing edge's
+/// twin is returned. Otherwise every face around the vertex is merged into
+/// one, which is returned. This needs the vertex to be surrounded by faces.
+pub fn dissolve_vertex<S: Scalar>(mesh: &mut Mesh<S>, vertex: VertexIndex) -> Result<FaceIndex> {
+    let mut span = trace::span!(mesh, "dissolve_vertex");
+    let mesh = span.mesh();
+    if mesh.get_element(vertex).is_none() {
+        return Err(Error::InvalidVertex(vertex));
+    }
+    let outgoing: Vec<EdgeIndex> = mesh.vertex(vertex).edges().map(|e| e.index).collect();
+    match outgoing.len() {
+        0 | 1 => Err(Error::NonManifold(format!(
+            "{:?} doesn't lie between other vertices",
+            vertex
+        ))),
+        2 => join_edges(mesh, vertex, [outgoing[0], outgoing[1]]),
+        _ => merge_fan(mesh, vertex, &outgoing),
+    }
+}
+
+fn join_edges<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vertex: VertexIndex,
+    outgoing: [EdgeIndex; 2],
+) -> Result<FaceIndex> {
+    // v -> a and v -> b become a -> b and b -> a, reusing the incoming halves.
+    let (to_a, to_b) = (edge_pair(mesh, outgoing[0])?, edge_pair(mesh, outgoing[1])?);
+    let (from_a, from_b) = (to_a.0.twin_index, to_b.0.twin_index);
+    if to_a.1.vertex_index == to_b.1.vertex_index {
+        return Err(Error::NonManifold(format!(
+            "both edges of {:?} lead to the same vertex",
+            vertex
+        )));
+    }
+    if to_a.1.next_index != outgoing[1] || to_b.1.next_index != outgoing[0] {
+        return Err(Error::Inconsistent(format!(
+            "the edges of {:?} don't continue each other",
+            vertex
+        )));
+    }
+    utils::link_unchecked(mesh, from_a, to_b.0.next_index);
+    utils::link_unchecked(mesh, from_b, to_a.0.next_index);
+    set_twins(mesh, from_a, from_b);
+    for (removed, kept) in [(outgoing[1], from_a), (outgoing[0], from_b)] {
+        let face = edge_data(mesh, removed)?.face_index;
+        if mesh.face(face).edge().index == removed {
+            if let Some(f) = mesh.get_element_mut(face) {
+                f.edge_index = kept;
+            }
+        }
+        mesh.remove_element(removed);
+    }
+    remove_vertex_and_point(mesh, vertex);
+    Ok(to_a.1.face_index)
+}
+
+fn merge_fan<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vertex: VertexIndex,
+    outgoing: &[EdgeIndex],
+) -> Result<FaceIndex> {
+    let mut faces = Vec::with_capacity(outgoing.len());
+    for spoke in outgoing {
+        let (e, twin) = edge_pair(mesh, *spoke)?;
+        if !mesh.face(e.face_index).is_valid() || !mesh.face(twin.face_index).is_valid() {
+            return Err(Error::NonManifold(format!(
+                "{:?} lies on the boundary",
+                vertex
+            )));
+        }
+        if faces.contains(&e.face_index) {
+            return Err(Error::NonManifold(format!(
+                "{:?} appears more than once around {:?}",
+                e.face_index, vertex
+            )));
+        }
+        faces.push(e.face_index);
+    }
+
+    // In each face the edge before the incoming spoke continues with the
+    // edge after the outgoing spoke of the next face.
+    let mut links = Vec::with_capacity(outgoing.len());
+    let mut ring_starts = Vec::with_capacity(outgoing.len());
+    for spoke in outgoing {
+        let e = edge_data(mesh, *spoke)?;
+        let incoming = edge_data(mesh, e.prev_index)?;
+        let next_spoke = edge_data(mesh, incoming.twin_index)?;
+        if next_spoke.next_index == e.prev_index || incoming.prev_index == next_spoke.next_index {
+            return Err(Error::NonManifold(format!(
+                "merging the faces around {:?} leaves a face without area",
+                vertex
+            )));
+        }
+        links.push((incoming.prev_index, next_spoke.next_index));
+        ring_starts.push(e.next_index);
+    }
+    let neighbors: Vec<(VertexIndex, Vec<EdgeIndex>)> = neighbors(mesh, vertex)
+        .into_iter()
+        .map(|n| (n, mesh.vertex(n).edges().map(|e| e.index).collect()))
+        .collect();
+    let kept = faces[0];
+    for face in &faces {
+        let edges: Vec<EdgeIndex> = mesh.face(*face).edges().map(|e| e.index).collect();
+        for index in edges {
+            if let Some(half) = mesh.get_element_mut(index) {
+                half.face_index = kept;
+            }
+        }
+    }
+    for (prev, next) in links {
+        utils::link_unchecked(mesh, prev, next);
+    }
+    for spoke in outgoing {
+        let twin = edge_data(mesh, *spoke)?.twin_index;
+        mesh.remove_element(*spoke);
+        mesh.remove_element(twin);
+    }
+    for face in &faces[1..] {
+        mesh.remove_element(*face);
+    }
+    if let Some(face) = mesh.get_element_mut(kept) {
+        face.edge_index = ring_starts[0];
+    }
+    for (neighbor, candidates) in neighbors {
+        let best = best_outgoing(mesh, &candidates).unwrap_or_default();
+        set_vertex_edge(mesh, neighbor, best);
+    }
+    remove_vertex_and_point(mesh, vertex);
+    Ok(kept)
+}
+
+fn remove_vertex_and_point<S: Scalar>(mesh: &mut Mesh<S>, vertex: VertexIndex) {
+    let point = mesh.vertex(vertex).element().map(|v| v.point_index);
+    mesh.remove_element(vertex);
+    if let Some(point) = point {
+        remove_point_if_unused(mesh, point);
+    }
+}
+
+/// Dissolves edges between faces that meet at less than `angle` radians,
+/// then vertices left between two edges that continue each other within
+/// `angle`. Returns the number of edges and vertices dissolved.
+///
+/// Edges are skipped where dissolving would leave a face touching itself,
+/// so the result stays manifold.
+pub fn limited_dissolve<S: Scalar>(mesh: &mut Mesh<S>, angle: f64) -> Result<usize> {
+    let mut span = trace::span!(mesh, "limited_dissolve");
+    let mesh = span.mesh();
+    let mut dissolved = 0;
+    let edges: Vec<EdgeIndex> = mesh.edges().map(|e| e.index).collect();
+    for edge in edges {
+        if !mesh.edge(edge).is_valid() || !is_flat_edge(mesh, edge, angle) {
+            continue;
+        }
+        if checked_edge(mesh, edge).is_ok() {
+            dissolve_edge(mesh, edge)?;
+            dissolved += 1;
+        }
+    }
+
+    let vertices: Vec<VertexIndex> = mesh.vertices().map(|v| v.index).collect();
+    for vertex in vertices {
+        let ends: Vec<VertexIndex> = neighbors(mesh, vertex);
+        if ends.len() != 2 {
+            continue;
+        }
+        let p = mesh.vertex_position(vertex);
+        let a = math::normalize(math::sub(p, mesh.vertex_position(ends[0])));
+        let b = math::normalize(math::sub(mesh.vertex_position(ends[1]), p));
+        if math::dot(a, b).clamp(-1.0, 1.0).acos() < angle && checked_vertex(mesh, vertex).is_ok() {
+            dissolve_vertex(mesh, vertex)?;
+            dissolved += 1;
+        }
+    }
+    Ok(dissolved)
+}
+
+/// Merges pairs of triangles sharing an edge into quads where their normals
+/// differ by less than `angle` radians. Returns the number of quads made.
+///
+/// Flatter pairs go first and, between equally flat ones, the longer shared
+/// edge, so the diagonals of a triangulated grid are removed before its
+/// sides. Pairs sharing more than the one edge are left alone.
+pub fn quadrangulate<S: Scalar>(mesh: &mut Mesh<S>, angle: f32) -> Result<usize> {
+    let mut span = trace::span!(mesh, "quadrangulate");
+    let mesh = span.mesh();
+    let is_triangle = |face: FaceFn<S>| face.edges().count() == 3;
+    let mut candidates = Vec::new();
+    for edge in mesh.edges() {
+        let (f, g) = (edge.face(), edge.twin().face());
+        if edge.index > edge.twin().index
+            || !f.is_valid()
+            || !g.is_valid()
+            || !is_triangle(f)
+            || !is_triangle(g)
+        {
+            continue;
+        }
+        let cos = math::dot(mesh.face_normal(f.index), mesh.face_normal(g.index));
+        let bend = cos.clamp(-1.0, 1.0).acos();
+        if bend < f64::from(angle) {
+            let length = math::distance(
+                mesh.vertex_position(edge.vertex().index),
+                mesh.vertex_position(edge.twin().vertex().index),
+            );
+            candidates.push((bend, -length, edge.index));
+        }
+    }
+    candidates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut merged = 0;
+    for (_, _, edge) in candidates {
+        let e = mesh.edge(edge);
+        if !e.is_valid() || !is_triangle(e.face()) || !is_triangle(e.twin().face()) {
+            continue;
+        }
+        if checked_edge(mesh, edge).is_ok() {
+            dissolve_edge(mesh, edge)?;
+            merged += 1;
+        }
+    }
+    Ok(merged)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Runs `ops::quadrangulate`, merging flat pairs of triangles into quads.
+    pub fn quadrangulate(&mut self, angle_threshold: f32) -> Result<usize> {
+        quadrangulate(self, angle_threshold)
+    }
+}
+
+fn is_flat_edge<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex, angle: f64) -> bool {
+    let (f, g) = (mesh.edge(edge).face(), mesh.edge(edge).twin().face());
+    if !f.is_valid() || !g.is_valid() {
+        return false;
+    }
+    let cos = math::dot(mesh.face_normal(f.index), mesh.face_normal(g.index));
+    cos.clamp(-1.0, 1.0).acos() < angle
+}
+
+/// Tests shared with `checked::dissolve_edge`.
+pub(super) fn checked_edge<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex) -> Result<()> {
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let (f, g) = (e.face_index, twin.face_index);
+    if !mesh.face(f).is_valid() || !mesh.face(g).is_valid() {
+        return Err(Error::NonManifold(format!(
+            "{:?} lies on the boundary",
+            edge
+        )));
+    }
+    let shared = mesh
+        .face(f)
+        .edges()
+        .filter(|e| e.twin().face().index == g)
+        .count();
+    if shared != 1 {
+        return Err(Error::NonManifold(format!(
+            "{:?} and {:?} share more than one edge",
+            f, g
+        )));
+    }
+    for end in [e.vertex_index, twin.vertex_index] {
+        if mesh.vertex(end).edges().count() < 3 {
+            return Err(Error::NonManifold(format!(
+                "dissolving {:?} would leave {:?} dangling",
+                edge, end
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Tests shared with `checked::dissolve_vertex`.
+pub(super) fn checked_vertex<S: Scalar>(mesh: &Mesh<S>, vertex: VertexIndex) -> Result<()> {
+    if !mesh.vertex(vertex).is_valid() {
+        return Err(Error::InvalidVertex(vertex));
+    }
+    let ends = neighbors(mesh, vertex);
+    if ends.len() == 2 {
+        if neighbors(mesh, ends[0]).contains(&ends[1]) {
+            return Err(Error::NonManifold(format!(
+                "dissolving {:?} would duplicate the edge between {:?} and {:?}",
+                vertex, ends[0], ends[1]
+            )));
+        }
+        for edge in mesh.vertex(vertex).edges() {
+            let face = edge.face();
+            if face.is_valid() && face.edges().count() <= 3 {
+                return Err(Error::NonManifold(format!(
+                    "dissolving {:?} would leave {:?} with two sides",
+                    vertex, face.index
+                )));
+            }
+        }
+        return Ok(());
+    }
+    if is_boundary_vertex(mesh, vertex) {
+        return Err(Error::NonManifold(format!(
+            "{:?} lies on the boundary",
+            vertex
+        )));
+    }
+    let mut ring = Vec::new();
+    for edge in mesh.vertex(vertex).edges() {
+        let mut side = edge.next();
+        while side.twin().vertex().index != vertex {
+            ring.push(side.vertex().index);
+            side = side.next();
+        }
+    }
+    let count = ring.len();
+    ring.sort_by_key(|v| v.offset);
+    ring.dedup();
+    if ring.len() != count {
+        return Err(Error::NonManifold(format!(
+            "dissolving {:?} would pinch the surrounding face",
+            vertex
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dissolving_edges_merges_faces() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let edge = mesh.edges().find(|e| !e.is_boundary()).unwrap().index;
+        let face = dissolve_edge(&mut mesh, edge).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.face(face).edges().count(), 6);
+        assert_eq!(mesh.edge_count(), 12);
+
+        let boundary = mesh.edges().find(|e| !e.face().is_valid()).unwrap().index;
+        assert!(matches!(
+            dissolve_edge(&mut mesh, boundary),
+            Err(Error::InvalidFace(_))
+        ));
+    }
+
+    #[test]
+    fn dissolving_vertices_undoes_splits_and_merges_fans() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let edge = mesh.edges().find(|e| !e.is_boundary()).unwrap().index;
+        let middle = split_edge(&mut mesh, edge, 0.5).unwrap();
+        dissolve_vertex(&mut mesh, middle).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.edge_count(), 14);
+        assert!(mesh.faces().all(|f| f.edges().count() == 4));
+
+        let mut grid: Mesh = builder::grid(2, 2);
+        let center = grid
+            .vertices()
+            .find(|v| v.position() == Some([1.0, 1.0, 0.0]))
+            .unwrap()
+            .index;
+        let face = dissolve_vertex(&mut grid, center).unwrap();
+        builder::assert_connectivity(&grid);
+        assert_eq!(grid.face_count(), 1);
+        assert_eq!(grid.face(face).edges().count(), 8);
+        assert_eq!(grid.point_count(), 8);
+        // A corner on the boundary just drops out of the face.
+        let corner = grid.vertices().next().unwrap().index;
+        dissolve_vertex(&mut grid, corner).unwrap();
+        builder::assert_connectivity(&grid);
+        assert_eq!(grid.face(face).edges().count(), 7);
+        assert_eq!(
+            dissolve_vertex(&mut grid, corner),
+            Err(Error::InvalidVertex(corner))
+        );
+    }
+
+    #[test]
+    fn limited_dissolve_keeps_creases() {
+        let mut mesh: Mesh = builder::triangle_grid(3, 3);
+        let flat = limited_dissolve(&mut mesh, 0.01).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(flat > 0);
+        assert!(mesh.face_count() < 18);
+        let area: f64 = mesh.faces().map(|f| f.area() as f64).sum();
+        assert!((area - 9.0).abs() < 1.0e-5);
+
+        let mut cube: Mesh = builder::cube();
+        assert_eq!(limited_dissolve(&mut cube, 0.01).unwrap(), 0);
+        assert_eq!(cube.face_count(), 6);
+    }
+
+    #[test]
+    fn flat_triangle_pairs_become_quads() {
+        let mut mesh: Mesh = builder::triangle_grid(3, 3);
+        assert_eq!(mesh.quadrangulate(0.01).unwrap(), 9);
+        builder::assert_connectivity(&mesh);
+        assert!(mesh.faces().all(|f| f.edges().count() == 4));
+        assert_eq!(mesh.quadrangulate(0.01).unwrap(), 0);
+
+        let mut cube: Mesh = builder::cube();
+        triangulate(&mut cube, TriangulationMethod::Fan).unwrap();
+        assert_eq!(cube.quadrangulate(0.01).unwrap(), 6);
+        builder::assert_connectivity(&cube);
+        assert_eq!(cube.face_count(), 6);
+
+        let mut folded: Mesh = builder::octahedron();
+        assert_eq!(folded.quadrangulate(0.01).unwrap(), 0);
+        assert_eq!(folded.face_count(), 8);
+    }
+}
@@ -0,0 +1,485 @@
+use super::*;
+use crate::math;
+
+/// Inserts a vertex on an edge at `t` along it from its origin, splitting
+/// both halves. Adjacent faces gain a corner and are not triangulated.
+pub fn split_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex, t: f64) -> Result<VertexIndex> {
+    let mut span = trace::span!(mesh, "split_edge");
+    let mesh = span.mesh();
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let (a, b) = (e.vertex_index, twin.vertex_index);
+    let position = math::lerp(mesh.vertex_position(a), mesh.vertex_position(b), t);
+
+    let point = mesh.add_element(Point::from_position(scalar::from_f64(position)));
+    let middle = mesh.add_element(Vertex::at_point(point));
+    // `edge` becomes a -> middle and `twin` b -> middle, the new halves
+    // continue from the middle to the original destinations.
+    let towards_b = mesh.add_element(Edge {
+        twin_index: e.twin_index,
+        next_index: e.next_index,
+        prev_index: edge,
+        face_index: e.face_index,
+        vertex_index: middle,
+    });
+    let towards_a = mesh.add_element(Edge {
+        twin_index: edge,
+        next_index: twin.next_index,
+        prev_index: e.twin_index,
+        face_index: twin.face_index,
+        vertex_index: middle,
+    });
+    utils::link_unchecked(mesh, towards_b, e.next_index);
+    utils::link_unchecked(mesh, edge, towards_b);
+    utils::link_unchecked(mesh, towards_a, twin.next_index);
+    utils::link_unchecked(mesh, e.twin_index, towards_a);
+    set_twins(mesh, edge, towards_a);
+    set_twins(mesh, e.twin_index, towards_b);
+
+    let outgoing = if twin.face_index.is_valid() {
+        towards_b
+    } else {
+        towards_a
+    };
+    set_vertex_edge(mesh, middle, outgoing);
+    Ok(middle)
+}
+
+/// Splits an edge into `cuts + 1` segments of equal length, returning the
+/// new vertices in order from the origin of `edge`, which stays on the
+/// first segment.
+pub fn subdivide_edge<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    edge: EdgeIndex,
+    cuts: usize,
+) -> Result<Vec<VertexIndex>> {
+    let mut span = trace::span!(mesh, "subdivide_edge");
+    let mesh = span.mesh();
+    edge_pair(mesh, edge)?;
+    // Cutting the far end off each time keeps `edge` at the origin.
+    let mut added = Vec::with_capacity(cuts);
+    for k in (1..=cuts).rev() {
+        added.push(split_edge(mesh, edge, k as f64 / (k + 1) as f64)?);
+    }
+    added.reverse();
+    Ok(added)
+}
+
+/// Rotates the edge shared by two triangles so that it connects their
+/// opposite corners instead.
+pub fn flip_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<()> {
+    let mut span = trace::span!(mesh, "flip_edge");
+    let mesh = span.mesh();
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let twin_index = e.twin_index;
+    let (f, g) = (e.face_index, twin.face_index);
+    if mesh.get_element(f).is_none() {
+        return Err(Error::InvalidFace(f));
+    }
+    if mesh.get_element(g).is_none() {
+        return Err(Error::InvalidFace(g));
+    }
+    // f is a -> b -> c and g is b -> a -> d.
+    let (bc, ca) = (e.next_index, e.prev_index);
+    let (ad, db) = (twin.next_index, twin.prev_index);
+    let (a, b) = (e.vertex_index, twin.vertex_index);
+    let c = edge_data(mesh, ca)?.vertex_index;
+    let d = edge_data(mesh, db)?.vertex_index;
+
+    // Afterwards f is c -> a -> d and g is d -> b -> c.
+    for (prev, next) in [(ca, ad), (ad, edge), (edge, ca)] {
+        utils::link_unchecked(mesh, prev, next);
+    }
+    for (prev, next) in [(db, bc), (bc, twin_index), (twin_index, db)] {
+        utils::link_unchecked(mesh, prev, next);
+    }
+    if let Some(half) = mesh.get_element_mut(edge) {
+        half.vertex_index = d;
+    }
+    if let Some(half) = mesh.get_element_mut(twin_index) {
+        half.vertex_index = c;
+    }
+    if let Some(half) = mesh.get_element_mut(ad) {
+        half.face_index = f;
+    }
+    if let Some(half) = mesh.get_element_mut(bc) {
+        half.face_index = g;
+    }
+    if let Some(face) = mesh.get_element_mut(f) {
+        face.edge_index = edge;
+    }
+    if let Some(face) = mesh.get_element_mut(g) {
+        face.edge_index = twin_index;
+    }
+    if mesh.vertex(a).edge().index == edge {
+        set_vertex_edge(mesh, a, ad);
+    }
+    if mesh.vertex(b).edge().index == twin_index {
+        set_vertex_edge(mesh, b, bc);
+    }
+    Ok(())
+}
+
+/// Merges the destination of an edge into its origin, which moves to the
+/// middle of the edge and is returned.
+///
+/// Triangles on either side of the edge collapse and are removed, larger
+/// faces and boundary loops just lose a corner.
+pub fn collapse_edge<S: Scalar>(mesh: &mut Mesh<S>, edge: EdgeIndex) -> Result<VertexIndex> {
+    let mut span = trace::span!(mesh, "collapse_edge");
+    let mesh = span.mesh();
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let twin_index = e.twin_index;
+    let (a, b) = (e.vertex_index, twin.vertex_index);
+    let candidates: Vec<EdgeIndex> = mesh
+        .vertex(a)
+        .edges()
+        .chain(mesh.vertex(b).edges())
+        .map(|e| e.index)
+        .collect();
+    let b_outgoing: Vec<EdgeIndex> = mesh.vertex(b).edges().map(|e| e.index).collect();
+    let middle = math::lerp(mesh.vertex_position(a), mesh.vertex_position(b), 0.5);
+
+    let mut removed = vec![edge, twin_index];
+    for (half, data) in [(edge, &e), (twin_index, &twin)] {
+        let face = data.face_index;
+        let is_triangle = mesh.face(face).is_valid() && mesh.face(face).edges().count() == 3;
+        if is_triangle {
+            // The two remaining sides of the triangle fold onto each other.
+            let (next, prev) = (data.next_index, data.prev_index);
+            let next_twin = edge_data(mesh, next)?.twin_index;
+            let prev_data = edge_data(mesh, prev)?;
+            set_twins(mesh, next_twin, prev_data.twin_index);
+            let opposite = prev_data.vertex_index;
+            if mesh.vertex(opposite).edge().index == prev {
+                set_vertex_edge(mesh, opposite, next_twin);
+            }
+            mesh.remove_element(face);
+            removed.extend([next, prev]);
+        } else {
+            utils::link_unchecked(mesh, data.prev_index, data.next_index);
+            if mesh.face(face).edge().index == half {
+                if let Some(f) = mesh.get_element_mut(face) {
+                    f.edge_index = data.next_index;
+                }
+            }
+        }
+    }
+    for index in &removed {
+        mesh.remove_element(*index);
+    }
+    for index in b_outgoing {
+        if let Some(half) = mesh.get_element_mut(index) {
+            half.vertex_index = a;
+        }
+    }
+
+    let point_b = mesh.vertex(b).element().map(|v| v.point_index);
+    mesh.remove_element(b);
+    if let Some(point_b) = point_b {
+        if Some(point_b) != mesh.vertex(a).element().map(|v| v.point_index) {
+            remove_point_if_unused(mesh, point_b);
+        }
+    }
+    if let Some(point) = mesh.vertex(a).element().map(|v| v.point_index) {
+        if let Some(p) = mesh.get_element_mut(point) {
+            p.position = scalar::from_f64(middle);
+        }
+    }
+    let outgoing = best_outgoing(mesh, &candidates).unwrap_or_default();
+    set_vertex_edge(mesh, a, outgoing);
+    Ok(a)
+}
+
+/// Splits a vertex in two, the inverse of `collapse_edge`.
+///
+/// `left` and `right` leave `vertex` towards two of its neighbors. The part
+/// of the fan turning from `left` towards `right`, through the face of
+/// `left`, moves to a new vertex at `position` with a point of its own. The
+/// new vertex is joined to `vertex` by an edge with triangles on both
+/// sides, one towards each neighbor. Returns the new vertex.
+pub fn split_vertex<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vertex: VertexIndex,
+    left: EdgeIndex,
+    right: EdgeIndex,
+    position: Position<S>,
+) -> Result<VertexIndex> {
+    let mut span = trace::span!(mesh, "split_vertex");
+    let mesh = span.mesh();
+    if !mesh.vertex(vertex).is_valid() {
+        return Err(Error::InvalidVertex(vertex));
+    }
+    for edge in [left, right] {
+        if edge_data(mesh, edge)?.vertex_index != vertex {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} doesn't leave {:?}",
+                edge, vertex
+            )));
+        }
+    }
+    if left == right {
+        return Err(Error::InvalidArgument(format!(
+            "a vertex is split between two different edges, got {:?} twice",
+            left
+        )));
+    }
+    // Turning from one outgoing edge to the next through its face.
+    let mut moved = vec![left];
+    let mut current = left;
+    loop {
+        current = mesh.edge(current).prev().twin().index;
+        if current == right {
+            break;
+        }
+        if current == left || moved.len() > mesh.edge_count() {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} and {:?} don't bound a part of the fan around {:?}",
+                left, right, vertex
+            )));
+        }
+        moved.push(current);
+    }
+    let stays: Vec<EdgeIndex> = mesh
+        .vertex(vertex)
+        .edges()
+        .map(|e| e.index)
+        .filter(|e| !moved.contains(e))
+        .collect();
+    let (outer_left, outer_right) = (
+        edge_data(mesh, left)?.twin_index,
+        edge_data(mesh, right)?.twin_index,
+    );
+    let (l, r) = (
+        edge_data(mesh, outer_left)?.vertex_index,
+        edge_data(mesh, outer_right)?.vertex_index,
+    );
+
+    let point = mesh.add_element(Point::from_position(position));
+    let split = mesh.add_element(Vertex::at_point(point));
+    for edge in &moved {
+        if let Some(e) = mesh.get_element_mut(*edge) {
+            e.vertex_index = split;
+        }
+    }
+    // vertex -> l -> split towards the left, vertex -> split -> r
+    // towards the right.
+    let mut add_triangle = |corners: [VertexIndex; 3]| {
+        let face = mesh.add_element(Face::default());
+        let sides = corners.map(|corner| {
+            mesh.add_element(Edge {
+                face_index: face,
+                vertex_index: corner,
+                ..Edge::default()
+            })
+        });
+        for i in 0..3 {
+            utils::link_unchecked(mesh, sides[i], sides[(i + 1) % 3]);
+        }
+        if let Some(f) = mesh.get_element_mut(face) {
+            f.edge_index = sides[0];
+        }
+        sides
+    };
+    let towards_left = add_triangle([vertex, l, split]);
+    let towards_right = add_triangle([vertex, split, r]);
+    set_twins(mesh, towards_left[0], outer_left);
+    set_twins(mesh, towards_left[1], left);
+    set_twins(mesh, towards_left[2], towards_right[0]);
+    set_twins(mesh, towards_right[1], outer_right);
+    set_twins(mesh, towards_right[2], right);
+
+    let outgoing = [towards_left[0], towards_right[0]];
+    let kept: Vec<EdgeIndex> = stays.into_iter().chain(outgoing).collect();
+    let outgoing = best_outgoing(mesh, &kept).unwrap_or_default();
+    set_vertex_edge(mesh, vertex, outgoing);
+    let leaving: Vec<EdgeIndex> = moved
+        .into_iter()
+        .chain([towards_left[2], towards_right[1]])
+        .collect();
+    let outgoing = best_outgoing(mesh, &leaving).unwrap_or_default();
+    set_vertex_edge(mesh, split, outgoing);
+    Ok(split)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Splits an edge into `cuts + 1` even segments, see
+    /// `ops::subdivide_edge`.
+    pub fn subdivide_edge(&mut self, edge: EdgeIndex, cuts: usize) -> Result<Vec<VertexIndex>> {
+        subdivide_edge(self, edge, cuts)
+    }
+
+    /// Splits a vertex in two, see `ops::split_vertex`.
+    pub fn split_vertex(
+        &mut self,
+        vertex: VertexIndex,
+        left_edge: EdgeIndex,
+        right_edge: EdgeIndex,
+        new_position: Position<S>,
+    ) -> Result<VertexIndex> {
+        split_vertex(self, vertex, left_edge, right_edge, new_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interior_edge(mesh: &Mesh) -> EdgeIndex {
+        mesh.edges()
+            .find(|e| !e.is_boundary())
+            .map(|e| e.index)
+            .unwrap()
+    }
+
+    #[test]
+    fn splitting_keeps_both_faces_connected() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let edge = interior_edge(&mesh);
+        let origin = mesh.edge(edge).vertex().position().unwrap();
+        let middle = split_edge(&mut mesh, edge, 0.25).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 7);
+        assert_eq!(mesh.edge_count(), 16);
+        assert!(mesh.faces().all(|f| f.edges().count() == 5));
+        let expected = if origin[1] == 0.0 { 0.25 } else { 0.75 };
+        assert_eq!(mesh.vertex(middle).position(), Some([1.0, expected, 0.0]));
+
+        let boundary = mesh.edges().find(|e| e.is_boundary()).unwrap().index;
+        let middle = split_edge(&mut mesh, boundary, 0.5).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(!mesh.vertex(middle).edge().face().is_valid());
+    }
+
+    #[test]
+    fn flipping_rotates_the_diagonal() {
+        let mut mesh: Mesh = builder::triangle_grid(1, 1);
+        let edge = interior_edge(&mesh);
+        flip_edge(&mut mesh, edge).unwrap();
+        builder::assert_connectivity(&mesh);
+        let ends = [
+            mesh.edge(edge).vertex().position().unwrap(),
+            mesh.edge(edge).twin().vertex().position().unwrap(),
+        ];
+        assert!(ends.contains(&[1.0, 0.0, 0.0]) && ends.contains(&[0.0, 1.0, 0.0]));
+        for face in mesh.faces() {
+            assert!(mesh.face_normal(face.index)[2] > 0.99);
+        }
+    }
+
+    #[test]
+    fn collapsing_removes_adjacent_triangles() {
+        let mut mesh: Mesh = builder::octahedron();
+        let edge = mesh.edges().next().unwrap().index;
+        let kept = collapse_edge(&mut mesh, edge).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 5);
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(mesh.edge_count(), 18);
+        assert_eq!(mesh.point_count(), 5);
+        assert_eq!(mesh.vertex(kept).edges().count(), 4);
+    }
+
+    #[test]
+    fn vertex_splits_undo_collapses() {
+        let mut mesh: Mesh = builder::triangle_grid(2, 2);
+        let (vertices, edges, faces) = (mesh.vertex_count(), mesh.edge_count(), mesh.face_count());
+        let center = mesh
+            .vertices()
+            .find(|v| v.position() == Some([1.0, 1.0, 0.0]))
+            .unwrap()
+            .index;
+        let around: Vec<EdgeIndex> = mesh.vertex(center).edges().map(|e| e.index).collect();
+        assert_eq!(around.len(), 6);
+        assert!(split_vertex(&mut mesh, center, around[0], around[0], [0.0; 3]).is_err());
+        let split = mesh
+            .split_vertex(center, around[0], around[3], [1.2, 1.2, 0.0])
+            .unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), vertices + 1);
+        assert_eq!(mesh.edge_count(), edges + 6);
+        assert_eq!(mesh.face_count(), faces + 2);
+        assert_eq!(mesh.vertex(split).edges().count(), 5);
+        assert_eq!(mesh.vertex(center).edges().count(), 5);
+
+        let joint = mesh
+            .vertex(center)
+            .edges()
+            .find(|e| e.twin().vertex().index == split)
+            .unwrap()
+            .index;
+        assert_eq!(collapse_edge(&mut mesh, joint).unwrap(), center);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(
+            (mesh.vertex_count(), mesh.edge_count(), mesh.face_count()),
+            (vertices, edges, faces)
+        );
+    }
+
+    #[test]
+    fn boundary_vertices_keep_starting_at_the_border() {
+        let mut mesh: Mesh = builder::triangle_grid(2, 1);
+        let vertex = mesh
+            .vertices()
+            .find(|v| v.position() == Some([1.0, 0.0, 0.0]))
+            .unwrap();
+        let around: Vec<EdgeIndex> = vertex.edges().map(|e| e.index).collect();
+        let vertex = vertex.index;
+        for (left, right) in [(around[0], around[2]), (around[2], around[0])] {
+            let mut mesh = mesh.clone();
+            let split = split_vertex(&mut mesh, vertex, left, right, [1.0, 0.5, 0.0]).unwrap();
+            builder::assert_connectivity(&mesh);
+            assert_eq!(mesh.face_count(), 6);
+            for v in [vertex, split] {
+                let on_boundary = mesh.vertex(v).edges().any(|e| !e.face().is_valid());
+                assert_eq!(!mesh.vertex(v).edge().face().is_valid(), on_boundary);
+            }
+        }
+        assert!(split_vertex(&mut mesh, vertex, around[1], around[1], [0.0; 3]).is_err());
+    }
+
+    #[test]
+    fn collapsing_on_quads_shrinks_faces() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let edge = mesh
+            .edges()
+            .find(|e| {
+                let (a, b) = (e.vertex().position().unwrap(), e.next().vertex().position());
+                !e.face().is_valid()
+                    && a[1] == 0.0
+                    && b.map(|b| b[1] == 0.0 && a[0] + b[0] == 1.0) == Some(true)
+            })
+            .unwrap()
+            .index;
+        let kept = collapse_edge(&mut mesh, edge).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 5);
+        assert_eq!(mesh.face_count(), 2);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 5);
+        assert_eq!(mesh.vertex(kept).position(), Some([0.5, 0.0, 0.0]));
+        assert!(!mesh.vertex(kept).edge().face().is_valid());
+    }
+
+    #[test]
+    fn subdivided_edges_have_even_segments() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let edge = mesh
+            .edges()
+            .find(|e| {
+                e.vertex().position() == Some([1.0, 0.0, 0.0])
+                    && e.twin().vertex().position() == Some([1.0, 1.0, 0.0])
+            })
+            .unwrap()
+            .index;
+        let added = mesh.subdivide_edge(edge, 3).unwrap();
+        builder::assert_connectivity(&mesh);
+        let heights: Vec<f32> = added
+            .iter()
+            .map(|v| mesh.vertex(*v).position().unwrap()[1])
+            .collect();
+        assert_eq!(heights, vec![0.25, 0.5, 0.75]);
+        assert_eq!(mesh.edge(edge).twin().vertex().index, added[0]);
+        assert!(mesh.faces().all(|f| f.edges().count() == 7));
+        assert!(mesh.subdivide_edge(edge, 0).unwrap().is_empty());
+    }
+}
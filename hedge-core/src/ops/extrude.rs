@@ -0,0 +1,475 @@
+use super::*;
+use crate::math::{self, Vec3};
+use std::collections::HashMap;
+
+/// Where the copy of an extruded loop is moved to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExtrudeOffset {
+    /// The same offset for every vertex.
+    Vector([f64; 3]),
+    /// The given distance along each vertex normal.
+    Normal(f64),
+}
+
+/// Grows a ring of quads out of an open boundary.
+///
+/// The boundary loop through `loop_root` is copied, the copy is moved by
+/// `offset` and every side of the loop gets a quad joining it to its copy,
+/// which becomes the new boundary. The copies get points of their own.
+/// Returns the side of the new loop running alongside `loop_root`, so rims
+/// can be extruded again step by step.
+pub fn extrude_boundary<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    loop_root: EdgeIndex,
+    offset: ExtrudeOffset,
+) -> Result<EdgeIndex> {
+    let mut span = trace::span!(mesh, "extrude_boundary");
+    let mesh = span.mesh();
+    let sides = boundary_loop(mesh, loop_root)?;
+    let corners: Vec<VertexIndex> = sides.iter().map(|e| mesh.edge(*e).vertex().index).collect();
+    let moved: Vec<Vec3> = corners
+        .iter()
+        .map(|v| {
+            let position = mesh.vertex_position(*v);
+            match offset {
+                ExtrudeOffset::Vector(delta) => math::add(position, delta),
+                ExtrudeOffset::Normal(distance) => {
+                    math::add(position, math::scale(mesh.vertex_normal(*v), distance))
+                }
+            }
+        })
+        .collect();
+
+    let copies: Vec<VertexIndex> = moved
+        .into_iter()
+        .map(|position| {
+            let point = mesh.add_element(Point::from_position(scalar::from_f64(position)));
+            mesh.add_element(Vertex::at_point(point))
+        })
+        .collect();
+    // Going up from each corner to its copy, the twin comes back down.
+    let rungs: Vec<EdgeIndex> = corners
+        .iter()
+        .zip(&copies)
+        .map(|(corner, copy)| utils::full_edge_unchecked(mesh, *corner, *copy))
+        .collect();
+    let n = sides.len();
+    // Running back over each side, the twin is the new boundary.
+    let tops: Vec<EdgeIndex> = (0..n)
+        .map(|i| utils::full_edge_unchecked(mesh, copies[(i + 1) % n], copies[i]))
+        .collect();
+
+    for i in 0..n {
+        let face = mesh.add_element(Face::new(sides[i]));
+        let up = rungs[(i + 1) % n];
+        let down = mesh.edge(rungs[i]).twin().index;
+        let ring = [sides[i], up, tops[i], down];
+        for (j, edge) in ring.iter().enumerate() {
+            utils::link_unchecked(mesh, *edge, ring[(j + 1) % 4]);
+            if let Some(e) = mesh.get_element_mut(*edge) {
+                e.face_index = face;
+            }
+        }
+    }
+    let boundary: Vec<EdgeIndex> = tops.iter().map(|e| mesh.edge(*e).twin().index).collect();
+    for (i, edge) in boundary.iter().enumerate() {
+        utils::link_unchecked(mesh, *edge, boundary[(i + 1) % n]);
+        set_vertex_edge(mesh, copies[i], *edge);
+    }
+    Ok(boundary[0])
+}
+
+/// Pushes a set of faces out of the surface, joined to it by side walls.
+///
+/// Vertices inside the region move with it, those on its border are copied
+/// and the copies move, so walls of quads only come up along the border of
+/// the region and not between its faces. Sides of the region on the open
+/// boundary of the mesh get walls as well. `ExtrudeOffset::Normal` follows
+/// the normals of the region's faces around each vertex. Returns the walls.
+///
+/// Every border vertex of the region may only be passed once by its
+/// boundary loops.
+pub fn extrude_region<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    faces: &Selection,
+    offset: ExtrudeOffset,
+) -> Result<Selection> {
+    let mut span = trace::span!(mesh, "extrude_region");
+    let mesh = span.mesh();
+    if let Some(face) = faces.iter().find(|f| !mesh.face(*f).is_valid()) {
+        return Err(Error::InvalidFace(face));
+    }
+    // Each side of the border with the vertex it leaves.
+    let loops: Vec<Vec<(EdgeIndex, VertexIndex)>> = faces
+        .boundary_loops(mesh)
+        .map(|sides| sides.iter().map(|e| (e.index, e.vertex().index)).collect())
+        .collect();
+
+    // Where every vertex of the region goes.
+    let mut normals: HashMap<VertexIndex, Vec3> = HashMap::new();
+    for face in faces.iter() {
+        let area = mesh.face_area_vector(face);
+        for vertex in mesh.face(face).vertices() {
+            let sum = normals.entry(vertex.index).or_insert([0.0; 3]);
+            *sum = math::add(*sum, area);
+        }
+    }
+    let mut targets: Vec<(VertexIndex, Vec3)> = normals
+        .into_iter()
+        .map(|(vertex, normal)| {
+            let position = mesh.vertex_position(vertex);
+            let delta = match offset {
+                ExtrudeOffset::Vector(delta) => delta,
+                ExtrudeOffset::Normal(distance) => math::scale(math::normalize(normal), distance),
+            };
+            (vertex, math::add(position, delta))
+        })
+        .collect();
+    targets.sort_by_key(|(v, _)| v.offset);
+    let targets: HashMap<VertexIndex, Vec3> = targets.into_iter().collect();
+
+    let mut copies: HashMap<VertexIndex, VertexIndex> = HashMap::new();
+    for sides in &loops {
+        for (_, vertex) in sides {
+            let vertex = *vertex;
+            let point = mesh.add_element(Point::from_position(scalar::from_f64(targets[&vertex])));
+            let copy = mesh.add_element(Vertex::at_point(point));
+            if copies.insert(vertex, copy).is_some() {
+                return Err(Error::NonManifold(format!(
+                    "the border of the region passes {:?} more than once",
+                    vertex
+                )));
+            }
+        }
+    }
+    for (vertex, target) in &targets {
+        if copies.contains_key(vertex) {
+            continue;
+        }
+        if let Some(point) = mesh.vertex(*vertex).element().map(|v| v.point_index) {
+            if let Some(p) = mesh.get_element_mut(point) {
+                p.position = scalar::from_f64(*target);
+            }
+        }
+    }
+
+    // The region lets go of its border vertices.
+    let inside: Vec<EdgeIndex> = faces
+        .iter()
+        .flat_map(|face| mesh.face(face).edges().map(|e| e.index).collect::<Vec<_>>())
+        .collect();
+    for edge in inside {
+        let origin = mesh.edge(edge).vertex().index;
+        if let Some(copy) = copies.get(&origin) {
+            if let Some(e) = mesh.get_element_mut(edge) {
+                e.vertex_index = *copy;
+            }
+            set_vertex_edge(mesh, *copy, edge);
+        }
+    }
+
+    let mut walls = Selection::new();
+    for sides in loops {
+        let (sides, corners): (Vec<EdgeIndex>, Vec<VertexIndex>) = sides.into_iter().unzip();
+        let outside: Vec<EdgeIndex> = sides.iter().map(|e| mesh.edge(*e).twin().index).collect();
+        let rungs: Vec<EdgeIndex> = corners
+            .iter()
+            .map(|corner| utils::full_edge_unchecked(mesh, *corner, copies[corner]))
+            .collect();
+        let n = sides.len();
+        let mut bottoms = Vec::with_capacity(n);
+        for i in 0..n {
+            let bottom = mesh.add_element(Edge {
+                vertex_index: corners[i],
+                ..Edge::default()
+            });
+            let top = mesh.add_element(Edge {
+                vertex_index: copies[&corners[(i + 1) % n]],
+                ..Edge::default()
+            });
+            set_twins(mesh, bottom, outside[i]);
+            set_twins(mesh, top, sides[i]);
+            let face = mesh.add_element(Face::new(bottom));
+            let up = rungs[(i + 1) % n];
+            let down = mesh.edge(rungs[i]).twin().index;
+            let ring = [bottom, up, top, down];
+            for (j, edge) in ring.iter().enumerate() {
+                utils::link_unchecked(mesh, *edge, ring[(j + 1) % 4]);
+                if let Some(e) = mesh.get_element_mut(*edge) {
+                    e.face_index = face;
+                }
+            }
+            walls.insert(face);
+            bottoms.push(bottom);
+        }
+        // Corners on the open boundary of the mesh keep starting there.
+        for i in 0..n {
+            let before = outside[(i + n - 1) % n];
+            let open = [before, mesh.edge(outside[i]).next().index]
+                .into_iter()
+                .find(|e| {
+                    mesh.edge(*e).vertex().index == corners[i] && !mesh.edge(*e).face().is_valid()
+                });
+            set_vertex_edge(mesh, corners[i], open.unwrap_or(bottoms[i]));
+        }
+    }
+    Ok(walls)
+}
+
+/// Shrinks a face into itself, leaving a ring of quads around it.
+///
+/// The corners of the face move `amount` of the way towards its centroid,
+/// `0.0` keeps them in place and `1.0` would collapse the face, and the
+/// ring joins them to where they were. The face keeps its handle and is
+/// returned, the ring is what `extrude_region` makes for a single face.
+pub fn inset_face<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    face: FaceIndex,
+    amount: f64,
+) -> Result<FaceIndex> {
+    let mut span = trace::span!(mesh, "inset_face");
+    let mesh = span.mesh();
+    if !(0.0..1.0).contains(&amount) {
+        return Err(Error::InvalidArgument(format!(
+            "faces are inset by a fraction in 0..1, got {}",
+            amount
+        )));
+    }
+    let mut region = Selection::new();
+    region.insert(face);
+    extrude_region(mesh, &region, ExtrudeOffset::Vector([0.0; 3]))?;
+    let corners: Vec<(PointIndex, Vec3)> = mesh
+        .face(face)
+        .vertices()
+        .filter_map(|v| Some((v.element()?.point_index, mesh.vertex_position(v.index))))
+        .collect();
+    let sum = corners
+        .iter()
+        .fold([0.0; 3], |sum, (_, p)| math::add(sum, *p));
+    let centroid = math::scale(sum, 1.0 / corners.len() as f64);
+    for (point, position) in corners {
+        if let Some(p) = mesh.get_element_mut(point) {
+            p.position = scalar::from_f64(math::lerp(position, centroid, amount));
+        }
+    }
+    Ok(face)
+}
+
+/// Gives a surface thickness, returning a closed solid.
+///
+/// The surface is kept as the outside and a copy moved `thickness` against
+/// the vertex normals, with its faces reversed, becomes the inside. Every
+/// boundary edge gets a quad joining the two, so an open surface ends up
+/// closed. A negative thickness grows the solid along the normals instead.
+pub fn solidify<S: Scalar>(mesh: &Mesh<S>, thickness: f64) -> Mesh<S> {
+    let mut index = HashMap::new();
+    let mut positions = Vec::new();
+    for vertex in mesh.vertices() {
+        let position = mesh.vertex_position(vertex.index);
+        let inner = math::sub(
+            position,
+            math::scale(mesh.vertex_normal(vertex.index), thickness),
+        );
+        index.insert(vertex.index, positions.len());
+        positions.push(scalar::from_f64(position));
+        positions.push(scalar::from_f64(inner));
+    }
+    let corner = |v: VertexIndex| index.get(&v).copied().unwrap_or_default();
+
+    let mut polygons = Vec::new();
+    for face in mesh.faces() {
+        let outer: Vec<usize> = face.vertices().map(|v| corner(v.index)).collect();
+        polygons.push(outer.iter().rev().map(|i| i + 1).collect());
+        polygons.push(outer);
+    }
+    for edge in mesh.edges().filter(|e| !e.face().is_valid()) {
+        let (a, b) = (
+            corner(edge.vertex().index),
+            corner(edge.next().vertex().index),
+        );
+        polygons.push(vec![a, b, b + 1, a + 1]);
+    }
+    Mesh::from_polygons(&positions, &polygons)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Extrudes a set of faces, see `ops::extrude_region`.
+    pub fn extrude_region(
+        &mut self,
+        faces: &[FaceIndex],
+        offset: ExtrudeOffset,
+    ) -> Result<Selection> {
+        extrude_region(self, &Selection::from(faces), offset)
+    }
+
+    /// A closed solid of the given thickness with this surface as its
+    /// outside, see `ops::solidify`.
+    pub fn solidify(&self, thickness: f64) -> Mesh<S> {
+        solidify(self, thickness)
+    }
+
+    /// Insets a face, see `ops::inset_face`.
+    pub fn inset_face(&mut self, face: FaceIndex, amount: f64) -> Result<FaceIndex> {
+        inset_face(self, face, amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary_edges(mesh: &Mesh) -> usize {
+        mesh.edges().filter(|e| !e.face().is_valid()).count()
+    }
+
+    #[test]
+    fn skirts_hang_below_the_boundary() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let root = mesh.edges().find(|e| !e.face().is_valid()).unwrap().index;
+        let rim =
+            extrude_boundary(&mut mesh, root, ExtrudeOffset::Vector([0.0, 0.0, -1.0])).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 12);
+        assert_eq!(mesh.vertex_count(), 17);
+        assert_eq!(mesh.point_count(), 17);
+        assert_eq!(boundary_edges(&mesh), 8);
+        assert!(mesh.edge(root).face().is_valid());
+        for edge in boundary_loop(&mesh, rim).unwrap() {
+            assert_eq!(
+                mesh.vertex_position(mesh.edge(edge).vertex().index)[2],
+                -1.0
+            );
+        }
+        // The sides face away from the middle of the grid.
+        let sides = mesh
+            .faces()
+            .filter(|f| f.vertices().any(|v| mesh.vertex_position(v.index)[2] < 0.0));
+        for face in sides {
+            let normal = mesh.face_normal(face.index);
+            let center = face.vertices().fold([0.0; 3], |sum, v| {
+                math::add(sum, mesh.vertex_position(v.index))
+            });
+            let outward = math::sub(math::scale(center, 0.25), [1.0, 1.0, -0.5]);
+            assert!(normal[2].abs() < 1.0e-9);
+            assert!(math::dot(normal, outward) > 0.0);
+        }
+
+        let again =
+            extrude_boundary(&mut mesh, rim, ExtrudeOffset::Vector([0.0, 0.0, -1.0])).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 20);
+        assert_eq!(boundary_loop(&mesh, again).unwrap().len(), 8);
+        assert!(extrude_boundary(&mut mesh, root, ExtrudeOffset::Normal(1.0)).is_err());
+    }
+
+    #[test]
+    fn rims_follow_vertex_normals() {
+        let mut mesh: Mesh = builder::grid(3, 1);
+        let root = mesh.edges().find(|e| !e.face().is_valid()).unwrap().index;
+        let rim = extrude_boundary(&mut mesh, root, ExtrudeOffset::Normal(0.5)).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 3 + 8);
+        for edge in boundary_loop(&mesh, rim).unwrap() {
+            assert_eq!(mesh.vertex_position(mesh.edge(edge).vertex().index)[2], 0.5);
+        }
+    }
+
+    #[test]
+    fn regions_only_get_walls_along_their_border() {
+        let mut mesh: Mesh = builder::grid(3, 3);
+        let inner: Vec<FaceIndex> = mesh
+            .faces()
+            .filter(|f| {
+                f.vertices()
+                    .all(|v| v.position().is_some_and(|p| p[0] >= 1.0))
+            })
+            .map(|f| f.index)
+            .collect();
+        assert_eq!(inner.len(), 6);
+        let (faces, vertices) = (mesh.face_count(), mesh.vertex_count());
+        let walls = mesh
+            .extrude_region(&inner, ExtrudeOffset::Vector([0.0, 0.0, 1.0]))
+            .unwrap();
+        builder::assert_connectivity(&mesh);
+        // Four inner sides and six on the open boundary.
+        assert_eq!(walls.len(), 10);
+        assert_eq!(mesh.face_count(), faces + 10);
+        assert_eq!(mesh.vertex_count(), vertices + 10);
+        for face in &inner {
+            assert!(mesh
+                .face(*face)
+                .vertices()
+                .all(|v| mesh.vertex_position(v.index)[2] == 1.0));
+            assert!(mesh.face_normal(*face)[2] > 0.99);
+        }
+        for wall in walls.iter() {
+            assert!(mesh.face_normal(wall)[2].abs() < 1.0e-9);
+        }
+        // Walls on the open side stand on the old boundary, which stays.
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 12);
+    }
+
+    #[test]
+    fn closed_regions_extrude_along_normals() {
+        let mut mesh: Mesh = builder::cube();
+        let top: Vec<FaceIndex> = mesh
+            .faces()
+            .filter(|f| mesh.face_normal(f.index)[2] > 0.99)
+            .map(|f| f.index)
+            .collect();
+        let walls = mesh
+            .extrude_region(&top, ExtrudeOffset::Normal(0.5))
+            .unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(walls.len(), 4);
+        assert!(mesh.edges().all(|e| !e.is_boundary()));
+        let highest = mesh
+            .vertices()
+            .map(|v| mesh.vertex_position(v.index)[2])
+            .fold(0.0, f64::max);
+        assert_eq!(highest, 1.5);
+
+        let everything: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let walls = mesh
+            .extrude_region(&everything, ExtrudeOffset::Vector([1.0, 0.0, 0.0]))
+            .unwrap();
+        assert!(walls.is_empty());
+        builder::assert_connectivity(&mesh);
+    }
+
+    #[test]
+    fn insets_leave_a_ring_of_quads() {
+        let mut mesh: Mesh = builder::grid(1, 1);
+        let face = mesh.faces().next().unwrap().index;
+        assert!(mesh.inset_face(face, 1.0).is_err());
+        assert_eq!(mesh.inset_face(face, 0.5).unwrap(), face);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 5);
+        assert!(mesh.faces().all(|f| f.edges().count() == 4));
+        assert!(mesh.faces().all(|f| mesh.face_normal(f.index)[2] > 0.99));
+        let area: f64 = mesh.faces().map(|f| f.area() as f64).sum();
+        assert!((area - 1.0).abs() < 1.0e-6);
+        assert!((mesh.face(face).area() - 0.25).abs() < 1.0e-6);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 4);
+    }
+
+    #[test]
+    fn solidified_sheets_are_closed() {
+        let sheet: Mesh<f64> = builder::grid(2, 2);
+        let solid = sheet.solidify(0.5);
+        builder::assert_connectivity(&solid);
+        let report = crate::analysis::watertight_report(&solid, 1.0e-9);
+        assert!(report.is_closed() && report.is_consistently_oriented());
+        assert!((report.volume - 2.0).abs() < 1.0e-9, "{}", report.volume);
+        assert_eq!(solid.face_count(), 4 + 4 + 8);
+        assert!(solid
+            .points
+            .iter()
+            .all(|(_, p)| p.position[2] == 0.0 || p.position[2] == -0.5));
+
+        // Closed surfaces just get a second shell inside.
+        let cube: Mesh<f64> = builder::cube();
+        assert_eq!(cube.solidify(0.1).face_count(), 12);
+    }
+}
@@ -0,0 +1,686 @@
+use super::*;
+use crate::math;
+
+/// Removes a face and opens a hole in its place.
+///
+/// Edges which end up without a face on either side are removed along with
+/// vertices that no longer have any edges.
+pub fn remove_face<S: Scalar>(mesh: &mut Mesh<S>, face: FaceIndex) -> Result<()> {
+    let mut span = trace::span!(mesh, "remove_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    let loop_edges: Vec<EdgeIndex> = mesh.face(face).edges().map(|e| e.index).collect();
+    let loop_vertices: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+    for index in &loop_edges {
+        if let Some(edge) = mesh.get_element_mut(*index) {
+            edge.face_index = FaceIndex::default();
+        }
+    }
+    mesh.remove_element(face);
+
+    // Sides shared with the existing boundary disappear, joining the hole to
+    // the neighboring boundary loops.
+    let mut kept = Vec::new();
+    for index in loop_edges {
+        let edge = edge_data(mesh, index)?;
+        let twin = edge_data(mesh, edge.twin_index)?;
+        if twin.face_index.is_valid() {
+            kept.push(index);
+            continue;
+        }
+        let (prev, next) = (edge.prev_index, edge.next_index);
+        let (twin_prev, twin_next) = (twin.prev_index, twin.next_index);
+        if next != edge.twin_index {
+            utils::link_unchecked(mesh, twin_prev, next);
+        }
+        if twin_next != index {
+            utils::link_unchecked(mesh, prev, twin_next);
+        }
+        mesh.remove_element(index);
+        mesh.remove_element(edge.twin_index);
+    }
+
+    for index in &kept {
+        let origin = mesh.edge(*index).vertex().index;
+        set_vertex_edge(mesh, origin, *index);
+    }
+    for vertex in loop_vertices {
+        let outgoing = mesh.vertex(vertex).edge();
+        if outgoing.is_valid() && outgoing.vertex().index == vertex {
+            continue;
+        }
+        // The vertex lost its outgoing edge, find any remaining one.
+        let remaining = mesh
+            .edges
+            .iter()
+            .find(|(_, e)| e.vertex_index == vertex && !e.face_index.is_valid())
+            .map(|(index, _)| index);
+        match remaining {
+            Some(edge) => set_vertex_edge(mesh, vertex, edge),
+            None => {
+                let point = mesh.vertex(vertex).element().map(|v| v.point_index);
+                mesh.remove_element(vertex);
+                if let Some(point) = point {
+                    remove_point_if_unused(mesh, point);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Cuts a face in two along a new edge between two of its corners.
+///
+/// The face keeps the part from `from` around to `to`, the returned edge
+/// runs from `from` to `to` and borders the new face.
+pub fn split_face<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    face: FaceIndex,
+    from: VertexIndex,
+    to: VertexIndex,
+) -> Result<EdgeIndex> {
+    let mut span = trace::span!(mesh, "split_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    let corner = |vertex: VertexIndex| {
+        mesh.face(face)
+            .edges()
+            .find(|e| e.vertex().index == vertex)
+            .map(|e| e.index)
+            .ok_or(Error::InvalidVertex(vertex))
+    };
+    let (leaving_from, leaving_to) = (corner(from)?, corner(to)?);
+    let (a, b) = (edge_data(mesh, leaving_from)?, edge_data(mesh, leaving_to)?);
+    if from == to
+        || edge_data(mesh, a.next_index)?.vertex_index == to
+        || edge_data(mesh, b.next_index)?.vertex_index == from
+    {
+        return Err(Error::InvalidArgument(format!(
+            "{:?} and {:?} are not separated by other corners of {:?}",
+            from, to, face
+        )));
+    }
+
+    let (outgoing_from, outgoing_to) =
+        (mesh.vertex(from).edge().index, mesh.vertex(to).edge().index);
+    let forward = utils::full_edge_unchecked(mesh, from, to);
+    let backward = mesh.edge(forward).twin().index;
+    set_vertex_edge(mesh, from, outgoing_from);
+    set_vertex_edge(mesh, to, outgoing_to);
+    // face: from ... to -> from, new face: to ... from -> to.
+    utils::link_unchecked(mesh, b.prev_index, backward);
+    utils::link_unchecked(mesh, backward, leaving_from);
+    utils::link_unchecked(mesh, a.prev_index, forward);
+    utils::link_unchecked(mesh, forward, leaving_to);
+    if let Some(e) = mesh.get_element_mut(backward) {
+        e.face_index = face;
+    }
+    if let Some(f) = mesh.get_element_mut(face) {
+        f.edge_index = backward;
+    }
+    let new = mesh.add_element(Face::new(forward));
+    let part: Vec<EdgeIndex> = FaceEdges::from_edge(mesh.edge(forward))
+        .map(|e| e.index)
+        .collect();
+    for index in part {
+        if let Some(e) = mesh.get_element_mut(index) {
+            e.face_index = new;
+        }
+    }
+    Ok(forward)
+}
+
+/// Cuts a face along a segment between two of its sides, each given as an
+/// edge of the face and how far along it the segment starts, `0 < t < 1`.
+///
+/// Both edges are split, so the faces on their other sides gain a corner,
+/// and the face is split between the new vertices. Returns the new edge as
+/// `split_face` does, running from the vertex on `a` to the one on `b`.
+pub fn cut_face<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    face: FaceIndex,
+    a: (EdgeIndex, f64),
+    b: (EdgeIndex, f64),
+) -> Result<EdgeIndex> {
+    let mut span = trace::span!(mesh, "cut_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    for (edge, t) in [a, b] {
+        if edge_data(mesh, edge)?.face_index != face {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} is not a side of {:?}",
+                edge, face
+            )));
+        }
+        if !(t > 0.0 && t < 1.0) {
+            return Err(Error::InvalidArgument(format!(
+                "{} lies outside of {:?}",
+                t, edge
+            )));
+        }
+    }
+    if a.0 == b.0 {
+        return Err(Error::InvalidArgument(format!(
+            "both ends of the cut lie on {:?}",
+            a.0
+        )));
+    }
+    let from = super::split_edge(mesh, a.0, a.1)?;
+    let to = super::split_edge(mesh, b.0, b.1)?;
+    split_face(mesh, face, from, to)
+}
+
+/// Reverses the winding of a single face in place.
+///
+/// Each side swaps `next` and `prev` and starts where its old `next` did,
+/// twins stay paired. This repairs a face which was built the wrong way
+/// round: flipping a face wound like its neighbours leaves it wound against
+/// them instead, which `Mesh::validate` reports, and `Mesh::flip_all_faces`
+/// is the way to turn a whole mesh. A face with only boundary edges around
+/// it flips its boundary loop too and stays valid. The vertex normals of
+/// corners used by no other face are negated.
+pub fn flip_face<S: Scalar>(mesh: &mut Mesh<S>, face: FaceIndex) -> Result<()> {
+    let mut span = trace::span!(mesh, "flip_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    let sides: Vec<EdgeFn<S>> = mesh.face(face).edges().collect();
+    let own: Vec<VertexIndex> = sides
+        .iter()
+        .map(|e| e.vertex())
+        .filter(|v| {
+            v.edges()
+                .all(|e| !e.face().is_valid() || e.face().index == face)
+        })
+        .map(|v| v.index)
+        .collect();
+    if sides.iter().all(|e| !e.twin().face().is_valid()) {
+        let edges: Vec<EdgeIndex> = sides
+            .iter()
+            .flat_map(|e| [e.index, e.twin().index])
+            .collect();
+        mesh.reverse_edges(&edges);
+    } else {
+        let moves: Vec<(EdgeIndex, VertexIndex, EdgeIndex)> = sides
+            .iter()
+            .map(|e| (e.index, e.next().vertex().index, e.prev().index))
+            .collect();
+        for (edge, origin, _) in &moves {
+            if let Some(e) = mesh.get_element_mut(*edge) {
+                std::mem::swap(&mut e.next_index, &mut e.prev_index);
+                e.vertex_index = *origin;
+            }
+        }
+        // A corner leaving along a side now leaves along the side that
+        // used to come into it.
+        for (edge, _, prev) in moves {
+            let vertex = mesh.edge(prev).vertex().index;
+            if mesh.vertex(vertex).edge().index == edge {
+                if let Some(v) = mesh.get_element_mut(vertex) {
+                    v.edge_index = prev;
+                }
+            }
+        }
+    }
+    for corner in own {
+        if let Some(normal) = mesh.vertex_normals.get_mut(corner) {
+            *normal = normal.map(|c| -c);
+        }
+    }
+    Ok(())
+}
+
+/// Inserts a vertex in a face and fans the face into triangles around it.
+///
+/// The vertex goes to `position`, or to the centroid of the corners when
+/// that is `None`, and is returned. `face` becomes the triangle on its
+/// first side.
+pub fn poke_face<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    face: FaceIndex,
+    position: Option<Position<S>>,
+) -> Result<VertexIndex> {
+    let mut span = trace::span!(mesh, "poke_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    let sides: Vec<Edge> = mesh
+        .face(face)
+        .edges()
+        .filter_map(|e| e.element().cloned())
+        .collect();
+    let corners: Vec<VertexIndex> = sides.iter().map(|e| e.vertex_index).collect();
+    let position = position.unwrap_or_else(|| {
+        let sum = corners
+            .iter()
+            .fold([0.0; 3], |sum, v| math::add(sum, mesh.vertex_position(*v)));
+        scalar::from_f64(math::scale(sum, 1.0 / corners.len() as f64))
+    });
+    let point = mesh.add_element(Point::from_position(position));
+    let center = mesh.add_element(Vertex::at_point(point));
+
+    // Start with a single spoke hanging into the face, then cut a
+    // triangle off towards every other corner.
+    let outgoing = mesh.vertex(corners[0]).edge().index;
+    let spoke = utils::full_edge_unchecked(mesh, corners[0], center);
+    let back = mesh.edge(spoke).twin().index;
+    set_vertex_edge(mesh, corners[0], outgoing);
+    utils::link_unchecked(mesh, sides[0].prev_index, spoke);
+    utils::link_unchecked(mesh, spoke, back);
+    let first = mesh.face(face).edge().index;
+    utils::link_unchecked(mesh, back, first);
+    for index in [spoke, back] {
+        if let Some(e) = mesh.get_element_mut(index) {
+            e.face_index = face;
+        }
+    }
+    let mut rest = face;
+    for corner in &corners[1..] {
+        let edge = split_face(mesh, rest, center, *corner)?;
+        rest = mesh.edge(edge).face().index;
+    }
+    Ok(center)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Inserts a vertex in a face and fans the face into triangles around
+    /// it, see `ops::poke_face`.
+    pub fn poke_face(
+        &mut self,
+        face: FaceIndex,
+        position: Option<Position<S>>,
+    ) -> Result<VertexIndex> {
+        poke_face(self, face, position)
+    }
+
+    /// Cuts a face in two along a new edge between two of its corners, the
+    /// dual of dissolving that edge.
+    ///
+    /// Returns the new face, which runs from `v1` around to `v0`, and the
+    /// new edge from `v0` to `v1` on its side. `face` keeps the part from
+    /// `v0` around to `v1`, see `ops::split_face`.
+    pub fn split_face(
+        &mut self,
+        face: FaceIndex,
+        v0: VertexIndex,
+        v1: VertexIndex,
+    ) -> Result<(FaceIndex, EdgeIndex)> {
+        let edge = split_face(self, face, v0, v1)?;
+        Ok((self.edge(edge).face().index, edge))
+    }
+
+    /// Cuts a face along a segment between points on two of its sides.
+    ///
+    /// Returns the new face and the edge along the cut on its side, like
+    /// `Mesh::split_face`, see `ops::cut_face`.
+    pub fn cut_face(
+        &mut self,
+        face: FaceIndex,
+        a: (EdgeIndex, f64),
+        b: (EdgeIndex, f64),
+    ) -> Result<(FaceIndex, EdgeIndex)> {
+        let edge = cut_face(self, face, a, b)?;
+        Ok((self.edge(edge).face().index, edge))
+    }
+
+    /// Reverses the winding of a face so its normal points the other way.
+    ///
+    /// This is `ops::flip_face`, meant for repairing faces built the wrong
+    /// way round. Neighbours are left as they are.
+    pub fn flip_face(&mut self, face: FaceIndex) -> Result<()> {
+        flip_face(self, face)
+    }
+
+    /// Removes a face, leaving no half-edge pointing at it.
+    ///
+    /// With `delete_isolated_edges` this is `ops::remove_face`: edges left
+    /// without a face on either side are removed and so are vertices and
+    /// points nothing uses anymore. Otherwise every edge is kept, sides the
+    /// face shared with the boundary stay behind as loose edges.
+    pub fn delete_face(&mut self, face: FaceIndex, delete_isolated_edges: bool) -> Result<()> {
+        if delete_isolated_edges {
+            return remove_face(self, face);
+        }
+        let mut span = trace::span!(self, "delete_face");
+        let mesh = span.mesh();
+        if mesh.get_element(face).is_none() {
+            return Err(Error::InvalidFace(face));
+        }
+        let sides: Vec<EdgeIndex> = mesh.face(face).edges().map(|e| e.index).collect();
+        for index in sides {
+            if let Some(edge) = mesh.get_element_mut(index) {
+                edge.face_index = FaceIndex::default();
+            }
+            let origin = mesh.edge(index).vertex().index;
+            set_vertex_edge(mesh, origin, index);
+        }
+        mesh.remove_element(face);
+        Ok(())
+    }
+
+    /// Removes a vertex with every face and edge around it.
+    ///
+    /// With `fill_hole` the faces around the vertex are merged into one
+    /// polygon instead, which is `checked::dissolve_vertex` and needs the
+    /// vertex to be surrounded by faces. Otherwise the faces are removed
+    /// like `ops::remove_face` does, starting next to the boundary, so edges
+    /// and vertices left without faces go as well. Loose edges at the vertex
+    /// are removed last.
+    pub fn delete_vertex(&mut self, vertex: VertexIndex, fill_hole: bool) -> Result<()> {
+        if fill_hole {
+            return checked::dissolve_vertex(self, vertex).map(|_| ());
+        }
+        let mut span = trace::span!(self, "delete_vertex");
+        let mesh = span.mesh();
+        if mesh.get_element(vertex).is_none() {
+            return Err(Error::InvalidVertex(vertex));
+        }
+        loop {
+            let around: Vec<EdgeFn<S>> = mesh
+                .edges()
+                .filter(|e| e.vertex().index == vertex && e.face().is_valid())
+                .collect();
+            let next = around
+                .iter()
+                .find(|e| !e.twin().face().is_valid() || !e.prev().twin().face().is_valid())
+                .or(around.first())
+                .map(|e| e.face().index);
+            match next {
+                Some(face) => remove_face(mesh, face)?,
+                None => break,
+            }
+        }
+        if mesh.get_element(vertex).is_none() {
+            return Ok(());
+        }
+        let loose: Vec<EdgeIndex> = mesh
+            .edges
+            .iter()
+            .filter(|(_, e)| e.vertex_index == vertex)
+            .map(|(index, _)| index)
+            .collect();
+        for index in loose {
+            let (edge, twin) = edge_pair(mesh, index)?;
+            if edge.next_index != edge.twin_index {
+                utils::link_unchecked(mesh, twin.prev_index, edge.next_index);
+            }
+            if twin.next_index != index {
+                utils::link_unchecked(mesh, edge.prev_index, twin.next_index);
+            }
+            mesh.remove_element(index);
+            mesh.remove_element(edge.twin_index);
+            let end = twin.vertex_index;
+            if mesh.vertex(end).edge().is_valid() {
+                continue;
+            }
+            // The far end keeps going where the loose edge led.
+            match (edge.next_index != edge.twin_index).then_some(edge.next_index) {
+                Some(outgoing) => set_vertex_edge(mesh, end, outgoing),
+                None => {
+                    let point = mesh.vertex(end).element().map(|v| v.point_index);
+                    mesh.remove_element(end);
+                    if let Some(point) = point {
+                        remove_point_if_unused(mesh, point);
+                    }
+                }
+            }
+        }
+        let point = mesh.vertex(vertex).element().map(|v| v.point_index);
+        mesh.remove_element(vertex);
+        if let Some(point) = point {
+            remove_point_if_unused(mesh, point);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn removing_an_inner_face_opens_a_hole() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(3, 3);
+        let center = mesh
+            .faces()
+            .find(|f| f.edges().all(|e| !e.is_boundary()))
+            .unwrap()
+            .index;
+        remove_face(&mut mesh, center).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 8);
+        assert_eq!(mesh.vertex_count(), 16);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 16);
+    }
+
+    #[test]
+    fn removing_corner_faces_drops_unused_elements() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let first = mesh.faces().next().unwrap().index;
+        remove_face(&mut mesh, first).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.point_count(), 4);
+        assert_eq!(mesh.edge_count(), 8);
+
+        let last = mesh.faces().next().unwrap().index;
+        remove_face(&mut mesh, last).unwrap();
+        assert_eq!(mesh.edge_count(), 0);
+        assert_eq!(mesh.vertex_count(), 0);
+        assert_eq!(mesh.point_count(), 0);
+        assert_eq!(remove_face(&mut mesh, last), Err(Error::InvalidFace(last)));
+    }
+
+    #[test]
+    fn splitting_a_face_adds_an_edge() {
+        let mut mesh: Mesh = builder::grid(1, 1);
+        let face = mesh.faces().next().unwrap().index;
+        let corners: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        assert!(split_face(&mut mesh, face, corners[0], corners[1]).is_err());
+        let edge = split_face(&mut mesh, face, corners[0], corners[2]).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 2);
+        assert!(mesh.faces().all(|f| f.edges().count() == 3));
+        assert_eq!(mesh.edge(edge).vertex().index, corners[0]);
+        assert_eq!(mesh.edge(edge).twin().face().index, face);
+        assert!(mesh.vertices().all(|v| !v.edge().face().is_valid()));
+    }
+
+    #[test]
+    fn mesh_splits_undo_dissolves() {
+        let mut mesh: Mesh = builder::grid(1, 1);
+        let face = mesh.faces().next().unwrap().index;
+        let corners: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        let (new, edge) = mesh.split_face(face, corners[1], corners[3]).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_ne!(new, face);
+        assert_eq!(mesh.edge(edge).face().index, new);
+        assert_eq!(mesh.face(new).vertices().nth(1).unwrap().index, corners[3]);
+        let twin = mesh.edge(edge).twin().index;
+        assert_eq!(dissolve_edge(&mut mesh, twin).unwrap(), face);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.edge_count(), 8);
+    }
+
+    #[test]
+    fn deleted_faces_can_leave_their_edges() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let first = mesh.faces().next().unwrap().index;
+        let (edges, vertices) = (mesh.edge_count(), mesh.vertex_count());
+        mesh.delete_face(first, false).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 1);
+        assert_eq!(mesh.edge_count(), edges);
+        assert_eq!(mesh.vertex_count(), vertices);
+        assert!(mesh.edges().all(|e| e.face().index != first));
+        assert_eq!(
+            mesh.delete_face(first, true),
+            Err(Error::InvalidFace(first))
+        );
+
+        let last = mesh.faces().next().unwrap().index;
+        mesh.delete_face(last, true).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 0);
+        assert_eq!(mesh.edge_count(), 6);
+        assert_eq!(mesh.vertex_count(), 4);
+    }
+
+    #[test]
+    fn deleting_vertices_takes_their_fans() {
+        let vertex_at = |mesh: &Mesh, position: Position| {
+            mesh.vertices()
+                .find(|v| v.position() == Some(position))
+                .unwrap()
+                .index
+        };
+        let mut mesh: Mesh = builder::grid(3, 3);
+        let inner = vertex_at(&mesh, [1.0, 1.0, 0.0]);
+        let mut filled = mesh.clone();
+        filled.delete_vertex(inner, true).unwrap();
+        builder::assert_connectivity(&filled);
+        assert_eq!(filled.face_count(), 6);
+        assert_eq!(filled.vertex_count(), 15);
+
+        mesh.delete_vertex(inner, false).unwrap();
+        builder::assert_connectivity(&mesh);
+        // The cells around the vertex go and so do the grid corner and the
+        // vertices beside it, which no face uses anymore.
+        assert_eq!(mesh.face_count(), 5);
+        assert_eq!(mesh.vertex_count(), 12);
+        assert_eq!(mesh.point_count(), 12);
+        assert!(!mesh.vertex(inner).is_valid());
+        let edge = mesh
+            .edges()
+            .find(|e| e.vertex().position() == Some([3.0, 0.0, 0.0]))
+            .unwrap()
+            .index;
+        assert_eq!(
+            mesh.delete_vertex(inner, false),
+            Err(Error::InvalidVertex(inner))
+        );
+
+        // A corner which only has loose edges left.
+        let corner = vertex_at(&mesh, [3.0, 0.0, 0.0]);
+        let face = mesh.edge(edge).face().index;
+        let face = if face.is_valid() {
+            face
+        } else {
+            mesh.edge(edge).twin().face().index
+        };
+        mesh.delete_face(face, false).unwrap();
+        mesh.delete_vertex(corner, false).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 4);
+        assert!(!mesh.vertex(corner).is_valid());
+        assert!(mesh.vertices().all(|v| v.edge().is_valid()));
+    }
+
+    #[test]
+    fn flipping_a_face_twice_restores_it() {
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let original = mesh.clone();
+        let face = mesh
+            .faces()
+            .find(|f| f.vertices().any(|v| v.position() == Some([1.0, 1.0, 0.0])))
+            .unwrap()
+            .index;
+        flip_face(&mut mesh, face).unwrap();
+        assert_eq!(mesh.vertex_count(), original.vertex_count());
+        assert_eq!(mesh.edge_count(), original.edge_count());
+        assert!(mesh.face_normal(face)[2] < -0.99);
+        // Wound against its neighbours, until it is flipped back.
+        assert!(matches!(mesh.validate(), Err(Error::Inconsistent(_))));
+        mesh.flip_face(face).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(mesh.face_normal(face)[2] > 0.99);
+        assert!(mesh.topologically_equals(&original));
+    }
+
+    #[test]
+    fn loose_faces_flip_with_their_boundary() {
+        let mut mesh: Mesh = builder::grid(1, 1);
+        mesh.compute_vertex_normals(normals::NormalWeighting::Angle);
+        let face = mesh.faces().next().unwrap().index;
+        let corners: HashSet<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        mesh.flip_face(face).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(mesh.face_normal(face)[2] < -0.99);
+        assert!(mesh
+            .face(face)
+            .vertices()
+            .all(|v| corners.contains(&v.index)));
+        assert!(mesh
+            .vertices()
+            .all(|v| mesh.vertex_normals.get(v.index) == Some(&[0.0, 0.0, -1.0])));
+        let stale = FaceIndex::default();
+        assert_eq!(mesh.flip_face(stale), Err(Error::InvalidFace(stale)));
+    }
+
+    #[test]
+    fn poked_faces_fan_around_their_centroid() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let face = mesh.faces().next().unwrap().index;
+        let corners: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        let center = mesh.poke_face(face, None).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 5);
+        assert_eq!(mesh.vertex(center).edges().count(), 4);
+        assert!(mesh.face(face).edges().any(|e| e.vertex().index == center));
+        let expected = corners
+            .iter()
+            .fold([0.0; 3], |sum, v| math::add(sum, mesh.vertex_position(*v)));
+        assert_eq!(mesh.vertex_position(center), math::scale(expected, 0.25));
+
+        let mut triangle: Mesh = builder::triangle_grid(1, 1);
+        let face = triangle.faces().next().unwrap().index;
+        let center = triangle.poke_face(face, Some([0.25, 0.25, 1.0])).unwrap();
+        builder::assert_connectivity(&triangle);
+        assert_eq!(triangle.face_count(), 4);
+        assert!(triangle.faces().all(|f| f.edges().count() == 3));
+        assert_eq!(triangle.vertex(center).position(), Some([0.25, 0.25, 1.0]));
+    }
+
+    #[test]
+    fn knife_cuts_split_the_face_and_its_sides() {
+        let mut mesh: Mesh = builder::grid(2, 1);
+        let face = mesh
+            .faces()
+            .find(|f| f.vertices().all(|v| v.position().unwrap()[0] <= 1.0))
+            .unwrap()
+            .index;
+        let sides: Vec<EdgeIndex> = mesh.face(face).edges().map(|e| e.index).collect();
+        let (new, edge) = mesh
+            .cut_face(face, (sides[0], 0.5), (sides[2], 0.25))
+            .unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 3);
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_eq!(mesh.edge(edge).face().index, new);
+        assert_eq!(mesh.face(face).edges().count(), 4);
+        assert_eq!(mesh.face(new).edges().count(), 4);
+        let area = |f: FaceIndex| mesh.face(f).area();
+        assert!((area(face) + area(new) - 1.0).abs() < 1.0e-6);
+
+        assert!(mesh
+            .cut_face(face, (sides[0], 0.5), (sides[0], 0.7))
+            .is_err());
+        assert!(mesh
+            .cut_face(face, (sides[1], 0.0), (sides[3], 0.5))
+            .is_err());
+        let other_side = mesh.edge(edge).twin().index;
+        assert!(mesh.cut_face(new, (other_side, 0.5), (edge, 0.5)).is_err());
+    }
+}
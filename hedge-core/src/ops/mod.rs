@@ -0,0 +1,142 @@
+//! Local topological operators.
+//!
+//! The operators here only check that the handles they are given are live,
+//! they will happily produce non-manifold connectivity when asked to. The
+//! `checked` module wraps each of them with the tests needed to guarantee the
+//! result is still a valid 2-manifold.
+
+use crate::*;
+
+mod bevel;
+pub mod checked;
+mod cut;
+mod dissolve;
+mod edge;
+mod extrude;
+mod face;
+mod refine;
+mod ring;
+mod rip;
+mod triangulate;
+mod weld;
+
+pub use self::bevel::bevel_vertex;
+pub use self::cut::{bisect, split_by_plane, symmetrize, KeepSide, Plane};
+pub use self::dissolve::{dissolve_edge, dissolve_vertex, limited_dissolve, quadrangulate};
+pub use self::edge::{collapse_edge, flip_edge, split_edge, split_vertex, subdivide_edge};
+pub use self::extrude::{extrude_boundary, extrude_region, inset_face, solidify, ExtrudeOffset};
+pub use self::face::{cut_face, flip_face, poke_face, remove_face, split_face};
+pub use self::refine::{collapse_short_edges, refine_region, split_long_edges};
+pub use self::ring::{edge_ring, insert_edge_loop};
+pub use self::rip::rip;
+pub(crate) use self::triangulate::{ear_clip, ear_clipping};
+pub use self::triangulate::{triangulate, triangulate_face, Triangulated, TriangulationMethod};
+pub use self::weld::weld_boundaries;
+pub(crate) use self::weld::zip_sides;
+
+fn edge_data<S: Scalar>(mesh: &Mesh<S>, index: EdgeIndex) -> Result<Edge> {
+    mesh.get_element(index)
+        .cloned()
+        .ok_or(Error::InvalidEdge(index))
+}
+
+/// Both halves of an edge, failing unless both are live.
+fn edge_pair<S: Scalar>(mesh: &Mesh<S>, index: EdgeIndex) -> Result<(Edge, Edge)> {
+    let edge = edge_data(mesh, index)?;
+    let twin = edge_data(mesh, edge.twin_index).map_err(|_| Error::InvalidEdge(index))?;
+    Ok((edge, twin))
+}
+
+fn set_vertex_edge<S: Scalar>(mesh: &mut Mesh<S>, vertex: VertexIndex, edge: EdgeIndex) {
+    if let Some(v) = mesh.get_element_mut(vertex) {
+        v.edge_index = edge;
+    }
+}
+
+fn set_twins<S: Scalar>(mesh: &mut Mesh<S>, a: EdgeIndex, b: EdgeIndex) {
+    if let Some(e) = mesh.get_element_mut(a) {
+        e.twin_index = b;
+    }
+    if let Some(e) = mesh.get_element_mut(b) {
+        e.twin_index = a;
+    }
+}
+
+/// Removes a point unless another vertex still refers to it.
+fn remove_point_if_unused<S: Scalar>(mesh: &mut Mesh<S>, point: PointIndex) {
+    if !mesh.vertices.iter().any(|(_, v)| v.point_index == point) {
+        mesh.remove_element(point);
+    }
+}
+
+/// Picks an outgoing edge for a vertex, preferring one on the boundary so
+/// that circulation starts at the open side of the fan.
+fn best_outgoing<S: Scalar>(mesh: &Mesh<S>, candidates: &[EdgeIndex]) -> Option<EdgeIndex> {
+    let live = candidates
+        .iter()
+        .copied()
+        .filter(|e| mesh.edge(*e).is_valid());
+    live.clone()
+        .find(|e| !mesh.edge(*e).face().is_valid())
+        .or_else(|| live.clone().next())
+}
+
+/// Vertices at the other end of each outgoing edge.
+fn neighbors<S: Scalar>(mesh: &Mesh<S>, vertex: VertexIndex) -> Vec<VertexIndex> {
+    mesh.vertex(vertex)
+        .edges()
+        .map(|e| e.twin().vertex().index)
+        .collect()
+}
+
+fn is_boundary_vertex<S: Scalar>(mesh: &Mesh<S>, vertex: VertexIndex) -> bool {
+    mesh.vertex(vertex).edges().any(|e| e.is_boundary())
+}
+
+/// The fans of faces around a vertex, each as the outgoing edge of its
+/// first face and the boundary edge leaving it after its last face.
+fn fans<S: Scalar>(mesh: &Mesh<S>, vertex: VertexIndex) -> Vec<(EdgeIndex, EdgeIndex)> {
+    let outgoing: Vec<EdgeIndex> = mesh
+        .edges
+        .iter()
+        .filter(|(_, e)| e.vertex_index == vertex && !e.face_index.is_valid())
+        .map(|(index, _)| index)
+        .collect();
+    let mut fans = Vec::new();
+    for last in outgoing {
+        // Walk back from the boundary edge towards the fan's first face.
+        let mut first = last;
+        loop {
+            let twin = mesh.edge(first).twin();
+            if !twin.face().is_valid() {
+                break;
+            }
+            first = twin.next().index;
+        }
+        if first != last {
+            fans.push((first, last));
+        }
+    }
+    fans
+}
+
+/// The sides of the boundary loop through `edge`, starting at `edge`.
+fn boundary_loop<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex) -> Result<Vec<EdgeIndex>> {
+    let data = edge_data(mesh, edge)?;
+    if data.face_index.is_valid() {
+        return Err(Error::InvalidArgument(format!(
+            "{:?} is not a boundary edge",
+            edge
+        )));
+    }
+    let mut sides = vec![edge];
+    let mut current = data.next_index;
+    while current != edge {
+        if sides.len() > mesh.edge_count() || !mesh.edge(current).is_valid() {
+            return Err(Error::OpenEdgeLoop(edge));
+        }
+        sides.push(current);
+        current = mesh.edge(current).next().index;
+    }
+    Ok(sides)
+}
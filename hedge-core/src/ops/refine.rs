@@ -0,0 +1,386 @@
+use super::*;
+use crate::math;
+use std::collections::{HashMap, HashSet};
+
+/// Splits every edge longer than `max_length` at its middle until none is
+/// left, returning the number of splits.
+///
+/// Triangles next to a split edge are cut in two through the new vertex so
+/// triangle meshes stay triangulated, larger faces just gain a corner.
+pub fn split_long_edges<S: Scalar>(mesh: &mut Mesh<S>, max_length: f64) -> Result<usize> {
+    let mut span = trace::span!(mesh, "split_long_edges");
+    let mesh = span.mesh();
+    if max_length <= 0.0 || !max_length.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "edges are split above a positive length, got {}",
+            max_length
+        )));
+    }
+    let mut splits = 0;
+    loop {
+        let long = edges_by_length(mesh, |length| length > max_length);
+        if long.is_empty() {
+            return Ok(splits);
+        }
+        // Longest first, so the split of one edge doesn't create a worse
+        // triangle next to an even longer one.
+        for (edge, _) in long.into_iter().rev() {
+            let (e, twin) = edge_pair(mesh, edge)?;
+            let opposite: Vec<(FaceIndex, VertexIndex)> = [&e, &twin]
+                .into_iter()
+                .filter(|half| {
+                    let face = mesh.face(half.face_index);
+                    face.is_valid() && face.edges().count() == 3
+                })
+                .map(|half| {
+                    Ok((
+                        half.face_index,
+                        edge_data(mesh, half.prev_index)?.vertex_index,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            let middle = split_edge(mesh, edge, 0.5)?;
+            for (face, corner) in opposite {
+                split_face(mesh, face, middle, corner)?;
+            }
+            splits += 1;
+        }
+    }
+}
+
+/// Collapses edges shorter than `min_length`, shortest first, until none
+/// can be collapsed. Returns the number of collapses.
+///
+/// Every collapse passes the checks of `checked::collapse_edge` and must not
+/// flip the faces around it. The boundary and constrained vertices keep
+/// their place: edges between two of them are left alone and an edge with
+/// one end on the boundary or constrained collapses onto that end.
+pub fn collapse_short_edges<S: Scalar>(mesh: &mut Mesh<S>, min_length: f64) -> Result<usize> {
+    let mut span = trace::span!(mesh, "collapse_short_edges");
+    let mesh = span.mesh();
+    if min_length <= 0.0 || !min_length.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "edges are collapsed below a positive length, got {}",
+            min_length
+        )));
+    }
+    let mut collapses = 0;
+    loop {
+        let mut collapsed = false;
+        for (edge, _) in edges_by_length(mesh, |length| length < min_length) {
+            let Ok((e, twin)) = edge_pair(mesh, edge) else {
+                continue;
+            };
+            let (a, b) = (e.vertex_index, twin.vertex_index);
+            let (pa, pb) = (mesh.vertex_position(a), mesh.vertex_position(b));
+            if math::distance(pa, pb) >= min_length {
+                continue;
+            }
+            let anchored =
+                |v| is_boundary_vertex(mesh, v) || mesh.constraints.is_constrained(mesh, v);
+            // The origin of the collapsed edge is the vertex which is kept.
+            let (collapsed_edge, target) = match (anchored(a), anchored(b)) {
+                (true, true) => continue,
+                (true, false) => (edge, pa),
+                (false, true) => (e.twin_index, pb),
+                (false, false) => (edge, math::lerp(pa, pb, 0.5)),
+            };
+            if flips_faces(mesh, edge, target) {
+                continue;
+            }
+            match checked::collapse_edge(mesh, collapsed_edge) {
+                Ok(kept) => {
+                    if let Some(point) = mesh.vertex(kept).element().map(|v| v.point_index) {
+                        if let Some(p) = mesh.get_element_mut(point) {
+                            p.position = scalar::from_f64(target);
+                        }
+                    }
+                    collapses += 1;
+                    collapsed = true;
+                }
+                Err(Error::NonManifold(_)) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        if !collapsed {
+            return Ok(collapses);
+        }
+    }
+}
+
+/// Splits the selected triangles into four, `levels` times over, and
+/// returns the faces which make up the refined region.
+///
+/// Unselected triangles which would get two or three split sides join the
+/// region, those with a single split side are cut in two from its middle to
+/// the opposite corner, so the refined patch meets the rest of the mesh
+/// without T-junctions. Larger faces next to the region just gain corners.
+pub fn refine_region<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    faces: &Selection,
+    levels: usize,
+) -> Result<Selection> {
+    let mut span = trace::span!(mesh, "refine_region");
+    let mesh = span.mesh();
+    let mut region = faces.clone();
+    for face in region.iter() {
+        let sides = mesh.face(face).edges().count();
+        if sides == 0 {
+            return Err(Error::InvalidFace(face));
+        }
+        if sides != 3 {
+            return Err(Error::InvalidArgument(format!(
+                "only triangles are refined, {:?} has {} sides",
+                face, sides
+            )));
+        }
+    }
+    for _ in 0..levels {
+        region = refine_once(mesh, &region)?;
+    }
+    Ok(region)
+}
+
+fn is_triangle<S: Scalar>(mesh: &Mesh<S>, face: FaceIndex) -> bool {
+    mesh.face(face).is_valid() && mesh.face(face).edges().count() == 3
+}
+
+fn refine_once<S: Scalar>(mesh: &mut Mesh<S>, faces: &Selection) -> Result<Selection> {
+    // Grow the region until no triangle outside has more than one side on it.
+    let mut region = faces.clone();
+    loop {
+        let mut touching: HashMap<FaceIndex, usize> = HashMap::new();
+        for edge in region.boundary(mesh) {
+            let outside = mesh.edge(edge).twin().face().index;
+            if is_triangle(mesh, outside) {
+                *touching.entry(outside).or_default() += 1;
+            }
+        }
+        let grown: Vec<FaceIndex> = touching
+            .into_iter()
+            .filter(|(_, sides)| *sides > 1)
+            .map(|(face, _)| face)
+            .collect();
+        if grown.is_empty() {
+            break;
+        }
+        region.extend(grown);
+    }
+
+    let mut split = HashSet::new();
+    let mut sides = Vec::new();
+    for face in region.iter() {
+        for edge in mesh.face(face).edges() {
+            if split.insert(edge.index) {
+                split.insert(edge.twin().index);
+                sides.push(edge.index);
+            }
+        }
+    }
+    let corners: Vec<(FaceIndex, Vec<VertexIndex>)> = region
+        .iter()
+        .map(|face| (face, mesh.face(face).vertices().map(|v| v.index).collect()))
+        .collect();
+    let mut middles = HashSet::new();
+    let mut green = Vec::new();
+    for edge in sides {
+        let outside = [edge, mesh.edge(edge).twin().index]
+            .map(|half| mesh.edge(half).face().index)
+            .into_iter()
+            .find(|face| face.is_valid() && !region.contains(*face));
+        let middle = split_edge(mesh, edge, 0.5)?;
+        middles.insert(middle);
+        if let Some(face) = outside.filter(|face| mesh.face(*face).edges().count() == 4) {
+            green.push((face, middle));
+        }
+    }
+
+    let mut refined = Selection::new();
+    for (face, original) in corners {
+        // Cut off each corner, leaving the triangle between the middles.
+        let ring: Vec<VertexIndex> = mesh.face(face).vertices().map(|v| v.index).collect();
+        for corner in original {
+            let at = ring.iter().position(|v| *v == corner).unwrap_or_default();
+            let n = ring.len();
+            let (prev, next) = (ring[(at + n - 1) % n], ring[(at + 1) % n]);
+            if !middles.contains(&prev) || !middles.contains(&next) {
+                return Err(Error::InvalidFace(face));
+            }
+            let cut = split_face(mesh, face, next, prev)?;
+            refined.insert(mesh.edge(cut).face().index);
+        }
+        refined.insert(face);
+    }
+    for (face, middle) in green {
+        let opposite = mesh
+            .face(face)
+            .edges()
+            .find(|e| e.vertex().index == middle)
+            .map(|e| e.next().next().vertex().index)
+            .ok_or(Error::InvalidFace(face))?;
+        split_face(mesh, face, middle, opposite)?;
+    }
+    Ok(refined)
+}
+
+/// True when moving both ends of `edge` to `target` turns a remaining face
+/// around either end upside down.
+fn flips_faces<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex, target: math::Vec3) -> bool {
+    let ends = [
+        mesh.edge(edge).vertex().index,
+        mesh.edge(edge).twin().vertex().index,
+    ];
+    let removed = [
+        mesh.edge(edge).face().index,
+        mesh.edge(edge).twin().face().index,
+    ];
+    ends.iter()
+        .flat_map(|end| mesh.vertex(*end).edges().map(|e| e.face()))
+        .filter(|face| face.is_valid() && !removed.contains(&face.index))
+        .any(|face| {
+            let before: Vec<math::Vec3> = face
+                .vertices()
+                .map(|v| mesh.vertex_position(v.index))
+                .collect();
+            let after: Vec<math::Vec3> = face
+                .vertices()
+                .map(|v| {
+                    if ends.contains(&v.index) {
+                        target
+                    } else {
+                        mesh.vertex_position(v.index)
+                    }
+                })
+                .collect();
+            math::dot(math::polygon_normal(&before), math::polygon_normal(&after)) <= 0.0
+        })
+}
+
+/// One half of every edge whose length passes `keep`, shortest first.
+fn edges_by_length<S: Scalar>(mesh: &Mesh<S>, keep: impl Fn(f64) -> bool) -> Vec<(EdgeIndex, f64)> {
+    let mut edges: Vec<(EdgeIndex, f64)> = mesh
+        .edges()
+        .filter(|e| e.index.offset < e.twin().index.offset)
+        .map(|e| {
+            let length = math::distance(
+                mesh.vertex_position(e.vertex().index),
+                mesh.vertex_position(e.twin().vertex().index),
+            );
+            (e.index, length)
+        })
+        .filter(|(_, length)| keep(*length))
+        .collect();
+    edges.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.offset.cmp(&b.0.offset)));
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_edges_collapse_away_from_the_boundary() {
+        let mut mesh: Mesh<f64> = builder::triangle_grid(4, 4);
+        let boundary: Vec<Position<f64>> = mesh
+            .vertices()
+            .filter(|v| is_boundary_vertex(&mesh, v.index))
+            .filter_map(|v| v.position())
+            .collect();
+        // Squeeze the inner columns together so their edges become slivers.
+        for (_, point) in mesh.points.iter_mut() {
+            let x = point.position[0];
+            if x > 0.0 && x < 4.0 {
+                point.position[0] = 2.0 + (x - 2.0) * 0.05;
+            }
+        }
+        let collapses = collapse_short_edges(&mut mesh, 0.5).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(collapses > 0);
+        assert!(mesh.faces().all(|f| mesh.face_normal(f.index)[2] > 0.0));
+        let area: f64 = mesh.faces().map(|f| f.area()).sum();
+        assert!((area - 16.0).abs() < 1.0e-9);
+        // Corners of the grid are still where they were.
+        for corner in [[0.0, 0.0, 0.0], [4.0, 4.0, 0.0]] {
+            assert!(boundary.contains(&corner));
+            assert!(mesh.vertices().any(|v| v.position() == Some(corner)));
+        }
+        assert!(collapse_short_edges(&mut mesh, -1.0).is_err());
+    }
+
+    #[test]
+    fn pinned_vertices_survive_collapses() {
+        let mut mesh: Mesh<f64> = builder::triangle_grid(4, 4);
+        for (_, point) in mesh.points.iter_mut() {
+            let x = point.position[0];
+            if x > 0.0 && x < 4.0 {
+                point.position[0] = 2.0 + (x - 2.0) * 0.05;
+            }
+        }
+        let pinned: Vec<VertexIndex> = mesh
+            .vertices()
+            .filter(|v| v.position().is_some_and(|p| p[0] > 2.0 && p[0] < 4.0))
+            .map(|v| v.index)
+            .collect();
+        for vertex in &pinned {
+            mesh.constraints.pin(*vertex);
+        }
+        let positions: Vec<math::Vec3> = pinned.iter().map(|v| mesh.vertex_position(*v)).collect();
+        assert!(collapse_short_edges(&mut mesh, 0.5).unwrap() > 0);
+        builder::assert_connectivity(&mesh);
+        for (vertex, position) in pinned.iter().zip(positions) {
+            assert!(mesh.vertex(*vertex).is_valid());
+            assert_eq!(mesh.vertex_position(*vertex), position);
+        }
+    }
+
+    #[test]
+    fn long_edges_are_split_until_short() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh<f64> = builder::triangle_grid(2, 2);
+        let splits = split_long_edges(&mut mesh, 0.6).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(splits > 0);
+        assert!(mesh.faces().all(|f| f.edges().count() == 3));
+        assert!(mesh.edges().all(|e| {
+            math::distance(
+                mesh.vertex_position(e.vertex().index),
+                mesh.vertex_position(e.twin().vertex().index),
+            ) <= 0.6
+        }));
+        let area: f64 = mesh.faces().map(|f| f.area()).sum();
+        assert!((area - 4.0).abs() < 1.0e-12);
+        assert!(mesh.faces().all(|f| mesh.face_normal(f.index)[2] > 0.99));
+        assert!(split_long_edges(&mut mesh, 0.0).is_err());
+    }
+
+    #[test]
+    fn refined_regions_stay_conforming() {
+        let mut mesh: Mesh<f64> = builder::triangle_grid(4, 4);
+        // The two triangles of the lower left cell.
+        let selection: Selection = mesh
+            .faces()
+            .filter(|f| {
+                f.vertices()
+                    .filter_map(|v| v.position())
+                    .all(|p| p[0] <= 1.0 && p[1] <= 1.0)
+            })
+            .map(|f| f.index)
+            .collect();
+        assert_eq!(selection.len(), 2);
+        let faces = mesh.face_count();
+        let refined = refine_region(&mut mesh, &selection, 2).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(refined.len() >= 32);
+        assert!(mesh.faces().all(|f| f.edges().count() == 3));
+        assert!(mesh.faces().all(|f| mesh.face_normal(f.index)[2] > 0.0));
+        assert!(mesh.face_count() > faces + 30);
+        let area: f64 = mesh.faces().map(|f| f.area()).sum();
+        assert!((area - 16.0).abs() < 1.0e-9);
+        let region: f64 = refined.iter().map(|f| mesh.face(f).area()).sum();
+        assert!(region >= 1.0);
+
+        let mut quads: Mesh = builder::grid(1, 1);
+        let quad: Selection = quads.faces().map(|f| f.index).collect();
+        assert!(refine_region(&mut quads, &quad, 1).is_err());
+    }
+}
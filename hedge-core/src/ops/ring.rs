@@ -0,0 +1,154 @@
+use super::*;
+use std::collections::HashSet;
+
+/// The ring of quads through an edge, as the sides it crosses.
+///
+/// Each side is a half-edge on the quad following it and all of them run
+/// the same way, so side `i` and the twin of side `i + 1` are opposite sides
+/// of quad `i`. A ring which closes has as many sides as quads, one ending
+/// at the boundary or at a face that isn't a quad has one more. The first
+/// side is `edge` itself, or its twin when only that lies on a quad.
+pub fn edge_ring<S: Scalar>(mesh: &Mesh<S>, edge: EdgeIndex) -> Result<(Vec<EdgeIndex>, bool)> {
+    let is_quad = |e: EdgeFn<S>| e.face().is_valid() && e.face().edges().count() == 4;
+    let (e, twin) = edge_pair(mesh, edge)?;
+    let start = if is_quad(mesh.edge(edge)) {
+        edge
+    } else if is_quad(mesh.edge(e.twin_index)) {
+        e.twin_index
+    } else {
+        return Err(Error::InvalidArgument(format!(
+            "neither side of {:?} is a quad, {:?} and {:?}",
+            edge, e.face_index, twin.face_index
+        )));
+    };
+
+    let mut faces = HashSet::new();
+    let mut sides = vec![start];
+    faces.insert(mesh.edge(start).face().index);
+    let mut current = mesh.edge(start);
+    loop {
+        let next = current.next().next().twin();
+        if next.index == start {
+            return Ok((sides, true));
+        }
+        sides.push(next.index);
+        if !is_quad(next) {
+            break;
+        }
+        if !faces.insert(next.face().index) {
+            return Err(Error::NonManifold(format!(
+                "the ring through {:?} crosses itself at {:?}",
+                edge,
+                next.face().index
+            )));
+        }
+        current = next;
+    }
+    // Walk back from the start to the other end of an open ring.
+    let mut before = Vec::new();
+    let mut current = mesh.edge(start);
+    while is_quad(current.twin()) {
+        let previous = current.twin().next().next();
+        if !faces.insert(previous.face().index) {
+            return Err(Error::NonManifold(format!(
+                "the ring through {:?} crosses itself at {:?}",
+                edge,
+                previous.face().index
+            )));
+        }
+        before.push(previous.index);
+        current = previous;
+    }
+    before.reverse();
+    before.extend(sides);
+    Ok((before, false))
+}
+
+/// Cuts a new loop of edges through the ring of quads around `edge`, see
+/// `edge_ring`. Every side of the ring is split `t` along it, measured from
+/// the origin of `edge`, and every quad is split between its two new
+/// vertices. Returns the new edges in ring order, each on the side of the
+/// quad that was added.
+pub fn insert_edge_loop<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    edge: EdgeIndex,
+    t: f64,
+) -> Result<Vec<EdgeIndex>> {
+    let mut span = trace::span!(mesh, "insert_edge_loop");
+    let mesh = span.mesh();
+    let (sides, closed) = edge_ring(mesh, edge)?;
+    // The ring starts at the twin when only that lies on a quad.
+    let t = if sides.contains(&edge) { t } else { 1.0 - t };
+    let faces: Vec<FaceIndex> = sides
+        .iter()
+        .take(if closed { sides.len() } else { sides.len() - 1 })
+        .map(|side| mesh.edge(*side).face().index)
+        .collect();
+    let mut middles = Vec::with_capacity(sides.len());
+    for side in &sides {
+        middles.push(split_edge(mesh, *side, t)?);
+    }
+    let mut added = Vec::with_capacity(faces.len());
+    for (i, face) in faces.iter().enumerate() {
+        let next = middles[(i + 1) % middles.len()];
+        added.push(split_face(mesh, *face, middles[i], next)?);
+    }
+    Ok(added)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Inserts a loop of edges across the ring of quads through
+    /// `start_edge`, see `ops::insert_edge_loop`.
+    pub fn insert_edge_loop(&mut self, start_edge: EdgeIndex, t: f64) -> Result<Vec<EdgeIndex>> {
+        insert_edge_loop(self, start_edge, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_between(mesh: &Mesh, a: Position, b: Position) -> EdgeIndex {
+        mesh.edges()
+            .find(|e| e.vertex().position() == Some(a) && e.twin().vertex().position() == Some(b))
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn loops_run_across_open_rings() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(3, 2);
+        let edge = edge_between(&mesh, [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]);
+        let (sides, closed) = edge_ring(&mesh, edge).unwrap();
+        assert!(!closed);
+        assert_eq!(sides.len(), 4);
+
+        let added = mesh.insert_edge_loop(edge, 0.25).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(added.len(), 3);
+        assert_eq!(mesh.face_count(), 9);
+        assert_eq!(mesh.vertex_count(), 12 + 4);
+        for edge in added {
+            let e = mesh.edge(edge);
+            assert_eq!(e.vertex().position().unwrap()[1], 0.25);
+            assert_eq!(e.twin().vertex().position().unwrap()[1], 0.25);
+        }
+    }
+
+    #[test]
+    fn loops_close_around_cubes() {
+        let mut mesh: Mesh = builder::cube();
+        let edge = mesh.edges().next().unwrap().index;
+        let added = mesh.insert_edge_loop(edge, 0.5).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(added.len(), 4);
+        assert_eq!(mesh.face_count(), 10);
+        assert_eq!(mesh.vertex_count(), 12);
+        assert!(mesh.faces().all(|f| f.edges().count() == 4));
+
+        let mut triangles: Mesh = builder::triangle_grid(1, 1);
+        let edge = triangles.edges().next().unwrap().index;
+        assert!(triangles.insert_edge_loop(edge, 0.5).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+use std::collections::HashSet;
+
+/// Opens a seam along interior edges.
+///
+/// Both faces along each edge keep their side, each getting a new boundary
+/// edge as its twin. Vertices along the seam are duplicated once for every
+/// additional fan of faces the seam separates around them, the copies share
+/// the original point and vertex normal. Returns the new vertices.
+///
+/// A path ending inside the mesh opens a slit, one running from boundary to
+/// boundary cuts the surface apart.
+pub fn rip<S: Scalar>(mesh: &mut Mesh<S>, path: &[EdgeIndex]) -> Result<Vec<VertexIndex>> {
+    let mut span = trace::span!(mesh, "rip");
+    let mesh = span.mesh();
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for index in path {
+        let (edge, twin) = edge_pair(mesh, *index)?;
+        if !edge.face_index.is_valid() || !twin.face_index.is_valid() {
+            return Err(Error::InvalidArgument(format!(
+                "only interior edges can be ripped, {:?} is on the boundary",
+                index
+            )));
+        }
+        if seen.insert(*index) && seen.insert(edge.twin_index) {
+            edges.push((*index, edge, twin));
+        }
+    }
+
+    let mut touched = Vec::new();
+    let mut reached = HashSet::new();
+    for (index, edge, twin) in edges {
+        let opposite = mesh.add_element(Edge {
+            vertex_index: twin.vertex_index,
+            ..Edge::default()
+        });
+        let back = mesh.add_element(Edge {
+            vertex_index: edge.vertex_index,
+            ..Edge::default()
+        });
+        set_twins(mesh, index, opposite);
+        set_twins(mesh, edge.twin_index, back);
+        for vertex in [edge.vertex_index, twin.vertex_index] {
+            if reached.insert(vertex) {
+                touched.push(vertex);
+            }
+        }
+    }
+
+    let mut added = Vec::new();
+    for vertex in touched {
+        for (i, (first, last)) in fans(mesh, vertex).into_iter().enumerate() {
+            let target = if i == 0 {
+                set_vertex_edge(mesh, vertex, last);
+                vertex
+            } else {
+                let point_index = mesh.vertex(vertex).element().map(|v| v.point_index);
+                let copy = mesh.add_element(Vertex {
+                    edge_index: last,
+                    point_index: point_index.unwrap_or_default(),
+                });
+                if let Some(normal) = mesh.vertex_normals.get(vertex).copied() {
+                    mesh.vertex_normals.set(copy, normal);
+                }
+                added.push(copy);
+                copy
+            };
+            // Reassign the fan from its first face around to the boundary
+            // edge leaving it.
+            let mut current = first;
+            loop {
+                if let Some(e) = mesh.get_element_mut(current) {
+                    e.vertex_index = target;
+                }
+                if current == last {
+                    break;
+                }
+                current = mesh.edge(current).prev().twin().index;
+            }
+            let incoming = mesh.edge(first).twin().index;
+            utils::link_unchecked(mesh, incoming, last);
+        }
+    }
+    Ok(added)
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Splits the mesh along a path of interior edges, giving each side its
+    /// own border, see `ops::rip`. Returns the vertices that were added, they
+    /// share the points of the vertices they were copied from so positions
+    /// stay welded for exporters splitting on seams.
+    pub fn split_along_edges(&mut self, edges: &[EdgeIndex]) -> Result<Vec<VertexIndex>> {
+        rip(self, edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_between(mesh: &Mesh, a: Position, b: Position) -> EdgeIndex {
+        mesh.edges()
+            .find(|e| e.vertex().position() == Some(a) && e.twin().vertex().position() == Some(b))
+            .unwrap()
+            .index
+    }
+
+    fn boundary_edges(mesh: &Mesh) -> usize {
+        mesh.edges().filter(|e| !e.face().is_valid()).count()
+    }
+
+    #[test]
+    fn ripping_inside_opens_a_slit() {
+        let _ = env_logger::try_init();
+        let mut mesh: Mesh = builder::grid(4, 4);
+        let path = [
+            edge_between(&mesh, [1.0, 2.0, 0.0], [2.0, 2.0, 0.0]),
+            edge_between(&mesh, [3.0, 2.0, 0.0], [2.0, 2.0, 0.0]),
+        ];
+        let added = rip(&mut mesh, &path).unwrap();
+        builder::assert_connectivity(&mesh);
+        // Only the middle vertex separates, the ends stay in one piece.
+        assert_eq!(added.len(), 1);
+        assert_eq!(mesh.vertex_count(), 26);
+        assert_eq!(mesh.point_count(), 25);
+        assert_eq!(boundary_edges(&mesh), 16 + 4);
+        assert!(rip(&mut mesh, &path).is_err());
+    }
+
+    #[test]
+    fn ripping_across_cuts_the_mesh_apart() {
+        let mut mesh: Mesh = builder::grid(3, 3);
+        let path: Vec<EdgeIndex> = (0..3)
+            .map(|x| {
+                let x = x as f32;
+                edge_between(&mesh, [x, 1.0, 0.0], [x + 1.0, 1.0, 0.0])
+            })
+            .collect();
+        let added = rip(&mut mesh, &path).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(added.len(), 4);
+        assert_eq!(boundary_edges(&mesh), 12 + 6);
+        // Walking the boundary from below the seam never reaches the top.
+        let start = mesh
+            .edges()
+            .find(|e| !e.face().is_valid() && e.vertex().position() == Some([0.0, 0.0, 0.0]))
+            .unwrap();
+        let mut edge = start.next();
+        let mut length = 1;
+        while edge.index != start.index {
+            assert!(edge.vertex().position().unwrap()[1] <= 1.0);
+            edge = edge.next();
+            length += 1;
+        }
+        assert_eq!(length, 8);
+    }
+
+    #[test]
+    fn seams_share_points_across_the_split() {
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let path = [
+            edge_between(&mesh, [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]),
+            edge_between(&mesh, [1.0, 1.0, 0.0], [1.0, 2.0, 0.0]),
+        ];
+        let added = mesh.split_along_edges(&path).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(added.len(), 3);
+        assert_eq!(mesh.point_count(), 9);
+        assert_eq!(boundary_edges(&mesh), 8 + 4);
+        for copy in added {
+            let point = mesh.vertex(copy).point().unwrap();
+            assert_eq!(
+                mesh.vertices()
+                    .filter(|v| v.point().map(|p| p.position) == Some(point.position))
+                    .count(),
+                2
+            );
+        }
+    }
+}
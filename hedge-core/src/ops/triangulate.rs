@@ -0,0 +1,320 @@
+use super::*;
+use crate::attributes::Channel;
+use crate::math::{self, Vec3};
+use crate::predicates;
+use std::collections::HashMap;
+
+/// How faces are cut into triangles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TriangulationMethod {
+    /// Fans for convex faces and ear clipping for the others.
+    #[default]
+    Auto,
+    /// Triangles around the first corner, only correct for convex faces.
+    Fan,
+    /// Ear clipping in the plane of the face, handles concave faces.
+    EarClipping,
+}
+
+/// The elements triangulation added, so attributes can be carried over.
+///
+/// Vertices, points and the original half-edges are kept, only faces and
+/// the diagonals between corners are new.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Triangulated {
+    /// Each new face with the face it was cut from.
+    pub faces: Vec<(FaceIndex, FaceIndex)>,
+    /// Each new half-edge with the original half-edge leaving the same
+    /// corner of the face, whose corner values it shares.
+    pub corners: Vec<(EdgeIndex, EdgeIndex)>,
+}
+
+impl Triangulated {
+    /// Gives every new face the value of the face it was cut from.
+    pub fn copy_face_values<T: Clone>(&self, channel: &mut Channel<Face, T>) {
+        for (new, source) in &self.faces {
+            if let Some(value) = channel.get(*source).cloned() {
+                channel.set(*new, value);
+            }
+        }
+    }
+
+    /// Gives every new half-edge the value of its corner.
+    pub fn copy_corner_values<T: Clone>(&self, channel: &mut Channel<Edge, T>) {
+        for (new, source) in &self.corners {
+            if let Some(value) = channel.get(*source).cloned() {
+                channel.set(*new, value);
+            }
+        }
+    }
+
+    fn extend(&mut self, other: Triangulated) {
+        self.faces.extend(other.faces);
+        self.corners.extend(other.corners);
+    }
+}
+
+/// Cuts every face with more than three sides into triangles.
+pub fn triangulate<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    method: TriangulationMethod,
+) -> Result<Triangulated> {
+    let mut span = trace::span!(mesh, "triangulate");
+    let mesh = span.mesh();
+    let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+    let mut added = Triangulated::default();
+    for face in faces {
+        added.extend(triangulate_face(mesh, face, method)?);
+    }
+    Ok(added)
+}
+
+/// Cuts a single face into triangles, the first of which keeps the face.
+pub fn triangulate_face<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    face: FaceIndex,
+    method: TriangulationMethod,
+) -> Result<Triangulated> {
+    let mut span = trace::span!(mesh, "triangulate_face");
+    let mesh = span.mesh();
+    if mesh.get_element(face).is_none() {
+        return Err(Error::InvalidFace(face));
+    }
+    let sides: Vec<EdgeIndex> = mesh.face(face).edges().map(|e| e.index).collect();
+    let mut added = Triangulated::default();
+    if sides.len() <= 3 {
+        return Ok(added);
+    }
+    let corners: Vec<VertexIndex> = sides.iter().map(|e| mesh.edge(*e).vertex().index).collect();
+    let positions: Vec<Vec3> = corners.iter().map(|v| mesh.vertex_position(*v)).collect();
+    let triangles = match method {
+        TriangulationMethod::Fan => fan(positions.len()),
+        TriangulationMethod::EarClipping => ear_clipping(&positions),
+        TriangulationMethod::Auto => {
+            let projected = project(&positions);
+            if is_convex(&projected) {
+                fan(positions.len())
+            } else {
+                ear_clipping(&positions)
+            }
+        }
+    };
+
+    // Wiring up the diagonals moves the vertices' outgoing edges, which are
+    // put back afterwards to keep boundary edges first.
+    let outgoing: Vec<EdgeIndex> = corners
+        .iter()
+        .map(|v| mesh.vertex(*v).edge().index)
+        .collect();
+    let count = sides.len();
+    let mut diagonals: HashMap<(usize, usize), EdgeIndex> = HashMap::new();
+    let mut half = |mesh: &mut Mesh<S>, from: usize, to: usize| -> EdgeIndex {
+        if (from + 1) % count == to {
+            return sides[from];
+        }
+        if let Some(edge) = diagonals.get(&(from, to)) {
+            return *edge;
+        }
+        let edge = utils::full_edge_unchecked(mesh, corners[from], corners[to]);
+        let twin = mesh.edge(edge).twin().index;
+        diagonals.insert((from, to), edge);
+        diagonals.insert((to, from), twin);
+        added.corners.push((edge, sides[from]));
+        added.corners.push((twin, sides[to]));
+        edge
+    };
+    for (i, triangle) in triangles.iter().enumerate() {
+        let target = if i == 0 {
+            face
+        } else {
+            let new = mesh.add_element(Face::default());
+            added.faces.push((new, face));
+            new
+        };
+        let edges: Vec<EdgeIndex> = (0..3)
+            .map(|k| half(mesh, triangle[k], triangle[(k + 1) % 3]))
+            .collect();
+        for k in 0..3 {
+            utils::link_unchecked(mesh, edges[k], edges[(k + 1) % 3]);
+            if let Some(e) = mesh.get_element_mut(edges[k]) {
+                e.face_index = target;
+            }
+        }
+        if let Some(f) = mesh.get_element_mut(target) {
+            f.edge_index = edges[0];
+        }
+    }
+    for (vertex, edge) in corners.iter().zip(outgoing) {
+        set_vertex_edge(mesh, *vertex, edge);
+    }
+    Ok(added)
+}
+
+fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+/// Drops the dominant axis of the polygon's normal, keeping the winding
+/// counter clockwise in the projected plane.
+fn project(points: &[Vec3]) -> Vec<[f64; 2]> {
+    let normal = math::polygon_area_vector(points);
+    let axis = (0..3)
+        .max_by(|a, b| normal[*a].abs().total_cmp(&normal[*b].abs()))
+        .unwrap_or(2);
+    let (u, v) = match axis {
+        0 => (1, 2),
+        1 => (2, 0),
+        _ => (0, 1),
+    };
+    let flip = normal[axis] < 0.0;
+    points
+        .iter()
+        .map(|p| if flip { [p[v], p[u]] } else { [p[u], p[v]] })
+        .collect()
+}
+
+fn is_convex(points: &[[f64; 2]]) -> bool {
+    let n = points.len();
+    (0..n).all(|i| {
+        predicates::orient2d(points[(i + n - 1) % n], points[i], points[(i + 1) % n]) >= 0.0
+    })
+}
+
+pub(crate) fn ear_clipping(positions: &[Vec3]) -> Vec<[usize; 3]> {
+    ear_clip(&project(positions))
+}
+
+/// Triangles of a counter clockwise polygon, which may touch itself at
+/// repeated points as long as it never crosses itself.
+pub(crate) fn ear_clip(points: &[[f64; 2]]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        let is_ear = |i: usize| {
+            let [a, b, c] = corner(i).map(|k| points[k]);
+            if predicates::orient2d(a, b, c) <= 0.0 {
+                return false;
+            }
+            remaining.iter().all(|k| {
+                let p = points[*k];
+                corner(i).contains(k)
+                    || p == a
+                    || p == b
+                    || p == c
+                    || predicates::orient2d(a, b, p) < 0.0
+                    || predicates::orient2d(b, c, p) < 0.0
+                    || predicates::orient2d(c, a, p) < 0.0
+            })
+        };
+        // Degenerate loops may have no proper ear, clipping any convex or
+        // finally any corner still finishes the face.
+        let ear = (0..n)
+            .find(|i| is_ear(*i))
+            .or_else(|| {
+                (0..n).find(|i| {
+                    let [a, b, c] = corner(*i).map(|k| points[k]);
+                    predicates::orient2d(a, b, c) > 0.0
+                })
+            })
+            .unwrap_or(0);
+        triangles.push(corner(ear));
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Cuts a face into triangles, fanning convex faces and ear clipping
+    /// concave ones. Returns every triangle, starting with `face` itself.
+    pub fn triangulate_face(&mut self, face: FaceIndex) -> Result<Vec<FaceIndex>> {
+        let added = triangulate_face(self, face, TriangulationMethod::Auto)?;
+        Ok(std::iter::once(face)
+            .chain(added.faces.into_iter().map(|(new, _)| new))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l_shape() -> Mesh<f64> {
+        let outline = [
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [0.0, 2.0, 0.0],
+        ];
+        Mesh::from_polygons(&outline, &[vec![0, 1, 2, 3, 4, 5]])
+    }
+
+    #[test]
+    fn concave_faces_are_ear_clipped() {
+        let _ = env_logger::try_init();
+        let mut mesh = l_shape();
+        let face = mesh.faces().next().unwrap().index;
+        let mut colors: Channel<Face, u8> = Channel::new();
+        colors.set(face, 7);
+        let added = triangulate(&mut mesh, TriangulationMethod::Auto).unwrap();
+        added.copy_face_values(&mut colors);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 4);
+        assert_eq!(added.faces.len(), 3);
+        assert_eq!(added.corners.len(), 6);
+        for face in mesh.faces() {
+            assert_eq!(face.edges().count(), 3);
+            assert_eq!(colors.get(face.index), Some(&7));
+            // Every triangle keeps the face's orientation.
+            assert!(mesh.face_normal(face.index)[2] > 0.99);
+        }
+        let area: f64 = mesh.faces().map(|f| f.area()).sum();
+        assert!((area - 3.0).abs() < 1.0e-12);
+        // Boundary vertices still start circulation on the boundary.
+        for vertex in mesh.vertices() {
+            assert!(!vertex.edge().face().is_valid());
+        }
+    }
+
+    #[test]
+    fn corners_of_diagonals_follow_their_vertex() {
+        let mut mesh: Mesh = builder::grid(2, 2);
+        let mut corners: Channel<Edge, VertexIndex> = Channel::new();
+        for edge in mesh.edges() {
+            corners.set(edge.index, edge.vertex().index);
+        }
+        let added = triangulate(&mut mesh, TriangulationMethod::Fan).unwrap();
+        added.copy_corner_values(&mut corners);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.face_count(), 8);
+        for edge in mesh.edges() {
+            assert_eq!(corners.get(edge.index), Some(&edge.vertex().index));
+        }
+        assert!(triangulate(&mut mesh, TriangulationMethod::EarClipping)
+            .unwrap()
+            .faces
+            .is_empty());
+    }
+
+    #[test]
+    fn mesh_triangulation_lists_every_triangle() {
+        let mut mesh = l_shape();
+        let face = mesh.faces().next().unwrap().index;
+        let triangles = mesh.triangulate_face(face).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(triangles[0], face);
+        assert!(triangles.iter().all(|t| mesh.face(*t).edges().count() == 3));
+        assert_eq!(mesh.triangulate_face(face).unwrap(), vec![face]);
+    }
+}
@@ -0,0 +1,203 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+/// Zips two boundary loops together where they run along each other.
+///
+/// Every side of `loop_a` whose ends lie within `eps` of the ends of a side
+/// of `loop_b` running the other way is joined with it into an interior
+/// edge, and the vertices at both ends are merged. Merged vertices keep the
+/// points of `loop_a`, points of `loop_b` left unused are removed. Returns
+/// the number of edges welded.
+pub fn weld_boundaries<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    loop_a: EdgeIndex,
+    loop_b: EdgeIndex,
+    eps: f64,
+) -> Result<usize> {
+    let mut span = trace::span!(mesh, "weld_boundaries");
+    let mesh = span.mesh();
+    let sides_a = boundary_loop(mesh, loop_a)?;
+    let sides_b = boundary_loop(mesh, loop_b)?;
+    if sides_b.contains(&loop_a) {
+        return Err(Error::InvalidArgument(
+            "both edges belong to the same boundary loop".to_string(),
+        ));
+    }
+
+    let ends = |mesh: &Mesh<S>, edge: EdgeIndex| {
+        let edge = mesh.edge(edge);
+        (
+            mesh.vertex_position(edge.vertex().index),
+            mesh.vertex_position(edge.next().vertex().index),
+        )
+    };
+    let mut pairs = Vec::new();
+    let mut taken = HashSet::new();
+    for a in &sides_a {
+        let (from, to) = ends(mesh, *a);
+        let closest = sides_b
+            .iter()
+            .filter(|b| !taken.contains(*b))
+            .map(|b| {
+                let (b_from, b_to) = ends(mesh, *b);
+                let gap = math::distance(b_from, to).max(math::distance(b_to, from));
+                (*b, gap)
+            })
+            .filter(|(_, gap)| *gap <= eps)
+            .min_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((b, _)) = closest {
+            taken.insert(b);
+            pairs.push((*a, b));
+        }
+    }
+
+    zip_sides(mesh, &pairs)
+}
+
+/// Joins each pair of boundary sides, which run opposite ways, into an
+/// interior edge and merges the vertices at their ends. The second side's
+/// vertices merge into the first's, and nothing is changed when a vertex
+/// would have to merge with more than one other.
+pub(crate) fn zip_sides<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    pairs: &[(EdgeIndex, EdgeIndex)],
+) -> Result<usize> {
+    // Vertices of the second sides merge into the vertex they meet, which has to be
+    // the same one for every edge welded at them.
+    let mut merged: HashMap<VertexIndex, VertexIndex> = HashMap::new();
+    for (a, b) in pairs {
+        let (a, b) = (mesh.edge(*a), mesh.edge(*b));
+        for (from, into) in [
+            (b.vertex().index, a.next().vertex().index),
+            (b.next().vertex().index, a.vertex().index),
+        ] {
+            if *merged.entry(from).or_insert(into) != into {
+                return Err(Error::NonManifold(format!(
+                    "{:?} would merge with more than one vertex",
+                    from
+                )));
+            }
+        }
+    }
+
+    for (a, b) in pairs {
+        let (inner_a, inner_b) = (mesh.edge(*a).twin().index, mesh.edge(*b).twin().index);
+        set_twins(mesh, inner_a, inner_b);
+        mesh.remove_element(*a);
+        mesh.remove_element(*b);
+    }
+    let mut kept = Vec::new();
+    for (from, into) in &merged {
+        if !kept.contains(into) {
+            kept.push(*into);
+        }
+        if from == into {
+            continue;
+        }
+        let leaving: Vec<EdgeIndex> = mesh
+            .edges
+            .iter()
+            .filter(|(_, e)| e.vertex_index == *from)
+            .map(|(index, _)| index)
+            .collect();
+        for edge in leaving {
+            if let Some(e) = mesh.get_element_mut(edge) {
+                e.vertex_index = *into;
+            }
+        }
+        let point = mesh.vertex(*from).element().map(|v| v.point_index);
+        mesh.remove_element(*from);
+        if let Some(point) = point {
+            remove_point_if_unused(mesh, point);
+        }
+    }
+
+    // The boundary around merged vertices continues from one patch into
+    // the other.
+    for vertex in kept {
+        let fans = fans(mesh, vertex);
+        for (first, last) in &fans {
+            let incoming = mesh.edge(*first).twin().index;
+            utils::link_unchecked(mesh, incoming, *last);
+        }
+        let outgoing = match fans.first() {
+            Some((_, last)) => Some(*last),
+            None => mesh
+                .edges
+                .iter()
+                .find(|(_, e)| e.vertex_index == vertex)
+                .map(|(index, _)| index),
+        };
+        if let Some(edge) = outgoing {
+            set_vertex_edge(mesh, vertex, edge);
+        }
+    }
+    Ok(pairs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary_edge(mesh: &Mesh, from: Position) -> EdgeIndex {
+        mesh.edges()
+            .find(|e| !e.face().is_valid() && e.vertex().position() == Some(from))
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn welding_side_by_side_patches_joins_them() {
+        let _ = env_logger::try_init();
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ];
+        let mut mesh: Mesh = Mesh::from_polygons(&positions, &[vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        let left = boundary_edge(&mesh, [0.0, 0.0, 0.0]);
+        let right = boundary_edge(&mesh, [2.0, 0.0, 0.0]);
+        assert!(weld_boundaries(&mut mesh, left, left, 1.0e-6).is_err());
+        assert_eq!(weld_boundaries(&mut mesh, left, right, 1.0e-6).unwrap(), 1);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.point_count(), 6);
+        assert_eq!(mesh.edge_count(), 14);
+        let start = boundary_edge(&mesh, [0.0, 0.0, 0.0]);
+        assert_eq!(boundary_loop(&mesh, start).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn welding_closes_a_rip() {
+        let mut mesh: Mesh = builder::grid(3, 3);
+        let path: Vec<EdgeIndex> = mesh
+            .edges()
+            .filter(|e| {
+                let (a, b) = (
+                    e.vertex().position().unwrap(),
+                    e.twin().vertex().position().unwrap(),
+                );
+                a[1] == 1.0 && b[1] == 1.0 && a[0] < b[0]
+            })
+            .map(|e| e.index)
+            .collect();
+        rip(&mut mesh, &path).unwrap();
+        let below = boundary_edge(&mesh, [0.0, 0.0, 0.0]);
+        let above = boundary_edge(&mesh, [3.0, 3.0, 0.0]);
+        assert_eq!(weld_boundaries(&mut mesh, below, above, 0.0).unwrap(), 3);
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 16);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 12);
+        for vertex in mesh.vertices() {
+            let inside = vertex
+                .position()
+                .is_some_and(|p| p[0] % 3.0 != 0.0 && p[1] % 3.0 != 0.0);
+            assert_eq!(vertex.edge().face().is_valid(), inside);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Data parallel helpers.
+//!
+//! With the `parallel` feature `Mesh` gains `par_map_*` and `par_reduce_*`
+//! methods which run on the rayon thread pool and take care of the handle
+//! bookkeeping. The builders split their work with `in_chunks` on scoped
+//! threads instead, as they take an explicit thread count.
+
+#[cfg(feature = "parallel")]
+use crate::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Runs `f` on up to `threads` consecutive chunks of `items`, passing the
+/// offset of each chunk, and returns the results in chunk order.
+pub(crate) fn in_chunks<T, U, F>(items: &[T], threads: usize, f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(usize, &[T]) -> U + Sync,
+{
+    let size = items.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let f = &f;
+        let workers: Vec<_> = items
+            .chunks(size)
+            .enumerate()
+            .map(|(i, chunk)| scope.spawn(move || f(i * size, chunk)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("worker thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(feature = "parallel")]
+fn map<'mesh, S, H, E, T, F>(mesh: &'mesh Mesh<S>, handles: &[H], f: F) -> Vec<T>
+where
+    S: Scalar,
+    H: Copy + Sync + Default,
+    E: FunctionSet<'mesh, S, H>,
+    T: Send,
+    F: Fn(E) -> T + Sync,
+{
+    handles.par_iter().map(|h| f(E::new(*h, mesh))).collect()
+}
+
+#[cfg(feature = "parallel")]
+fn reduce<'mesh, S, H, E, T, I, F, R>(
+    mesh: &'mesh Mesh<S>,
+    handles: &[H],
+    identity: I,
+    f: F,
+    reduce: R,
+) -> T
+where
+    S: Scalar,
+    H: Copy + Sync + Default,
+    E: FunctionSet<'mesh, S, H>,
+    T: Send,
+    I: Fn() -> T + Sync + Send,
+    F: Fn(E) -> T + Sync,
+    R: Fn(T, T) -> T + Sync + Send,
+{
+    handles
+        .par_iter()
+        .map(|h| f(E::new(*h, mesh)))
+        .reduce(identity, reduce)
+}
+
+#[cfg(feature = "parallel")]
+impl<S: Scalar> Mesh<S> {
+    /// Applies `f` to every face in parallel, results are in `faces()` order.
+    pub fn par_map_faces<T: Send, F: Fn(FaceFn<'_, S>) -> T + Sync>(&self, f: F) -> Vec<T> {
+        let faces: Vec<FaceIndex> = self.faces.iter().map(|(index, _)| index).collect();
+        map(self, &faces, f)
+    }
+
+    /// Applies `f` to every edge in parallel, results are in `edges()` order.
+    pub fn par_map_edges<T: Send, F: Fn(EdgeFn<'_, S>) -> T + Sync>(&self, f: F) -> Vec<T> {
+        let edges: Vec<EdgeIndex> = self.edges.iter().map(|(index, _)| index).collect();
+        map(self, &edges, f)
+    }
+
+    /// Applies `f` to every vertex in parallel, results are in `vertices()` order.
+    pub fn par_map_vertices<T: Send, F: Fn(VertexFn<'_, S>) -> T + Sync>(&self, f: F) -> Vec<T> {
+        let vertices: Vec<VertexIndex> = self.vertices.iter().map(|(index, _)| index).collect();
+        map(self, &vertices, f)
+    }
+
+    /// Maps every face and combines the results with `reduce`, which has to
+    /// be associative as the pool reduces shares of the faces first.
+    /// `identity` seeds every partial result.
+    pub fn par_reduce_faces<T, I, F, R>(&self, identity: I, f: F, reduce: R) -> T
+    where
+        T: Send,
+        I: Fn() -> T + Sync + Send,
+        F: Fn(FaceFn<'_, S>) -> T + Sync,
+        R: Fn(T, T) -> T + Sync + Send,
+    {
+        let faces: Vec<FaceIndex> = self.faces.iter().map(|(index, _)| index).collect();
+        self::reduce(self, &faces, identity, f, reduce)
+    }
+
+    /// Like `par_reduce_faces` over every vertex.
+    pub fn par_reduce_vertices<T, I, F, R>(&self, identity: I, f: F, reduce: R) -> T
+    where
+        T: Send,
+        I: Fn() -> T + Sync + Send,
+        F: Fn(VertexFn<'_, S>) -> T + Sync,
+        R: Fn(T, T) -> T + Sync + Send,
+    {
+        let vertices: Vec<VertexIndex> = self.vertices.iter().map(|(index, _)| index).collect();
+        self::reduce(self, &vertices, identity, f, reduce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_cover_every_item_in_order() {
+        let _ = env_logger::try_init();
+        let items: Vec<usize> = (0..103).collect();
+        for threads in [1, 4, 200] {
+            let starts = in_chunks(&items, threads, |start, chunk| {
+                assert_eq!(chunk[0], start);
+                chunk.to_vec()
+            });
+            assert_eq!(starts.concat(), items);
+        }
+        assert!(in_chunks(&[] as &[usize], 4, |_, c| c.len()).is_empty());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn maps_and_reductions_match_sequential_results() {
+        let mesh: Mesh = builder::grid(16, 12);
+        let sides = mesh.par_map_faces(|face| face.edges().count());
+        assert_eq!(sides.len(), mesh.face_count());
+        assert!(sides.iter().all(|n| *n == 4));
+
+        let area = mesh.par_reduce_faces(|| 0.0, |face| face.area() as f64, |a, b| a + b);
+        assert_eq!(area, 192.0);
+        let valence: usize =
+            mesh.par_reduce_vertices(|| 0, |vertex| vertex.edges().count(), |a, b| a + b);
+        assert_eq!(valence, mesh.edge_count());
+
+        let origins = mesh.par_map_edges(|edge| edge.vertex().index);
+        let sequential: Vec<VertexIndex> = mesh.edges().map(|e| e.vertex().index).collect();
+        assert_eq!(origins, sequential);
+        let indices = mesh.par_map_vertices(|vertex| vertex.index);
+        assert_eq!(indices.len(), mesh.vertex_count());
+    }
+}
@@ -0,0 +1,158 @@
+//! Point storage which several meshes can share.
+//!
+//! `Mesh::points` is a `PointPool`, a reference counted buffer that is
+//! copied on the first write while other meshes still refer to it. Pieces
+//! cut from a large mesh can then keep every position in one place and
+//! address it with the same point handles as the original.
+//!
+//! A mesh sharing a pool counts and iterates every point in it, including
+//! those only the other meshes use.
+
+use crate::*;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// The buffer behind a `PointPool`.
+pub type PointBuffer<S = f32> = hbuf::ElementBuffer<Point<S>, Point>;
+
+#[derive(Clone)]
+pub struct PointPool<S: Scalar = f32> {
+    buffer: Arc<PointBuffer<S>>,
+}
+
+impl<S: Scalar> Default for PointPool<S> {
+    fn default() -> Self {
+        PointPool::from(PointBuffer::new())
+    }
+}
+
+impl<S: Scalar> fmt::Debug for PointPool<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PointPool {{ {} points, {} users }}",
+            self.buffer.len(),
+            Arc::strong_count(&self.buffer)
+        )
+    }
+}
+
+impl<S: Scalar> From<PointBuffer<S>> for PointPool<S> {
+    fn from(buffer: PointBuffer<S>) -> Self {
+        PointPool {
+            buffer: Arc::new(buffer),
+        }
+    }
+}
+
+impl<S: Scalar> Deref for PointPool<S> {
+    type Target = PointBuffer<S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<S: Scalar> DerefMut for PointPool<S> {
+    /// Copies the points first when another pool refers to them.
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.buffer)
+    }
+}
+
+/// A pool is written as its points, loading it gives a pool of its own.
+#[cfg(feature = "serde")]
+impl<S: Scalar + serde::Serialize> serde::Serialize for PointPool<S> {
+    fn serialize<Se: serde::Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        self.buffer.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, S: Scalar + serde::Deserialize<'de>> serde::Deserialize<'de> for PointPool<S> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PointBuffer::deserialize(deserializer).map(PointPool::from)
+    }
+}
+
+impl<S: Scalar> PointPool<S> {
+    /// True while another mesh refers to the same points.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.buffer) > 1
+    }
+
+    /// True when both pools refer to the same points.
+    pub fn shares_with(&self, other: &PointPool<S>) -> bool {
+        Arc::ptr_eq(&self.buffer, &other.buffer)
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// An empty mesh whose points are those of `pool`.
+    pub fn with_points(pool: PointPool<S>) -> Self {
+        let mut mesh = Mesh::new();
+        mesh.points = pool;
+        mesh
+    }
+
+    /// Copies the given faces into a new mesh which shares this mesh's
+    /// points, so its point handles stay valid for both.
+    pub fn extract_faces(&self, faces: &[FaceIndex]) -> Result<Mesh<S>> {
+        let polygons = faces
+            .iter()
+            .map(|face| {
+                if !self.face(*face).is_valid() {
+                    return Err(Error::InvalidFace(*face));
+                }
+                Ok(self
+                    .face(*face)
+                    .vertices()
+                    .filter_map(|v| v.element().map(|v| v.point_index))
+                    .collect())
+            })
+            .collect::<Result<Vec<Vec<PointIndex>>>>()?;
+        let mut piece = Mesh::with_points(self.points.clone());
+        builder::add_polygons(&mut piece, &polygons);
+        Ok(piece)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_share_points_until_written() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(4, 2);
+        let (left, right): (Vec<FaceIndex>, Vec<FaceIndex>) =
+            mesh.faces().map(|f| f.index).partition(|f| {
+                mesh.face(*f)
+                    .vertices()
+                    .all(|v| v.position().unwrap()[0] <= 2.0)
+            });
+        let left = mesh.extract_faces(&left).unwrap();
+        let mut right = mesh.extract_faces(&right).unwrap();
+        builder::assert_connectivity(&left);
+        builder::assert_connectivity(&right);
+        assert_eq!((left.face_count(), right.face_count()), (4, 4));
+        assert!(left.points.shares_with(&mesh.points));
+        assert!(right.points.shares_with(&left.points));
+        assert_eq!(left.point_count(), mesh.point_count());
+
+        let corner = right.faces().next().unwrap().vertices().next().unwrap();
+        let point = corner.element().unwrap().point_index;
+        right.get_element_mut(point).unwrap().position[2] = 1.0;
+        assert!(!right.points.shares_with(&mesh.points));
+        assert_eq!(mesh.position(point).unwrap()[2], 0.0);
+        assert_eq!(left.position(point), mesh.position(point));
+
+        drop(right);
+        let removed = mesh.faces().next().unwrap().index;
+        let mut copy = mesh.clone();
+        copy.remove_element(removed);
+        assert!(copy.points.is_shared());
+        assert!(mesh.extract_faces(&[removed]).is_ok());
+        assert!(copy.extract_faces(&[removed]).is_err());
+    }
+}
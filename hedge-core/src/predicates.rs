@@ -0,0 +1,198 @@
+//! Adaptive precision geometric predicates.
+//!
+//! Each predicate first evaluates its determinant in plain floating point and
+//! only falls back to exact expansion arithmetic (after Shewchuk, "Adaptive
+//! Precision Floating-Point Arithmetic and Fast Robust Geometric Predicates")
+//! when the result is too close to zero to trust its sign. The returned value
+//! always has the correct sign, its magnitude is only an approximation.
+
+const EPSILON: f64 = f64::EPSILON * 0.5;
+const ORIENT2D_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+const ORIENT3D_BOUND: f64 = (7.0 + 56.0 * EPSILON) * EPSILON;
+const INCIRCLE_BOUND: f64 = (10.0 + 96.0 * EPSILON) * EPSILON;
+
+/// Positive when `a`, `b` and `c` wind counter clockwise, negative when they
+/// wind clockwise and zero when they are collinear.
+pub fn orient2d(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    let left = (a[0] - c[0]) * (b[1] - c[1]);
+    let right = (a[1] - c[1]) * (b[0] - c[0]);
+    let det = left - right;
+    if det.abs() >= ORIENT2D_BOUND * (left.abs() + right.abs()) {
+        return det;
+    }
+
+    let [adx, ady, bdx, bdy] = [
+        Expansion::diff(a[0], c[0]),
+        Expansion::diff(a[1], c[1]),
+        Expansion::diff(b[0], c[0]),
+        Expansion::diff(b[1], c[1]),
+    ];
+    adx.mul(&bdy).sub(&ady.mul(&bdx)).estimate()
+}
+
+/// Positive when `d` lies below the plane through `a`, `b` and `c`, where
+/// below means the points appear counter clockwise when seen from above.
+/// Zero when all four points are coplanar.
+pub fn orient3d(a: [f64; 3], b: [f64; 3], c: [f64; 3], d: [f64; 3]) -> f64 {
+    let [ad, bd, cd] = [a, b, c].map(|p| [0, 1, 2].map(|i| p[i] - d[i]));
+    let det = ad[2] * (bd[0] * cd[1] - cd[0] * bd[1])
+        + bd[2] * (cd[0] * ad[1] - ad[0] * cd[1])
+        + cd[2] * (ad[0] * bd[1] - bd[0] * ad[1]);
+    let permanent = ((bd[0] * cd[1]).abs() + (cd[0] * bd[1]).abs()) * ad[2].abs()
+        + ((cd[0] * ad[1]).abs() + (ad[0] * cd[1]).abs()) * bd[2].abs()
+        + ((ad[0] * bd[1]).abs() + (bd[0] * ad[1]).abs()) * cd[2].abs();
+    if det.abs() >= ORIENT3D_BOUND * permanent {
+        return det;
+    }
+
+    let [ad, bd, cd] = [a, b, c].map(|p| [0, 1, 2].map(|i| Expansion::diff(p[i], d[i])));
+    let minor = |p: &[Expansion; 3], q: &[Expansion; 3]| p[0].mul(&q[1]).sub(&q[0].mul(&p[1]));
+    ad[2]
+        .mul(&minor(&bd, &cd))
+        .add(&bd[2].mul(&minor(&cd, &ad)))
+        .add(&cd[2].mul(&minor(&ad, &bd)))
+        .estimate()
+}
+
+/// Positive when `d` lies inside the circle through `a`, `b` and `c`, which
+/// have to wind counter clockwise. Zero when all four points are cocircular.
+pub fn incircle(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> f64 {
+    let [ad, bd, cd] = [a, b, c].map(|p| [p[0] - d[0], p[1] - d[1]]);
+    let lift = |p: [f64; 2]| p[0] * p[0] + p[1] * p[1];
+    let cross = |p: [f64; 2], q: [f64; 2]| p[0] * q[1] - q[0] * p[1];
+    let det = lift(ad) * cross(bd, cd) + lift(bd) * cross(cd, ad) + lift(cd) * cross(ad, bd);
+    let magnitude = |p: [f64; 2], q: [f64; 2]| (p[0] * q[1]).abs() + (q[0] * p[1]).abs();
+    let permanent =
+        lift(ad) * magnitude(bd, cd) + lift(bd) * magnitude(cd, ad) + lift(cd) * magnitude(ad, bd);
+    if det.abs() >= INCIRCLE_BOUND * permanent {
+        return det;
+    }
+
+    let [ad, bd, cd] =
+        [a, b, c].map(|p| [Expansion::diff(p[0], d[0]), Expansion::diff(p[1], d[1])]);
+    let lift = |p: &[Expansion; 2]| p[0].mul(&p[0]).add(&p[1].mul(&p[1]));
+    let cross = |p: &[Expansion; 2], q: &[Expansion; 2]| p[0].mul(&q[1]).sub(&q[0].mul(&p[1]));
+    lift(&ad)
+        .mul(&cross(&bd, &cd))
+        .add(&lift(&bd).mul(&cross(&cd, &ad)))
+        .add(&lift(&cd).mul(&cross(&ad, &bd)))
+        .estimate()
+}
+
+/// A sum of non-overlapping doubles ordered by increasing magnitude, which
+/// represents a value exactly.
+#[derive(Debug, Clone)]
+struct Expansion(Vec<f64>);
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let virtual_b = x - a;
+    let virtual_a = x - virtual_b;
+    (x, (a - virtual_a) + (b - virtual_b))
+}
+
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, a.mul_add(b, -x))
+}
+
+impl Expansion {
+    fn diff(a: f64, b: f64) -> Expansion {
+        let (x, y) = two_sum(a, -b);
+        Expansion(vec![y, x]).compressed()
+    }
+
+    fn compressed(mut self) -> Expansion {
+        self.0.retain(|c| *c != 0.0);
+        self
+    }
+
+    /// Adds a single double, Shewchuk's GROW-EXPANSION.
+    fn grow(&self, b: f64) -> Expansion {
+        let mut out = Vec::with_capacity(self.0.len() + 1);
+        let mut q = b;
+        for component in &self.0 {
+            let (sum, error) = two_sum(q, *component);
+            out.push(error);
+            q = sum;
+        }
+        out.push(q);
+        Expansion(out).compressed()
+    }
+
+    fn add(&self, other: &Expansion) -> Expansion {
+        other.0.iter().fold(self.clone(), |sum, c| sum.grow(*c))
+    }
+
+    fn negated(&self) -> Expansion {
+        Expansion(self.0.iter().map(|c| -c).collect())
+    }
+
+    fn sub(&self, other: &Expansion) -> Expansion {
+        self.add(&other.negated())
+    }
+
+    fn scale(&self, b: f64) -> Expansion {
+        self.0.iter().fold(Expansion(Vec::new()), |sum, c| {
+            let (product, error) = two_product(*c, b);
+            sum.grow(error).grow(product)
+        })
+    }
+
+    fn mul(&self, other: &Expansion) -> Expansion {
+        other
+            .0
+            .iter()
+            .fold(Expansion(Vec::new()), |sum, c| sum.add(&self.scale(*c)))
+    }
+
+    /// The most significant component carries the sign of the whole sum.
+    fn estimate(&self) -> f64 {
+        self.0.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_matches_winding() {
+        let _ = env_logger::try_init();
+        assert!(orient2d([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]) > 0.0);
+        assert!(orient2d([0.0, 0.0], [0.0, 1.0], [1.0, 0.0]) < 0.0);
+        assert_eq!(orient2d([0.0, 0.0], [1.0, 1.0], [2.0, 2.0]), 0.0);
+
+        let [a, b, c] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        assert!(orient3d(a, b, c, [0.0, 0.0, -1.0]) > 0.0);
+        assert!(orient3d(a, b, c, [0.0, 0.0, 1.0]) < 0.0);
+        assert_eq!(orient3d(a, b, c, [0.3, 0.7, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn nearly_collinear_points_get_exact_signs() {
+        // Points on the line y = x, nudged by the smallest representable step.
+        let base = 0.5;
+        for i in 0..64 {
+            let x = base + i as f64 * f64::EPSILON;
+            assert_eq!(orient2d([x, x], [12.0, 12.0], [24.0, 24.0]), 0.0);
+            let above = [x, f64::from_bits(x.to_bits() + 1)];
+            assert!(orient2d([12.0, 12.0], [24.0, 24.0], above) > 0.0);
+            let below = [x, f64::from_bits(x.to_bits() - 1)];
+            assert!(orient2d([12.0, 12.0], [24.0, 24.0], below) < 0.0);
+        }
+    }
+
+    #[test]
+    fn incircle_separates_inside_and_outside() {
+        let [a, b, c] = [[1.0, 0.0], [0.0, 1.0], [-1.0, 0.0]];
+        assert!(incircle(a, b, c, [0.0, 0.0]) > 0.0);
+        assert!(incircle(a, b, c, [2.0, 0.0]) < 0.0);
+        assert_eq!(incircle(a, b, c, [0.0, -1.0]), 0.0);
+        // Just inside and outside the unit circle.
+        let inside = [0.0, -(1.0 - f64::EPSILON)];
+        assert!(incircle(a, b, c, inside) > 0.0);
+        let outside = [0.0, -(1.0 + f64::EPSILON)];
+        assert!(incircle(a, b, c, outside) < 0.0);
+    }
+}
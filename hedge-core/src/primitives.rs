@@ -0,0 +1,523 @@
+//! Ready made meshes to start modeling from.
+//!
+//! Every primitive is centered on the origin and built through
+//! `Mesh::from_polygons`, so twins are paired and closed solids have all of
+//! their faces pointing outwards.
+
+use crate::math::{self, Vec3};
+use crate::*;
+
+/// An axis aligned box with sides of length `size` and one quad per side.
+pub fn cube<S: Scalar>(size: f64) -> Mesh<S> {
+    let half = size / 2.0;
+    let positions: Vec<[f64; 3]> = (0..8)
+        .map(|i| {
+            let side = |bit: usize| if i & bit == 0 { -half } else { half };
+            [side(1), side(2), side(4)]
+        })
+        .collect();
+    let polygons = [
+        vec![0, 2, 3, 1],
+        vec![4, 5, 7, 6],
+        vec![0, 1, 5, 4],
+        vec![2, 6, 7, 3],
+        vec![0, 4, 6, 2],
+        vec![1, 3, 7, 5],
+    ];
+    builder::from_polygons(&positions, &polygons)
+}
+
+/// A flat grid of `x_divs * y_divs` quads facing `+z`, covering `width`
+/// along X and `height` along Y. Its border is a single boundary loop.
+/// Fails without at least one division each way.
+pub fn grid<S: Scalar>(width: f64, height: f64, x_divs: usize, y_divs: usize) -> Result<Mesh<S>> {
+    if x_divs == 0 || y_divs == 0 {
+        return Err(Error::InvalidArgument(format!(
+            "a grid needs at least one division each way, got {}x{}",
+            x_divs, y_divs
+        )));
+    }
+    let positions: Vec<[f64; 3]> = (0..=y_divs)
+        .flat_map(|y| {
+            (0..=x_divs).map(move |x| {
+                [
+                    width * (x as f64 / x_divs as f64 - 0.5),
+                    height * (y as f64 / y_divs as f64 - 0.5),
+                    0.0,
+                ]
+            })
+        })
+        .collect();
+    let polygons: Vec<Vec<usize>> = (0..y_divs)
+        .flat_map(|y| {
+            (0..x_divs).map(move |x| {
+                let i = y * (x_divs + 1) + x;
+                vec![i, i + 1, i + x_divs + 2, i + x_divs + 1]
+            })
+        })
+        .collect();
+    Ok(builder::from_polygons(&positions, &polygons))
+}
+
+/// A regular tetrahedron with its corners `radius` from the origin.
+pub fn tetrahedron<S: Scalar>(radius: f64) -> Mesh<S> {
+    let corners = vec![
+        [1.0, 1.0, 1.0],
+        [1.0, -1.0, -1.0],
+        [-1.0, 1.0, -1.0],
+        [-1.0, -1.0, 1.0],
+    ];
+    let faces = triangles(&corners);
+    solid(radius, corners, faces)
+}
+
+/// A regular octahedron with its corners `radius` from the origin, on the
+/// axes.
+pub fn octahedron<S: Scalar>(radius: f64) -> Mesh<S> {
+    let corners: Vec<Vec3> = (0..6)
+        .map(|i| {
+            let mut corner = [0.0; 3];
+            corner[i / 2] = if i % 2 == 0 { 1.0 } else { -1.0 };
+            corner
+        })
+        .collect();
+    let faces = triangles(&corners);
+    solid(radius, corners, faces)
+}
+
+/// A regular icosahedron with its corners `radius` from the origin.
+pub fn icosahedron<S: Scalar>(radius: f64) -> Mesh<S> {
+    let corners = icosahedron_corners();
+    let faces = triangles(&corners);
+    solid(radius, corners, faces)
+}
+
+/// A regular dodecahedron with its corners `radius` from the origin, built
+/// as the dual of the icosahedron.
+pub fn dodecahedron<S: Scalar>(radius: f64) -> Mesh<S> {
+    let corners = icosahedron_corners();
+    let triangles = triangles(&corners);
+    let centers: Vec<Vec3> = triangles
+        .iter()
+        .map(|t| {
+            math::scale(
+                t.iter().map(|i| corners[*i]).fold([0.0; 3], math::add),
+                1.0 / 3.0,
+            )
+        })
+        .collect();
+    // Each corner of the icosahedron becomes a pentagon through the centers
+    // of the triangles around it, in order of their angle around the corner.
+    let faces = corners
+        .iter()
+        .enumerate()
+        .map(|(corner, position)| {
+            let mut around: Vec<usize> = (0..triangles.len())
+                .filter(|t| triangles[*t].contains(&corner))
+                .collect();
+            let normal = math::normalize(*position);
+            let tangent = |p: Vec3| math::sub(p, math::scale(normal, math::dot(p, normal)));
+            let u = math::normalize(tangent(centers[around[0]]));
+            let v = math::cross(normal, u);
+            let angle = |t: &usize| {
+                let d = tangent(centers[*t]);
+                math::dot(d, v).atan2(math::dot(d, u))
+            };
+            around.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+            around
+        })
+        .collect();
+    solid(radius, centers, faces)
+}
+
+/// A terrain grid with one vertex per sample of `heights`, which holds
+/// `width` samples along X for each of `depth` rows along Y.
+///
+/// Samples are spaced `scale[0]` and `scale[1]` apart and their heights are
+/// multiplied by `scale[2]`, see `generate::heightfield` for the layout
+/// of the faces. Fails unless there are `width * depth` samples, at least
+/// 2x2 of them. Only the grid is centered on the origin, heights are kept.
+pub fn from_heightfield<S: Scalar>(
+    heights: &[f32],
+    width: usize,
+    depth: usize,
+    scale: [f64; 3],
+) -> Result<Mesh<S>> {
+    if heights.len() != width * depth {
+        return Err(Error::InvalidArgument(format!(
+            "a {}x{} heightfield needs {} samples, got {}",
+            width,
+            depth,
+            width * depth,
+            heights.len()
+        )));
+    }
+    let mut mesh = generate::heightfield(width, depth, |x, y| heights[x + y * width])?;
+    let center = [(width - 1) as f64 / 2.0, (depth - 1) as f64 / 2.0, 0.0];
+    for (_, point) in mesh.points.iter_mut() {
+        let offset = math::sub(scalar::to_f64(point.position), center);
+        let scaled = [
+            offset[0] * scale[0],
+            offset[1] * scale[1],
+            offset[2] * scale[2],
+        ];
+        point.position = scalar::from_f64(scaled);
+    }
+    Ok(mesh)
+}
+
+/// Sweeps a profile a full turn around `axis` in `segments` steps.
+///
+/// Each step is joined to the next with quads and the last one to the
+/// first, so the seam is welded. Profile points on the axis are shared by
+/// every step and their quads become triangles. With `close_caps` the ends
+/// of the profile which are off the axis are closed with a polygon each.
+/// Faces point away from the axis where the profile runs along its
+/// direction. Fails with fewer than two profile points or three segments.
+pub fn revolve<S: Scalar>(
+    profile: &[Position<S>],
+    axis: &generate::Axis,
+    segments: usize,
+    close_caps: bool,
+) -> Result<Mesh<S>> {
+    let length = math::length(axis.direction);
+    if profile.len() < 2 || segments < 3 || length == 0.0 || !length.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "revolving needs two profile points, three segments and an axis, got {}, {} and {:?}",
+            profile.len(),
+            segments,
+            axis.direction
+        )));
+    }
+    let axis = generate::Axis::new(axis.origin, math::scale(axis.direction, 1.0 / length));
+    let profile: Vec<Vec3> = profile.iter().map(|p| scalar::to_f64(*p)).collect();
+    let extent = profile
+        .iter()
+        .map(|p| math::distance(*p, axis.origin))
+        .fold(0.0, f64::max);
+    let eps = Tolerances::relative_to(extent.max(1.0)).weld;
+
+    let mut positions = Vec::new();
+    let rings: Vec<Vec<usize>> = profile
+        .iter()
+        .map(|p| {
+            let offset = math::sub(*p, axis.origin);
+            let along = math::scale(axis.direction, math::dot(offset, axis.direction));
+            let steps = if math::length(math::sub(offset, along)) <= eps {
+                1
+            } else {
+                segments
+            };
+            let start = positions.len();
+            for step in 0..steps {
+                let angle = std::f64::consts::TAU * step as f64 / segments as f64;
+                positions.push(axis.rotate(*p, angle));
+            }
+            (0..segments).map(|step| start + step % steps).collect()
+        })
+        .collect();
+
+    let mut polygons = Vec::new();
+    for pair in rings.windows(2) {
+        for k in 0..segments {
+            let next = (k + 1) % segments;
+            let mut quad = vec![pair[0][k], pair[0][next], pair[1][next], pair[1][k]];
+            quad.dedup();
+            if quad.first() == quad.last() {
+                quad.pop();
+            }
+            if quad.len() >= 3 {
+                polygons.push(quad);
+            }
+        }
+    }
+    if close_caps {
+        let (first, last) = (&rings[0], &rings[rings.len() - 1]);
+        if first[0] != first[1] {
+            polygons.push(first.iter().rev().copied().collect());
+        }
+        if last[0] != last[1] {
+            polygons.push(last.clone());
+        }
+    }
+    Ok(builder::from_polygons(&positions, &polygons))
+}
+
+/// A prism over a simple polygon in the XY plane, reaching from
+/// `-height / 2` to `height / 2` along Z.
+///
+/// The outline may be concave and wind either way. Both caps are ear
+/// clipped into triangles and each side of the outline becomes a quad of
+/// the wall. Fails with fewer than three points, an outline without area or
+/// a height that isn't positive.
+pub fn extrude_polygon<S: Scalar>(outline: &[[f64; 2]], height: f64) -> Result<Mesh<S>> {
+    let flat: Vec<Vec3> = outline.iter().map(|p| [p[0], p[1], 0.0]).collect();
+    let area = math::polygon_area_vector(&flat)[2];
+    if outline.len() < 3 || area == 0.0 || !area.is_finite() || height <= 0.0 || !height.is_finite()
+    {
+        return Err(Error::InvalidArgument(format!(
+            "extruding needs an outline with area and a positive height, got {} points and {}",
+            outline.len(),
+            height
+        )));
+    }
+    let mut outline = outline.to_vec();
+    if area < 0.0 {
+        outline.reverse();
+    }
+    let n = outline.len();
+    let half = height / 2.0;
+    let positions: Vec<Vec3> = [-half, half]
+        .iter()
+        .flat_map(|z| outline.iter().map(move |p| [p[0], p[1], *z]))
+        .collect();
+
+    let mut polygons: Vec<Vec<usize>> = Vec::with_capacity(n * 3);
+    for [a, b, c] in ops::ear_clip(&outline) {
+        polygons.push(vec![c, b, a]);
+        polygons.push(vec![a + n, b + n, c + n]);
+    }
+    for i in 0..n {
+        let j = (i + 1) % n;
+        polygons.push(vec![i, j, j + n, i + n]);
+    }
+    Ok(builder::from_polygons(&positions, &polygons))
+}
+
+fn icosahedron_corners() -> Vec<Vec3> {
+    let phi = (1.0 + 5.0f64.sqrt()) / 2.0;
+    let mut corners = Vec::with_capacity(12);
+    for a in [-1.0, 1.0] {
+        for b in [-phi, phi] {
+            corners.push([0.0, a, b]);
+            corners.push([a, b, 0.0]);
+            corners.push([b, 0.0, a]);
+        }
+    }
+    corners
+}
+
+/// Triangles of the corners which are all as close as the closest pair,
+/// the faces of a convex solid with equal triangular sides.
+fn triangles(corners: &[Vec3]) -> Vec<Vec<usize>> {
+    let n = corners.len();
+    let d = |i: usize, j: usize| math::distance(corners[i], corners[j]);
+    let side = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .map(|(i, j)| d(i, j))
+        .fold(f64::INFINITY, f64::min);
+    let close = |i, j| d(i, j) <= side * (1.0 + 1.0e-9);
+    let mut faces = Vec::new();
+    for i in 0..n {
+        for j in i + 1..n {
+            for k in j + 1..n {
+                if close(i, j) && close(j, k) && close(i, k) {
+                    faces.push(vec![i, j, k]);
+                }
+            }
+        }
+    }
+    faces
+}
+
+/// Moves the corners of a convex solid around the origin `radius` out and
+/// winds every face to point away from the origin.
+fn solid<S: Scalar>(radius: f64, corners: Vec<Vec3>, mut faces: Vec<Vec<usize>>) -> Mesh<S> {
+    let positions: Vec<Vec3> = corners
+        .iter()
+        .map(|p| math::scale(math::normalize(*p), radius))
+        .collect();
+    for face in &mut faces {
+        let points: Vec<Vec3> = face.iter().map(|i| positions[*i]).collect();
+        if math::dot(math::polygon_area_vector(&points), points[0]) < 0.0 {
+            face.reverse();
+        }
+    }
+    builder::from_polygons(&positions, &faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_closed<S: Scalar>(mesh: &Mesh<S>, volume: f64) {
+        builder::assert_connectivity(mesh);
+        let report = analysis::watertight_report(mesh, 1.0e-9);
+        assert!(report.is_closed());
+        assert!(report.is_consistently_oriented());
+        assert!(
+            (report.volume - volume).abs() < 1.0e-6 * volume.max(1.0),
+            "{} != {}",
+            report.volume,
+            volume
+        );
+    }
+
+    #[test]
+    fn cubes_are_closed_boxes() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = cube(2.0);
+        assert_eq!(mesh.face_count(), 6);
+        assert_eq!(mesh.vertex_count(), 8);
+        assert!(mesh.faces().all(|f| f.edges().count() == 4));
+        assert_closed(&mesh, 8.0);
+        for face in mesh.faces() {
+            let normal = mesh.face_normal(face.index);
+            let center = face
+                .edges()
+                .map(|e| mesh.vertex_position(e.vertex().index))
+                .fold([0.0; 3], math::add);
+            assert!(math::dot(normal, center) > 0.0);
+        }
+    }
+
+    #[test]
+    fn grids_have_a_single_border() {
+        let mesh: Mesh = grid(4.0, 2.0, 4, 3).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert!(grid::<f32>(1.0, 1.0, 0, 3).is_err());
+        assert_eq!(mesh.face_count(), 12);
+        assert_eq!(mesh.vertex_count(), 20);
+        let start = mesh.edges().find(|e| !e.face().is_valid()).unwrap();
+        let mut border = 1;
+        let mut edge = start.next();
+        while edge.index != start.index {
+            assert!(!edge.face().is_valid());
+            border += 1;
+            edge = edge.next();
+        }
+        assert_eq!(border, 14);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 14);
+        assert!(mesh
+            .faces()
+            .all(|f| mesh.face_normal(f.index) == [0.0, 0.0, 1.0]));
+        let corners: Vec<Position> = mesh
+            .vertices()
+            .filter_map(|v| v.position())
+            .filter(|p| p[0].abs() == 2.0 && p[1].abs() == 1.0)
+            .collect();
+        assert_eq!(corners.len(), 4);
+    }
+
+    #[test]
+    fn platonic_solids_are_regular() {
+        let root5 = 5.0f64.sqrt();
+        let r = 2.0f64;
+        let tetrahedron_side = r * 4.0 / 6.0f64.sqrt();
+        let icosahedron_side = r / (2.0 * std::f64::consts::PI / 5.0).sin();
+        let dodecahedron_side = 4.0 * r / (3.0f64.sqrt() * (1.0 + root5));
+        let solids: [(Mesh, usize, usize, usize, f64); 4] = [
+            (
+                tetrahedron(r),
+                4,
+                4,
+                3,
+                tetrahedron_side.powi(3) / (6.0 * 2.0f64.sqrt()),
+            ),
+            (octahedron(r), 6, 8, 3, 4.0 / 3.0 * r.powi(3)),
+            (
+                icosahedron(r),
+                12,
+                20,
+                3,
+                5.0 / 12.0 * (3.0 + root5) * icosahedron_side.powi(3),
+            ),
+            (
+                dodecahedron(r),
+                20,
+                12,
+                5,
+                (15.0 + 7.0 * root5) / 4.0 * dodecahedron_side.powi(3),
+            ),
+        ];
+        for (mesh, vertices, faces, sides, volume) in &solids {
+            assert_eq!(mesh.vertex_count(), *vertices);
+            assert_eq!(mesh.face_count(), *faces);
+            assert!(mesh.faces().all(|f| f.edges().count() == *sides));
+            assert!(mesh
+                .vertices()
+                .all(|v| (math::length(mesh.vertex_position(v.index)) - r).abs() < 1.0e-6));
+            assert_closed(mesh, *volume);
+        }
+    }
+
+    #[test]
+    fn heightfields_are_scaled_around_the_origin() {
+        let heights = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        assert!(from_heightfield::<f32>(&heights, 4, 2, [1.0; 3]).is_err());
+        let mesh: Mesh = from_heightfield(&heights, 3, 2, [2.0, 1.0, 0.5]).unwrap();
+        builder::assert_connectivity(&mesh);
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.face_count(), 2);
+        let mut positions: Vec<Position> = mesh.vertices().filter_map(|v| v.position()).collect();
+        positions.sort_by(|a, b| a[2].total_cmp(&b[2]));
+        assert_eq!(positions[0], [-2.0, -0.5, 0.0]);
+        assert_eq!(positions[5], [2.0, 0.5, 2.5]);
+        assert!(mesh.faces().all(|f| mesh.face_normal(f.index)[2] > 0.0));
+    }
+
+    #[test]
+    fn revolving_welds_the_seam() {
+        let axis = generate::Axis::new([0.0; 3], [0.0, 0.0, 2.0]);
+        let segments = 16;
+        let polygon = segments as f64 / 2.0 * (std::f64::consts::TAU / segments as f64).sin();
+        let wall = [[1.0, 0.0, 0.0], [1.0, 0.0, 2.0]];
+        assert!(revolve::<f32>(&wall[..1], &axis, segments, true).is_err());
+        assert!(revolve::<f32>(&wall, &axis, 2, true).is_err());
+
+        let open: Mesh = revolve(&wall, &axis, segments, false).unwrap();
+        builder::assert_connectivity(&open);
+        assert_eq!(open.vertex_count(), 2 * segments);
+        assert_eq!(open.face_count(), segments);
+        assert_eq!(
+            open.edges().filter(|e| !e.face().is_valid()).count(),
+            2 * segments
+        );
+
+        let cylinder: Mesh = revolve(&wall, &axis, segments, true).unwrap();
+        assert_eq!(cylinder.face_count(), segments + 2);
+        assert_closed(&cylinder, polygon * 2.0);
+
+        // Points on the axis become poles shared by a fan of triangles.
+        let diamond = [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        let cone: Mesh = revolve(&diamond, &axis, segments, true).unwrap();
+        assert_eq!(cone.vertex_count(), segments + 2);
+        assert_eq!(cone.face_count(), 2 * segments);
+        assert!(cone.faces().all(|f| f.edges().count() == 3));
+        assert_closed(&cone, polygon * 2.0 / 3.0);
+    }
+
+    #[test]
+    fn concave_outlines_extrude_into_closed_prisms() {
+        // An L shape of three unit squares, listed clockwise.
+        let outline = [
+            [0.0, 0.0],
+            [0.0, 2.0],
+            [1.0, 2.0],
+            [1.0, 1.0],
+            [2.0, 1.0],
+            [2.0, 0.0],
+        ];
+        assert!(extrude_polygon::<f32>(&outline[..2], 1.0).is_err());
+        assert!(extrude_polygon::<f32>(&outline, 0.0).is_err());
+        let line = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]];
+        assert!(extrude_polygon::<f32>(&line, 1.0).is_err());
+
+        let mesh: Mesh = extrude_polygon(&outline, 3.0).unwrap();
+        assert_eq!(mesh.vertex_count(), 12);
+        assert_eq!(mesh.face_count(), 2 * 4 + 6);
+        assert_closed(&mesh, 9.0);
+        for face in mesh.faces() {
+            let normal = mesh.face_normal(face.index);
+            let z = face
+                .edges()
+                .map(|e| e.vertex().position().unwrap()[2])
+                .sum::<f32>();
+            if face.edges().count() == 3 {
+                assert_eq!(normal[2].signum(), z.signum() as f64);
+            } else {
+                assert_eq!(normal[2], 0.0);
+            }
+        }
+    }
+}
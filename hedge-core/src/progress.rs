@@ -0,0 +1,123 @@
+//! Progress reporting and cancellation for long running operations.
+//!
+//! Operations which may take a while have a `_with` variant accepting a
+//! `&mut dyn Progress`. It is told how far along the operation is every now
+//! and then and gets asked whether to stop, in which case the operation
+//! returns `Error::Cancelled` and discards its partial result.
+
+use crate::error::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Receives progress from a long running operation.
+pub trait Progress {
+    /// Called with the completed fraction of the work, between 0 and 1.
+    fn report(&mut self, _fraction: f64) {}
+
+    /// The operation stops at its next checkpoint once this returns true.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Ignores progress and never cancels.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silent;
+
+impl Progress for Silent {}
+
+impl<F: FnMut(f64)> Progress for F {
+    fn report(&mut self, fraction: f64) {
+        self(fraction)
+    }
+}
+
+/// A flag which can be raised from another thread to stop an operation.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    flag: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Cancellation::default()
+    }
+
+    /// Asks every operation watching this flag, or a clone of it, to stop.
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// Watches the flag while forwarding progress to `callback`.
+    pub fn reporting<F: FnMut(f64)>(self, callback: F) -> Reporting<F> {
+        Reporting {
+            cancellation: self,
+            callback,
+        }
+    }
+}
+
+impl Progress for Cancellation {
+    fn is_cancelled(&self) -> bool {
+        Cancellation::is_cancelled(self)
+    }
+}
+
+/// Progress forwarded to a callback, cancelled through a `Cancellation`.
+#[derive(Debug, Clone)]
+pub struct Reporting<F> {
+    cancellation: Cancellation,
+    callback: F,
+}
+
+impl<F: FnMut(f64)> Progress for Reporting<F> {
+    fn report(&mut self, fraction: f64) {
+        (self.callback)(fraction)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+}
+
+/// Reports `done` out of `total` steps and fails once cancelled.
+pub(crate) fn checkpoint(progress: &mut dyn Progress, done: usize, total: usize) -> Result<()> {
+    if progress.is_cancelled() {
+        return Err(Error::Cancelled);
+    }
+    let fraction = if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    progress.report(fraction);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoints_report_and_cancel() {
+        let _ = env_logger::try_init();
+        let mut reported = Vec::new();
+        let mut callback = |fraction| reported.push(fraction);
+        checkpoint(&mut callback, 1, 4).unwrap();
+        checkpoint(&mut callback, 4, 4).unwrap();
+        checkpoint(&mut Silent, 0, 0).unwrap();
+        assert_eq!(reported, vec![0.25, 1.0]);
+
+        let cancellation = Cancellation::new();
+        let mut watched = cancellation.clone().reporting(|_| {});
+        checkpoint(&mut watched, 0, 1).unwrap();
+        std::thread::spawn(move || cancellation.cancel())
+            .join()
+            .unwrap();
+        assert_eq!(checkpoint(&mut watched, 1, 1), Err(Error::Cancelled));
+    }
+}
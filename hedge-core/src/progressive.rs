@@ -0,0 +1,603 @@
+//! Progressive meshes, a coarse base mesh plus a stream of vertex splits.
+//!
+//! `encode` simplifies a copy of the mesh by quadric error edge collapses
+//! and records the inverse of each collapse as a `VertexSplit`. Replaying
+//! the first `n` splits on the base mesh gives the level of detail which
+//! had `n` collapses left to go, replaying all of them gives the input
+//! back. Levels refer to vertices and triangles by their position in the
+//! stream: the `k`th split adds vertex `base + k` and appends its triangles,
+//! so a prefix of the stream is all a reader needs to show a level.
+//!
+//! Faces with more sides are triangulated first, vertices without faces
+//! are left out.
+
+use crate::math::{self, Vec3};
+use crate::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Boundary edges are kept in place by planes through them, weighted this
+/// much more than the planes of the triangles.
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+/// The inverse of one edge collapse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexSplit {
+    /// The vertex which splits off the new one.
+    pub vertex: u32,
+    /// Where `vertex` moves back to.
+    pub position: [f64; 3],
+    /// Position of the new vertex.
+    pub split_position: [f64; 3],
+    /// Triangle corners, as triangle and corner within it, which switch
+    /// from `vertex` to the new vertex.
+    pub corners: Vec<(u32, u8)>,
+    /// Triangles the split adds back.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// A base mesh with the splits refining it back to the original.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProgressiveMesh {
+    pub positions: Vec<[f64; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+    pub splits: Vec<VertexSplit>,
+}
+
+impl ProgressiveMesh {
+    /// Number of levels above the base mesh.
+    pub fn len(&self) -> usize {
+        self.splits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.splits.is_empty()
+    }
+
+    /// Vertices of the level with `splits` splits applied.
+    pub fn vertex_count(&self, splits: usize) -> usize {
+        self.positions.len() + splits.min(self.splits.len())
+    }
+}
+
+/// Simplifies `mesh` down to `target` vertices, or as far as collapses stay
+/// manifold, and records how to refine it back.
+pub fn encode<S: Scalar>(mesh: &Mesh<S>, target: usize) -> Result<ProgressiveMesh> {
+    let mut soup = Soup::new(mesh)?;
+    let mut heap = BinaryHeap::new();
+    for t in 0..soup.triangles.len() {
+        for (a, b) in soup.sides(t) {
+            soup.push_candidate(&mut heap, a, b);
+        }
+    }
+    let mut collapses = Vec::new();
+    while soup.live_vertices > target {
+        let Some(candidate) = heap.pop() else {
+            break;
+        };
+        let (a, b) = (candidate.a, candidate.b);
+        if soup.versions[a] != candidate.versions[0] || soup.versions[b] != candidate.versions[1] {
+            continue;
+        }
+        if let Some(collapse) = soup.collapse(a, b, candidate.position) {
+            collapses.push(collapse);
+            for t in soup.around[a].clone() {
+                for (x, y) in soup.sides(t) {
+                    if x == a || y == a {
+                        soup.push_candidate(&mut heap, x, y);
+                    }
+                }
+            }
+        }
+    }
+    log::debug!(
+        "Encoded {} collapses down to {} vertices.",
+        collapses.len(),
+        soup.live_vertices
+    );
+    Ok(soup.stream(collapses))
+}
+
+/// Rebuilds the mesh with the first `splits` vertex splits applied.
+///
+/// Streams come from files as often as from `encode`, so every index is
+/// checked: a split naming a vertex or triangle which doesn't exist yet is
+/// an `Error::InvalidArgument`, and a level which isn't a manifold triangle
+/// mesh fails as `Mesh::from_indexed_triangles` does.
+pub fn decode<S: Scalar>(progressive: &ProgressiveMesh, splits: usize) -> Result<Mesh<S>> {
+    let mut positions = progressive.positions.clone();
+    let mut triangles = progressive.triangles.clone();
+    check_triangles(&triangles, positions.len(), "base mesh")?;
+    for (k, split) in progressive.splits.iter().take(splits).enumerate() {
+        let new = positions.len() as u32;
+        let vertex = positions.get_mut(split.vertex as usize).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "split {} refers to vertex {} of {}",
+                k, split.vertex, new
+            ))
+        })?;
+        *vertex = split.position;
+        positions.push(split.split_position);
+        for (triangle, corner) in &split.corners {
+            let count = triangles.len();
+            let slot = triangles
+                .get_mut(*triangle as usize)
+                .and_then(|t| t.get_mut(*corner as usize))
+                .ok_or_else(|| {
+                    Error::InvalidArgument(format!(
+                        "split {} refers to corner {} of triangle {} of {}",
+                        k, corner, triangle, count
+                    ))
+                })?;
+            *slot = new;
+        }
+        check_triangles(&split.triangles, positions.len(), &format!("split {}", k))?;
+        triangles.extend_from_slice(&split.triangles);
+    }
+    let positions: Vec<Position<S>> = positions.into_iter().map(scalar::from_f64).collect();
+    let indices: Vec<u32> = triangles.into_iter().flatten().collect();
+    Mesh::from_indexed_triangles(&positions, &indices)
+}
+
+fn check_triangles(triangles: &[[u32; 3]], vertices: usize, what: &str) -> Result<()> {
+    match triangles
+        .iter()
+        .flatten()
+        .find(|v| **v as usize >= vertices)
+    {
+        Some(v) => Err(Error::InvalidArgument(format!(
+            "{} refers to vertex {} of {}",
+            what, v, vertices
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A symmetric 4x4 matrix measuring squared distances to a set of planes.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: Vec3, through: Vec3, weight: f64) -> Self {
+        let [a, b, c] = normal;
+        let d = -math::dot(normal, through);
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+
+    fn add(self, other: Quadric) -> Self {
+        let mut sum = self.0;
+        for (s, o) in sum.iter_mut().zip(other.0) {
+            *s += o;
+        }
+        Quadric(sum)
+    }
+
+    fn error(&self, [x, y, z]: Vec3) -> f64 {
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// The position of least error, when it is well defined.
+    fn minimum(&self) -> Option<Vec3> {
+        let q = &self.0;
+        let m = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+        let rhs = [-q[3], -q[6], -q[8]];
+        let det = math::dot(m[0], math::cross(m[1], m[2]));
+        let scale = m.iter().flatten().fold(0.0f64, |s, v| s.max(v.abs()));
+        if det.abs() <= 1.0e-12 * scale.powi(3) || scale == 0.0 {
+            return None;
+        }
+        // Cramer's rule, the matrix is symmetric so its columns are its rows.
+        let column = |k: usize| {
+            let mut c = m;
+            for (row, value) in c.iter_mut().zip(rhs) {
+                row[k] = value;
+            }
+            math::dot(c[0], math::cross(c[1], c[2])) / det
+        };
+        Some([column(0), column(1), column(2)])
+    }
+}
+
+/// A collapse waiting in the queue, valid while neither end has changed.
+struct Candidate {
+    cost: f64,
+    a: usize,
+    b: usize,
+    position: Vec3,
+    versions: [u32; 2],
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    /// Cheapest first out of the max heap, ties broken by vertex.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| (other.a, other.b).cmp(&(self.a, self.b)))
+    }
+}
+
+/// A collapse as it happened, in terms of the original vertices and
+/// triangles.
+struct Collapse {
+    kept: usize,
+    removed: usize,
+    kept_position: Vec3,
+    removed_position: Vec3,
+    corners: Vec<(usize, u8)>,
+    triangles: Vec<usize>,
+}
+
+/// Indexed triangles being simplified.
+struct Soup {
+    positions: Vec<Vec3>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    /// Live triangles around each vertex.
+    around: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    live_vertices: usize,
+}
+
+impl Soup {
+    fn new<S: Scalar>(mesh: &Mesh<S>) -> Result<Self> {
+        let mut triangulated;
+        let mut mesh = mesh;
+        if mesh.faces().any(|face| face.edges().count() != 3) {
+            triangulated = mesh.clone();
+            ops::triangulate(&mut triangulated, ops::TriangulationMethod::Auto)?;
+            mesh = &triangulated;
+        }
+        let mut ids: HashMap<VertexIndex, usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut triangles = Vec::with_capacity(mesh.face_count());
+        for face in mesh.faces() {
+            let mut triangle = [0; 3];
+            for (corner, vertex) in triangle.iter_mut().zip(face.vertices()) {
+                *corner = *ids.entry(vertex.index).or_insert_with(|| {
+                    positions.push(mesh.vertex_position(vertex.index));
+                    positions.len() - 1
+                });
+            }
+            triangles.push(triangle);
+        }
+        let mut around = vec![Vec::new(); positions.len()];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for v in triangle {
+                around[*v].push(t);
+            }
+        }
+        let mut soup = Soup {
+            quadrics: vec![Quadric::default(); positions.len()],
+            versions: vec![0; positions.len()],
+            live_vertices: positions.len(),
+            alive: vec![true; triangles.len()],
+            positions,
+            triangles,
+            around,
+        };
+        soup.init_quadrics();
+        Ok(soup)
+    }
+
+    fn init_quadrics(&mut self) {
+        for t in 0..self.triangles.len() {
+            let [a, b, c] = self.triangles[t].map(|v| self.positions[v]);
+            let normal = math::normalize(math::cross(math::sub(b, a), math::sub(c, a)));
+            let plane = Quadric::plane(normal, a, 1.0);
+            for v in self.triangles[t] {
+                self.quadrics[v] = self.quadrics[v].add(plane);
+            }
+            for (x, y) in self.sides(t) {
+                if self.shared(x, y).len() == 1 {
+                    let (p, q) = (self.positions[x], self.positions[y]);
+                    let side = math::normalize(math::cross(math::sub(q, p), normal));
+                    let fence = Quadric::plane(side, p, BOUNDARY_WEIGHT);
+                    self.quadrics[x] = self.quadrics[x].add(fence);
+                    self.quadrics[y] = self.quadrics[y].add(fence);
+                }
+            }
+        }
+    }
+
+    fn sides(&self, t: usize) -> [(usize, usize); 3] {
+        let [a, b, c] = self.triangles[t];
+        [(a, b), (b, c), (c, a)]
+    }
+
+    /// Live triangles containing both vertices.
+    fn shared(&self, a: usize, b: usize) -> Vec<usize> {
+        self.around[a]
+            .iter()
+            .copied()
+            .filter(|t| self.triangles[*t].contains(&b))
+            .collect()
+    }
+
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.around[v]
+            .iter()
+            .flat_map(|t| self.triangles[*t])
+            .filter(|n| *n != v)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn is_boundary(&self, v: usize) -> bool {
+        self.neighbours(v)
+            .into_iter()
+            .any(|n| self.shared(v, n).len() == 1)
+    }
+
+    fn push_candidate(&self, heap: &mut BinaryHeap<Candidate>, a: usize, b: usize) {
+        let (a, b) = (a.min(b), a.max(b));
+        let quadric = self.quadrics[a].add(self.quadrics[b]);
+        let (p, q) = (self.positions[a], self.positions[b]);
+        let fallback = [p, q, math::lerp(p, q, 0.5)]
+            .into_iter()
+            .min_by(|x, y| quadric.error(*x).total_cmp(&quadric.error(*y)))
+            .unwrap_or(p);
+        let position = quadric
+            .minimum()
+            .filter(|m| quadric.error(*m) <= quadric.error(fallback))
+            .unwrap_or(fallback);
+        heap.push(Candidate {
+            cost: quadric.error(position).max(0.0),
+            a,
+            b,
+            position,
+            versions: [self.versions[a], self.versions[b]],
+        });
+    }
+
+    /// Merges `b` into `a` at `position` unless that would make the mesh
+    /// non-manifold, fold a triangle over or leave a vertex without faces.
+    fn collapse(&mut self, a: usize, b: usize, position: Vec3) -> Option<Collapse> {
+        let shared = self.shared(a, b);
+        let remaining = self.around[a].len() + self.around[b].len() - 2 * shared.len();
+        if shared.is_empty() || remaining == 0 {
+            return None;
+        }
+        // The link condition: the ends may only have the vertices opposite
+        // the edge as common neighbours.
+        let opposite: Vec<usize> = shared
+            .iter()
+            .flat_map(|t| self.triangles[*t])
+            .filter(|v| *v != a && *v != b)
+            .collect();
+        let (na, nb) = (self.neighbours(a), self.neighbours(b));
+        if na.iter().filter(|n| nb.contains(n)).count() != opposite.len() {
+            return None;
+        }
+        if shared.len() == 2 && self.is_boundary(a) && self.is_boundary(b) {
+            return None;
+        }
+        if opposite.iter().any(|c| self.around[*c].len() < 2) {
+            return None;
+        }
+        for (moving, t) in self.around[a]
+            .iter()
+            .map(|t| (a, *t))
+            .chain(self.around[b].iter().map(|t| (b, *t)))
+        {
+            if shared.contains(&t) {
+                continue;
+            }
+            let corners = self.triangles[t].map(|v| self.positions[v]);
+            let mut moved = corners;
+            let corner = self.triangles[t].iter().position(|v| *v == moving)?;
+            moved[corner] = position;
+            let normal = |[p, q, r]: [Vec3; 3]| math::cross(math::sub(q, p), math::sub(r, p));
+            let (before, after) = (normal(corners), normal(moved));
+            if math::dot(before, after) <= 0.0 || math::length(after) <= 1.0e-12 {
+                return None;
+            }
+        }
+
+        let collapse = Collapse {
+            kept: a,
+            removed: b,
+            kept_position: self.positions[a],
+            removed_position: self.positions[b],
+            corners: self.around[b]
+                .iter()
+                .filter(|t| !shared.contains(t))
+                .filter_map(|t| {
+                    let corner = self.triangles[*t].iter().position(|v| *v == b)?;
+                    Some((*t, corner as u8))
+                })
+                .collect(),
+            triangles: shared.clone(),
+        };
+        for t in &shared {
+            self.alive[*t] = false;
+            for v in self.triangles[*t] {
+                self.around[v].retain(|x| x != t);
+            }
+        }
+        for (t, corner) in &collapse.corners {
+            self.triangles[*t][*corner as usize] = a;
+            self.around[a].push(*t);
+        }
+        self.around[b].clear();
+        self.positions[a] = position;
+        self.quadrics[a] = self.quadrics[a].add(self.quadrics[b]);
+        self.versions[a] += 1;
+        self.versions[b] += 1;
+        self.live_vertices -= 1;
+        Some(collapse)
+    }
+
+    /// Numbers the surviving vertices and triangles first, then those each
+    /// split brings back in the order the splits are applied.
+    fn stream(self, collapses: Vec<Collapse>) -> ProgressiveMesh {
+        let mut vertex_ids = vec![u32::MAX; self.positions.len()];
+        let mut positions = Vec::new();
+        for (id, (around, position)) in vertex_ids
+            .iter_mut()
+            .zip(self.around.iter().zip(&self.positions))
+        {
+            if !around.is_empty() {
+                *id = positions.len() as u32;
+                positions.push(*position);
+            }
+        }
+        let mut triangle_ids = vec![u32::MAX; self.triangles.len()];
+        let mut triangles = Vec::new();
+        for (id, (alive, triangle)) in triangle_ids
+            .iter_mut()
+            .zip(self.alive.iter().zip(&self.triangles))
+        {
+            if *alive {
+                *id = triangles.len() as u32;
+                triangles.push(*triangle);
+            }
+        }
+        let mut next_triangle = triangles.len() as u32;
+        for (k, collapse) in collapses.iter().rev().enumerate() {
+            vertex_ids[collapse.removed] = (positions.len() + k) as u32;
+            for t in &collapse.triangles {
+                triangle_ids[*t] = next_triangle;
+                next_triangle += 1;
+            }
+        }
+        // Triangles removed by a collapse keep the vertices they had at that
+        // moment, which all exist again once it is undone.
+        let renumber = |triangle: [usize; 3]| triangle.map(|v| vertex_ids[v]);
+        let base: Vec<[u32; 3]> = triangles.into_iter().map(renumber).collect();
+        let splits = collapses
+            .iter()
+            .rev()
+            .map(|collapse| VertexSplit {
+                vertex: vertex_ids[collapse.kept],
+                position: collapse.kept_position,
+                split_position: collapse.removed_position,
+                corners: collapse
+                    .corners
+                    .iter()
+                    .map(|(t, corner)| (triangle_ids[*t], *corner))
+                    .collect(),
+                triangles: collapse
+                    .triangles
+                    .iter()
+                    .map(|t| renumber(self.triangles[*t]))
+                    .collect(),
+            })
+            .collect();
+        ProgressiveMesh {
+            positions,
+            triangles: base,
+            splits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_level_decodes() {
+        let _ = env_logger::try_init();
+        let sphere: Mesh<f64> = testing::random_triangulated_sphere(120, 3);
+        let progressive = encode(&sphere, 12).unwrap();
+        assert_eq!(progressive.positions.len(), 12);
+        assert_eq!(
+            progressive.vertex_count(progressive.len()),
+            sphere.vertex_count()
+        );
+        for level in 0..=progressive.len() {
+            let mesh: Mesh<f64> = decode(&progressive, level).unwrap();
+            builder::assert_connectivity(&mesh);
+            assert_eq!(mesh.vertex_count(), progressive.vertex_count(level));
+            assert_eq!(mesh.edges().filter(|e| e.is_boundary()).count(), 0);
+        }
+        let full: Mesh<f64> = decode(&progressive, progressive.len()).unwrap();
+        assert!(full.geometrically_equals(&sphere, 1.0e-12));
+    }
+
+    #[test]
+    fn boundaries_are_kept() {
+        let grid: Mesh<f64> = builder::triangle_grid(6, 6);
+        let progressive = encode(&grid, 4).unwrap();
+        let base: Mesh<f64> = decode(&progressive, 0).unwrap();
+        builder::assert_connectivity(&base);
+        assert!(base.vertex_count() < grid.vertex_count() / 2);
+        // The base still spans the same square.
+        let area: f64 = base.faces().map(|f| f.area()).sum();
+        assert!((area - 36.0).abs() < 1.0e-6);
+        let full: Mesh<f64> = decode(&progressive, progressive.len()).unwrap();
+        assert!(full.geometrically_equals(&grid, 1.0e-12));
+    }
+
+    #[test]
+    fn broken_streams_are_errors() {
+        let grid: Mesh<f64> = builder::triangle_grid(3, 3);
+        let progressive = encode(&grid, 6).unwrap();
+        assert!(!progressive.is_empty());
+        let levels = progressive.len();
+        let broken = |change: &dyn Fn(&mut ProgressiveMesh)| {
+            let mut stream = progressive.clone();
+            change(&mut stream);
+            decode::<f64>(&stream, levels)
+        };
+        assert!(broken(&|_| ()).is_ok());
+        let bad = [
+            broken(&|s| s.splits[0].vertex = 1000),
+            broken(&|s| s.splits[0].corners.push((1000, 0))),
+            broken(&|s| s.splits[0].corners.push((0, 3))),
+            broken(&|s| s.splits[0].triangles.push([0, 1, 1000])),
+            broken(&|s| s.triangles[0][2] = 1000),
+        ];
+        for result in bad {
+            assert!(
+                matches!(result, Err(Error::InvalidArgument(_))),
+                "{:?}",
+                result
+            );
+        }
+        // In range but no longer a surface.
+        assert!(broken(&|s| s.triangles.push(s.triangles[0])).is_err());
+    }
+}
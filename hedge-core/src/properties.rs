@@ -0,0 +1,452 @@
+//! Named per-element values which the mesh keeps in step with its elements.
+//!
+//! Unlike a `Channel`, which callers hold on to themselves, a `PropertyMap`
+//! is registered with the mesh. Adding an element gives it the map's
+//! default value, removing one drops its value, and `Mesh::defrag` or
+//! `Mesh::canonicalize` move every value to its element's new handle.
+
+use crate::canonical::Renumbering;
+use crate::*;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// Element indices the mesh can keep property maps for.
+pub trait PropertyIndex: ElementIndex + Eq + Hash + Send + Sync + 'static {
+    /// Offset and generation of every live element of this kind.
+    fn live<S: Scalar>(mesh: &Mesh<S>) -> Vec<(Offset, Generation)>;
+    /// Where a renumbering moved the elements of this kind.
+    fn renumbered(renumbering: &Renumbering) -> &HashMap<Self, Self>;
+}
+
+impl PropertyIndex for EdgeIndex {
+    fn live<S: Scalar>(mesh: &Mesh<S>) -> Vec<(Offset, Generation)> {
+        mesh.edges
+            .iter()
+            .map(|(i, _)| (i.offset, i.generation))
+            .collect()
+    }
+
+    fn renumbered(renumbering: &Renumbering) -> &HashMap<Self, Self> {
+        &renumbering.edges
+    }
+}
+
+impl PropertyIndex for VertexIndex {
+    fn live<S: Scalar>(mesh: &Mesh<S>) -> Vec<(Offset, Generation)> {
+        mesh.vertices
+            .iter()
+            .map(|(i, _)| (i.offset, i.generation))
+            .collect()
+    }
+
+    fn renumbered(renumbering: &Renumbering) -> &HashMap<Self, Self> {
+        &renumbering.vertices
+    }
+}
+
+impl PropertyIndex for FaceIndex {
+    fn live<S: Scalar>(mesh: &Mesh<S>) -> Vec<(Offset, Generation)> {
+        mesh.faces
+            .iter()
+            .map(|(i, _)| (i.offset, i.generation))
+            .collect()
+    }
+
+    fn renumbered(renumbering: &Renumbering) -> &HashMap<Self, Self> {
+        &renumbering.faces
+    }
+}
+
+impl PropertyIndex for PointIndex {
+    fn live<S: Scalar>(mesh: &Mesh<S>) -> Vec<(Offset, Generation)> {
+        mesh.points
+            .iter()
+            .map(|(i, _)| (i.offset, i.generation))
+            .collect()
+    }
+
+    fn renumbered(renumbering: &Renumbering) -> &HashMap<Self, Self> {
+        &renumbering.points
+    }
+}
+
+/// A value for every element of one kind, stored densely by offset.
+///
+/// Elements without a value of their own, and stale handles, read back as
+/// the default. Like a channel the generation of the handle a value was
+/// written with is kept, so cells reused by the mesh don't leak old values.
+pub struct PropertyMap<I, T> {
+    values: Vec<Option<(Generation, T)>>,
+    default: T,
+    _index: PhantomData<I>,
+}
+
+impl<I, T: Clone> Clone for PropertyMap<I, T> {
+    fn clone(&self) -> Self {
+        PropertyMap {
+            values: self.values.clone(),
+            default: self.default.clone(),
+            _index: PhantomData,
+        }
+    }
+}
+
+impl<I, T> fmt::Debug for PropertyMap<I, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PropertyMap<> {{ {} values }}", self.len())
+    }
+}
+
+impl<I, T> PropertyMap<I, T> {
+    /// Number of elements with a value.
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, T: Clone> PropertyMap<Handle<K>, T> {
+    pub fn new(default: T) -> Self {
+        PropertyMap {
+            values: Vec::new(),
+            default,
+            _index: PhantomData,
+        }
+    }
+
+    pub fn default_value(&self) -> &T {
+        &self.default
+    }
+
+    pub fn get(&self, index: Handle<K>) -> &T {
+        match self.values.get(index.offset as usize) {
+            Some(Some((generation, value))) if *generation == index.generation => value,
+            _ => &self.default,
+        }
+    }
+
+    /// The value of a live element, `None` for stale handles.
+    pub fn get_mut(&mut self, index: Handle<K>) -> Option<&mut T> {
+        match self.values.get_mut(index.offset as usize) {
+            Some(Some((generation, value))) if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Stores a value, returning the one it replaces.
+    pub fn set(&mut self, index: Handle<K>, value: T) -> T {
+        let previous = self.get(index).clone();
+        self.fill(index.offset, index.generation, value);
+        previous
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<K>, &T)> {
+        self.values.iter().enumerate().filter_map(|(offset, slot)| {
+            slot.as_ref()
+                .map(|(generation, value)| (Handle::new(offset as Offset, *generation), value))
+        })
+    }
+
+    fn fill(&mut self, offset: Offset, generation: Generation, value: T) {
+        let offset = offset as usize;
+        if self.values.len() <= offset {
+            self.values.resize_with(offset + 1, || None);
+        }
+        self.values[offset] = Some((generation, value));
+    }
+}
+
+/// What the mesh needs from a map without knowing its value type.
+trait Property: Send + Sync {
+    fn added(&mut self, offset: Offset, generation: Generation);
+    fn removed(&mut self, offset: Offset, generation: Generation);
+    fn renumber(&mut self, renumbering: &Renumbering);
+    fn clone_box(&self) -> Box<dyn Property>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<K, T> Property for PropertyMap<Handle<K>, T>
+where
+    Handle<K>: PropertyIndex,
+    T: Clone + Send + Sync + 'static,
+{
+    fn added(&mut self, offset: Offset, generation: Generation) {
+        let value = self.default.clone();
+        self.fill(offset, generation, value);
+    }
+
+    fn removed(&mut self, offset: Offset, generation: Generation) {
+        if let Some(slot) = self.values.get_mut(offset as usize) {
+            if slot.as_ref().is_some_and(|(g, _)| *g == generation) {
+                *slot = None;
+            }
+        }
+    }
+
+    fn renumber(&mut self, renumbering: &Renumbering) {
+        let moves = Handle::<K>::renumbered(renumbering);
+        let values = std::mem::take(&mut self.values);
+        for (offset, slot) in values.into_iter().enumerate() {
+            let Some((generation, value)) = slot else {
+                continue;
+            };
+            if let Some(new) = moves.get(&Handle::new(offset as Offset, generation)) {
+                self.fill(new.offset, new.generation, value);
+            }
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Property> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+struct Entry {
+    name: String,
+    kind: TypeId,
+    map: Box<dyn Property>,
+}
+
+impl Clone for Entry {
+    fn clone(&self) -> Self {
+        Entry {
+            name: self.name.clone(),
+            kind: self.kind,
+            map: self.map.clone_box(),
+        }
+    }
+}
+
+/// The property maps of a mesh, each named uniquely for its element kind.
+#[derive(Clone, Default)]
+pub struct Properties {
+    entries: Vec<Entry>,
+}
+
+impl fmt::Debug for Properties {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl Properties {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Names of the maps in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    fn position<I: 'static>(&self, name: &str) -> Option<usize> {
+        let kind = TypeId::of::<I>();
+        self.entries
+            .iter()
+            .position(|e| e.kind == kind && e.name == name)
+    }
+
+    pub(crate) fn added<K: 'static>(&mut self, index: Handle<K>) {
+        let kind = TypeId::of::<Handle<K>>();
+        for entry in self.entries.iter_mut().filter(|e| e.kind == kind) {
+            entry.map.added(index.offset, index.generation);
+        }
+    }
+
+    pub(crate) fn removed<K: 'static>(&mut self, index: Handle<K>) {
+        let kind = TypeId::of::<Handle<K>>();
+        for entry in self.entries.iter_mut().filter(|e| e.kind == kind) {
+            entry.map.removed(index.offset, index.generation);
+        }
+    }
+
+    pub(crate) fn renumber(&mut self, renumbering: &Renumbering) {
+        for entry in &mut self.entries {
+            entry.map.renumber(renumbering);
+        }
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Registers a map named `name` giving every element of kind `K` the
+    /// value `default`, returning it for the values to be filled in.
+    ///
+    /// Fails when the elements of kind `K` already have a map by that name.
+    pub fn add_property<K, T>(
+        &mut self,
+        name: &str,
+        default: T,
+    ) -> Result<&mut PropertyMap<Handle<K>, T>>
+    where
+        K: 'static,
+        Handle<K>: PropertyIndex,
+        T: Clone + Send + Sync + 'static,
+    {
+        if self.properties.position::<Handle<K>>(name).is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "a property named {:?} already exists",
+                name
+            )));
+        }
+        let mut map = PropertyMap::<Handle<K>, T>::new(default);
+        for (offset, generation) in Handle::<K>::live(self) {
+            map.added(offset, generation);
+        }
+        self.properties.entries.push(Entry {
+            name: name.to_string(),
+            kind: TypeId::of::<Handle<K>>(),
+            map: Box::new(map),
+        });
+        let entry = self.properties.entries.last_mut().unwrap();
+        Ok(entry.map.as_any_mut().downcast_mut().unwrap())
+    }
+
+    /// The map named `name` for elements of kind `K`, if it holds `T`s.
+    pub fn property<K, T>(&self, name: &str) -> Option<&PropertyMap<Handle<K>, T>>
+    where
+        K: 'static,
+        T: 'static,
+    {
+        let position = self.properties.position::<Handle<K>>(name)?;
+        self.properties.entries[position]
+            .map
+            .as_any()
+            .downcast_ref()
+    }
+
+    pub fn property_mut<K, T>(&mut self, name: &str) -> Option<&mut PropertyMap<Handle<K>, T>>
+    where
+        K: 'static,
+        T: 'static,
+    {
+        let position = self.properties.position::<Handle<K>>(name)?;
+        self.properties.entries[position]
+            .map
+            .as_any_mut()
+            .downcast_mut()
+    }
+
+    /// Unregisters a map, handing it back with its values as they are.
+    pub fn remove_property<K, T>(&mut self, name: &str) -> Option<PropertyMap<Handle<K>, T>>
+    where
+        K: 'static,
+        T: 'static,
+    {
+        let position = self.properties.position::<Handle<K>>(name)?;
+        if !self.properties.entries[position]
+            .map
+            .as_any()
+            .is::<PropertyMap<Handle<K>, T>>()
+        {
+            return None;
+        }
+        let entry = self.properties.entries.remove(position);
+        entry.map.into_any().downcast().ok().map(|map| *map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::remove_face;
+    use crate::primitives;
+
+    #[test]
+    fn maps_follow_added_and_removed_elements() {
+        let mut mesh: Mesh = primitives::cube(1.0);
+        let labels = mesh.add_property::<Face, u32>("label", 7).unwrap();
+        assert_eq!(labels.len(), 6);
+        assert!(mesh.add_property::<Face, u32>("label", 0).is_err());
+        assert!(mesh.add_property::<Vertex, u32>("label", 0).is_ok());
+
+        let face = mesh.faces().next().unwrap().index;
+        mesh.property_mut::<Face, u32>("label")
+            .unwrap()
+            .set(face, 1);
+        remove_face(&mut mesh, face).unwrap();
+        let labels = mesh.property::<Face, u32>("label").unwrap();
+        assert_eq!(labels.len(), 5);
+        assert_eq!(labels.get(face), &7);
+
+        let added = mesh.add_element(Face::default());
+        let labels = mesh.property::<Face, u32>("label").unwrap();
+        assert_eq!(added.offset, face.offset);
+        assert_eq!(labels.get(added), &7);
+        assert_eq!(labels.len(), 6);
+
+        assert!(mesh.property::<Face, f32>("label").is_none());
+        assert!(mesh.remove_property::<Face, f32>("label").is_none());
+        assert_eq!(mesh.remove_property::<Face, u32>("label").unwrap().len(), 6);
+        assert_eq!(mesh.properties.names().collect::<Vec<_>>(), vec!["label"]);
+    }
+
+    #[test]
+    fn values_follow_defrag() {
+        let mut mesh: Mesh = primitives::grid(2.0, 1.0, 2, 1).unwrap();
+        let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+        let centers = mesh.add_property::<Face, f32>("x", 0.0).unwrap();
+        centers.set(faces[0], 0.5);
+        centers.set(faces[1], 1.5);
+        let kept = mesh.face(faces[1]).vertices().count();
+        let first = mesh.face(faces[0]).edges().next().unwrap().index;
+        let weights = mesh.add_property::<Edge, u8>("w", 0).unwrap();
+        weights.set(first, 9);
+
+        remove_face(&mut mesh, faces[0]).unwrap();
+        let renumbering = mesh.defrag();
+        mesh.validate().unwrap();
+
+        let moved = renumbering.faces[&faces[1]];
+        assert_eq!(moved.offset, faces[0].offset);
+        assert_eq!(mesh.face(moved).vertices().count(), kept);
+        let centers = mesh.property::<Face, f32>("x").unwrap();
+        assert_eq!(centers.get(moved), &1.5);
+        assert_eq!(centers.len(), 1);
+        let weights = mesh.property::<Edge, u8>("w").unwrap();
+        assert_eq!(weights.len(), mesh.edge_count());
+        assert!(weights.iter().all(|(_, w)| *w == 0));
+    }
+
+    #[test]
+    fn values_follow_canonicalize() {
+        let mut mesh: Mesh = primitives::cube(1.0);
+        let vertex = mesh.vertices().last().unwrap().index;
+        let point = mesh.vertices[vertex].point_index;
+        let weights = mesh.add_property::<Vertex, f32>("weight", 1.0).unwrap();
+        weights.set(vertex, 0.25);
+
+        let renumbering = mesh.canonicalize();
+        let vertex = renumbering.vertices[&vertex];
+        assert_eq!(
+            mesh.vertices[vertex].point_index,
+            renumbering.points[&point]
+        );
+        let weights = mesh.property::<Vertex, f32>("weight").unwrap();
+        assert_eq!(weights.get(vertex), &0.25);
+        assert_eq!(weights.len(), 8);
+    }
+}
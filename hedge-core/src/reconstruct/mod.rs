@@ -0,0 +1,9 @@
+//! Building meshes from oriented point clouds.
+
+mod pivot;
+#[cfg(feature = "poisson")]
+mod poisson;
+
+pub use self::pivot::{ball_pivoting, ball_pivoting_with};
+#[cfg(feature = "poisson")]
+pub use self::poisson::{poisson, poisson_with, PoissonOptions};
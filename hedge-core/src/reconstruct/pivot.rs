@@ -0,0 +1,393 @@
+//! Ball pivoting surface reconstruction.
+//!
+//! A ball of fixed radius is rolled over the point cloud: each triangle is
+//! formed by three points the ball touches without containing any others.
+//! Starting from a seed triangle the ball pivots around every open edge of
+//! the front until it hits the next point, a new seed is searched once the
+//! front is exhausted.
+
+use crate::math::{self, Vec3};
+use crate::spatial::PointGrid;
+use crate::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f64::consts::TAU;
+
+const EPSILON: f64 = 1.0e-9;
+/// Number of triangles between progress reports.
+const CHECKPOINT_INTERVAL: usize = 256;
+
+/// Reconstructs a surface from points with outward facing normals.
+///
+/// `radius` should be a bit larger than the typical spacing of the points,
+/// holes remain where the ball falls through the cloud. Normals only need to
+/// be roughly right as they are used to orient the triangles. Fails when the
+/// normals don't pair up with the points or the radius isn't positive.
+pub fn ball_pivoting<S: Scalar>(
+    points: &[Position<S>],
+    normals: &[[S; 3]],
+    radius: f64,
+) -> Result<Mesh<S>> {
+    ball_pivoting_with(points, normals, radius, &mut progress::Silent)
+}
+
+/// `ball_pivoting` reporting the share of points reached so far.
+pub fn ball_pivoting_with<S: Scalar>(
+    points: &[Position<S>],
+    normals: &[[S; 3]],
+    radius: f64,
+    progress: &mut dyn Progress,
+) -> Result<Mesh<S>> {
+    if points.len() != normals.len() {
+        return Err(Error::LengthMismatch {
+            expected: points.len(),
+            found: normals.len(),
+        });
+    }
+    if radius <= 0.0 || !radius.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "ball pivoting needs a positive radius, got {}",
+            radius
+        )));
+    }
+
+    let points: Vec<Vec3> = points.iter().map(|p| scalar::to_f64(*p)).collect();
+    let normals: Vec<Vec3> = normals.iter().map(|n| scalar::to_f64(*n)).collect();
+    let mut pivot = Pivot::new(&points, &normals, radius);
+    pivot.run(progress)?;
+
+    let mut remap = HashMap::new();
+    let mut positions = Vec::new();
+    let polygons: Vec<Vec<usize>> = pivot
+        .triangles
+        .iter()
+        .map(|triangle| {
+            triangle
+                .iter()
+                .map(|i| {
+                    *remap.entry(*i).or_insert_with(|| {
+                        positions.push(points[*i]);
+                        positions.len() - 1
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    Ok(builder::from_polygons(&positions, &polygons))
+}
+
+/// An open edge `from -> to` of the triangle `from, to, opposite`.
+struct FrontEdge {
+    from: usize,
+    to: usize,
+    opposite: usize,
+    center: Vec3,
+}
+
+struct Pivot<'a> {
+    points: &'a [Vec3],
+    normals: &'a [Vec3],
+    radius: f64,
+    grid: PointGrid,
+    used: Vec<bool>,
+    /// Number of points touched by a triangle.
+    reached: usize,
+    /// Number of unpaired half-edges touching each point.
+    open: Vec<usize>,
+    edges: HashSet<(usize, usize)>,
+    triangles: Vec<[usize; 3]>,
+    front: VecDeque<FrontEdge>,
+    next_seed: usize,
+}
+
+impl<'a> Pivot<'a> {
+    fn new(points: &'a [Vec3], normals: &'a [Vec3], radius: f64) -> Self {
+        Pivot {
+            points,
+            normals,
+            radius,
+            grid: PointGrid::from_points(2.0 * radius, points),
+            used: vec![false; points.len()],
+            reached: 0,
+            open: vec![0; points.len()],
+            edges: HashSet::new(),
+            triangles: Vec::new(),
+            front: VecDeque::new(),
+            next_seed: 0,
+        }
+    }
+
+    fn run(&mut self, progress: &mut dyn Progress) -> Result<()> {
+        loop {
+            while let Some(edge) = self.front.pop_front() {
+                if !self.is_open(edge.from, edge.to) {
+                    continue;
+                }
+                if let Some((x, center)) = self.pivot(&edge) {
+                    self.add_triangle([edge.to, edge.from, x], center);
+                    if self.triangles.len().is_multiple_of(CHECKPOINT_INTERVAL) {
+                        progress::checkpoint(progress, self.reached, self.points.len())?;
+                    }
+                }
+            }
+            progress::checkpoint(progress, self.reached, self.points.len())?;
+            if !self.seed() {
+                return progress::checkpoint(progress, 1, 1);
+            }
+        }
+    }
+
+    fn is_open(&self, from: usize, to: usize) -> bool {
+        self.edges.contains(&(from, to)) && !self.edges.contains(&(to, from))
+    }
+
+    /// Center of the ball touching the triangle on the side its normal faces.
+    fn ball_center(&self, triangle: [usize; 3]) -> Option<Vec3> {
+        let [a, b, c] = triangle.map(|i| self.points[i]);
+        let ab = math::sub(b, a);
+        let ac = math::sub(c, a);
+        let n = math::cross(ab, ac);
+        let nn = math::dot(n, n);
+        if nn < EPSILON * EPSILON {
+            return None;
+        }
+        let offset = math::scale(
+            math::add(
+                math::scale(math::cross(n, ab), math::dot(ac, ac)),
+                math::scale(math::cross(ac, n), math::dot(ab, ab)),
+            ),
+            0.5 / nn,
+        );
+        let height = self.radius * self.radius - math::dot(offset, offset);
+        if height < 0.0 {
+            return None;
+        }
+        let circumcenter = math::add(a, offset);
+        Some(math::add(
+            circumcenter,
+            math::scale(math::normalize(n), height.sqrt()),
+        ))
+    }
+
+    fn agrees_with_normals(&self, triangle: [usize; 3]) -> bool {
+        let [a, b, c] = triangle.map(|i| self.points[i]);
+        let n = math::cross(math::sub(b, a), math::sub(c, a));
+        triangle
+            .iter()
+            .all(|i| math::dot(n, self.normals[*i]) > 0.0)
+    }
+
+    fn is_empty(&self, center: Vec3, triangle: [usize; 3]) -> bool {
+        self.grid
+            .within(self.points, center, self.radius - EPSILON)
+            .all(|i| triangle.contains(&i))
+    }
+
+    /// Keeps the surface an oriented manifold: no half-edge is used twice and
+    /// points already surrounded by triangles are not touched again.
+    fn can_add(&self, triangle: [usize; 3]) -> bool {
+        (0..3).all(|i| !self.edges.contains(&(triangle[i], triangle[(i + 1) % 3])))
+            && triangle.iter().all(|v| !self.used[*v] || self.open[*v] > 0)
+    }
+
+    fn add_triangle(&mut self, triangle: [usize; 3], center: Vec3) {
+        for i in 0..3 {
+            let (from, to) = (triangle[i], triangle[(i + 1) % 3]);
+            self.edges.insert((from, to));
+            if self.edges.contains(&(to, from)) {
+                self.open[from] -= 1;
+                self.open[to] -= 1;
+            } else {
+                self.open[from] += 1;
+                self.open[to] += 1;
+                self.front.push_back(FrontEdge {
+                    from,
+                    to,
+                    opposite: triangle[(i + 2) % 3],
+                    center,
+                });
+            }
+            if !self.used[from] {
+                self.used[from] = true;
+                self.reached += 1;
+            }
+        }
+        self.triangles.push(triangle);
+    }
+
+    /// Rolls the ball over an open edge and returns the first point it hits.
+    fn pivot(&self, edge: &FrontEdge) -> Option<(usize, Vec3)> {
+        let (pi, pj, pk) = (
+            self.points[edge.from],
+            self.points[edge.to],
+            self.points[edge.opposite],
+        );
+        let mid = math::scale(math::add(pi, pj), 0.5);
+        let along = math::normalize(math::sub(pj, pi));
+        let perpendicular = |v: Vec3| math::sub(v, math::scale(along, math::dot(v, along)));
+
+        let normal = math::cross(math::sub(pj, pi), math::sub(pk, pi));
+        let towards_opposite = perpendicular(math::sub(pk, mid));
+        let axis = math::normalize(math::cross(towards_opposite, normal));
+        let start = perpendicular(math::sub(edge.center, mid));
+
+        let mut candidates: Vec<(f64, usize, Vec3)> = self
+            .grid
+            .within(self.points, mid, 2.0 * self.radius)
+            .filter(|x| ![edge.from, edge.to, edge.opposite].contains(x))
+            .filter_map(|x| {
+                let triangle = [edge.to, edge.from, x];
+                if !self.agrees_with_normals(triangle) {
+                    return None;
+                }
+                let center = self.ball_center(triangle)?;
+                let end = perpendicular(math::sub(center, mid));
+                let mut angle =
+                    math::dot(math::cross(start, end), axis).atan2(math::dot(start, end));
+                if angle < 0.0 {
+                    angle += TAU;
+                }
+                if angle > TAU - 1.0e-7 {
+                    angle = 0.0;
+                }
+                Some((angle, x, center))
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let (_, x, center) = candidates
+            .into_iter()
+            .find(|(_, x, center)| self.is_empty(*center, [edge.to, edge.from, *x]))?;
+        self.can_add([edge.to, edge.from, x]).then_some((x, center))
+    }
+
+    /// Finds a triangle of unused points touched by an empty ball.
+    fn seed(&mut self) -> bool {
+        while self.next_seed < self.points.len() {
+            let p = self.next_seed;
+            self.next_seed += 1;
+            if self.used[p] {
+                continue;
+            }
+            if let Some((triangle, center)) = self.seed_at(p) {
+                self.add_triangle(triangle, center);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn seed_at(&self, p: usize) -> Option<([usize; 3], Vec3)> {
+        let origin = self.points[p];
+        let mut neighbors: Vec<usize> = self
+            .grid
+            .within(self.points, origin, 2.0 * self.radius)
+            .filter(|i| *i != p && !self.used[*i])
+            .collect();
+        neighbors.sort_by(|a, b| {
+            math::distance(self.points[*a], origin)
+                .total_cmp(&math::distance(self.points[*b], origin))
+                .then(a.cmp(b))
+        });
+        for (n, q) in neighbors.iter().enumerate() {
+            for s in &neighbors[n + 1..] {
+                let mut triangle = [p, *q, *s];
+                if !self.agrees_with_normals(triangle) {
+                    triangle = [p, *s, *q];
+                    if !self.agrees_with_normals(triangle) {
+                        continue;
+                    }
+                }
+                if let Some(center) = self.ball_center(triangle) {
+                    if self.is_empty(center, triangle) {
+                        return Some((triangle, center));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary_edge_count<S: Scalar>(mesh: &Mesh<S>) -> usize {
+        mesh.edges().filter(|e| !e.face().is_valid()).count()
+    }
+
+    #[test]
+    fn reconstructs_a_plane() {
+        let _ = env_logger::try_init();
+        let points: Vec<[f32; 3]> = (0..5)
+            .flat_map(|y| (0..5).map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let normals = vec![[0.0, 0.0, 1.0]; points.len()];
+        let mesh = ball_pivoting(&points, &normals, 0.8).unwrap();
+
+        assert_eq!(mesh.face_count(), 32);
+        assert_eq!(mesh.vertex_count(), 25);
+        assert_eq!(boundary_edge_count(&mesh), 16);
+        for face in mesh.faces() {
+            assert!(mesh.face_normal(face.index)[2] > 0.99);
+        }
+    }
+
+    #[test]
+    fn reconstructs_a_closed_sphere() {
+        // Fibonacci sphere with outward normals.
+        let count = 200;
+        let golden = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+        let points: Vec<[f64; 3]> = (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f64 + 0.5) / count as f64;
+                let r = (1.0 - y * y).sqrt();
+                let theta = golden * i as f64;
+                [r * theta.cos(), y, r * theta.sin()]
+            })
+            .collect();
+        let mesh = ball_pivoting(&points, &points, 0.4).unwrap();
+
+        assert_eq!(mesh.vertex_count(), count);
+        assert_eq!(boundary_edge_count(&mesh), 0);
+        assert_eq!(
+            mesh.vertex_count() + mesh.face_count() - mesh.edge_count() / 2,
+            2
+        );
+        for face in mesh.faces() {
+            let center = mesh.vertex_position(face.vertices().next().unwrap().index);
+            assert!(math::dot(mesh.face_normal(face.index), center) > 0.0);
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_input() {
+        let result = ball_pivoting(&[[0.0f32; 3]; 3], &[[0.0f32, 0.0, 1.0]], 1.0);
+        assert_eq!(
+            result.unwrap_err(),
+            Error::LengthMismatch {
+                expected: 3,
+                found: 1
+            }
+        );
+        assert!(ball_pivoting(&[[0.0f32; 3]], &[[0.0f32, 0.0, 1.0]], 0.0).is_err());
+    }
+
+    #[test]
+    fn reports_progress_until_cancelled() {
+        let points: Vec<[f64; 3]> = (0..100)
+            .map(|i| [(i % 10) as f64, (i / 10) as f64, 0.0])
+            .collect();
+        let normals = vec![[0.0, 0.0, 1.0]; points.len()];
+        let mut fractions = Vec::new();
+        let mut record = |fraction| fractions.push(fraction);
+        ball_pivoting_with(&points, &normals, 0.8, &mut record).unwrap();
+        assert!(fractions.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+
+        let cancellation = progress::Cancellation::new();
+        cancellation.cancel();
+        let result = ball_pivoting_with(&points, &normals, 0.8, &mut cancellation.clone());
+        assert_eq!(result.unwrap_err(), Error::Cancelled);
+    }
+}
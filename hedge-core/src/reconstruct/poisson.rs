@@ -0,0 +1,399 @@
+//! Screened Poisson surface reconstruction.
+//!
+//! Follows Kazhdan and Hoppe: the normals are splatted into a vector field and
+//! an implicit function whose gradient best matches that field is solved for,
+//! while a screening term pulls the function to zero at the samples. The zero
+//! level set is then extracted with surface nets, giving a watertight quad
+//! mesh inside the bounds.
+//!
+//! The function is solved on every level of a complete octree over the padded
+//! bounds, each level starting from the prolongated solution of its parent.
+
+use crate::linalg::{self, SparseMatrix};
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::{HashMap, HashSet};
+
+/// Parameters for `poisson`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoissonOptions {
+    /// Depth of the octree, the finest level has `2^depth` cells per side.
+    pub depth: u32,
+    /// Weight of the interpolation constraint at the samples.
+    pub screening: f64,
+    /// Ratio between the size of the bounds and the extent of the points.
+    pub scale: f64,
+    /// Relative residual at which each level stops iterating.
+    pub tolerance: f64,
+}
+
+impl Default for PoissonOptions {
+    fn default() -> Self {
+        PoissonOptions {
+            depth: 6,
+            screening: 4.0,
+            scale: 1.25,
+            tolerance: 1.0e-7,
+        }
+    }
+}
+
+/// Reconstructs a closed surface from points with outward facing normals.
+pub fn poisson<S: Scalar>(
+    points: &[Position<S>],
+    normals: &[[S; 3]],
+    options: &PoissonOptions,
+) -> Result<Mesh<S>> {
+    poisson_with(points, normals, options, &mut progress::Silent)
+}
+
+/// `poisson` reporting progress after each octree level is solved.
+pub fn poisson_with<S: Scalar>(
+    points: &[Position<S>],
+    normals: &[[S; 3]],
+    options: &PoissonOptions,
+    progress: &mut dyn Progress,
+) -> Result<Mesh<S>> {
+    if points.len() != normals.len() {
+        return Err(Error::LengthMismatch {
+            expected: points.len(),
+            found: normals.len(),
+        });
+    }
+    if points.is_empty() {
+        return Err(Error::InvalidArgument(
+            "poisson reconstruction needs at least one point".to_string(),
+        ));
+    }
+    let points: Vec<Vec3> = points.iter().map(|p| scalar::to_f64(*p)).collect();
+    let normals: Vec<Vec3> = normals
+        .iter()
+        .map(|n| math::normalize(scalar::to_f64(*n)))
+        .collect();
+
+    let mut min = points[0];
+    let mut max = points[0];
+    for p in &points {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    let extent = (0..3)
+        .map(|axis| max[axis] - min[axis])
+        .fold(f64::EPSILON, f64::max);
+    let size = extent * options.scale.max(1.0);
+    let center = math::scale(math::add(min, max), 0.5);
+    let origin = math::sub(center, [0.5 * size; 3]);
+
+    // Each level has eight times the nodes of its parent, weigh them alike.
+    let levels = options.depth.max(1);
+    let work = |depth: u32| (1..=depth).map(|d| 1usize << (3 * d)).sum::<usize>();
+    let mut solution: Option<(Grid, Vec<f64>)> = None;
+    for depth in 1..=levels {
+        progress::checkpoint(progress, work(depth - 1), work(levels))?;
+        let grid = Grid::new(origin, size, 1 << depth);
+        let initial = match &solution {
+            Some((coarse, values)) => coarse.prolongate(values, &grid),
+            None => vec![0.0; grid.node_count()],
+        };
+        let values = grid.solve(&points, &normals, options, initial);
+        solution = Some((grid, values));
+    }
+
+    progress::checkpoint(progress, 1, 1)?;
+    Ok(match solution {
+        Some((grid, values)) => grid.surface_nets(&values),
+        None => Mesh::new(),
+    })
+}
+
+/// Nodes on the corners of `cells³` cubes.
+struct Grid {
+    origin: Vec3,
+    spacing: f64,
+    cells: usize,
+}
+
+impl Grid {
+    fn new(origin: Vec3, size: f64, cells: usize) -> Self {
+        Grid {
+            origin,
+            spacing: size / cells as f64,
+            cells,
+        }
+    }
+
+    fn nodes_per_side(&self) -> usize {
+        self.cells + 1
+    }
+
+    fn node_count(&self) -> usize {
+        self.nodes_per_side().pow(3)
+    }
+
+    fn node(&self, [i, j, k]: [usize; 3]) -> usize {
+        let n = self.nodes_per_side();
+        i + n * (j + n * k)
+    }
+
+    fn node_position(&self, index: [usize; 3]) -> Vec3 {
+        math::add(self.origin, index.map(|i| i as f64 * self.spacing))
+    }
+
+    /// The eight nodes around a position along with their trilinear weights.
+    fn weights(&self, p: Vec3) -> [(usize, f64); 8] {
+        let local = math::scale(math::sub(p, self.origin), 1.0 / self.spacing);
+        let base = local.map(|v| (v.floor().max(0.0) as usize).min(self.cells - 1));
+        let t = [0, 1, 2].map(|axis| (local[axis] - base[axis] as f64).clamp(0.0, 1.0));
+        let mut out = [(0, 0.0); 8];
+        for (corner, slot) in out.iter_mut().enumerate() {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                weight *= if offset[axis] == 1 {
+                    t[axis]
+                } else {
+                    1.0 - t[axis]
+                };
+            }
+            let index = [0, 1, 2].map(|axis| base[axis] + offset[axis]);
+            *slot = (self.node(index), weight);
+        }
+        out
+    }
+
+    fn solve(
+        &self,
+        points: &[Vec3],
+        normals: &[Vec3],
+        options: &PoissonOptions,
+        initial: Vec<f64>,
+    ) -> Vec<f64> {
+        let h = self.spacing;
+        let n = self.nodes_per_side();
+
+        // Splat the normals, the field has unit length wherever samples land.
+        let mut field = vec![[0.0; 3]; self.node_count()];
+        let mut density = vec![0.0; self.node_count()];
+        let mut occupied = HashSet::new();
+        for (p, normal) in points.iter().zip(normals) {
+            for (node, weight) in self.weights(*p) {
+                field[node] = math::add(field[node], math::scale(*normal, weight));
+                density[node] += weight;
+            }
+            occupied.insert(self.weights(*p)[0].0);
+        }
+        for (v, d) in field.iter_mut().zip(&density) {
+            if *d > 0.0 {
+                *v = math::scale(*v, 1.0 / d);
+            }
+        }
+
+        let mut triplets = Vec::new();
+        let mut rhs = vec![0.0; self.node_count()];
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let index = [i, j, k];
+                    let a = self.node(index);
+                    for axis in 0..3 {
+                        if index[axis] + 1 >= n {
+                            continue;
+                        }
+                        let mut next = index;
+                        next[axis] += 1;
+                        let b = self.node(next);
+                        let v = 0.5 * (field[a][axis] + field[b][axis]);
+                        triplets.extend([(a, a, h), (b, b, h), (a, b, -h), (b, a, -h)]);
+                        rhs[a] -= h * h * v;
+                        rhs[b] += h * h * v;
+                    }
+                }
+            }
+        }
+
+        // Balance the screening against the gradient term over the sampled area.
+        let alpha = options.screening * occupied.len() as f64 * h / points.len() as f64;
+        for p in points {
+            let weights = self.weights(*p);
+            for (a, wa) in weights {
+                for (b, wb) in weights {
+                    triplets.push((a, b, alpha * wa * wb));
+                }
+            }
+        }
+
+        let matrix = SparseMatrix::from_triplets(self.node_count(), self.node_count(), triplets);
+        linalg::conjugate_gradient(&matrix, &rhs, initial, options.tolerance, 8 * n + 200)
+    }
+
+    /// Trilinearly interpolates coarse node values onto a grid with twice the cells.
+    fn prolongate(&self, values: &[f64], fine: &Grid) -> Vec<f64> {
+        let n = fine.nodes_per_side();
+        let mut out = vec![0.0; fine.node_count()];
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let p = fine.node_position([i, j, k]);
+                    out[fine.node([i, j, k])] = self
+                        .weights(p)
+                        .iter()
+                        .map(|(node, weight)| values[*node] * weight)
+                        .sum();
+                }
+            }
+        }
+        out
+    }
+
+    /// Extracts the zero level set, one vertex per cell and one quad per crossed edge.
+    fn surface_nets<S: Scalar>(&self, values: &[f64]) -> Mesh<S> {
+        let cells = self.cells;
+        let mut cell_vertices: HashMap<[usize; 3], usize> = HashMap::new();
+        let mut positions = Vec::new();
+        for k in 0..cells {
+            for j in 0..cells {
+                for i in 0..cells {
+                    let mut sum = [0.0; 3];
+                    let mut count = 0;
+                    for (a, b) in cube_edges() {
+                        let pa = [i + a[0], j + a[1], k + a[2]];
+                        let pb = [i + b[0], j + b[1], k + b[2]];
+                        let (va, vb) = (values[self.node(pa)], values[self.node(pb)]);
+                        if (va < 0.0) != (vb < 0.0) {
+                            let t = va / (va - vb);
+                            let crossing =
+                                math::lerp(self.node_position(pa), self.node_position(pb), t);
+                            sum = math::add(sum, crossing);
+                            count += 1;
+                        }
+                    }
+                    if count > 0 {
+                        cell_vertices.insert([i, j, k], positions.len());
+                        positions.push(math::scale(sum, 1.0 / count as f64));
+                    }
+                }
+            }
+        }
+
+        let mut quads = Vec::new();
+        let n = self.nodes_per_side();
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let index = [i, j, k];
+                    for axis in 0..3 {
+                        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                        if index[axis] + 1 >= n
+                            || index[u] == 0
+                            || index[v] == 0
+                            || index[u] >= cells
+                            || index[v] >= cells
+                        {
+                            continue;
+                        }
+                        let mut next = index;
+                        next[axis] += 1;
+                        let inside = values[self.node(index)] < 0.0;
+                        if inside == (values[self.node(next)] < 0.0) {
+                            continue;
+                        }
+                        let cell = |du: usize, dv: usize| {
+                            let mut c = index;
+                            c[u] = c[u] + du - 1;
+                            c[v] = c[v] + dv - 1;
+                            cell_vertices.get(&c).copied()
+                        };
+                        let corners = [cell(0, 0), cell(1, 0), cell(1, 1), cell(0, 1)];
+                        if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+                            // Faces point from the inside, where the function is negative, out.
+                            quads.push(if inside {
+                                vec![a, b, c, d]
+                            } else {
+                                vec![d, c, b, a]
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        builder::from_polygons(&positions, &quads)
+    }
+}
+
+fn cube_edges() -> impl Iterator<Item = ([usize; 3], [usize; 3])> {
+    (0..3).flat_map(|axis| {
+        (0..4).map(move |corner| {
+            let mut a = [0; 3];
+            a[(axis + 1) % 3] = corner & 1;
+            a[(axis + 2) % 3] = corner >> 1;
+            let mut b = a;
+            b[axis] = 1;
+            (a, b)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere(count: usize) -> Vec<[f64; 3]> {
+        let golden = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f64 + 0.5) / count as f64;
+                let r = (1.0 - y * y).sqrt();
+                let theta = golden * i as f64;
+                [r * theta.cos(), y, r * theta.sin()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reconstructs_a_watertight_sphere() {
+        let _ = env_logger::try_init();
+        let points = sphere(400);
+        let options = PoissonOptions {
+            depth: 4,
+            ..Default::default()
+        };
+        let mesh = poisson(&points, &points, &options).unwrap();
+
+        assert!(mesh.face_count() > 50);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 0);
+        assert_eq!(
+            mesh.vertex_count() + mesh.face_count() - mesh.edge_count() / 2,
+            2
+        );
+        let spacing = 2.5 / 16.0;
+        for vertex in mesh.vertices() {
+            let r = math::length(mesh.vertex_position(vertex.index));
+            assert!((r - 1.0).abs() < spacing, "{}", r);
+        }
+        for face in mesh.faces() {
+            let p = mesh.vertex_position(face.vertices().next().unwrap().index);
+            assert!(math::dot(mesh.face_normal(face.index), p) > 0.0);
+        }
+    }
+
+    #[test]
+    fn prolongation_preserves_linear_functions() {
+        let coarse = Grid::new([0.0; 3], 1.0, 2);
+        let fine = Grid::new([0.0; 3], 1.0, 4);
+        let n = coarse.nodes_per_side();
+        let mut values = vec![0.0; coarse.node_count()];
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let p = coarse.node_position([i, j, k]);
+                    values[coarse.node([i, j, k])] = p[0] + 2.0 * p[1] - p[2];
+                }
+            }
+        }
+        let out = coarse.prolongate(&values, &fine);
+        let p = fine.node_position([1, 3, 2]);
+        assert!((out[fine.node([1, 3, 2])] - (p[0] + 2.0 * p[1] - p[2])).abs() < 1.0e-12);
+    }
+}
@@ -0,0 +1,170 @@
+//! Sampling points on the surface of a mesh.
+
+use crate::math::{self, Vec3};
+use crate::random::Rng;
+use crate::spatial::PointGrid;
+use crate::*;
+use std::collections::HashMap;
+
+/// Number of candidate darts thrown per `radius²` of surface area.
+const DARTS_PER_CELL: f64 = 30.0;
+
+/// A point on the surface of a mesh.
+///
+/// Faces are fan triangulated, `vertices` are the corners of the triangle the
+/// point landed in and `barycentric` holds the weights of those corners.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfacePoint<S: Scalar = f32> {
+    pub face: FaceIndex,
+    pub vertices: [VertexIndex; 3],
+    pub barycentric: [S; 3],
+    pub position: Position<S>,
+    /// Interpolated from the area weighted vertex normals.
+    pub normal: [S; 3],
+}
+
+struct Triangle {
+    face: FaceIndex,
+    vertices: [VertexIndex; 3],
+    positions: [Vec3; 3],
+}
+
+/// Samples the surface so that no two points are closer than `radius`.
+///
+/// Uses dart throwing with area weighted triangle selection, the same `seed`
+/// always produces the same samples for the same mesh. Fails unless the
+/// radius is positive.
+pub fn poisson_disk<S: Scalar>(
+    mesh: &Mesh<S>,
+    radius: f64,
+    seed: u64,
+) -> Result<Vec<SurfacePoint<S>>> {
+    if radius <= 0.0 || !radius.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "poisson disk sampling needs a positive radius, got {}",
+            radius
+        )));
+    }
+
+    let triangles = triangles(mesh);
+    let mut cumulative = Vec::with_capacity(triangles.len());
+    let mut total = 0.0;
+    for triangle in &triangles {
+        let p = triangle.positions;
+        total += math::triangle_area(p[0], p[1], p[2]);
+        cumulative.push(total);
+    }
+    if total <= 0.0 {
+        return Ok(Vec::new());
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut grid = PointGrid::new(radius);
+    let mut normals: HashMap<VertexIndex, Vec3> = HashMap::new();
+    let mut samples = Vec::new();
+    let mut positions: Vec<Vec3> = Vec::new();
+
+    let darts = (DARTS_PER_CELL * total / (radius * radius)).ceil() as usize;
+    for _ in 0..darts {
+        let target = rng.next_f64() * total;
+        let index = cumulative
+            .partition_point(|area| *area < target)
+            .min(triangles.len() - 1);
+        let triangle = &triangles[index];
+
+        let r1 = rng.next_f64().sqrt();
+        let r2 = rng.next_f64();
+        let weights = [1.0 - r1, r1 * (1.0 - r2), r1 * r2];
+        let position = interpolate(triangle.positions, weights);
+
+        if grid.within(&positions, position, radius).next().is_some() {
+            continue;
+        }
+
+        let corner_normals = triangle
+            .vertices
+            .map(|v| *normals.entry(v).or_insert_with(|| mesh.vertex_normal(v)));
+        let normal = math::normalize(interpolate(corner_normals, weights));
+
+        grid.insert(positions.len(), position);
+        positions.push(position);
+        samples.push(SurfacePoint {
+            face: triangle.face,
+            vertices: triangle.vertices,
+            barycentric: weights.map(S::from_f64),
+            position: scalar::from_f64(position),
+            normal: scalar::from_f64(normal),
+        });
+    }
+    log::debug!("Accepted {} of {} darts.", samples.len(), darts);
+    Ok(samples)
+}
+
+fn triangles<S: Scalar>(mesh: &Mesh<S>) -> Vec<Triangle> {
+    let mut out = Vec::new();
+    for face in mesh.faces() {
+        let vertices: Vec<VertexIndex> = face.vertices().map(|v| v.index).collect();
+        for i in 1..vertices.len().saturating_sub(1) {
+            let corners = [vertices[0], vertices[i], vertices[i + 1]];
+            out.push(Triangle {
+                face: face.index,
+                vertices: corners,
+                positions: corners.map(|v| mesh.vertex_position(v)),
+            });
+        }
+    }
+    out
+}
+
+fn interpolate(values: [Vec3; 3], weights: [f64; 3]) -> Vec3 {
+    let mut out = [0.0; 3];
+    for (value, weight) in values.iter().zip(weights) {
+        out = math::add(out, math::scale(*value, weight));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_respect_radius() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::grid(4, 4);
+        let radius = 0.25;
+        let samples = poisson_disk(&mesh, radius, 42).unwrap();
+        // A maximal packing of 0.25 discs in a 4x4 square holds well over 100.
+        assert!(samples.len() > 100, "{}", samples.len());
+
+        for (i, a) in samples.iter().enumerate() {
+            assert!(mesh.face(a.face).is_valid());
+            assert!((a.barycentric.iter().sum::<f32>() - 1.0).abs() < 1.0e-5);
+            assert!((a.normal[2] - 1.0).abs() < 1.0e-5);
+            for b in &samples[i + 1..] {
+                let d = math::distance(scalar::to_f64(a.position), scalar::to_f64(b.position));
+                assert!(d >= radius * 0.999);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_reproducible() {
+        let mesh: Mesh<f64> = builder::cube();
+        let a = poisson_disk(&mesh, 0.2, 7).unwrap();
+        let b = poisson_disk(&mesh, 0.2, 7).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, poisson_disk(&mesh, 0.2, 8).unwrap());
+        assert!(poisson_disk(&mesh, 0.0, 7).is_err());
+    }
+
+    #[test]
+    fn barycentric_coordinates_reconstruct_position() {
+        let mesh: Mesh<f64> = builder::cube();
+        for sample in poisson_disk(&mesh, 0.3, 1).unwrap() {
+            let corners = sample.vertices.map(|v| mesh.vertex_position(v));
+            let p = interpolate(corners, sample.barycentric);
+            assert!(math::distance(p, sample.position) < 1.0e-9);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Scalar types usable for point positions.
+
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Floating point type used for the geometric payload of a `Mesh`.
+///
+/// Algorithms do their arithmetic in `f64` and convert back on write, so an
+/// `f64` mesh keeps full precision end to end while `f32` meshes stay compact.
+pub trait Scalar:
+    Copy
+    + Default
+    + fmt::Debug
+    + PartialEq
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Send
+    + Sync
+    + 'static
+{
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Scalar for f32 {
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Scalar for f64 {
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Converts a position to `f64` components.
+#[inline]
+pub fn to_f64<S: Scalar>(position: [S; 3]) -> [f64; 3] {
+    [
+        position[0].to_f64(),
+        position[1].to_f64(),
+        position[2].to_f64(),
+    ]
+}
+
+/// Converts `f64` components to a position.
+#[inline]
+pub fn from_f64<S: Scalar>(position: [f64; 3]) -> [S; 3] {
+    [
+        S::from_f64(position[0]),
+        S::from_f64(position[1]),
+        S::from_f64(position[2]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f64_round_trip_is_lossless() {
+        let p = [0.1f64, 1.0e-12, 123_456_789.123_456_78];
+        assert_eq!(to_f64(from_f64::<f64>(p)), p);
+    }
+
+    #[test]
+    fn f32_round_trip_truncates() {
+        let p = [0.1f64, 0.2, 0.3];
+        let q = to_f64(from_f64::<f32>(p));
+        assert_ne!(q, p);
+        assert!((q[0] - p[0]).abs() < 1.0e-7);
+    }
+}
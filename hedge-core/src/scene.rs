@@ -0,0 +1,296 @@
+//! Several named meshes placed in a common space.
+//!
+//! Objects refer to transforms and materials by index, so any number of
+//! objects can share a transform and moving it moves all of them. Materials
+//! are only named here, describing them is left to the file formats.
+//!
+//! `io::obj` reads and writes scenes, `io::gltf` only writes them.
+
+use crate::*;
+
+/// Index of a transform in `Scene::transforms`.
+pub type TransformId = usize;
+/// Index of a material in `Scene::materials`.
+pub type MaterialId = usize;
+
+/// An affine transform acting on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Row major, the last row is always `[0, 0, 0, 1]`.
+    pub matrix: [[f64; 4]; 4],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        matrix: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn translation(offset: [f64; 3]) -> Self {
+        let mut transform = Transform::IDENTITY;
+        for (row, value) in offset.iter().enumerate() {
+            transform.matrix[row][3] = *value;
+        }
+        transform
+    }
+
+    pub fn scaling(factors: [f64; 3]) -> Self {
+        let mut transform = Transform::IDENTITY;
+        for (axis, factor) in factors.iter().enumerate() {
+            transform.matrix[axis][axis] = *factor;
+        }
+        transform
+    }
+
+    /// The transform which applies `self` first and `other` afterwards.
+    pub fn then(&self, other: &Transform) -> Transform {
+        let mut matrix = [[0.0; 4]; 4];
+        for (row, values) in matrix.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4)
+                    .map(|k| other.matrix[row][k] * self.matrix[k][column])
+                    .sum();
+            }
+        }
+        Transform { matrix }
+    }
+
+    /// Determinant of the linear part, negative for transforms which mirror.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn apply(&self, point: [f64; 3]) -> [f64; 3] {
+        let m = &self.matrix;
+        [0, 1, 2].map(|row| {
+            m[row][0] * point[0] + m[row][1] * point[1] + m[row][2] * point[2] + m[row][3]
+        })
+    }
+
+    /// Transforms a unit normal by the inverse transpose of the linear
+    /// part, so it stays perpendicular to transformed surfaces.
+    pub fn apply_normal(&self, normal: [f64; 3]) -> [f64; 3] {
+        let m = &self.matrix;
+        // The cofactor matrix is the inverse transpose scaled by the
+        // determinant, whose sign has to be kept.
+        let cofactor = |row: usize, column: usize| {
+            let (r, c) = (
+                [(row + 1) % 3, (row + 2) % 3],
+                [(column + 1) % 3, (column + 2) % 3],
+            );
+            m[r[0]][c[0]] * m[r[1]][c[1]] - m[r[0]][c[1]] * m[r[1]][c[0]]
+        };
+        let sign = self.determinant().signum();
+        let turned = [0, 1, 2].map(|row| {
+            (0..3)
+                .map(|column| sign * cofactor(row, column) * normal[column])
+                .sum()
+        });
+        math::normalize(turned)
+    }
+}
+
+/// A mesh placed in a scene.
+#[derive(Debug, Clone, Default)]
+pub struct Object<S: Scalar = f32> {
+    pub name: String,
+    pub mesh: Mesh<S>,
+    /// No transform places the mesh as is.
+    pub transform: Option<TransformId>,
+    pub material: Option<MaterialId>,
+}
+
+impl<S: Scalar> Object<S> {
+    pub fn new(name: impl Into<String>, mesh: Mesh<S>) -> Self {
+        Object {
+            name: name.into(),
+            mesh,
+            transform: None,
+            material: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scene<S: Scalar = f32> {
+    pub objects: Vec<Object<S>>,
+    pub transforms: Vec<Transform>,
+    /// Names of the materials objects refer to.
+    pub materials: Vec<String>,
+}
+
+impl<S: Scalar> Scene<S> {
+    pub fn new() -> Self {
+        Scene {
+            objects: Vec::new(),
+            transforms: Vec::new(),
+            materials: Vec::new(),
+        }
+    }
+
+    pub fn add_object(&mut self, object: Object<S>) -> usize {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+
+    pub fn add_transform(&mut self, transform: Transform) -> TransformId {
+        self.transforms.push(transform);
+        self.transforms.len() - 1
+    }
+
+    /// Returns the existing material of that name or adds it.
+    pub fn material(&mut self, name: &str) -> MaterialId {
+        match self.materials.iter().position(|m| m == name) {
+            Some(id) => id,
+            None => {
+                self.materials.push(name.to_string());
+                self.materials.len() - 1
+            }
+        }
+    }
+
+    /// The first object with the given name.
+    pub fn object(&self, name: &str) -> Option<&Object<S>> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    /// Where an object ends up, identity when it has no valid transform.
+    pub fn transform_of(&self, object: &Object<S>) -> Transform {
+        object
+            .transform
+            .and_then(|id| self.transforms.get(id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn material_of(&self, object: &Object<S>) -> Option<&str> {
+        object
+            .material
+            .and_then(|id| self.materials.get(id))
+            .map(String::as_str)
+    }
+
+    /// Copy of an object's mesh with its transform applied to the points
+    /// and to the stored vertex normals.
+    pub fn placed_mesh(&self, object: &Object<S>) -> Mesh<S> {
+        let transform = self.transform_of(object);
+        let mut mesh = object.mesh.clone();
+        for (_, point) in mesh.points.iter_mut() {
+            point.position = scalar::from_f64(transform.apply(scalar::to_f64(point.position)));
+        }
+        let normals: Vec<(VertexIndex, [f64; 3])> = mesh
+            .vertex_normals
+            .iter()
+            .map(|(vertex, n)| (vertex, transform.apply_normal(n.map(f64::from))))
+            .collect();
+        for (vertex, normal) in normals {
+            mesh.vertex_normals.set(vertex, normal.map(|c| c as f32));
+        }
+        mesh
+    }
+
+    /// Every object placed and combined into one mesh, the objects stay
+    /// disconnected from each other.
+    pub fn flatten(&self) -> Mesh<S> {
+        let mut positions = Vec::new();
+        let mut polygons = Vec::new();
+        for object in &self.objects {
+            let placed = self.placed_mesh(object);
+            let offset = positions.len();
+            let mut dense = std::collections::HashMap::new();
+            for (index, point) in placed.points.iter() {
+                dense.insert(index, offset + dense.len());
+                positions.push(point.position);
+            }
+            for face in placed.faces() {
+                polygons.push(
+                    face.vertices()
+                        .filter_map(|v| v.element())
+                        .filter_map(|v| dense.get(&v.point_index).copied())
+                        .collect(),
+                );
+            }
+        }
+        Mesh::from_polygons(&positions, &polygons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_share_transforms() {
+        let _ = env_logger::try_init();
+        let mut scene: Scene<f64> = Scene::new();
+        let lift = scene.add_transform(
+            Transform::scaling([2.0; 3]).then(&Transform::translation([0.0, 0.0, 5.0])),
+        );
+        let steel = scene.material("steel");
+        for name in ["left", "right"] {
+            let mut object = Object::new(name, builder::grid(1, 1));
+            object.transform = Some(lift);
+            object.material = Some(steel);
+            scene.add_object(object);
+        }
+        scene.add_object(Object::new("ground", builder::grid(2, 2)));
+        assert_eq!(scene.material("steel"), steel);
+
+        let right = scene.object("right").unwrap();
+        assert_eq!(scene.material_of(right), Some("steel"));
+        let placed = scene.placed_mesh(right);
+        assert!(placed
+            .points
+            .iter()
+            .any(|(_, p)| p.position == [2.0, 2.0, 5.0]));
+
+        scene.transforms[lift] = Transform::translation([0.0, 0.0, 1.0]);
+        let flat = scene.flatten();
+        builder::assert_connectivity(&flat);
+        assert_eq!(flat.face_count(), 6);
+        assert_eq!(flat.point_count(), 17);
+        let raised = flat.points.iter().filter(|(_, p)| p.position[2] == 1.0);
+        assert_eq!(raised.count(), 8);
+    }
+
+    #[test]
+    fn placed_normals_follow_the_transform() {
+        let mut scene: Scene = Scene::new();
+        // Squashing and then turning a quarter around z.
+        let mut turn = Transform::IDENTITY;
+        turn.matrix[0] = [0.0, -1.0, 0.0, 0.0];
+        turn.matrix[1] = [1.0, 0.0, 0.0, 0.0];
+        let squash = scene.add_transform(Transform::scaling([1.0, 4.0, 1.0]).then(&turn));
+        let mut mesh: Mesh = builder::grid(1, 1);
+        mesh.compute_vertex_normals(normals::NormalWeighting::Angle);
+        let vertex = mesh.vertices().next().unwrap().index;
+        mesh.vertex_normals.set(vertex, [0.6, 0.8, 0.0]);
+        let mut object = Object::new("tilted", mesh);
+        object.transform = Some(squash);
+        let placed = scene.placed_mesh(&object);
+
+        let normal = placed.vertex_normals.get(vertex).unwrap();
+        let expected = math::normalize([-0.2, 0.6, 0.0]);
+        assert!((0..3).all(|k| (normal[k] as f64 - expected[k]).abs() < 1.0e-6));
+        let others = placed.vertices().filter(|v| v.index != vertex);
+        assert!(others
+            .into_iter()
+            .all(|v| placed.vertex_normals.get(v.index) == Some(&[0.0, 0.0, 1.0])));
+
+        let mirror = Transform::scaling([1.0, 1.0, -1.0]);
+        assert_eq!(mirror.apply_normal([0.0, 0.0, 1.0]), [0.0, 0.0, -1.0]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Sets of faces picked out for an operator to work on.
+
+use crate::*;
+use std::collections::HashSet;
+
+/// A set of faces.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    faces: HashSet<FaceIndex>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` when the face was not selected yet.
+    pub fn insert(&mut self, face: FaceIndex) -> bool {
+        self.faces.insert(face)
+    }
+
+    pub fn remove(&mut self, face: FaceIndex) -> bool {
+        self.faces.remove(&face)
+    }
+
+    pub fn contains(&self, face: FaceIndex) -> bool {
+        self.faces.contains(&face)
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// Selected faces in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = FaceIndex> + '_ {
+        self.faces.iter().copied()
+    }
+
+    /// Half-edges of selected faces whose twin lies outside the selection,
+    /// including the open boundary of the mesh.
+    pub fn boundary<S: Scalar>(&self, mesh: &Mesh<S>) -> Vec<EdgeIndex> {
+        self.iter()
+            .flat_map(|face| mesh.face(face).edges())
+            .filter(|edge| !self.contains(edge.twin().face().index))
+            .map(|edge| edge.index)
+            .collect()
+    }
+
+    /// The boundary as closed loops, see `RegionBoundary`.
+    pub fn boundary_loops<'mesh, S: Scalar>(
+        &'mesh self,
+        mesh: &'mesh Mesh<S>,
+    ) -> RegionBoundary<'mesh, S> {
+        RegionBoundary::new(mesh, self)
+    }
+}
+
+impl FromIterator<FaceIndex> for Selection {
+    fn from_iter<I: IntoIterator<Item = FaceIndex>>(faces: I) -> Self {
+        Selection {
+            faces: faces.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<FaceIndex> for Selection {
+    fn extend<I: IntoIterator<Item = FaceIndex>>(&mut self, faces: I) {
+        self.faces.extend(faces);
+    }
+}
+
+impl From<&[FaceIndex]> for Selection {
+    fn from(faces: &[FaceIndex]) -> Self {
+        faces.iter().copied().collect()
+    }
+}
@@ -0,0 +1,78 @@
+//! Uniform hash grid for fixed radius neighbor queries.
+
+use crate::math::{self, Vec3};
+use std::collections::HashMap;
+
+pub(crate) struct PointGrid {
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl PointGrid {
+    pub fn new(cell_size: f64) -> Self {
+        PointGrid {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn from_points(cell_size: f64, points: &[Vec3]) -> Self {
+        let mut grid = PointGrid::new(cell_size);
+        for (i, p) in points.iter().enumerate() {
+            grid.insert(i, *p);
+        }
+        grid
+    }
+
+    fn cell(&self, p: Vec3) -> [i64; 3] {
+        p.map(|v| (v / self.cell_size).floor() as i64)
+    }
+
+    pub fn insert(&mut self, index: usize, p: Vec3) {
+        let key = self.cell(p);
+        self.cells.entry(key).or_default().push(index);
+    }
+
+    /// Indices in the cells around `p`, a superset of the points within `cell_size`.
+    pub fn nearby(&self, p: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let key = self.cell(p);
+        (-1..=1)
+            .flat_map(move |x| {
+                (-1..=1)
+                    .flat_map(move |y| (-1..=1).map(move |z| [key[0] + x, key[1] + y, key[2] + z]))
+            })
+            .filter_map(move |k| self.cells.get(&k))
+            .flatten()
+            .copied()
+    }
+
+    /// Indices of the points strictly closer than `radius`, which must not exceed the cell size.
+    pub fn within<'a>(
+        &'a self,
+        points: &'a [Vec3],
+        p: Vec3,
+        radius: f64,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.nearby(p)
+            .filter(move |i| math::distance(points[*i], p) < radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_neighbors_across_cells() {
+        let points = [
+            [0.0, 0.0, 0.0],
+            [0.9, 0.0, 0.0],
+            [-0.5, 0.0, 0.0],
+            [3.0, 0.0, 0.0],
+        ];
+        let grid = PointGrid::from_points(1.0, &points);
+        let mut found: Vec<usize> = grid.within(&points, [0.2, 0.0, 0.0], 1.0).collect();
+        found.sort();
+        assert_eq!(found, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,506 @@
+//! Random meshes and operation sequences for property testing.
+//!
+//! Everything is driven by a seeded `Generator`, so a failing case can be
+//! reproduced from the seed reported by `for_all`. Meshes come out of the
+//! regular builder and are always valid, operations refer to elements by
+//! ordinal so that a sequence can be replayed on any mesh.
+//!
+//! The same shapes are available as proptest strategies, `meshes` and
+//! `operations` among them, which shrink towards fewer cells and shorter
+//! sequences, and through `arbitrary::Arbitrary` for coverage guided
+//! fuzzers.
+//!
+//! Enabled by the `testing` feature.
+
+use crate::ops::checked;
+use crate::random::Rng;
+use crate::*;
+use proptest::prelude::*;
+use std::fmt;
+
+/// Source of random test inputs.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rng: Rng,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub fn unit(&mut self) -> f64 {
+        self.rng.next_f64()
+    }
+
+    /// Uniform integer in `range`, which must not be empty.
+    pub fn range(&mut self, range: std::ops::RangeInclusive<usize>) -> usize {
+        let span = (range.end() - range.start() + 1) as u64;
+        range.start() + (self.rng.next_u64() % span) as usize
+    }
+
+    /// Triangles around a shared center, either a full disk or an open fan.
+    pub fn fan<S: Scalar>(&mut self) -> Mesh<S> {
+        let count = self.range(3..=12);
+        let closed = self.unit() < 0.5;
+        self.fan_of(count, closed)
+    }
+
+    /// A fan of `count` triangles with random radii.
+    pub fn fan_of<S: Scalar>(&mut self, count: usize, closed: bool) -> Mesh<S> {
+        let sweep = if closed {
+            std::f64::consts::TAU
+        } else {
+            std::f64::consts::PI * (0.5 + self.unit())
+        };
+        let rim = if closed { count } else { count + 1 };
+        let mut positions = vec![[0.0, 0.0, self.unit() - 0.5]];
+        for i in 0..rim {
+            let angle = sweep * i as f64 / count as f64;
+            let radius = 0.5 + self.unit();
+            positions.push([radius * angle.cos(), radius * angle.sin(), 0.0]);
+        }
+        let polygons: Vec<Vec<usize>> = (0..count)
+            .map(|i| vec![0, i + 1, (i + 1) % rim + 1])
+            .collect();
+        builder::from_polygons(&positions, &polygons)
+    }
+
+    /// A quad grid with a random number of cells and jittered heights.
+    pub fn grid<S: Scalar>(&mut self) -> Mesh<S> {
+        let (cols, rows) = (self.range(1..=6), self.range(1..=6));
+        self.grid_of(cols, rows)
+    }
+
+    /// A grid of `cols` by `rows` jittered quads.
+    pub fn grid_of<S: Scalar>(&mut self, cols: usize, rows: usize) -> Mesh<S> {
+        let positions = self.lattice(cols, rows);
+        let polygons: Vec<Vec<usize>> = (0..rows)
+            .flat_map(|y| {
+                (0..cols).map(move |x| {
+                    let i = y * (cols + 1) + x;
+                    vec![i, i + 1, i + cols + 2, i + cols + 1]
+                })
+            })
+            .collect();
+        builder::from_polygons(&positions, &polygons)
+    }
+
+    /// A jittered grid with each cell split along a random diagonal.
+    pub fn triangulation<S: Scalar>(&mut self) -> Mesh<S> {
+        let (cols, rows) = (self.range(1..=6), self.range(1..=6));
+        self.triangulation_of(cols, rows)
+    }
+
+    /// A triangulation of a `cols` by `rows` jittered grid.
+    pub fn triangulation_of<S: Scalar>(&mut self, cols: usize, rows: usize) -> Mesh<S> {
+        let positions = self.lattice(cols, rows);
+        let mut polygons = Vec::new();
+        for y in 0..rows {
+            for x in 0..cols {
+                let a = y * (cols + 1) + x;
+                let [b, c, d] = [a + 1, a + cols + 2, a + cols + 1];
+                if self.unit() < 0.5 {
+                    polygons.extend([vec![a, b, c], vec![a, c, d]]);
+                } else {
+                    polygons.extend([vec![a, b, d], vec![b, c, d]]);
+                }
+            }
+        }
+        builder::from_polygons(&positions, &polygons)
+    }
+
+    /// Any of the meshes above.
+    pub fn mesh<S: Scalar>(&mut self) -> Mesh<S> {
+        match self.range(0..=2) {
+            0 => self.fan(),
+            1 => self.grid(),
+            _ => self.triangulation(),
+        }
+    }
+
+    pub fn operation(&mut self) -> Operation {
+        let element = self.rng.next_u64() as usize;
+        match self.range(0..=3) {
+            0 => Operation::SplitEdge {
+                edge: element,
+                t: 0.05 + 0.9 * self.unit(),
+            },
+            1 => Operation::FlipEdge { edge: element },
+            2 => Operation::CollapseEdge { edge: element },
+            _ => Operation::RemoveFace { face: element },
+        }
+    }
+
+    pub fn operations(&mut self, count: std::ops::RangeInclusive<usize>) -> Vec<Operation> {
+        (0..self.range(count)).map(|_| self.operation()).collect()
+    }
+
+    /// Unit cell corners with a little noise, keeping every cell convex.
+    fn lattice(&mut self, cols: usize, rows: usize) -> Vec<[f64; 3]> {
+        let mut positions = Vec::with_capacity((cols + 1) * (rows + 1));
+        for y in 0..=rows {
+            for x in 0..=cols {
+                let jitter = [self.unit(), self.unit()].map(|j| 0.4 * (j - 0.5));
+                positions.push([
+                    x as f64 + jitter[0],
+                    y as f64 + jitter[1],
+                    0.25 * (self.unit() - 0.5),
+                ]);
+            }
+        }
+        positions
+    }
+}
+
+/// A closed triangulated sphere of radius one with about `n` vertices.
+///
+/// Vertices sit on jittered rings of latitude and quads between the rings
+/// are split along random diagonals, the poles are closed with fans.
+pub fn random_triangulated_sphere<S: Scalar>(n: usize, seed: u64) -> Mesh<S> {
+    let mut generator = Generator::new(seed);
+    let segments = ((2 * n) as f64).sqrt().round().max(3.0) as usize;
+    let rings = (n.saturating_sub(2) / segments).max(1);
+    let mut positions = vec![[0.0, 0.0, 1.0]];
+    let twist = generator.unit();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let jitter = [generator.unit(), generator.unit()].map(|j| 0.4 * (j - 0.5));
+            let polar =
+                std::f64::consts::PI * (ring as f64 + 1.0 + jitter[0]) / (rings as f64 + 1.0);
+            let azimuth =
+                std::f64::consts::TAU * (segment as f64 + twist + jitter[1]) / segments as f64;
+            positions.push([
+                polar.sin() * azimuth.cos(),
+                polar.sin() * azimuth.sin(),
+                polar.cos(),
+            ]);
+        }
+    }
+    positions.push([0.0, 0.0, -1.0]);
+    let south = positions.len() - 1;
+    let at = |ring: usize, segment: usize| 1 + ring * segments + segment % segments;
+    let mut polygons = Vec::new();
+    for segment in 0..segments {
+        polygons.push(vec![0, at(0, segment), at(0, segment + 1)]);
+        polygons.push(vec![
+            south,
+            at(rings - 1, segment + 1),
+            at(rings - 1, segment),
+        ]);
+    }
+    for ring in 0..rings - 1 {
+        for segment in 0..segments {
+            let [a, b] = [at(ring, segment), at(ring, segment + 1)];
+            let [c, d] = [at(ring + 1, segment + 1), at(ring + 1, segment)];
+            if generator.unit() < 0.5 {
+                polygons.extend([vec![a, d, c], vec![a, c, b]]);
+            } else {
+                polygons.extend([vec![a, d, b], vec![b, d, c]]);
+            }
+        }
+    }
+    builder::from_polygons(&positions, &polygons)
+}
+
+/// A heightfield of random size with rolling hills, made by smoothly
+/// interpolating random heights on a coarser lattice.
+pub fn random_terrain<S: Scalar>(seed: u64) -> Mesh<S> {
+    let mut generator = Generator::new(seed);
+    let (width, height) = (generator.range(8..=32), generator.range(8..=32));
+    let spacing = generator.range(2..=6);
+    let (cols, rows) = (width / spacing + 2, height / spacing + 2);
+    let lattice: Vec<f64> = (0..cols * rows).map(|_| 4.0 * generator.unit()).collect();
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let sample = |x: usize, y: usize| {
+        let (cx, cy) = (x / spacing, y / spacing);
+        let tx = smooth((x % spacing) as f64 / spacing as f64);
+        let ty = smooth((y % spacing) as f64 / spacing as f64);
+        let h = |i: usize, j: usize| lattice[(cy + j) * cols + cx + i];
+        let bottom = h(0, 0) + (h(1, 0) - h(0, 0)) * tx;
+        let top = h(0, 1) + (h(1, 1) - h(0, 1)) * tx;
+        (bottom + (top - bottom) * ty) as f32
+    };
+    match generate::heightfield(width, height, sample) {
+        Ok(mesh) => mesh,
+        Err(_) => unreachable!("terrains are at least 8x8 samples"),
+    }
+}
+
+/// Removes about `fraction` of the faces at random, returning how many
+/// went. Removals which would leave a non-manifold vertex are skipped, so
+/// the mesh stays valid and manifold.
+pub fn random_deletions<S: Scalar>(mesh: &mut Mesh<S>, fraction: f64, seed: u64) -> usize {
+    let mut generator = Generator::new(seed);
+    let faces: Vec<FaceIndex> = mesh.faces().map(|f| f.index).collect();
+    let mut removed = 0;
+    for face in faces {
+        if generator.unit() < fraction && checked::remove_face(mesh, face).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// A local operator applied to the `n`th live element, wrapping around the
+/// number of elements in the mesh it is applied to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    SplitEdge { edge: usize, t: f64 },
+    FlipEdge { edge: usize },
+    CollapseEdge { edge: usize },
+    RemoveFace { face: usize },
+}
+
+impl Operation {
+    /// Applies the checked version of the operator.
+    ///
+    /// Refusals because the result wouldn't be manifold are expected and
+    /// reported as `Ok(false)`, any other error is passed on.
+    pub fn apply<S: Scalar>(&self, mesh: &mut Mesh<S>) -> Result<bool> {
+        let nth_edge = |mesh: &Mesh<S>, n: usize| {
+            let count = mesh.edge_count();
+            (count > 0).then(|| mesh.edges().nth(n % count).map(|e| e.index))
+        };
+        let result = match *self {
+            Operation::SplitEdge { edge, t } => match nth_edge(mesh, edge).flatten() {
+                Some(edge) => checked::split_edge(mesh, edge, t).map(|_| ()),
+                None => return Ok(false),
+            },
+            Operation::FlipEdge { edge } => match nth_edge(mesh, edge).flatten() {
+                Some(edge) => checked::flip_edge(mesh, edge),
+                None => return Ok(false),
+            },
+            Operation::CollapseEdge { edge } => match nth_edge(mesh, edge).flatten() {
+                Some(edge) => checked::collapse_edge(mesh, edge).map(|_| ()),
+                None => return Ok(false),
+            },
+            Operation::RemoveFace { face } => {
+                let count = mesh.face_count();
+                if count == 0 {
+                    return Ok(false);
+                }
+                let face = mesh.faces().nth(face % count).map(|f| f.index);
+                checked::remove_face(mesh, face.unwrap_or_default())
+            }
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(Error::NonManifold(_)) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Values which can be generated at random.
+pub trait Arbitrary: Sized {
+    fn arbitrary(generator: &mut Generator) -> Self;
+}
+
+impl<S: Scalar> Arbitrary for Mesh<S> {
+    fn arbitrary(generator: &mut Generator) -> Self {
+        generator.mesh()
+    }
+}
+
+impl Arbitrary for Operation {
+    fn arbitrary(generator: &mut Generator) -> Self {
+        generator.operation()
+    }
+}
+
+impl<A: Arbitrary, B: Arbitrary> Arbitrary for (A, B) {
+    fn arbitrary(generator: &mut Generator) -> Self {
+        (A::arbitrary(generator), B::arbitrary(generator))
+    }
+}
+
+impl<T: Arbitrary> Arbitrary for Vec<T> {
+    fn arbitrary(generator: &mut Generator) -> Self {
+        (0..generator.range(0..=32))
+            .map(|_| T::arbitrary(generator))
+            .collect()
+    }
+}
+
+/// Proptest strategy for `Generator::fan`, shrinking towards three
+/// triangles.
+pub fn fans<S: Scalar>() -> impl Strategy<Value = Mesh<S>> {
+    (3..=12usize, any::<bool>(), any::<u64>())
+        .prop_map(|(count, closed, seed)| Generator::new(seed).fan_of(count, closed))
+}
+
+/// Proptest strategy for `Generator::grid`, shrinking towards one cell.
+pub fn grids<S: Scalar>() -> impl Strategy<Value = Mesh<S>> {
+    (1..=6usize, 1..=6usize, any::<u64>())
+        .prop_map(|(cols, rows, seed)| Generator::new(seed).grid_of(cols, rows))
+}
+
+/// Proptest strategy for `Generator::triangulation`.
+pub fn triangulations<S: Scalar>() -> impl Strategy<Value = Mesh<S>> {
+    (1..=6usize, 1..=6usize, any::<u64>())
+        .prop_map(|(cols, rows, seed)| Generator::new(seed).triangulation_of(cols, rows))
+}
+
+/// Proptest strategy for any of the meshes above.
+pub fn meshes<S: Scalar>() -> impl Strategy<Value = Mesh<S>> {
+    prop_oneof![fans(), grids(), triangulations()]
+}
+
+/// Proptest strategy for single operations.
+pub fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        (any::<usize>(), 0.05..0.95f64).prop_map(|(edge, t)| Operation::SplitEdge { edge, t }),
+        any::<usize>().prop_map(|edge| Operation::FlipEdge { edge }),
+        any::<usize>().prop_map(|edge| Operation::CollapseEdge { edge }),
+        any::<usize>().prop_map(|face| Operation::RemoveFace { face }),
+    ]
+}
+
+/// Proptest strategy for sequences of `count` operations.
+pub fn operations(count: std::ops::RangeInclusive<usize>) -> impl Strategy<Value = Vec<Operation>> {
+    proptest::collection::vec(operation(), count)
+}
+
+impl<'a, S: Scalar> arbitrary::Arbitrary<'a> for Mesh<S> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let kind = u.int_in_range(0..=2)?;
+        let (cols, rows) = (u.int_in_range(1..=6)?, u.int_in_range(1..=6)?);
+        let mut generator = Generator::new(u.arbitrary()?);
+        Ok(match kind {
+            0 => generator.fan_of(cols + 2, u.arbitrary()?),
+            1 => generator.grid_of(cols, rows),
+            _ => generator.triangulation_of(cols, rows),
+        })
+    }
+}
+
+impl<'a> arbitrary::Arbitrary<'a> for Operation {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let element = u.arbitrary()?;
+        Ok(match u.int_in_range(0..=3)? {
+            0 => Operation::SplitEdge {
+                edge: element,
+                t: 0.05 + 0.9 * f64::from(u.arbitrary::<u16>()?) / f64::from(u16::MAX),
+            },
+            1 => Operation::FlipEdge { edge: element },
+            2 => Operation::CollapseEdge { edge: element },
+            _ => Operation::RemoveFace { face: element },
+        })
+    }
+}
+
+/// Checks `property` against `cases` random values, panicking with the seed
+/// and value of the first case that fails.
+pub fn for_all<T, F, E>(cases: u64, mut property: F)
+where
+    T: Arbitrary + fmt::Debug + Clone,
+    F: FnMut(T) -> std::result::Result<(), E>,
+    E: fmt::Display,
+{
+    for seed in 0..cases {
+        let value = T::arbitrary(&mut Generator::new(seed));
+        if let Err(error) = property(value.clone()) {
+            panic!(
+                "property failed for seed {}: {}\ninput: {:?}",
+                seed, error, value
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_meshes_are_valid() {
+        let _ = env_logger::try_init();
+        for_all(64, |mesh: Mesh| {
+            mesh.validate()?;
+            if mesh.face_count() == 0 {
+                return Err(Error::InvalidArgument("no faces were built".into()));
+            }
+            Ok::<(), Error>(())
+        });
+    }
+
+    #[test]
+    fn checked_operations_keep_meshes_valid() {
+        for_all(
+            256,
+            |(mut mesh, operations): (Mesh<f64>, Vec<Operation>)| {
+                for operation in &operations {
+                    operation.apply(&mut mesh)?;
+                    mesh.validate()?;
+                }
+                Ok::<(), Error>(())
+            },
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn strategies_keep_meshes_valid(
+            mut mesh in meshes::<f64>(),
+            operations in operations(0..=16),
+        ) {
+            prop_assert!(mesh.face_count() > 0);
+            for operation in &operations {
+                operation.apply(&mut mesh).unwrap();
+                prop_assert!(mesh.validate().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn unstructured_bytes_make_valid_meshes() {
+        use arbitrary::Unstructured;
+        let mut bytes = Generator::new(1);
+        for _ in 0..32 {
+            let data: Vec<u8> = (0..64).map(|_| bytes.range(0..=255) as u8).collect();
+            let mut u = Unstructured::new(&data);
+            let mut mesh: Mesh<f64> = u.arbitrary().unwrap();
+            for operation in u.arbitrary::<Vec<Operation>>().unwrap() {
+                operation.apply(&mut mesh).unwrap();
+            }
+            mesh.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn generators_are_reproducible() {
+        let a: Vec<Operation> = Generator::new(3).operations(4..=4);
+        let b: Vec<Operation> = Generator::new(3).operations(4..=4);
+        assert_eq!(a, b);
+        let mesh: Mesh = Generator::new(9).triangulation();
+        assert!(mesh.faces().all(|f| f.edges().count() == 3));
+    }
+
+    #[test]
+    fn random_shapes_are_reproducible() {
+        let sphere: Mesh<f64> = random_triangulated_sphere(200, 7);
+        builder::assert_connectivity(&sphere);
+        assert!(sphere.content_hash() == random_triangulated_sphere::<f64>(200, 7).content_hash());
+        assert!((150..=250).contains(&sphere.vertex_count()));
+        let report = analysis::watertight_report(&sphere, 1.0e-9);
+        assert!(report.is_closed() && report.is_consistently_oriented());
+        assert!(report.volume > 3.0 && report.volume < 4.0 * std::f64::consts::PI / 3.0);
+
+        let terrain: Mesh = random_terrain(5);
+        builder::assert_connectivity(&terrain);
+        assert!(terrain
+            .points
+            .iter()
+            .all(|(_, p)| (0.0..=4.0).contains(&p.position[2])));
+
+        let mut holey = terrain.clone();
+        let removed = random_deletions(&mut holey, 0.25, 5);
+        builder::assert_connectivity(&holey);
+        assert!(removed > 0);
+        assert_eq!(holey.face_count(), terrain.face_count() - removed);
+    }
+}
@@ -0,0 +1,52 @@
+//! Tolerances shared by operations that compare geometry.
+//!
+//! Rather than each operation picking its own epsilon, repair and merge
+//! operations accept a `Tolerances` so that a single context describes how
+//! close is close enough for a given mesh.
+
+/// Dihedral angle, in radians, above which an edge is treated as a feature.
+pub const DEFAULT_FEATURE_ANGLE: f64 = std::f64::consts::FRAC_PI_3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// Points closer than this distance are considered coincident.
+    pub weld: f64,
+    /// Dihedral angle in radians above which an edge is considered sharp.
+    pub feature_angle: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances {
+            weld: 1.0e-6,
+            feature_angle: DEFAULT_FEATURE_ANGLE,
+        }
+    }
+}
+
+impl Tolerances {
+    /// Tolerances scaled to a mesh whose bounds have the given diagonal, so
+    /// that distances stay meaningful regardless of the model's units.
+    pub fn relative_to(diagonal: f64) -> Self {
+        let defaults = Tolerances::default();
+        Tolerances {
+            weld: defaults.weld * diagonal,
+            ..defaults
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::MergePolicy;
+
+    #[test]
+    fn distances_scale_with_the_model() {
+        let tolerances = Tolerances::relative_to(1000.0);
+        assert_eq!(tolerances.weld, 1.0e-3);
+        assert_eq!(tolerances.feature_angle, DEFAULT_FEATURE_ANGLE);
+        assert_eq!(Tolerances::relative_to(1.0), Tolerances::default());
+        assert_eq!(MergePolicy::weld(&tolerances), MergePolicy::Weld(1.0e-3));
+    }
+}
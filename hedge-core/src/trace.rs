@@ -0,0 +1,265 @@
+//! Instrumentation of mesh edits, enabled by the `tracing` feature.
+//!
+//! Operators run inside trace level `tracing` spans named after them. A
+//! span records the element counts before and after the edit, and how many
+//! elements the kernel added and removed on the way, nested spans included.
+//! Durations are up to the installed subscriber. Without the feature the
+//! span is a plain borrow of the mesh.
+
+use crate::*;
+
+/// Opens a span called `$name` around an edit of `$mesh`, closed when the
+/// returned guard is dropped. The edit goes through `mesh()` of the guard.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($mesh:expr, $name:literal) => {
+        $crate::trace::Span::new(
+            $mesh,
+            tracing::trace_span!(
+                $name,
+                before = tracing::field::Empty,
+                after = tracing::field::Empty,
+                added = tracing::field::Empty,
+                removed = tracing::field::Empty,
+            ),
+        )
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($mesh:expr, $name:literal) => {
+        $crate::trace::Span::new($mesh)
+    };
+}
+
+pub(crate) use span;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span<'a, S: Scalar> {
+    mesh: &'a mut Mesh<S>,
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<'a, S: Scalar> Span<'a, S> {
+    #[inline(always)]
+    pub(crate) fn new(mesh: &'a mut Mesh<S>) -> Self {
+        Span { mesh }
+    }
+
+    #[inline(always)]
+    pub(crate) fn mesh(&mut self) -> &mut Mesh<S> {
+        self.mesh
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn added() {}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn removed() {}
+
+#[cfg(feature = "tracing")]
+pub(crate) use self::enabled::*;
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Live element counts of a mesh.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Counts {
+        edges: usize,
+        vertices: usize,
+        faces: usize,
+        points: usize,
+    }
+
+    impl Counts {
+        fn of<S: Scalar>(mesh: &Mesh<S>) -> Self {
+            Counts {
+                edges: mesh.edge_count(),
+                vertices: mesh.vertex_count(),
+                faces: mesh.face_count(),
+                points: mesh.point_count(),
+            }
+        }
+    }
+
+    /// Kernel edits counted by each span open on this thread.
+    #[derive(Default)]
+    struct Open {
+        added: usize,
+        removed: usize,
+    }
+
+    thread_local! {
+        static OPEN: RefCell<Vec<Open>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(crate) struct Span<'a, S: Scalar> {
+        mesh: &'a mut Mesh<S>,
+        span: tracing::span::EnteredSpan,
+    }
+
+    impl<'a, S: Scalar> Span<'a, S> {
+        pub(crate) fn new(mesh: &'a mut Mesh<S>, span: tracing::Span) -> Self {
+            span.record("before", tracing::field::debug(Counts::of(mesh)));
+            OPEN.with(|open| open.borrow_mut().push(Open::default()));
+            Span {
+                mesh,
+                span: span.entered(),
+            }
+        }
+
+        pub(crate) fn mesh(&mut self) -> &mut Mesh<S> {
+            self.mesh
+        }
+    }
+
+    /// Closes the span on every way out of the edit, unwinding included, so
+    /// the counters of the spans around it stay in step.
+    impl<S: Scalar> Drop for Span<'_, S> {
+        fn drop(&mut self) {
+            let own = OPEN
+                .with(|open| open.borrow_mut().pop())
+                .unwrap_or_default();
+            // Edits of a nested span also count for the spans around it.
+            OPEN.with(|open| {
+                if let Some(outer) = open.borrow_mut().last_mut() {
+                    outer.added += own.added;
+                    outer.removed += own.removed;
+                }
+            });
+            self.span
+                .record("after", tracing::field::debug(Counts::of(self.mesh)));
+            self.span.record("added", own.added);
+            self.span.record("removed", own.removed);
+        }
+    }
+
+    pub(crate) fn added() {
+        OPEN.with(|open| {
+            if let Some(span) = open.borrow_mut().last_mut() {
+                span.added += 1;
+            }
+        });
+    }
+
+    pub(crate) fn removed() {
+        OPEN.with(|open| {
+            if let Some(span) = open.borrow_mut().last_mut() {
+                span.removed += 1;
+            }
+        });
+    }
+
+    #[cfg(test)]
+    pub(super) fn open_spans() -> usize {
+        OPEN.with(|open| open.borrow().len())
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+
+    #[derive(Debug, Default)]
+    struct Recorded {
+        name: &'static str,
+        parent: Option<u64>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    impl Visit for Recorded {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.fields.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    /// Keeps every span with the fields recorded on it.
+    #[derive(Clone, Default)]
+    struct Collect {
+        spans: Arc<Mutex<Vec<Recorded>>>,
+        entered: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl tracing::Subscriber for Collect {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock().unwrap();
+            let mut span = Recorded {
+                name: attributes.metadata().name(),
+                parent: self.entered.lock().unwrap().last().copied(),
+                ..Recorded::default()
+            };
+            attributes.record(&mut span);
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &Id, values: &Record<'_>) {
+            values.record(&mut self.spans.lock().unwrap()[id.into_u64() as usize - 1]);
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, id: &Id) {
+            self.entered.lock().unwrap().push(id.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn nested_spans_are_recorded() {
+        let collect = Collect::default();
+        tracing::subscriber::with_default(collect.clone(), || {
+            let mut mesh: Mesh = builder::grid(2, 2);
+            ops::triangulate(&mut mesh, ops::TriangulationMethod::Fan).unwrap();
+        });
+
+        let spans = collect.spans.lock().unwrap();
+        let (id, outer) = spans
+            .iter()
+            .enumerate()
+            .find(|(_, s)| s.name == "triangulate")
+            .unwrap();
+        assert_eq!(outer.parent, None);
+        assert!(outer.fields["before"].contains("faces: 4"));
+        assert!(outer.fields["after"].contains("faces: 8"));
+        assert_eq!(outer.fields["added"], (4 + 4 * 2).to_string());
+        assert_eq!(outer.fields["removed"], "0");
+        let faces = spans
+            .iter()
+            .filter(|s| s.name == "triangulate_face" && s.parent == Some(id as u64 + 1))
+            .count();
+        assert_eq!(faces, 4);
+    }
+
+    #[test]
+    fn spans_close_while_unwinding() {
+        let mut mesh: Mesh = builder::grid(1, 1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _span = span!(&mut mesh, "panicking");
+            panic!("the edit failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(enabled::open_spans(), 0);
+    }
+}
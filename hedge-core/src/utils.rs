@@ -0,0 +1,243 @@
+//! Low level helpers for wiring up connectivity by hand.
+//!
+//! Every helper checks the handles it is given before touching the mesh, so
+//! a call that fails leaves connectivity exactly as it was.
+
+use super::*;
+
+fn vertex_exists<S: Scalar>(mesh: &Mesh<S>, index: VertexIndex) -> Result<()> {
+    match mesh.get_element(index) {
+        Some(_) => Ok(()),
+        None => Err(Error::InvalidVertex(index)),
+    }
+}
+
+fn edge_exists<S: Scalar>(mesh: &Mesh<S>, index: EdgeIndex) -> Result<&Edge> {
+    mesh.get_element(index).ok_or(Error::InvalidEdge(index))
+}
+
+/// The vertex at the end of an edge, which is the origin of its twin.
+fn end_vertex<S: Scalar>(mesh: &Mesh<S>, index: EdgeIndex) -> Result<VertexIndex> {
+    let twin = edge_exists(mesh, index)?.twin_index;
+    let vertex = edge_exists(mesh, twin)
+        .map_err(|_| Error::InvalidEdge(index))?
+        .vertex_index;
+    vertex_exists(mesh, vertex)?;
+    Ok(vertex)
+}
+
+/// Given two vertex indices, create an adjacent edge pair
+pub fn build_full_edge<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    v0: VertexIndex,
+    v1: VertexIndex,
+) -> Result<EdgeIndex> {
+    vertex_exists(mesh, v0)?;
+    vertex_exists(mesh, v1)?;
+    Ok(full_edge_unchecked(mesh, v0, v1))
+}
+
+/// `build_full_edge` for callers which just created both vertices.
+pub(crate) fn full_edge_unchecked<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    v0: VertexIndex,
+    v1: VertexIndex,
+) -> EdgeIndex {
+    let e0 = mesh.add_element(Edge {
+        vertex_index: v0,
+        ..Edge::default()
+    });
+
+    let e1 = mesh.add_element(Edge {
+        twin_index: e0,
+        vertex_index: v1,
+        ..Edge::default()
+    });
+
+    if let Some(e) = mesh.get_element_mut(e0) {
+        e.twin_index = e1;
+    }
+    if let Some(v) = mesh.get_element_mut(v0) {
+        v.edge_index = e0;
+    }
+    if let Some(v) = mesh.get_element_mut(v1) {
+        v.edge_index = e1;
+    }
+
+    e0
+}
+
+pub fn build_half_edge<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    twin: EdgeIndex,
+    vert: VertexIndex,
+) -> Result<EdgeIndex> {
+    edge_exists(mesh, twin)?;
+    vertex_exists(mesh, vert)?;
+
+    let e0 = mesh.add_element(Edge {
+        vertex_index: vert,
+        twin_index: twin,
+        ..Edge::default()
+    });
+
+    if let Some(e) = mesh.get_element_mut(twin) {
+        e.twin_index = e0;
+    }
+    if let Some(v) = mesh.get_element_mut(vert) {
+        v.edge_index = e0;
+    }
+
+    Ok(e0)
+}
+
+pub fn assoc_vert_edge<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    vert: VertexIndex,
+    edge: EdgeIndex,
+) -> Result<()> {
+    vertex_exists(mesh, vert)?;
+    edge_exists(mesh, edge)?;
+
+    if let Some(v) = mesh.get_element_mut(vert) {
+        v.edge_index = edge;
+    }
+    if let Some(e) = mesh.get_element_mut(edge) {
+        e.vertex_index = vert;
+    }
+    Ok(())
+}
+
+/// Given an edge index, and a vertex index, creates a new edge connected to the specified edge
+pub fn build_full_edge_from<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    prev: EdgeIndex,
+    v1: VertexIndex,
+) -> Result<EdgeIndex> {
+    let v0 = end_vertex(mesh, prev)?;
+    vertex_exists(mesh, v1)?;
+
+    let e0 = full_edge_unchecked(mesh, v0, v1);
+    link_unchecked(mesh, prev, e0);
+    Ok(e0)
+}
+
+/// Creates the edge pair which runs from the end of `prev` to the start of `next`.
+pub fn close_edge_loop<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    prev: EdgeIndex,
+    next: EdgeIndex,
+) -> Result<EdgeIndex> {
+    let v0 = end_vertex(mesh, prev)?;
+    let v1 = edge_exists(mesh, next)?.vertex_index;
+    vertex_exists(mesh, v1)?;
+
+    let e0 = full_edge_unchecked(mesh, v0, v1);
+    link_unchecked(mesh, prev, e0);
+    link_unchecked(mesh, e0, next);
+    Ok(e0)
+}
+
+/// Associates a previous and next edge
+pub fn connect_edges<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    prev: EdgeIndex,
+    next: EdgeIndex,
+) -> Result<()> {
+    edge_exists(mesh, prev)?;
+    edge_exists(mesh, next)?;
+    link_unchecked(mesh, prev, next);
+    Ok(())
+}
+
+/// `connect_edges` for callers which know both edges are live.
+pub(crate) fn link_unchecked<S: Scalar>(mesh: &mut Mesh<S>, prev: EdgeIndex, next: EdgeIndex) {
+    if let Some(e) = mesh.get_element_mut(prev) {
+        e.next_index = next;
+    }
+    if let Some(e) = mesh.get_element_mut(next) {
+        e.prev_index = prev;
+    }
+}
+
+/// Assigns a face to every edge of the loop starting at `root_edge_index`.
+///
+/// The loop has to lead back to the root edge within `edge_count` steps,
+/// otherwise nothing is changed and `Error::OpenEdgeLoop` is returned.
+pub fn assign_face_to_loop<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    root_edge_index: EdgeIndex,
+    face_index: FaceIndex,
+) -> Result<()> {
+    if mesh.get_element(face_index).is_none() {
+        return Err(Error::InvalidFace(face_index));
+    }
+    let mut loop_edges = Vec::new();
+    let mut edge_index = root_edge_index;
+    loop {
+        if loop_edges.len() >= mesh.edge_count() {
+            return Err(Error::OpenEdgeLoop(root_edge_index));
+        }
+        let next_index = edge_exists(mesh, edge_index)?.next_index;
+        loop_edges.push(edge_index);
+        if next_index == root_edge_index {
+            break;
+        }
+        if !next_index.is_valid() {
+            return Err(Error::OpenEdgeLoop(root_edge_index));
+        }
+        edge_index = next_index;
+    }
+
+    if let Some(face) = mesh.get_element_mut(face_index) {
+        face.edge_index = root_edge_index;
+    }
+    for edge_index in loop_edges {
+        if let Some(edge) = mesh.get_element_mut(edge_index) {
+            edge.face_index = face_index;
+        }
+    }
+    Ok(())
+}
+
+/// Zips two open borders together without adding faces.
+///
+/// `edges_a[i]` is joined with `edges_b[i]`, both boundary half-edges which
+/// have to run opposite ways, and the vertices at their ends are merged no
+/// matter how far apart they are. Merged vertices keep the points of
+/// `edges_a`, points of `edges_b` left unused are removed. Returns the number
+/// of edges stitched.
+pub fn stitch_boundaries<S: Scalar>(
+    mesh: &mut Mesh<S>,
+    edges_a: &[EdgeIndex],
+    edges_b: &[EdgeIndex],
+) -> Result<usize> {
+    if edges_a.len() != edges_b.len() {
+        return Err(Error::InvalidArgument(format!(
+            "can't stitch {} edges to {}",
+            edges_a.len(),
+            edges_b.len()
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for edge in edges_a.iter().chain(edges_b) {
+        if edge_exists(mesh, *edge)?.face_index.is_valid() {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} isn't on a boundary",
+                edge
+            )));
+        }
+        if !seen.insert(*edge) {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} is stitched more than once",
+                edge
+            )));
+        }
+    }
+    let pairs: Vec<(EdgeIndex, EdgeIndex)> = edges_a
+        .iter()
+        .copied()
+        .zip(edges_b.iter().copied())
+        .collect();
+    ops::zip_sides(mesh, &pairs)
+}
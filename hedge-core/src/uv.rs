@@ -0,0 +1,417 @@
+//! Texture coordinate generation.
+//!
+//! UVs are written per corner. A corner is identified by the half-edge leaving
+//! the corner's vertex inside the face, so vertices on a seam can carry a
+//! different UV in each of the charts they belong to.
+
+use crate::attributes::Channel;
+use crate::linalg::{self, SparseMatrix};
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub type Uv = [f32; 2];
+pub type UvChannel = Channel<Edge, Uv>;
+
+/// A set of faces which are parameterized together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Chart {
+    pub faces: Vec<FaceIndex>,
+}
+
+/// Groups faces into charts of edge-connected faces.
+pub fn connected_charts<S: Scalar>(mesh: &Mesh<S>) -> Vec<Chart> {
+    charts_bounded_by(mesh, |_| false)
+}
+
+/// Dihedral angle, in radians, above which an edge is treated as a seam.
+pub const DEFAULT_SEAM_ANGLE: f64 = crate::tolerance::DEFAULT_FEATURE_ANGLE;
+
+/// Splits faces into charts along feature edges sharper than `DEFAULT_SEAM_ANGLE`.
+pub fn split_charts_by_seams<S: Scalar>(mesh: &Mesh<S>) -> Vec<Chart> {
+    split_charts_by_seams_with(mesh, &Tolerances::default())
+}
+
+/// Splits faces into charts along edges sharper than the feature angle.
+pub fn split_charts_by_seams_with<S: Scalar>(
+    mesh: &Mesh<S>,
+    tolerances: &Tolerances,
+) -> Vec<Chart> {
+    split_charts_along(mesh, &feature_edges(mesh, tolerances.feature_angle))
+}
+
+/// Splits faces into charts along the given seams.
+///
+/// Flagging either half of an edge is enough to cut along it.
+pub fn split_charts_along<S: Scalar>(mesh: &Mesh<S>, seams: &HashSet<EdgeIndex>) -> Vec<Chart> {
+    charts_bounded_by(mesh, |edge| {
+        seams.contains(&edge) || seams.contains(&mesh.edge(edge).twin().index)
+    })
+}
+
+/// Collects both halves of every interior edge whose dihedral angle exceeds `angle`.
+pub fn feature_edges<S: Scalar>(mesh: &Mesh<S>, angle: f64) -> HashSet<EdgeIndex> {
+    let threshold = angle.cos();
+    let mut features = HashSet::new();
+    for edge in mesh.edges() {
+        let twin = edge.twin();
+        if !edge.face().is_valid() || !twin.face().is_valid() {
+            continue;
+        }
+        let a = mesh.face_normal(edge.face().index);
+        let b = mesh.face_normal(twin.face().index);
+        if math::dot(a, b) < threshold {
+            features.insert(edge.index);
+            features.insert(twin.index);
+        }
+    }
+    features
+}
+
+/// Flood fills faces across every edge for which `is_cut` returns `false`.
+pub(crate) fn charts_bounded_by<S: Scalar, F>(mesh: &Mesh<S>, is_cut: F) -> Vec<Chart>
+where
+    F: Fn(EdgeIndex) -> bool,
+{
+    let mut visited = HashSet::new();
+    let mut charts = Vec::new();
+    for face in mesh.faces() {
+        if !visited.insert(face.index) {
+            continue;
+        }
+        let mut chart = Chart::default();
+        let mut queue = VecDeque::from([face.index]);
+        while let Some(current) = queue.pop_front() {
+            chart.faces.push(current);
+            for edge in mesh.face(current).edges() {
+                if is_cut(edge.index) {
+                    continue;
+                }
+                let neighbor = edge.twin().face();
+                if neighbor.is_valid() && visited.insert(neighbor.index) {
+                    queue.push_back(neighbor.index);
+                }
+            }
+        }
+        charts.push(chart);
+    }
+    charts
+}
+
+/// Computes a least squares conformal map for each edge-connected chart.
+///
+/// Every chart needs two pinned vertices to fix the solution. Charts with
+/// fewer user supplied pins are completed with the vertices furthest apart
+/// along the longest axis of the chart bounds.
+pub fn unwrap_lscm<S: Scalar>(mesh: &Mesh<S>, pinned: &[(VertexIndex, Uv)]) -> UvChannel {
+    unwrap_charts_lscm(mesh, &connected_charts(mesh), pinned)
+}
+
+/// Computes a least squares conformal map for each of the given charts.
+pub fn unwrap_charts_lscm<S: Scalar>(
+    mesh: &Mesh<S>,
+    charts: &[Chart],
+    pinned: &[(VertexIndex, Uv)],
+) -> UvChannel {
+    let mut uvs = UvChannel::new();
+    for chart in charts {
+        unwrap_chart(mesh, chart, pinned, &mut uvs);
+    }
+    uvs
+}
+
+fn unwrap_chart<S: Scalar>(
+    mesh: &Mesh<S>,
+    chart: &Chart,
+    pinned: &[(VertexIndex, Uv)],
+    uvs: &mut UvChannel,
+) {
+    let mut locals: HashMap<VertexIndex, usize> = HashMap::new();
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut triangles: Vec<[usize; 3]> = Vec::new();
+    for face in &chart.faces {
+        let corners: Vec<usize> = mesh
+            .face(*face)
+            .vertices()
+            .map(|v| {
+                *locals.entry(v.index).or_insert_with(|| {
+                    positions.push(mesh.vertex_position(v.index));
+                    positions.len() - 1
+                })
+            })
+            .collect();
+        for i in 1..corners.len().saturating_sub(1) {
+            triangles.push([corners[0], corners[i], corners[i + 1]]);
+        }
+    }
+    if positions.is_empty() {
+        return;
+    }
+
+    let mut pins: HashMap<usize, [f64; 2]> = pinned
+        .iter()
+        .filter_map(|(v, uv)| locals.get(v).map(|i| (*i, [uv[0] as f64, uv[1] as f64])))
+        .collect();
+    complete_pins(&positions, &mut pins);
+
+    let mut columns = vec![usize::MAX; positions.len()];
+    let mut free = 0;
+    for (i, column) in columns.iter_mut().enumerate() {
+        if !pins.contains_key(&i) {
+            *column = free;
+            free += 1;
+        }
+    }
+
+    let mut triplets = Vec::new();
+    let mut rhs = Vec::new();
+    for triangle in &triangles {
+        let Some((coefficients, scale)) = conformal_coefficients(triangle.map(|i| positions[i]))
+        else {
+            continue;
+        };
+        let row = rhs.len();
+        let mut re = 0.0;
+        let mut im = 0.0;
+        for (corner, (a, b)) in triangle.iter().zip(coefficients) {
+            let (a, b) = (a * scale, b * scale);
+            // (a + ib)(u + iv) = (au - bv) + i(bu + av)
+            if let Some(uv) = pins.get(corner) {
+                re -= a * uv[0] - b * uv[1];
+                im -= b * uv[0] + a * uv[1];
+            } else {
+                let column = 2 * columns[*corner];
+                triplets.push((row, column, a));
+                triplets.push((row, column + 1, -b));
+                triplets.push((row + 1, column, b));
+                triplets.push((row + 1, column + 1, a));
+            }
+        }
+        rhs.push(re);
+        rhs.push(im);
+    }
+
+    let solution = if free > 0 && !rhs.is_empty() {
+        let matrix = SparseMatrix::from_triplets(rhs.len(), 2 * free, triplets);
+        linalg::least_squares(&matrix, &rhs, 1.0e-12, 20 * free + 100)
+    } else {
+        vec![0.0; 2 * free]
+    };
+
+    let local_uv = |i: usize| -> Uv {
+        match pins.get(&i) {
+            Some(uv) => [uv[0] as f32, uv[1] as f32],
+            None => [
+                solution[2 * columns[i]] as f32,
+                solution[2 * columns[i] + 1] as f32,
+            ],
+        }
+    };
+    for face in &chart.faces {
+        for edge in mesh.face(*face).edges() {
+            if let Some(i) = locals.get(&edge.vertex().index) {
+                uvs.set(edge.index, local_uv(*i));
+            }
+        }
+    }
+}
+
+/// Adds pins until there are at least two, preserving the 3D distance between them.
+fn complete_pins(positions: &[Vec3], pins: &mut HashMap<usize, [f64; 2]>) {
+    if pins.len() >= 2 || positions.len() < 2 {
+        if pins.is_empty() && !positions.is_empty() {
+            pins.insert(0, [0.0, 0.0]);
+        }
+        return;
+    }
+    let (mut min, mut max) = (positions[0], positions[0]);
+    for p in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    let extent = math::sub(max, min);
+    let axis = (0..3)
+        .max_by(|a, b| extent[*a].total_cmp(&extent[*b]))
+        .unwrap_or(0);
+    let by_axis = |i: &usize, j: &usize| positions[*i][axis].total_cmp(&positions[*j][axis]);
+
+    let (anchor, anchor_uv) = match pins.iter().next() {
+        Some((i, uv)) => (*i, *uv),
+        None => {
+            let lowest = (0..positions.len()).min_by(by_axis).unwrap_or(0);
+            pins.insert(lowest, [0.0, 0.0]);
+            (lowest, [0.0, 0.0])
+        }
+    };
+    let furthest = (0..positions.len())
+        .filter(|i| *i != anchor)
+        .max_by(|i, j| {
+            math::distance(positions[*i], positions[anchor])
+                .total_cmp(&math::distance(positions[*j], positions[anchor]))
+        });
+    if let Some(furthest) = furthest {
+        let d = math::distance(positions[furthest], positions[anchor]);
+        pins.insert(furthest, [anchor_uv[0] + d, anchor_uv[1]]);
+    }
+}
+
+/// Returns the complex coefficients `W_j` of a triangle and the row scale.
+fn conformal_coefficients(p: [Vec3; 3]) -> Option<([(f64, f64); 3], f64)> {
+    let e1 = math::sub(p[1], p[0]);
+    let e2 = math::sub(p[2], p[0]);
+    let normal = math::cross(e1, e2);
+    let double_area = math::length(normal);
+    if double_area <= f64::EPSILON {
+        return None;
+    }
+    let x_axis = math::normalize(e1);
+    let y_axis = math::normalize(math::cross(normal, e1));
+    let local = [
+        (0.0, 0.0),
+        (math::length(e1), 0.0),
+        (math::dot(e2, x_axis), math::dot(e2, y_axis)),
+    ];
+    let w = |k: usize, l: usize| (local[l].0 - local[k].0, local[l].1 - local[k].1);
+    Some(([w(1, 2), w(2, 0), w(0, 1)], (1.0 / double_area).sqrt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_uv_matches_xy<S: Scalar>(mesh: &Mesh<S>, uvs: &UvChannel, tolerance: f32) {
+        for face in mesh.faces() {
+            for edge in face.edges() {
+                let uv = uvs.get(edge.index).expect("Missing corner uv.");
+                let p = mesh.vertex_position(edge.vertex().index);
+                assert!(
+                    (uv[0] - p[0] as f32).abs() < tolerance,
+                    "{:?} vs {:?}",
+                    uv,
+                    p
+                );
+                assert!(
+                    (uv[1] - p[1] as f32).abs() < tolerance,
+                    "{:?} vs {:?}",
+                    uv,
+                    p
+                );
+            }
+        }
+    }
+
+    fn vertex_at<S: Scalar>(mesh: &Mesh<S>, x: f64, y: f64) -> VertexIndex {
+        mesh.vertices()
+            .find(|v| {
+                let p = mesh.vertex_position(v.index);
+                p[0] == x && p[1] == y
+            })
+            .map(|v| v.index)
+            .expect("No vertex at position.")
+    }
+
+    #[test]
+    fn planar_grid_is_reproduced_with_pins() {
+        let mesh: Mesh = builder::grid(4, 3);
+        let pins = [
+            (vertex_at(&mesh, 0.0, 0.0), [0.0, 0.0]),
+            (vertex_at(&mesh, 4.0, 3.0), [4.0, 3.0]),
+        ];
+        let uvs = unwrap_lscm(&mesh, &pins);
+        assert_eq!(uvs.len(), 4 * 3 * 4);
+        assert_uv_matches_xy(&mesh, &uvs, 1.0e-3);
+    }
+
+    fn uv_edge_lengths<S: Scalar>(mesh: &Mesh<S>, uvs: &UvChannel) -> Vec<f32> {
+        mesh.faces()
+            .flat_map(|face| face.edges())
+            .map(|edge| {
+                let u0 = uvs.get(edge.index).expect("Missing corner uv.");
+                let u1 = uvs.get(edge.next().index).expect("Missing corner uv.");
+                ((u0[0] - u1[0]).powi(2) + (u0[1] - u1[1]).powi(2)).sqrt()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn automatic_pins_preserve_scale_of_planar_charts() {
+        let mesh: Mesh<f64> = builder::grid(3, 1);
+        let uvs = unwrap_lscm(&mesh, &[]);
+        for length in uv_edge_lengths(&mesh, &uvs) {
+            assert!((length - 1.0).abs() < 1.0e-3, "{}", length);
+        }
+    }
+
+    #[test]
+    fn folded_strip_is_flattened_without_distortion() {
+        // Two quads sharing an edge, folded by 90 degrees.
+        let mesh: Mesh = builder::from_polygons(
+            &[
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 1.0, 1.0],
+                [0.0, 1.0, 1.0],
+            ],
+            &[vec![0, 1, 2, 3], vec![3, 2, 4, 5]],
+        );
+        let uvs = unwrap_lscm(&mesh, &[]);
+        // The overall scale follows the pins, but every edge must remain equal.
+        let lengths = uv_edge_lengths(&mesh, &uvs);
+        for length in &lengths {
+            assert!((length / lengths[0] - 1.0).abs() < 1.0e-3, "{:?}", lengths);
+        }
+    }
+
+    #[test]
+    fn cube_is_split_into_faces() {
+        let mesh: Mesh = builder::cube();
+        assert_eq!(feature_edges(&mesh, DEFAULT_SEAM_ANGLE).len(), 24);
+        let charts = split_charts_by_seams(&mesh);
+        assert_eq!(charts.len(), 6);
+        assert!(charts.iter().all(|chart| chart.faces.len() == 1));
+
+        let blunt = Tolerances {
+            feature_angle: std::f64::consts::FRAC_PI_2 + 0.1,
+            ..Tolerances::default()
+        };
+        assert_eq!(split_charts_by_seams_with(&mesh, &blunt).len(), 1);
+    }
+
+    #[test]
+    fn explicit_seams_cut_flat_charts() {
+        let mesh: Mesh = builder::grid(2, 1);
+        assert_eq!(split_charts_by_seams(&mesh).len(), 1);
+
+        let seam = mesh
+            .edges()
+            .find(|e| !e.is_boundary() && !e.twin().is_boundary())
+            .expect("Expected an interior edge.");
+        let seams = HashSet::from([seam.index]);
+        let charts = split_charts_along(&mesh, &seams);
+        assert_eq!(charts.len(), 2);
+
+        let uvs = unwrap_charts_lscm(&mesh, &charts, &[]);
+        assert_eq!(uvs.len(), 8);
+    }
+
+    #[test]
+    fn disconnected_pieces_are_separate_charts() {
+        let mesh: Mesh = builder::from_polygons(
+            &[
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [5.0, 0.0, 0.0],
+                [6.0, 0.0, 0.0],
+                [5.0, 1.0, 0.0],
+            ],
+            &[vec![0, 1, 2], vec![3, 4, 5]],
+        );
+        assert_eq!(connected_charts(&mesh).len(), 2);
+        assert_eq!(unwrap_lscm(&mesh, &[]).len(), 6);
+    }
+}
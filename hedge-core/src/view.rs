@@ -0,0 +1,106 @@
+//! Shared read-only access to a mesh.
+//!
+//! Elements only refer to each other through handles and the mesh holds no
+//! interior mutability, so a shared borrow is all it takes to read a mesh
+//! from several threads. `MeshView` makes that explicit: it is `Copy`, `Send`
+//! and `Sync`, offers the same traversal entry points as `Mesh`, and holding
+//! one keeps the mesh from being mutated until every view is dropped.
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshView<'mesh, S: Scalar = f32> {
+    mesh: &'mesh Mesh<S>,
+}
+
+impl<'mesh, S: Scalar> MeshView<'mesh, S> {
+    /// The mesh being viewed, for functions which take a `&Mesh`.
+    pub fn mesh(&self) -> &'mesh Mesh<S> {
+        self.mesh
+    }
+
+    pub fn face(&self, index: FaceIndex) -> FaceFn<'mesh, S> {
+        self.mesh.face(index)
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.mesh.face_count()
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = FaceFn<'mesh, S>> {
+        self.mesh.faces()
+    }
+
+    pub fn edge(&self, index: EdgeIndex) -> EdgeFn<'mesh, S> {
+        self.mesh.edge(index)
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.mesh.edge_count()
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = EdgeFn<'mesh, S>> {
+        self.mesh.edges()
+    }
+
+    pub fn vertex(&self, index: VertexIndex) -> VertexFn<'mesh, S> {
+        self.mesh.vertex(index)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.mesh.vertex_count()
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = VertexFn<'mesh, S>> {
+        self.mesh.vertices()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.mesh.point_count()
+    }
+
+    pub fn position(&self, index: PointIndex) -> Option<Position<S>> {
+        self.mesh.position(index)
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Borrows the mesh for reading, possibly from many threads at once.
+    pub fn read_view(&self) -> MeshView<'_, S> {
+        MeshView { mesh: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn views_are_shared_across_threads() {
+        let _ = env_logger::try_init();
+        assert_send_sync::<MeshView<'_, f32>>();
+        assert_send_sync::<FaceFn<'_, f64>>();
+
+        let mesh: Mesh = builder::grid(8, 8);
+        let view = mesh.read_view();
+        let faces: Vec<FaceIndex> = view.faces().map(|f| f.index).collect();
+        let sides: usize = std::thread::scope(|scope| {
+            let workers: Vec<_> = faces
+                .chunks(16)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|f| view.face(*f).edges().count())
+                            .sum::<usize>()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        });
+        assert_eq!(sides, 4 * view.face_count());
+        assert_eq!(view.mesh().edge_count(), view.edge_count());
+    }
+}
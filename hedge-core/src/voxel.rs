@@ -0,0 +1,308 @@
+//! Voxelization of meshes and blocky remeshing.
+
+use crate::bvh::{Aabb, Bvh};
+use crate::math::{self, Vec3};
+use crate::*;
+use std::collections::HashMap;
+
+/// Classification of a single voxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Voxel {
+    #[default]
+    Empty,
+    /// Intersects the surface of the mesh.
+    Surface,
+    /// Lies entirely inside the mesh.
+    Interior,
+}
+
+/// How the inside of a mesh is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Inside when a ray crosses the surface an odd number of times.
+    Parity,
+    /// Inside when the signed crossings of a ray don't cancel out, which also
+    /// handles overlapping shells.
+    #[default]
+    Winding,
+}
+
+/// A dense grid of cubic voxels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    /// Minimum corner of the first voxel.
+    pub origin: [f64; 3],
+    pub cell_size: f64,
+    pub dimensions: [usize; 3],
+    voxels: Vec<Voxel>,
+}
+
+impl VoxelGrid {
+    pub fn new(origin: [f64; 3], cell_size: f64, dimensions: [usize; 3]) -> Self {
+        VoxelGrid {
+            origin,
+            cell_size,
+            dimensions,
+            voxels: vec![Voxel::Empty; dimensions.iter().product()],
+        }
+    }
+
+    fn offset(&self, [i, j, k]: [usize; 3]) -> Option<usize> {
+        let [x, y, z] = self.dimensions;
+        (i < x && j < y && k < z).then(|| i + x * (j + y * k))
+    }
+
+    pub fn get(&self, index: [usize; 3]) -> Voxel {
+        self.offset(index)
+            .map(|offset| self.voxels[offset])
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, index: [usize; 3], voxel: Voxel) {
+        if let Some(offset) = self.offset(index) {
+            self.voxels[offset] = voxel;
+        }
+    }
+
+    pub fn is_filled(&self, index: [usize; 3]) -> bool {
+        self.get(index) != Voxel::Empty
+    }
+
+    pub fn count(&self, voxel: Voxel) -> usize {
+        self.voxels.iter().filter(|v| **v == voxel).count()
+    }
+
+    pub fn center(&self, index: [usize; 3]) -> [f64; 3] {
+        math::add(
+            self.origin,
+            index.map(|i| (i as f64 + 0.5) * self.cell_size),
+        )
+    }
+
+    fn indices(&self) -> impl Iterator<Item = [usize; 3]> {
+        let [x, y, z] = self.dimensions;
+        (0..z).flat_map(move |k| (0..y).flat_map(move |j| (0..x).map(move |i| [i, j, k])))
+    }
+
+    /// Builds quads on every side of a filled voxel which faces an empty one.
+    ///
+    /// Voxels that only touch along an edge produce non-manifold edges, the
+    /// builder skips one of the quads sharing such an edge.
+    pub fn to_mesh<S: Scalar>(&self) -> Mesh<S> {
+        let mut corners: HashMap<[usize; 3], usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut quads = Vec::new();
+        for index in self.indices() {
+            if !self.is_filled(index) {
+                continue;
+            }
+            for axis in 0..3 {
+                for positive in [false, true] {
+                    let mut neighbor = index;
+                    if positive {
+                        neighbor[axis] += 1;
+                    } else if neighbor[axis] == 0 {
+                        neighbor[axis] = usize::MAX;
+                    } else {
+                        neighbor[axis] -= 1;
+                    }
+                    if self.is_filled(neighbor) {
+                        continue;
+                    }
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    let mut base = index;
+                    base[axis] += positive as usize;
+                    let mut quad: Vec<usize> = [(0, 0), (1, 0), (1, 1), (0, 1)]
+                        .iter()
+                        .map(|(du, dv)| {
+                            let mut corner = base;
+                            corner[u] += du;
+                            corner[v] += dv;
+                            *corners.entry(corner).or_insert_with(|| {
+                                positions.push(math::add(
+                                    self.origin,
+                                    corner.map(|c| c as f64 * self.cell_size),
+                                ));
+                                positions.len() - 1
+                            })
+                        })
+                        .collect();
+                    if !positive {
+                        quad.reverse();
+                    }
+                    quads.push(quad);
+                }
+            }
+        }
+        builder::from_polygons(&positions, &quads)
+    }
+}
+
+/// Voxelizes the surface of a mesh and fills its interior using winding numbers.
+pub fn voxelize<S: Scalar>(mesh: &Mesh<S>, bvh: &Bvh, cell_size: f64) -> Result<VoxelGrid> {
+    voxelize_with(mesh, bvh, cell_size, FillRule::default())
+}
+
+/// Voxelizes the surface of a mesh and fills its interior using `rule`.
+///
+/// The interior is found by casting a ray along `+x` through the center of
+/// every voxel, so the mesh should be closed for the fill to be meaningful.
+pub fn voxelize_with<S: Scalar>(
+    mesh: &Mesh<S>,
+    bvh: &Bvh,
+    cell_size: f64,
+    rule: FillRule,
+) -> Result<VoxelGrid> {
+    if cell_size <= 0.0 || !cell_size.is_finite() {
+        return Err(Error::InvalidArgument(format!(
+            "voxels need a positive size, got {}",
+            cell_size
+        )));
+    }
+    let bounds = bvh.bounds();
+    if bounds.is_empty() {
+        log::debug!("Nothing to voxelize in {:?}.", mesh);
+        return Ok(VoxelGrid::new([0.0; 3], cell_size, [0; 3]));
+    }
+
+    let dimensions = bounds
+        .extent()
+        .map(|e| (e / cell_size).floor() as usize + 1);
+    let padding = math::scale(
+        math::sub(dimensions.map(|d| d as f64 * cell_size), bounds.extent()),
+        0.5,
+    );
+    let mut grid = VoxelGrid::new(math::sub(bounds.min, padding), cell_size, dimensions);
+    let half = [0.5 * cell_size; 3];
+    let epsilon = 1.0e-9 * cell_size;
+
+    for index in grid.indices().collect::<Vec<_>>() {
+        let center = grid.center(index);
+        let aabb = Aabb {
+            min: math::sub(center, half),
+            max: math::add(center, half),
+        };
+        let touches = bvh
+            .query_triangles(&aabb)
+            .iter()
+            .any(|(_, triangle)| box_overlaps_triangle(center, half, *triangle));
+        if touches {
+            grid.set(index, Voxel::Surface);
+        }
+    }
+
+    let [x, y, z] = dimensions;
+    for k in 0..z {
+        for j in 0..y {
+            let start = grid.center([0, j, k]);
+            let origin = [bounds.min[0] - cell_size, start[1], start[2]];
+            let mut hits = bvh.intersect_ray_all(origin, [1.0, 0.0, 0.0]);
+            // Rays through a shared edge hit both triangles and must only count once.
+            hits.dedup_by(|a, b| {
+                a.front_facing == b.front_facing && (a.distance - b.distance).abs() < epsilon
+            });
+            for i in 0..x {
+                if grid.get([i, j, k]) != Voxel::Empty {
+                    continue;
+                }
+                let distance = grid.center([i, j, k])[0] - origin[0];
+                let crossed = hits.iter().take_while(|hit| hit.distance < distance);
+                let inside = match rule {
+                    FillRule::Parity => crossed.count() % 2 == 1,
+                    FillRule::Winding => {
+                        crossed
+                            .map(|hit| if hit.front_facing { 1 } else { -1 })
+                            .sum::<i32>()
+                            != 0
+                    }
+                };
+                if inside {
+                    grid.set([i, j, k], Voxel::Interior);
+                }
+            }
+        }
+    }
+    Ok(grid)
+}
+
+/// Separating axis test between a box and a triangle (Akenine-Möller).
+fn box_overlaps_triangle(center: Vec3, half: Vec3, triangle: [Vec3; 3]) -> bool {
+    let v = triangle.map(|p| math::sub(p, center));
+    let edges = [
+        math::sub(v[1], v[0]),
+        math::sub(v[2], v[1]),
+        math::sub(v[0], v[2]),
+    ];
+    let separated = |axis: Vec3| {
+        let projections = v.map(|p| math::dot(p, axis));
+        let radius = (0..3).map(|i| half[i] * axis[i].abs()).sum::<f64>();
+        let min = projections.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = projections
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        min > radius || max < -radius
+    };
+
+    let basis = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    if basis.iter().any(|axis| separated(*axis)) {
+        return false;
+    }
+    if separated(math::cross(edges[0], edges[1])) {
+        return false;
+    }
+    !basis.iter().any(|axis| {
+        edges
+            .iter()
+            .any(|edge| separated(math::cross(*axis, *edge)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_fills_its_interior() {
+        let _ = env_logger::try_init();
+        let mesh: Mesh = builder::cube();
+        let bvh = Bvh::new(&mesh);
+        let grid = voxelize(&mesh, &bvh, 0.2).unwrap();
+        assert_eq!(grid.dimensions, [6, 6, 6]);
+        // The outer shell touches the faces, the rest is interior.
+        assert_eq!(grid.count(Voxel::Interior), 4 * 4 * 4);
+        assert_eq!(grid.count(Voxel::Surface), 6 * 6 * 6 - 4 * 4 * 4);
+        assert_eq!(
+            voxelize_with(&mesh, &bvh, 0.2, FillRule::Parity).unwrap(),
+            grid
+        );
+        assert!(voxelize(&mesh, &bvh, -1.0).is_err());
+    }
+
+    #[test]
+    fn open_surfaces_have_no_interior() {
+        let mesh: Mesh = builder::grid(2, 2);
+        let bvh = Bvh::new(&mesh);
+        let grid = voxelize(&mesh, &bvh, 0.5).unwrap();
+        assert_eq!(grid.dimensions, [5, 5, 1]);
+        assert_eq!(grid.count(Voxel::Interior), 0);
+        assert_eq!(grid.count(Voxel::Surface), 25);
+    }
+
+    #[test]
+    fn blocks_are_remeshed_as_a_closed_surface() {
+        let mut grid = VoxelGrid::new([0.0; 3], 1.0, [3, 2, 1]);
+        grid.set([0, 0, 0], Voxel::Interior);
+        grid.set([1, 0, 0], Voxel::Surface);
+        grid.set([1, 1, 0], Voxel::Surface);
+        let mesh: Mesh = grid.to_mesh();
+
+        assert_eq!(mesh.face_count(), 14);
+        assert_eq!(mesh.edges().filter(|e| !e.face().is_valid()).count(), 0);
+        assert_eq!(mesh.vertex_count(), 16);
+        assert_eq!(
+            mesh.vertex_count() + mesh.face_count() - mesh.edge_count() / 2,
+            2
+        );
+    }
+}
@@ -4,6 +4,8 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 
 pub type Tag = u32;
 pub type Offset = u32;
@@ -14,7 +16,7 @@ pub const INVALID_ELEMENT_OFFSET: Offset = 0;
 pub const INVALID_ELEMENT_GENERATION: Generation = 0;
 
 /// Type-safe index into kernel storage.
-#[derive(Default, Debug, Eq)]
+#[derive(Default, Debug)]
 pub struct Handle<T> {
     pub offset: Offset,
     pub generation: Generation,
@@ -22,13 +24,10 @@ pub struct Handle<T> {
 }
 
 impl<T> Copy for Handle<T> {}
+impl<T> Eq for Handle<T> {}
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Self {
-        Self {
-            offset: self.offset,
-            generation: self.generation,
-            _marker: self._marker,
-        }
+        *self
     }
 }
 
@@ -44,7 +43,7 @@ impl<T> Handle<T> {
         Handle {
             offset,
             generation,
-            _marker: PhantomData::default(),
+            _marker: PhantomData,
         }
     }
 
@@ -53,6 +52,57 @@ impl<T> Handle<T> {
     }
 }
 
+/// Element types whose handles print and parse as `"<kind>:<offset>@<generation>"`,
+/// for example `f:12@3` for the face at offset 12 in its third generation.
+pub trait HandleKind {
+    /// Short name of the element kind, without a colon or an `@`.
+    const KIND: &'static str;
+}
+
+impl<T: HandleKind> fmt::Display for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}@{}", T::KIND, self.offset, self.generation)
+    }
+}
+
+/// Why a string could not be parsed as a handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseHandleError {
+    /// The string is not of the form `kind:offset@generation`.
+    Malformed,
+    /// The string names a different kind of element.
+    WrongKind { expected: &'static str },
+}
+
+impl fmt::Display for ParseHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHandleError::Malformed => write!(f, "expected kind:offset@generation"),
+            ParseHandleError::WrongKind { expected } => {
+                write!(f, "expected a handle of kind {}", expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseHandleError {}
+
+impl<T: HandleKind> FromStr for Handle<T> {
+    type Err = ParseHandleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = s.split_once(':').ok_or(ParseHandleError::Malformed)?;
+        let (offset, generation) = rest.split_once('@').ok_or(ParseHandleError::Malformed)?;
+        if kind != T::KIND {
+            return Err(ParseHandleError::WrongKind { expected: T::KIND });
+        }
+        match (offset.parse(), generation.parse()) {
+            (Ok(offset), Ok(generation)) => Ok(Handle::new(offset, generation)),
+            _ => Err(ParseHandleError::Malformed),
+        }
+    }
+}
+
 impl<T> PartialOrd for Handle<T> {
     fn partial_cmp(&self, other: &Handle<T>) -> Option<Ordering> {
         // Only the offset should matter when it comes to ordering
@@ -66,34 +116,75 @@ impl<T> PartialEq for Handle<T> {
     }
 }
 
+/// Identifies the state of a buffer for caches of derived data.
+///
+/// Every buffer gets an id of its own when it is created or cloned and
+/// counts the calls which may have modified it. Two equal versions mean the
+/// buffer has not been touched in between.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Version {
+    buffer: u64,
+    changes: u64,
+}
+
+static NEXT_BUFFER: AtomicU64 = AtomicU64::new(1);
+
+impl Version {
+    fn fresh() -> Self {
+        Version {
+            buffer: NEXT_BUFFER.fetch_add(1, AtomicOrdering::Relaxed),
+            changes: 0,
+        }
+    }
+}
+
 /// A pretty simple wrapper over a pair of 'Vec's.
-pub struct ElementBuffer<D: Default> {
+///
+/// `K` is the kind of handle handed out by the buffer and defaults to the
+/// element type. Overriding it lets a generic element share one handle type.
+pub struct ElementBuffer<D: Default, K = D> {
     buffer: Vec<D>,
     generations: Vec<Generation>,
     // Why not put the index? Because the generation of an index could give us
     // false negatives if we're not careful ... I'm still considering this.
     free_cells: HashSet<Offset>,
     //tags: Vec<Tag>, // TODO: use a Set instead. This isn't a persistent array of attributes.
+    version: Version,
+    _kind: PhantomData<K>,
 }
 
-impl<D: Default> Default for ElementBuffer<D> {
+impl<D: Default, K> Default for ElementBuffer<D, K> {
     fn default() -> Self {
         ElementBuffer {
             buffer: vec![Default::default()],
             generations: vec![Default::default()],
             free_cells: HashSet::new(),
             //tags: Vec::new(),
+            version: Version::fresh(),
+            _kind: PhantomData,
         }
     }
 }
 
-impl<D: Default> fmt::Debug for ElementBuffer<D> {
+impl<D: Default + Clone, K> Clone for ElementBuffer<D, K> {
+    fn clone(&self) -> Self {
+        ElementBuffer {
+            buffer: self.buffer.clone(),
+            generations: self.generations.clone(),
+            free_cells: self.free_cells.clone(),
+            version: Version::fresh(),
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<D: Default, K> fmt::Debug for ElementBuffer<D, K> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "ElementBuffer<> {{ {} items }}", self.len())
     }
 }
 
-impl<D: Default> ElementBuffer<D> {
+impl<D: Default, K> ElementBuffer<D, K> {
     pub fn new() -> Self {
         Self::default()
     }
@@ -103,13 +194,27 @@ impl<D: Default> ElementBuffer<D> {
             buffer: Vec::with_capacity(capacity + 1),
             generations: Vec::with_capacity(capacity + 1),
             free_cells: HashSet::new(),
+            version: Version::fresh(),
+            _kind: PhantomData,
         };
         out.buffer.push(Default::default());
         out.generations.push(Default::default());
         out
     }
 
+    /// Changes with every call which may modify the buffer, including
+    /// mutable access which ends up changing nothing.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    #[inline(always)]
+    fn touch(&mut self) {
+        self.version.changes = self.version.changes.wrapping_add(1);
+    }
+
     pub fn clear(&mut self) {
+        self.touch();
         self.buffer.clear();
         self.generations.clear();
         self.free_cells.clear();
@@ -118,9 +223,13 @@ impl<D: Default> ElementBuffer<D> {
         self.generations.push(Default::default());
     }
 
+    /// The reserved cell at offset zero and anything past the end of the
+    /// buffer are never considered active.
     #[inline(always)]
     fn is_active_cell(&self, offset: Offset) -> bool {
-        !self.free_cells.contains(&offset)
+        offset != INVALID_ELEMENT_OFFSET
+            && (offset as usize) < self.buffer.len()
+            && !self.free_cells.contains(&offset)
     }
 
     /// Returns the number of currently active cells.
@@ -139,7 +248,7 @@ impl<D: Default> ElementBuffer<D> {
         !self.free_cells.is_empty()
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (Handle<D>, &D)> {
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<K>, &D)> {
         self.buffer
             .iter()
             .enumerate()
@@ -151,7 +260,8 @@ impl<D: Default> ElementBuffer<D> {
             })
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<D>, &mut D)> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<K>, &mut D)> {
+        self.touch();
         self.buffer
             .iter_mut()
             .enumerate()
@@ -166,7 +276,7 @@ impl<D: Default> ElementBuffer<D> {
             })
     }
 
-    pub fn get(&self, handle: Handle<D>) -> Option<&D> {
+    pub fn get(&self, handle: Handle<K>) -> Option<&D> {
         if !self.is_active_cell(handle.offset) {
             return None;
         }
@@ -186,16 +296,16 @@ impl<D: Default> ElementBuffer<D> {
         self.buffer.get(offset as usize)
     }
 
-    pub fn get_mut(&mut self, handle: Handle<D>) -> Option<&mut D> {
+    pub fn get_mut(&mut self, handle: Handle<K>) -> Option<&mut D> {
         if !self.is_active_cell(handle.offset) {
             return None;
         }
-
         let generation = self.generations[handle.offset as usize];
         if generation != handle.generation {
             return None;
         }
 
+        self.touch();
         self.buffer.get_mut(handle.offset as usize)
     }
 
@@ -203,11 +313,13 @@ impl<D: Default> ElementBuffer<D> {
         if !self.is_active_cell(offset) {
             return None;
         }
+        self.touch();
         self.buffer.get_mut(offset as usize)
     }
 
     /// .
-    pub fn push(&mut self, element: D) -> Handle<D> {
+    pub fn push(&mut self, element: D) -> Handle<K> {
+        self.touch();
         if let Some(offset) = self.free_cells.iter().next().cloned() {
             self.free_cells.remove(&offset);
             // In this situation we just re-use an existing cell
@@ -223,12 +335,14 @@ impl<D: Default> ElementBuffer<D> {
     }
 
     /// .
-    pub fn remove(&mut self, handle: Handle<D>) {
+    pub fn remove(&mut self, handle: Handle<K>) {
+        self.touch();
         self.free_cells.insert(handle.offset);
         self.generations[handle.offset as usize] += 1;
     }
 
     pub fn remove_offset(&mut self, offset: Offset) {
+        self.touch();
         self.free_cells.insert(offset);
         self.generations[offset as usize] += 1;
     }
@@ -246,6 +360,7 @@ impl<D: Default> ElementBuffer<D> {
     }
 
     pub fn defragment(&mut self) -> Vec<(u32, u32)> {
+        self.touch();
         let plan = self.build_defrag_plan();
         for (free, active) in &plan {
             self.buffer.swap(*free as usize, *active as usize);
@@ -267,7 +382,7 @@ impl<D: Default> ElementBuffer<D> {
     }
 }
 
-impl<D: Default> Index<usize> for ElementBuffer<D> {
+impl<D: Default, K> Index<usize> for ElementBuffer<D, K> {
     type Output = D;
 
     fn index(&self, index: usize) -> &Self::Output {
@@ -276,7 +391,7 @@ impl<D: Default> Index<usize> for ElementBuffer<D> {
     }
 }
 
-impl<D: Default> Index<Offset> for ElementBuffer<D> {
+impl<D: Default, K> Index<Offset> for ElementBuffer<D, K> {
     type Output = D;
 
     fn index(&self, index: Offset) -> &Self::Output {
@@ -285,30 +400,30 @@ impl<D: Default> Index<Offset> for ElementBuffer<D> {
     }
 }
 
-impl<D: Default> Index<Handle<D>> for ElementBuffer<D> {
+impl<D: Default, K> Index<Handle<K>> for ElementBuffer<D, K> {
     type Output = D;
 
-    fn index(&self, handle: Handle<D>) -> &Self::Output {
+    fn index(&self, handle: Handle<K>) -> &Self::Output {
         self.get(handle)
             .expect("Unable to retrieve element specified by the provided handle.")
     }
 }
 
-impl<D: Default> IndexMut<Handle<D>> for ElementBuffer<D> {
-    fn index_mut(&mut self, handle: Handle<D>) -> &mut Self::Output {
+impl<D: Default, K> IndexMut<Handle<K>> for ElementBuffer<D, K> {
+    fn index_mut(&mut self, handle: Handle<K>) -> &mut Self::Output {
         self.get_mut(handle)
             .expect("Unable to retrieve element specified by the provided handle.")
     }
 }
 
-impl<D: Default> IndexMut<Offset> for ElementBuffer<D> {
+impl<D: Default, K> IndexMut<Offset> for ElementBuffer<D, K> {
     fn index_mut(&mut self, offset: Offset) -> &mut Self::Output {
         self.get_offset_mut(offset)
             .expect("Unable to retrieve element for provided offset.")
     }
 }
 
-impl<D: Default> IndexMut<usize> for ElementBuffer<D> {
+impl<D: Default, K> IndexMut<usize> for ElementBuffer<D, K> {
     fn index_mut(&mut self, offset: usize) -> &mut Self::Output {
         self.get_offset_mut(offset as Offset)
             .expect("Unable to retrieve element for provided offset.")
@@ -317,6 +432,87 @@ impl<D: Default> IndexMut<usize> for ElementBuffer<D> {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Handles are written as `(offset, generation)` and buffers along with
+/// their generations and free cells, so handles given out before saving a
+/// buffer are still live, or still stale, once it is loaded again.
+#[cfg(feature = "serde")]
+mod serialization {
+    use super::*;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    impl<T> Serialize for Handle<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            (self.offset, self.generation).serialize(serializer)
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for Handle<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (offset, generation) = Deserialize::deserialize(deserializer)?;
+            Ok(Handle::new(offset, generation))
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Stored<'a, D> {
+        elements: &'a [D],
+        generations: &'a [Generation],
+        free_cells: Vec<Offset>,
+    }
+
+    #[derive(Deserialize)]
+    struct Loaded<D> {
+        elements: Vec<D>,
+        generations: Vec<Generation>,
+        free_cells: Vec<Offset>,
+    }
+
+    impl<D: Default + Serialize, K> Serialize for ElementBuffer<D, K> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut free_cells: Vec<Offset> = self.free_cells.iter().copied().collect();
+            free_cells.sort_unstable();
+            Stored {
+                elements: &self.buffer,
+                generations: &self.generations,
+                free_cells,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, D: Default + Deserialize<'de>, K> Deserialize<'de> for ElementBuffer<D, K> {
+        fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+            let loaded = Loaded::<D>::deserialize(deserializer)?;
+            let len = loaded.elements.len();
+            if len == 0 || loaded.generations.len() != len {
+                return Err(De::Error::custom(format!(
+                    "a buffer needs its reserved cell and a generation per cell, got {} cells and {} generations",
+                    len,
+                    loaded.generations.len()
+                )));
+            }
+            if let Some(cell) = loaded
+                .free_cells
+                .iter()
+                .find(|cell| **cell == INVALID_ELEMENT_OFFSET || **cell as usize >= len)
+            {
+                return Err(De::Error::custom(format!(
+                    "free cell {} is out of range",
+                    cell
+                )));
+            }
+            Ok(ElementBuffer {
+                buffer: loaded.elements,
+                generations: loaded.generations,
+                free_cells: loaded.free_cells.into_iter().collect(),
+                version: Version::fresh(),
+                _kind: PhantomData,
+            })
+        }
+    }
+}
+
 pub mod prelude {
     pub use super::{ElementBuffer, Generation, Handle, Offset, Tag};
 }
@@ -325,20 +521,77 @@ pub mod prelude {
 mod tests {
     use super::*;
 
-    #[derive(Default)]
+    #[derive(Default, Debug, Clone)]
     struct TestElement {
         foo: u32,
     }
 
+    impl HandleKind for TestElement {
+        const KIND: &'static str = "t";
+    }
+
     type TestHandle = Handle<TestElement>;
     type TestBuffer = ElementBuffer<TestElement>;
 
+    #[test]
+    fn handles_round_trip_through_strings() {
+        let handle = TestHandle::new(12, 3);
+        assert_eq!(handle.to_string(), "t:12@3");
+        assert_eq!("t:12@3".parse::<TestHandle>(), Ok(handle));
+        assert_eq!(
+            "f:12@3".parse::<TestHandle>(),
+            Err(ParseHandleError::WrongKind { expected: "t" })
+        );
+        for malformed in ["t:12", "t12@3", "t:x@3", "t:12@-1", ""] {
+            assert_eq!(
+                malformed.parse::<TestHandle>(),
+                Err(ParseHandleError::Malformed)
+            );
+        }
+    }
+
+    #[test]
+    fn mutation_changes_the_version() {
+        let mut buffer = TestBuffer::default();
+        let start = buffer.version();
+        let handle = buffer.push(TestElement { foo: 1 });
+        let pushed = buffer.version();
+        assert_ne!(start, pushed);
+        let _ = buffer.get(handle);
+        assert_eq!(buffer.version(), pushed);
+        buffer[handle].foo = 2;
+        assert_ne!(buffer.version(), pushed);
+
+        let mut copy = buffer.clone();
+        assert_ne!(copy.version(), buffer.version());
+        copy.remove(handle);
+        buffer.remove(handle);
+        assert_ne!(copy.version(), buffer.version());
+
+        // A stale handle to a reused cell changes nothing.
+        let reused = buffer.push(TestElement { foo: 3 });
+        assert_eq!(reused.offset, handle.offset);
+        let pushed = buffer.version();
+        assert!(buffer.get_mut(handle).is_none());
+        assert_eq!(buffer.version(), pushed);
+    }
+
     #[test]
     fn default_index_is_invalid() {
         let index = TestHandle::default();
         assert!(!index.is_valid());
     }
 
+    #[test]
+    fn invalid_handles_are_not_resolved() {
+        let mut buffer = TestBuffer::default();
+        let _ = buffer.push(TestElement { foo: 1 });
+        assert!(buffer.get(TestHandle::default()).is_none());
+        assert!(buffer.get(TestHandle::new(42, 1)).is_none());
+        assert!(buffer.get_offset(0).is_none());
+        assert!(buffer.get_offset_mut(42).is_none());
+    }
+
     #[test]
     fn default_element_buffer_properties() {
         let buffer = TestBuffer::default();